@@ -13,24 +13,88 @@ pub mod routes {
     pub const GET_INFO: &str = "/v1/getinfo";
     /// Websocket
     pub const WEBSOCKET: &str = "/v1/ws";
+    /// Run several API requests in one call. The macaroon must be valid for every request.
+    pub const BATCH: &str = "/v1/batch";
+
+    /// --- Utility ---
+    /// Sign a message with the node key.
+    pub const SIGN_MESSAGE: &str = "/v1/utility/signMessage";
+    /// Verify a message signed by a lightning node.
+    pub const VERIFY_MESSAGE: &str = "/v1/utility/verifyMessage";
+    /// Replace the probabilistic scorer with a fresh one.
+    pub const RESET_SCORER: &str = "/v1/utility/resetscorer";
+    /// Check that the persisted channel monitors match the ones in memory
+    pub const CHECK_MONITORS: &str = "/v1/utility/checkmonitors";
+    /// Derive a public key from the node seed, needs the derivekey macaroon
+    pub const DERIVE_KEY: &str = "/v1/utility/derivekey";
+    /// Outputs of closed channels which have not been swept into the wallet yet.
+    pub const PENDING_SWEEPS: &str = "/v1/utility/pendingsweeps";
+    /// Export channel monitors, network graph and peers for migration tooling. Requires confirm=true.
+    pub const EXPORT_STATE: &str = "/v1/utility/exportstate";
+    /// Change the node alias and color until the next restart.
+    pub const SET_ALIAS: &str = "/v1/utility/setalias";
+    /// Broadcast the node announcement now instead of waiting for the next regular broadcast.
+    pub const ANNOUNCE: &str = "/v1/utility/announce";
+    /// Enable or disable maintenance mode. New channels are rejected while existing channels keep routing.
+    pub const MAINTENANCE: &str = "/v1/maintenance";
+
+    /// --- Invoices ---
+    /// Create a BOLT11 invoice, optionally with an on-chain fallback address.
+    pub const GENERATE_INVOICE: &str = "/v1/invoice/genInvoice";
+    /// List invoices created by this node along with their payment status.
+    pub const LIST_INVOICES: &str = "/v1/invoices";
+
+    /// --- Payments ---
+    /// Pay to a LNURL or lightning address (user@domain).
+    pub const PAY_LNURL: &str = "/v1/pay/lnurl";
+    /// Get the status of an outbound payment.
+    pub const GET_PAYMENT: &str = "/v1/pay/:payment_hash";
+    /// List outbound payments.
+    pub const LIST_PAYMENTS: &str = "/v1/pays";
+
+    /// --- Forwards ---
+    /// List the forwarding history.
+    pub const LIST_FORWARDS: &str = "/v1/forwards";
+    /// Download the forwarding history as CSV.
+    pub const EXPORT_FORWARDS: &str = "/v1/forwards/export.csv";
 
     /// --- Peers ---
     /// Connect with a network peer.
     pub const CONNECT_PEER: &str = "/v1/peer/connect";
     /// Returns the list of peers connected with the node.
     pub const LIST_PEERS: &str = "/v1/peer/listPeers";
-    /// Disconnect from a connected network peer.
+    /// Disconnect from a connected network peer. Channel peers are reconnected.
     pub const DISCONNECT_PEER: &str = "/v1/peer/disconnect/:id";
+    /// Delete the stored addresses of a peer without disconnecting.
+    pub const FORGET_PEER: &str = "/v1/peer/forget/:id";
 
     /// --- Channels ---
     /// Get the list of channels open on the node.
     pub const LIST_CHANNELS: &str = "/v1/channel/listChannels";
-    /// Open channel with a connected peer node.
+    /// Open channel with a peer node. Connects to the peer first if an address is given.
     pub const OPEN_CHANNEL: &str = "/v1/channel/openChannel";
+    /// Calculate reserves and balances of a channel open without opening it.
+    pub const OPEN_CHANNEL_PREVIEW: &str = "/v1/channel/openpreview";
+    /// Open a channel funded by an external wallet. Returns an unsigned PSBT paying the funding output.
+    pub const OPEN_CHANNEL_PSBT: &str = "/v1/channel/openpsbt";
+    /// Complete a channel open started with openpsbt with the signed PSBT.
+    pub const FUNDING_PSBT: &str = "/v1/channel/fundingpsbt";
+    /// List closed channels with their closing and sweep transactions.
+    pub const LIST_CLOSED_CHANNELS: &str = "/v1/channel/closed";
+    /// Status of a channel open started with async=true.
+    pub const GET_PENDING_CHANNEL_OPEN: &str = "/v1/channel/pending/:id";
     /// Update channel fee policy.
     pub const SET_CHANNEL_FEE: &str = "/v1/channel/setChannelFee";
     /// Close an existing channel with a peer.
     pub const CLOSE_CHANNEL: &str = "/v1/channel/closeChannel/:id";
+    /// Abandon a pending channel whose funding transaction will never confirm.
+    pub const ABANDON_CHANNEL: &str = "/v1/channel/abandon";
+    /// Get forwarding statistics of a channel.
+    pub const GET_CHANNEL_STATS: &str = "/v1/channel/:id/stats";
+    /// Fees earned by a channel per hour, day or week.
+    pub const GET_CHANNEL_EARNINGS: &str = "/v1/channel/:id/earnings";
+    /// Our forwarding policy and the peer's advertised policy for each channel.
+    pub const FEE_REPORT: &str = "/v1/feereport";
 
     /// --- Network ---
     /// Look up a node on the network.
@@ -41,6 +105,12 @@ pub mod routes {
     pub const LIST_NETWORK_CHANNEL: &str = "/v1/network/listchannel/:id";
     /// Return list of all channels on the network
     pub const LIST_NETWORK_CHANNELS: &str = "/v1/network/listchannel";
+    /// Return all channels on the network between two nodes
+    pub const LIST_NETWORK_CHANNELS_BETWEEN: &str = "/v1/network/channels/between/:node1/:node2";
+    /// Aggregate statistics of the network graph.
+    pub const GET_NETWORK_STATS: &str = "/v1/network/stats";
+    /// Remove stale channels from the network graph
+    pub const PRUNE_NETWORK_GRAPH: &str = "/v1/network/prune";
 
     /// --- On chain wallet ---
     /// Returns total, confirmed and unconfirmed on-chain balances.
@@ -49,14 +119,81 @@ pub mod routes {
     pub const NEW_ADDR: &str = "/v1/newaddr";
     /// Withdraw on-chain funds to an address.
     pub const WITHDRAW: &str = "/v1/withdraw";
+    /// Rescan the blockchain for wallet transactions.
+    pub const WALLET_RESCAN: &str = "/v1/wallet/rescan";
+    /// Watch an external descriptor. Its balance is reported separately and never spent.
+    pub const IMPORT_DESCRIPTOR: &str = "/v1/wallet/importdescriptor";
+
+    /// --- HTLCs ---
+    /// Unresolved HTLCs of all channels, closest to expiry first. Dust HTLCs are not included.
+    pub const LIST_HTLCS: &str = "/v1/htlcs";
+    /// Get a fake short channel id for route hints. HTLCs to it are held for an external policy.
+    pub const GET_INTERCEPT_SCID: &str = "/v1/htlc/interceptscid";
+    /// Forward an intercepted HTLC through a channel, e.g. one opened just in time.
+    pub const FORWARD_HTLC: &str = "/v1/htlc/forward";
+    /// Fail an intercepted HTLC back to the sender.
+    pub const FAIL_HTLC: &str = "/v1/htlc/fail";
+    /// Intercepted HTLCs which have not been forwarded or failed yet.
+    pub const LIST_INTERCEPTED_HTLCS: &str = "/v1/htlc/intercepted";
+
+    /// --- Webhooks ---
+    /// Events which have not been delivered to the webhook yet.
+    pub const LIST_PENDING_WEBHOOKS: &str = "/v1/webhook/pending";
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Error {
     pub status: String,
+    /// Machine readable reason of the error, stable across releases
+    pub code: ErrorCode,
+    /// Description of the error for humans
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    Unauthorized,
+    NotFound,
+    /// The request is malformed or not allowed in the current state
+    InvalidRequest,
+    InternalError,
+    MaintenanceMode,
+    /// Bitcoind or the node is still catching up with the chain
+    BitcoindSyncing,
+    PeerNotConnected,
+    InsufficientFunds,
+    NoRoute,
+    /// A code added in a later version of the API
+    #[serde(other)]
+    Unknown,
+}
+
+/// An error with a code for API clients. Return it through anyhow and the REST API reports the
+/// code instead of a generic one.
+#[derive(Debug)]
+pub struct CodedError {
+    pub code: ErrorCode,
     pub detail: String,
 }
 
+impl CodedError {
+    pub fn new(code: ErrorCode, detail: impl Into<String>) -> CodedError {
+        CodedError {
+            code,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl Display for CodedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.detail)
+    }
+}
+
+impl std::error::Error for CodedError {}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetInfo {
@@ -70,12 +207,19 @@ pub struct GetInfo {
     #[serde(rename = "blockheight")]
     pub block_height: u64,
     pub synced_to_chain: bool,
+    /// True on every network except bitcoin mainnet. Kept for compatibility, use network instead.
     pub testnet: bool,
     pub chains: Vec<Chain>,
     pub version: String,
     pub api_version: String,
+    /// The network the node runs on: bitcoin, testnet, signet or regtest
     pub network: String,
     pub address: Vec<Address>,
+    /// Connect strings for this node (pubkey@host:port)
+    pub uris: Vec<String>,
+    pub maintenance_mode: bool,
+    /// Total balance of the imported watch-only descriptors
+    pub watch_only_balance: u64,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -116,9 +260,16 @@ pub struct NetworkChannel {
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WalletBalance {
+    /// Confirmed, unconfirmed and reserved balance combined
     pub total_balance: u64,
     pub conf_balance: u64,
     pub unconf_balance: u64,
+    /// Committed to channel opens whose funding transaction has not confirmed yet
+    pub reserved_balance: u64,
+    /// Coinbase outputs which can't be spent yet. Not included in the total
+    pub immature_balance: u64,
+    /// Total balance of the imported watch-only descriptors
+    pub watch_only_balance: u64,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -144,6 +295,32 @@ pub struct WalletTransferResponse {
     pub txid: String,
 }
 
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletRescan {
+    /// Block height to start scanning from. Scans from genesis if not set
+    pub birthday_height: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct WalletRescanParams {
+    /// Return immediately and report the progress on the health endpoint
+    #[serde(rename = "async")]
+    pub run_async: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ImportDescriptor {
+    /// Output descriptor with public keys only, e.g. wpkh(xpub.../0/*)
+    pub descriptor: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WalletRescanResponse {
+    /// "complete" or "started" for an async rescan
+    pub status: String,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Channel {
@@ -179,12 +356,87 @@ pub struct Channel {
     pub direction: u8,
     /// Alias of the node
     pub alias: String,
+    /// Number of confirmations of the funding transaction
+    pub confirmations: Option<u32>,
+    /// Number of confirmations required before the channel is ready
+    pub confirmations_required: Option<u32>,
+    /// Number of HTLCs forwarded through this channel
+    pub htlcs_attempted: u64,
+    /// Number of forwarded HTLCs that were fulfilled
+    pub htlcs_succeeded: u64,
+    /// Number of forwarded HTLCs that failed
+    pub htlcs_failed: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelFeeReport {
+    /// Channel ID
+    pub channel_id: String,
+    /// Short channel ID
+    pub short_channel_id: String,
+    /// Peer ID
+    pub peer_id: String,
+    /// Our policy for HTLCs forwarded out through this channel
+    pub local_policy: Option<ChannelPolicy>,
+    /// The policy the peer advertises in the network graph
+    pub remote_policy: Option<ChannelPolicy>,
+    /// Largest total value of dust HTLCs the channel accepts in msats
+    pub max_dust_htlc_exposure_msat: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelPolicy {
+    /// Base fee in msats
+    pub base_fee_msat: u32,
+    /// Fee per-millionths
+    pub ppm_fee: u32,
+    pub cltv_expiry_delta: u16,
+    pub htlc_minimum_msat: Option<u64>,
+    pub htlc_maximum_msat: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelStats {
+    /// Channel ID
+    pub channel_id: String,
+    /// Short channel ID
+    pub short_channel_id: String,
+    /// Number of HTLCs forwarded through this channel
+    pub htlcs_attempted: u64,
+    /// Number of forwarded HTLCs that were fulfilled
+    pub htlcs_succeeded: u64,
+    /// Number of forwarded HTLCs that failed
+    pub htlcs_failed: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ChannelEarningsParams {
+    /// Length of the buckets: hour, day (default) or week.
+    pub bucket: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelEarnings {
+    /// Unix timestamp (seconds) of the start of the bucket
+    pub bucket_start: u64,
+    /// Fees of payments which entered the node through the channel
+    pub inbound_fee_msat: u64,
+    /// Fees of payments which left the node through the channel
+    pub outbound_fee_msat: u64,
+    /// Number of payments which entered the node through the channel
+    pub inbound_forwards: u64,
+    /// Number of payments which left the node through the channel
+    pub outbound_forwards: u64,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FundChannel {
-    /// Pub key of the peer
+    /// Pub key of the peer, or pubkey@host:port to connect to the peer before opening
     pub id: String,
     /// Amount in satoshis
     pub satoshis: String,
@@ -204,6 +456,35 @@ pub struct FundChannel {
     pub request_amt: Option<String>,
     /// Compact represenation of the peer's expected channel lease terms
     pub compact_lease: Option<String>,
+    /// Confirmations required before the channel can be used (1-144). Uses the node setting if not set.
+    pub minimum_depth: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenChannelPreview {
+    /// Capacity of the channel
+    pub channel_size_sat: u64,
+    /// Our balance after the push to the peer
+    pub local_balance_msat: u64,
+    /// Balance of the peer, i.e. the pushed amount
+    pub remote_balance_msat: u64,
+    /// Reserve we have to keep in the channel. Chosen by the peer, estimated at 1% as the BOLTs recommend
+    pub local_reserve_sat: u64,
+    /// Reserve the peer has to keep in the channel, as configured on this node
+    pub remote_reserve_sat: u64,
+    /// Part of our balance above the reserve
+    pub spendable_msat: u64,
+    /// Part of the peer's balance above its reserve
+    pub receivable_msat: u64,
+    /// Outputs below this value are not created in commitment transactions
+    pub dust_limit_sat: u64,
+    /// Estimated fee of the funding transaction
+    pub funding_fee_sat: u64,
+    /// Confirmed balance of the on-chain wallet
+    pub confirmed_balance_sat: u64,
+    /// True if the confirmed balance covers the channel, the fee and the wallet reserve
+    pub sufficient_funds: bool,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -305,8 +586,63 @@ impl Default for FeeRate {
     }
 }
 
+#[derive(Serialize, Deserialize, Default)]
+pub struct OpenChannelParams {
+    /// Return a tracking id immediately instead of waiting for the funding transaction
+    #[serde(rename = "async")]
+    pub run_async: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenChannelPsbt {
+    /// Pub key of the peer, optionally with the address [id@host:port]
+    pub id: String,
+    /// Amount in satoshis
+    pub satoshis: String,
+    /// Flag to announce the channel
+    pub announce: Option<bool>,
+    /// Amount of millisatoshis to push to the channel peer at open
+    pub push_msat: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenChannelPsbtResponse {
+    /// Temporary channel_id (hex) to submit the signed PSBT with
+    pub channel_id: String,
+    /// Address of the funding output
+    pub address: String,
+    /// Amount in satoshis the funding output must have
+    pub satoshis: u64,
+    /// Base64 PSBT with only the funding output. Add inputs, sign and finalize it externally.
+    pub psbt: String,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct FundingPsbt {
+    /// Temporary channel_id (hex) returned by openpsbt
+    pub channel_id: String,
+    /// Base64 finalized PSBT. All inputs must be segwit.
+    pub psbt: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingChannelOpen {
+    /// Tracking id to poll the status with
+    pub id: String,
+    /// pending/funded/failed
+    pub status: String,
+    /// Set once the funding transaction has been created
+    pub result: Option<FundChannelResponse>,
+    /// Set if the channel open failed
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct FundChannelResponse {
     /// Transaction
     pub tx: Transaction,
@@ -316,9 +652,42 @@ pub struct FundChannelResponse {
     pub channel_id: String,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClosedChannel {
+    /// Channel ID (hex)
+    pub channel_id: String,
+    /// Pub key of the peer, unknown for channels which were never funded
+    pub counterparty_node_id: Option<String>,
+    /// Funding output as "txid:vout"
+    pub funding_txo: Option<String>,
+    /// Why the channel was closed
+    pub reason: Option<String>,
+    /// Transaction spending the funding output, set once it has confirmed
+    pub closing_txid: Option<String>,
+    /// Transaction sweeping our balance from the closing transaction into the wallet
+    pub sweep_txid: Option<String>,
+    /// Unix timestamp (seconds) of the close
+    pub closed_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AbandonChannel {
+    /// Channel ID (hex)
+    pub id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbandonChannelResponse {
+    /// Wallet outputs that were spent by the funding transaction and can be used again
+    pub freed_outpoints: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ChannelFee {
-    // Short channel ID or channel id. It can be "all" for updating all channels.
+    // Short channel ID, channel id or the public key of a peer to update all channels with that peer.
+    // It can be "all" for updating all channels.
     pub id: String,
     // Optional value in msats added as base fee to any routed payment.
     pub base: Option<u32>,
@@ -344,6 +713,12 @@ pub struct SetChannelFee {
 #[derive(Serialize, Deserialize)]
 pub struct SetChannelFeeResponse(pub Vec<SetChannelFee>);
 
+#[derive(Serialize, Deserialize, Default)]
+pub struct SetChannelFeeParams {
+    // Apply the fee even if it exceeds the configured maximum.
+    pub force: Option<bool>,
+}
+
 #[derive(Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct NewAddress {
@@ -357,12 +732,352 @@ pub struct NewAddressResponse {
     pub address: String,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateInvoice {
+    /// Amount in millisatoshis. Leave empty for an invoice that accepts any amount
+    pub amount: Option<u64>,
+    /// Description embedded in the invoice
+    pub description: String,
+    /// Seconds until the invoice expires (default 3600)
+    pub expiry: Option<u32>,
+    /// Embed a fresh on-chain address of the wallet so the payer can fall back to an on-chain payment
+    #[serde(default)]
+    pub fallback_address: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateInvoiceResponse {
+    pub payment_hash: String,
+    /// Unix timestamp (seconds) of when the invoice expires
+    pub expires_at: u64,
+    pub bolt11: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PayLnurl {
+    /// Bech32 encoded LNURL or lightning address (user@domain)
+    pub target: String,
+    /// Amount in millisatoshis
+    pub amount: u64,
+    /// Largest total routing fee in millisatoshis
+    pub max_fee_msat: Option<u64>,
+    /// Largest total routing fee as a percentage of the amount. The smaller limit applies if
+    /// both are set. Defaults to 0.5% + 1 sat if neither is set.
+    pub max_fee_percent: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentResponse {
+    pub payment_hash: String,
+    pub payment_preimage: String,
+    pub amount_msat: Option<u64>,
+    pub fee_msat: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payment {
+    pub payment_hash: String,
+    /// One of pending, succeeded or failed.
+    pub status: String,
+    pub payment_preimage: Option<String>,
+    pub amount_msat: Option<u64>,
+    pub fee_msat: Option<u64>,
+    pub failure_reason: Option<String>,
+    /// Unix timestamp (seconds) of when the payment succeeded or failed.
+    pub settled_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ListPaymentsParams {
+    /// Only return payments with this status (pending, succeeded or failed).
+    pub status: Option<String>,
+    /// Only return payments settled at or after this unix timestamp (seconds).
+    pub since: Option<u64>,
+    /// Only return payments settled before this unix timestamp (seconds).
+    pub until: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ExportForwardsParams {
+    /// Only export forwards at or after this unix timestamp (seconds).
+    pub since: Option<u64>,
+    /// Only export forwards before this unix timestamp (seconds).
+    pub until: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ListForwardsParams {
+    /// Only list forwards at or after this unix timestamp (seconds).
+    pub since: Option<u64>,
+    /// Only list forwards before this unix timestamp (seconds).
+    pub until: Option<u64>,
+    /// Return at most this many forwards, oldest first.
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Forward {
+    /// Channel ID of the incoming HTLC
+    pub in_channel_id: Option<String>,
+    /// Channel ID of the outgoing HTLC
+    pub out_channel_id: Option<String>,
+    pub in_short_channel_id: Option<u64>,
+    pub out_short_channel_id: Option<u64>,
+    pub amount_in_msat: Option<u64>,
+    pub amount_out_msat: Option<u64>,
+    /// Fee earned by forwarding the payment
+    pub fee_msat: Option<u64>,
+    /// Unix timestamp (seconds) of when the payment was forwarded
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Maintenance {
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SetAlias {
+    /// New alias of the node (at most 32 bytes)
+    pub alias: String,
+    /// New color as hex, e.g. "ff9900". The color is kept if left out.
+    pub color: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AnnounceResponse {
+    /// False if the node has no public channel to be announced with
+    pub announced: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SignMessage {
+    /// The message to sign
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SignMessageResponse {
+    /// zbase32 encoded signature, compatible with LND and CLN
+    pub signature: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DeriveKey {
+    /// BIP32 derivation path from the node seed, e.g. m/138'/0
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeriveKeyResponse {
+    pub path: String,
+    /// Hex encoded compressed public key
+    pub public_key: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VerifyMessage {
+    /// The signed message
+    pub message: String,
+    /// zbase32 encoded signature
+    pub signature: String,
+    /// The node expected to have signed the message
+    pub pubkey: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyMessageResponse {
+    /// True if the message was signed by the given pubkey. Without a pubkey, true if the signer is a node in the network graph
+    pub verified: bool,
+    /// The node that signed the message
+    pub pubkey: String,
+    /// Whether the signer is a node in the network graph
+    pub known_node: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingSweep {
+    /// Output of a closing transaction as "txid:vout"
+    pub outpoint: String,
+    /// Value of the output
+    pub value_sats: u64,
+    /// "pending" while waiting for a sweep transaction, "sweeping" until it confirms
+    pub status: String,
+    /// Transaction sweeping the output into the wallet
+    pub sweep_txid: Option<String>,
+    /// Unix timestamp (seconds) when LDK handed us the output
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ExportStateParams {
+    /// Must be true. Restoring the state on a node with the same keys while this node is still
+    /// running broadcasts revoked states and loses the funds in its channels.
+    pub confirm: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportState {
+    /// Version of the export format
+    pub version: u32,
+    /// Pub key of the exporting node
+    pub node_id: String,
+    /// The network the node runs on: bitcoin, testnet, signet or regtest
+    pub network: String,
+    /// Unix timestamp (seconds) of the export
+    pub exported_at: u64,
+    /// Channel monitors serialized by LDK (hex)
+    pub channel_monitors: Vec<String>,
+    /// Network graph serialized by LDK (hex)
+    pub network_graph: Option<String>,
+    /// Peers the node reconnects to
+    pub peers: Vec<ExportedPeer>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportedPeer {
+    /// Pub key of the peer
+    pub id: String,
+    /// Address as "host:port"
+    pub address: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ResetScorerResponse {
+    /// Confirmation that the scorer has been reset
+    pub status: String,
+    /// Unix timestamp (seconds) of the reset
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorCheck {
+    /// Channel monitors read from the database
+    pub checked_monitors: usize,
+    /// Channel monitors which failed to deserialize
+    pub corrupt_monitors: usize,
+    pub discrepancies: Vec<MonitorDiscrepancy>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorDiscrepancy {
+    /// Funding transaction output as txid:index
+    pub funding_txo: String,
+    /// None if the monitor is missing in the database
+    pub persisted_update_id: Option<u64>,
+    /// None if the chain monitor doesn't watch the channel
+    pub in_memory_update_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BatchRequest {
+    /// HTTP method, e.g. GET or POST
+    pub method: String,
+    /// Path of the route including the query, e.g. /v1/channel/list
+    pub path: String,
+    /// JSON body of the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BatchResponse {
+    /// HTTP status code of the request
+    pub status: u16,
+    /// JSON body of the response. Bodies which are not JSON are returned as a string.
+    pub body: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingWebhook {
+    /// Event type
+    pub event: String,
+    /// Unix timestamp (seconds) of when the event was raised
+    pub timestamp: u64,
+    /// Number of failed delivery attempts
+    pub attempts: u32,
+    /// Error of the most recent delivery attempt
+    pub last_error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterceptScid {
+    /// Short channel id to use in the route hint of an invoice
+    pub short_channel_id: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardHtlc {
+    /// Intercept ID (hex) from the htlc_intercepted event
+    pub intercept_id: String,
+    /// Channel ID (hex) of the channel to forward the HTLC through
+    pub channel_id: String,
+    /// Amount in msats to forward, can be less than the event's expected outbound amount to skim a fee
+    pub amount_msat: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailHtlc {
+    /// Intercept ID (hex) from the htlc_intercepted event
+    pub intercept_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Htlc {
+    /// Channel ID (hex)
+    pub channel_id: String,
+    /// inbound or outbound
+    pub direction: String,
+    /// Amount in satoshis, rounded down as the channel monitor reports it
+    pub amount_sat: u64,
+    /// Block height at which the HTLC expires
+    pub cltv_expiry: u32,
+    /// Blocks left until the HTLC expires, negative once it has expired. The channel is force
+    /// closed around the expiry if the HTLC is still unresolved then.
+    pub blocks_until_expiry: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterceptedHtlc {
+    /// Intercept ID (hex) to forward or fail the HTLC with
+    pub intercept_id: String,
+    /// The fake short channel id the HTLC was sent to
+    pub requested_next_hop_scid: u64,
+    pub payment_hash: String,
+    pub inbound_amount_msat: u64,
+    pub expected_outbound_amount_msat: u64,
+    /// Unix timestamp (seconds) of when the HTLC was intercepted
+    pub intercepted_at: u64,
+}
+
 #[derive(Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct Peer {
     pub id: String,
     pub connected: bool,
     pub netaddr: Option<Address>,
     pub alias: String,
+    /// Number of channels with the peer, 0 for peers we only exchange gossip with
+    pub num_channels: usize,
+    /// Total capacity of the channels with the peer in sats
+    pub capacity_sat: u64,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -377,6 +1092,28 @@ pub struct NetworkNode {
     pub addresses: Vec<Address>,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkStats {
+    pub num_nodes: usize,
+    pub num_channels: usize,
+    /// Sum of the capacity of all announced channels with a known capacity
+    pub total_capacity_sat: u64,
+    pub median_channel_size_sat: u64,
+    /// Median over the fee policies of both directions of all channels
+    pub median_base_fee_msat: u32,
+    pub median_fee_ppm: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneNetworkGraphResponse {
+    /// Channels which had no update for two weeks
+    pub removed_channels: usize,
+    /// Nodes which were left without channels
+    pub removed_nodes: usize,
+}
+
 #[test]
 fn test_fee_rate() -> Result<(), ParseFeeRateError> {
     let urgent_fee_rate = FeeRate::from_str("urgent")?;
@@ -395,3 +1132,20 @@ fn test_fee_rate() -> Result<(), ParseFeeRateError> {
     assert_eq!(pkw_fee_rate, FeeRate::PerKw(37));
     Ok(())
 }
+
+#[test]
+fn test_error_code() -> Result<(), serde_json::Error> {
+    assert_eq!(
+        "\"PEER_NOT_CONNECTED\"",
+        serde_json::to_string(&ErrorCode::PeerNotConnected)?
+    );
+    assert_eq!(
+        ErrorCode::NoRoute,
+        serde_json::from_str::<ErrorCode>("\"NO_ROUTE\"")?
+    );
+    assert_eq!(
+        ErrorCode::Unknown,
+        serde_json::from_str::<ErrorCode>("\"SOMETHING_NEW\"")?
+    );
+    Ok(())
+}
@@ -1,20 +1,26 @@
 extern crate criterion;
+use std::str::FromStr;
+
 use anyhow::{Error, Result};
-use criterion::{criterion_group, criterion_main, Criterion};
+use bitcoin::secp256k1::PublicKey;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use kld::api::MacaroonAuth;
 use kld::database::{migrate_database, LdkDatabase};
 
 use lightning::ln::functional_test_utils::{
     create_announced_chan_between_nodes, create_chanmon_cfgs, create_network, create_node_cfgs,
     create_node_chanmgrs, send_payment,
 };
+use lightning::ln::msgs::NetAddress;
 use lightning::util::logger::Level::Warn;
 use lightning::util::test_utils::TestChainMonitor;
-use test_utils::{cockroach, test_settings, CockroachManager};
+use macaroon::Macaroon;
+use test_utils::{cockroach, test_settings, CockroachManager, TEST_PUBLIC_KEY};
 
 criterion_group! {
     name = benches;
     config = Criterion::default().significance_level(0.1).sample_size(10).measurement_time(std::time::Duration::from_secs(30));
-    targets = bench_send_payment_two_nodes
+    targets = bench_send_payment_two_nodes, bench_verify_macaroon, bench_fetch_peer
 }
 criterion_main!(benches);
 
@@ -23,6 +29,67 @@ pub fn bench_send_payment_two_nodes(c: &mut Criterion) {
     send_payment_two_nodes(c).unwrap()
 }
 
+pub fn bench_verify_macaroon(c: &mut Criterion) {
+    verify_macaroon(c).unwrap()
+}
+
+pub fn bench_fetch_peer(c: &mut Criterion) {
+    fetch_peer(c).unwrap()
+}
+
+/// Fetch a peer like the reconnect loop does for every channel peer. The query is prepared once
+/// and reused, compare with a baseline from before to see the effect of statement caching.
+pub fn fetch_peer(c: &mut Criterion) -> Result<()> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_io()
+        .enable_time()
+        .build()?;
+    let public_key = PublicKey::from_str(TEST_PUBLIC_KEY)?;
+
+    let (_cockroach, db) = runtime.block_on(async {
+        let mut settings = test_settings(env!("CARGO_TARGET_TMPDIR"), "bench_peer");
+        let cockroach = cockroach!(settings);
+        migrate_database(&settings).await?;
+        let db = LdkDatabase::new(&settings).await?;
+        db.persist_peer(
+            &public_key,
+            &NetAddress::IPv4 {
+                addr: [127, 0, 0, 1],
+                port: 9735,
+            },
+        )
+        .await?;
+        Ok::<(CockroachManager, LdkDatabase), Error>((cockroach, db))
+    })?;
+
+    c.bench_function("fetch_peer", |b| {
+        b.iter(|| runtime.block_on(db.fetch_peer(&public_key)).unwrap());
+    });
+    Ok(())
+}
+
+/// Verify the readonly macaroon once with a fresh MacaroonAuth and repeatedly with a cached
+/// verification, like the API does for every request.
+pub fn verify_macaroon(c: &mut Criterion) -> Result<()> {
+    let data_dir = format!("{}/bench_macaroon", env!("CARGO_TARGET_TMPDIR"));
+    let macaroon_auth = MacaroonAuth::init(&[0u8; 32], &data_dir)?;
+    let macaroon = Macaroon::deserialize(std::fs::read(format!(
+        "{data_dir}/macaroons/readonly.macaroon"
+    ))?)?;
+
+    c.bench_function("verify_macaroon_uncached", |b| {
+        b.iter_batched_ref(
+            || MacaroonAuth::init(&[0u8; 32], &data_dir).unwrap(),
+            |macaroon_auth| macaroon_auth.verify_readonly_macaroon(&macaroon).unwrap(),
+            BatchSize::SmallInput,
+        );
+    });
+    c.bench_function("verify_macaroon_cached", |b| {
+        b.iter(|| macaroon_auth.verify_readonly_macaroon(&macaroon).unwrap());
+    });
+    Ok(())
+}
+
 /// Send one payment between two nodes with two cockroach instances.
 /// The functional_test_utils just calls the message handlers on each node, no network involved.
 pub fn send_payment_two_nodes(c: &mut Criterion) -> Result<()> {
@@ -35,10 +102,10 @@ pub fn send_payment_two_nodes(c: &mut Criterion) -> Result<()> {
         let mut settings_0 = test_settings(env!("CARGO_TARGET_TMPDIR"), "bench_1");
         let cockroach_0 = cockroach!(settings_0);
         let db_0 = LdkDatabase::new(&settings_0).await?;
-        migrate_database(&settings_0).await;
+        migrate_database(&settings_0).await?;
         let mut settings_1 = test_settings(env!("CARGO_TARGET_TMPDIR"), "bench_2");
         let cockroach_1 = cockroach!(settings_1);
-        migrate_database(&settings_1).await;
+        migrate_database(&settings_1).await?;
         let db_1 = LdkDatabase::new(&settings_1).await?;
         Ok::<(CockroachManager, LdkDatabase, CockroachManager, LdkDatabase), Error>((
             cockroach_0,
@@ -0,0 +1,90 @@
+use anyhow::{anyhow, Context};
+use api::{BatchRequest, BatchResponse};
+use axum::{
+    body::Body,
+    http::{header, Method, Request},
+    response::IntoResponse,
+    Extension, Json, Router,
+};
+use futures::future::poll_fn;
+use hyper::service::Service;
+use std::sync::Arc;
+
+use super::{bad_request, internal_server, unauthorized, ApiError, KldMacaroon, MacaroonAuth};
+
+/// Upper bound on the sub-requests of a single batch.
+const MAX_BATCH_SIZE: usize = 100;
+
+/// The API routes which can be part of a batch.
+#[derive(Clone)]
+pub(crate) struct BatchRouter(pub Router);
+
+/// Dispatch every sub-request to the API router in order. Sub-requests are authorised with the
+/// macaroon of the batch, a request that the macaroon is not valid for gets a 401 response and the
+/// others still run. The batch is rejected before anything runs if a sub-request is malformed.
+pub(crate) async fn batch(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(BatchRouter(router)): Extension<BatchRouter>,
+    Json(requests): Json<Vec<BatchRequest>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    if requests.len() > MAX_BATCH_SIZE {
+        return Err(bad_request(anyhow!(
+            "Batch contains {} requests, at most {MAX_BATCH_SIZE} are allowed",
+            requests.len()
+        )));
+    }
+    let serialized_macaroon = macaroon
+        .0
+        .serialize(macaroon::Format::V2)
+        .map_err(internal_server)?;
+
+    let mut sub_requests = vec![];
+    for request in requests {
+        let method = Method::from_bytes(request.method.to_uppercase().as_bytes())
+            .with_context(|| format!("Invalid method {}", request.method))
+            .map_err(bad_request)?;
+        let body = match request.body {
+            Some(body) => Body::from(serde_json::to_vec(&body).map_err(internal_server)?),
+            None => Body::empty(),
+        };
+        sub_requests.push(
+            Request::builder()
+                .method(method)
+                .uri(&request.path)
+                .header("macaroon", &serialized_macaroon)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body)
+                .with_context(|| format!("Invalid request {} {}", request.method, request.path))
+                .map_err(bad_request)?,
+        );
+    }
+
+    let mut responses = vec![];
+    for sub_request in sub_requests {
+        let mut router = router.clone();
+        poll_fn(|cx| router.poll_ready(cx))
+            .await
+            .map_err(internal_server)?;
+        let response = router.call(sub_request).await.map_err(internal_server)?;
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(internal_server)?;
+        let body = if bytes.is_empty() {
+            serde_json::Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap_or_else(|_| {
+                serde_json::Value::String(String::from_utf8_lossy(&bytes).to_string())
+            })
+        };
+        responses.push(BatchResponse {
+            status: status.as_u16(),
+            body,
+        });
+    }
+    Ok(Json(responses))
+}
@@ -1,26 +1,58 @@
 use std::collections::HashMap;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
 
+use anyhow::anyhow;
+use api::AbandonChannel;
+use api::AbandonChannelResponse;
 use api::Channel;
+use api::ChannelEarnings;
+use api::ChannelEarningsParams;
 use api::ChannelFee;
+use api::ChannelFeeReport;
+use api::ChannelPolicy;
+use api::ChannelStats;
+use api::ClosedChannel;
+use api::ErrorCode;
 use api::FundChannel;
 use api::FundChannelResponse;
+use api::FundingPsbt;
+use api::OpenChannelParams;
+use api::OpenChannelPreview;
+use api::OpenChannelPsbt;
+use api::OpenChannelPsbtResponse;
+use api::PendingChannelOpen;
 use api::SetChannelFee;
+use api::SetChannelFeeParams;
 use api::SetChannelFeeResponse;
-use axum::extract::Path;
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
 use axum::{response::IntoResponse, Extension, Json};
+use base64::{engine::general_purpose, Engine};
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::secp256k1::constants::PUBLIC_KEY_SIZE;
 use bitcoin::secp256k1::PublicKey;
+use bitcoin::util::psbt::PartiallySignedTransaction;
 use hex::ToHex;
 use lightning::ln::channelmanager::ChannelDetails;
+use lightning::routing::gossip::NodeId;
+use log::warn;
+use rand::random;
+use settings::Settings;
 
 use crate::api::bad_request;
+use crate::database::forward::EarningsBucket;
+use crate::ldk::check_channel_minimum_depth;
 use crate::ldk::net_utils::PeerAddress;
 use crate::ldk::LightningInterface;
+use crate::ldk::OpenChannelResult;
 use crate::ldk::PeerStatus;
 use crate::to_string_empty;
+use crate::wallet::WalletInterface;
 
 use super::internal_server;
+use super::parse_pubkey;
 use super::unauthorized;
 use super::ApiError;
 use super::KldMacaroon;
@@ -40,66 +72,274 @@ pub(crate) async fn list_channels(
         .await
         .map_err(internal_server)?;
 
+    let stats = lightning_interface
+        .channel_stats()
+        .await
+        .map_err(internal_server)?;
+
     let channels: Vec<Channel> = lightning_interface
         .list_channels()
         .iter()
-        .map(|c| Channel {
-            id: c.counterparty.node_id.to_string(),
-            connected: peers
+        .map(|c| {
+            let channel_stats = stats
                 .iter()
-                .find(|p| p.public_key == c.counterparty.node_id)
-                .map(|p| p.status == PeerStatus::Connected)
-                .unwrap_or_default()
+                .find(|s| s.channel_id == c.channel_id)
+                .cloned()
+                .unwrap_or_default();
+            Channel {
+                id: c.counterparty.node_id.to_string(),
+                connected: peers
+                    .iter()
+                    .find(|p| p.public_key == c.counterparty.node_id)
+                    .map(|p| p.status == PeerStatus::Connected)
+                    .unwrap_or_default()
+                    .to_string(),
+                state: (if c.is_usable {
+                    "usable"
+                } else if c.is_channel_ready {
+                    "ready"
+                } else {
+                    "pending"
+                })
                 .to_string(),
-            state: (if c.is_usable {
-                "usable"
-            } else if c.is_channel_ready {
-                "ready"
-            } else {
-                "pending"
-            })
-            .to_string(),
-            short_channel_id: to_string_empty!(c.short_channel_id),
+                short_channel_id: to_string_empty!(c.short_channel_id),
+                channel_id: c.channel_id.encode_hex(),
+                funding_txid: to_string_empty!(c.funding_txo.map(|x| x.txid)),
+                private: (!c.is_public).to_string(),
+                msatoshi_to_us: c.outbound_capacity_msat.to_string(),
+                msatoshi_total: c.channel_value_satoshis.to_string(),
+                msatoshi_to_them: c.inbound_capacity_msat.to_string(),
+                their_channel_reserve_satoshis: c
+                    .counterparty
+                    .unspendable_punishment_reserve
+                    .to_string(),
+                our_channel_reserve_satoshis: to_string_empty!(c.unspendable_punishment_reserve),
+                spendable_msatoshi: c.outbound_capacity_msat.to_string(),
+                direction: u8::from(c.is_outbound),
+                alias: lightning_interface
+                    .alias_of(&c.counterparty.node_id)
+                    .unwrap_or_default(),
+                confirmations: c.confirmations,
+                confirmations_required: c.confirmations_required,
+                htlcs_attempted: channel_stats.htlcs_attempted,
+                htlcs_succeeded: channel_stats.htlcs_succeeded,
+                htlcs_failed: channel_stats.htlcs_failed,
+            }
+        })
+        .collect();
+    Ok(Json(channels))
+}
+
+pub(crate) async fn list_closed_channels(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let channels: Vec<ClosedChannel> = lightning_interface
+        .list_closed_channels()
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(|c| ClosedChannel {
             channel_id: c.channel_id.encode_hex(),
-            funding_txid: to_string_empty!(c.funding_txo.map(|x| x.txid)),
-            private: (!c.is_public).to_string(),
-            msatoshi_to_us: c.outbound_capacity_msat.to_string(),
-            msatoshi_total: c.channel_value_satoshis.to_string(),
-            msatoshi_to_them: c.inbound_capacity_msat.to_string(),
-            their_channel_reserve_satoshis: c
-                .counterparty
-                .unspendable_punishment_reserve
-                .to_string(),
-            our_channel_reserve_satoshis: to_string_empty!(c.unspendable_punishment_reserve),
-            spendable_msatoshi: c.outbound_capacity_msat.to_string(),
-            direction: u8::from(c.is_outbound),
-            alias: lightning_interface
-                .alias_of(&c.counterparty.node_id)
-                .unwrap_or_default(),
+            counterparty_node_id: c.counterparty_node_id.map(|id| id.to_string()),
+            funding_txo: c.funding_txo.map(|txo| txo.to_string()),
+            reason: c.reason,
+            closing_txid: c.closing_txid.map(|txid| txid.to_string()),
+            sweep_txid: c.sweep_txid.map(|txid| txid.to_string()),
+            closed_at: c
+                .closed_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
         })
         .collect();
     Ok(Json(channels))
 }
 
+pub(crate) async fn get_channel_stats(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let channel = find_channel(lightning_interface.as_ref(), id)?;
+    let channel_stats = lightning_interface
+        .channel_stats()
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .find(|s| s.channel_id == channel.channel_id)
+        .unwrap_or_default();
+
+    Ok(Json(ChannelStats {
+        channel_id: channel.channel_id.encode_hex(),
+        short_channel_id: to_string_empty!(channel.short_channel_id),
+        htlcs_attempted: channel_stats.htlcs_attempted,
+        htlcs_succeeded: channel_stats.htlcs_succeeded,
+        htlcs_failed: channel_stats.htlcs_failed,
+    }))
+}
+
+pub(crate) async fn get_channel_earnings(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+    Query(params): Query<ChannelEarningsParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let bucket = params
+        .bucket
+        .as_deref()
+        .map(EarningsBucket::from_str)
+        .transpose()
+        .map_err(bad_request)?
+        .unwrap_or(EarningsBucket::Day);
+    let channel = find_channel(lightning_interface.as_ref(), id)?;
+    // A channel can't have forwarded anything before it has a short channel id.
+    let earnings = match channel.short_channel_id {
+        Some(short_channel_id) => lightning_interface
+            .channel_earnings(short_channel_id, bucket)
+            .await
+            .map_err(internal_server)?,
+        None => vec![],
+    };
+    let earnings: Vec<ChannelEarnings> = earnings
+        .into_iter()
+        .map(|e| ChannelEarnings {
+            bucket_start: e
+                .bucket_start
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            inbound_fee_msat: e.inbound_fee_msat,
+            outbound_fee_msat: e.outbound_fee_msat,
+            inbound_forwards: e.inbound_forwards,
+            outbound_forwards: e.outbound_forwards,
+        })
+        .collect();
+    Ok(Json(earnings))
+}
+
+/// Look up an open channel by channel id or short channel id.
+fn find_channel(
+    lightning_interface: &(dyn LightningInterface + Send + Sync),
+    id: String,
+) -> Result<ChannelDetails, ApiError> {
+    lightning_interface
+        .list_channels()
+        .into_iter()
+        .find(|c| {
+            c.channel_id.encode_hex::<String>() == id
+                || c.short_channel_id.unwrap_or_default().to_string() == id
+        })
+        .ok_or(ApiError::NotFound(id))
+}
+
+pub(crate) async fn fee_report(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let report: Vec<ChannelFeeReport> = lightning_interface
+        .list_channels()
+        .iter()
+        .map(|c| ChannelFeeReport {
+            channel_id: c.channel_id.encode_hex(),
+            short_channel_id: to_string_empty!(c.short_channel_id),
+            peer_id: c.counterparty.node_id.to_string(),
+            local_policy: c.config.map(|config| ChannelPolicy {
+                base_fee_msat: config.forwarding_fee_base_msat,
+                ppm_fee: config.forwarding_fee_proportional_millionths,
+                cltv_expiry_delta: config.cltv_expiry_delta,
+                htlc_minimum_msat: c.counterparty.outbound_htlc_minimum_msat,
+                htlc_maximum_msat: c.counterparty.outbound_htlc_maximum_msat,
+            }),
+            remote_policy: c
+                .short_channel_id
+                .and_then(|scid| lightning_interface.get_channel(scid))
+                .and_then(|info| {
+                    let peer = NodeId::from_pubkey(&c.counterparty.node_id);
+                    if info.node_one == peer {
+                        info.one_to_two
+                    } else {
+                        info.two_to_one
+                    }
+                })
+                .map(|update| ChannelPolicy {
+                    base_fee_msat: update.fees.base_msat,
+                    ppm_fee: update.fees.proportional_millionths,
+                    cltv_expiry_delta: update.cltv_expiry_delta,
+                    htlc_minimum_msat: Some(update.htlc_minimum_msat),
+                    htlc_maximum_msat: Some(update.htlc_maximum_msat),
+                }),
+            max_dust_htlc_exposure_msat: c.config.map(|config| config.max_dust_htlc_exposure_msat),
+        })
+        .collect();
+    Ok(Json(report))
+}
+
+/// Channel opens started with async=true by tracking id.
+#[derive(Default)]
+pub(crate) struct PendingChannelOpens(Mutex<HashMap<String, PendingChannelOpen>>);
+
+const MAX_PENDING_CHANNEL_OPENS: usize = 1000;
+
+impl PendingChannelOpens {
+    fn insert(&self, pending: PendingChannelOpen) {
+        let mut opens = self.0.lock().unwrap();
+        if opens.len() >= MAX_PENDING_CHANNEL_OPENS {
+            opens.retain(|_, open| open.status == "pending");
+        }
+        opens.insert(pending.id.clone(), pending);
+    }
+
+    fn get(&self, id: &str) -> Option<PendingChannelOpen> {
+        self.0.lock().unwrap().get(id).cloned()
+    }
+}
+
 pub(crate) async fn open_channel(
     macaroon: KldMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(pending_opens): Extension<Arc<PendingChannelOpens>>,
+    Query(params): Query<OpenChannelParams>,
     Json(fund_channel): Json<FundChannel>,
 ) -> Result<impl IntoResponse, ApiError> {
     macaroon_auth
         .verify_admin_macaroon(&macaroon.0)
         .map_err(unauthorized)?;
 
+    if lightning_interface.maintenance_mode() {
+        return Err(ApiError::ServiceUnavailable(
+            ErrorCode::MaintenanceMode,
+            "Node is in maintenance mode".to_string(),
+        ));
+    }
+
     let (public_key, net_address) = match fund_channel.id.split_once('@') {
         Some((public_key, net_address)) => (
-            PublicKey::from_str(public_key).map_err(bad_request)?,
+            parse_pubkey(public_key)?,
             Some(net_address.parse::<PeerAddress>().map_err(bad_request)?),
         ),
-        None => (
-            PublicKey::from_str(&fund_channel.id).map_err(bad_request)?,
-            None,
-        ),
+        None => (parse_pubkey(&fund_channel.id)?, None),
     };
     lightning_interface
         .connect_peer(public_key, net_address)
@@ -117,6 +357,51 @@ pub(crate) async fn open_channel(
     if let Some(announce) = fund_channel.announce {
         user_config.channel_handshake_config.announced_channel = announce;
     }
+    if let Some(minimum_depth) = fund_channel.minimum_depth {
+        check_channel_minimum_depth(minimum_depth).map_err(bad_request)?;
+        user_config.channel_handshake_config.minimum_depth = minimum_depth;
+    }
+
+    if params.run_async.unwrap_or_default() {
+        let id: String = random::<[u8; 16]>().encode_hex();
+        let pending = PendingChannelOpen {
+            id: id.clone(),
+            status: "pending".to_string(),
+            result: None,
+            error: None,
+        };
+        pending_opens.insert(pending.clone());
+        tokio::spawn(async move {
+            let result = lightning_interface
+                .open_channel(
+                    public_key,
+                    value,
+                    push_msat,
+                    fund_channel.fee_rate,
+                    Some(user_config),
+                )
+                .await;
+            let pending = match result {
+                Ok(result) => PendingChannelOpen {
+                    id,
+                    status: "funded".to_string(),
+                    result: Some(to_fund_channel_response(result)),
+                    error: None,
+                },
+                Err(e) => {
+                    warn!("Channel open with {public_key} failed: {e}");
+                    PendingChannelOpen {
+                        id,
+                        status: "failed".to_string(),
+                        result: None,
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+            pending_opens.insert(pending);
+        });
+        return Ok((StatusCode::ACCEPTED, Json(pending)).into_response());
+    }
 
     let result = lightning_interface
         .open_channel(
@@ -128,25 +413,208 @@ pub(crate) async fn open_channel(
         )
         .await
         .map_err(internal_server)?;
+    Ok(Json(to_fund_channel_response(result)).into_response())
+}
+
+/// LDK never uses a lower dust limit for its commitment transactions.
+const DUST_LIMIT_SATOSHIS: u64 = 354;
+/// LDK doesn't select or accept channel reserves below this.
+const MIN_CHANNEL_RESERVE_SATOSHIS: u64 = 1000;
+/// The channel reserve recommended by BOLT 2.
+const DEFAULT_RESERVE_PROPORTIONAL_MILLIONTHS: u32 = 10_000;
+/// No channel can be larger than all the bitcoin there is, which also keeps the msat amounts of
+/// the preview from overflowing.
+const MAX_MONEY_SATOSHIS: u64 = 21_000_000 * 100_000_000;
+
+pub(crate) async fn open_channel_preview(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+    Extension(settings): Extension<Arc<Settings>>,
+    Json(fund_channel): Json<FundChannel>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let public_key = fund_channel
+        .id
+        .split_once('@')
+        .map(|(public_key, _)| public_key)
+        .unwrap_or(&fund_channel.id);
+    parse_pubkey(public_key)?;
+    let channel_size_sat = fund_channel.satoshis.parse::<u64>().map_err(bad_request)?;
+    if channel_size_sat > MAX_MONEY_SATOSHIS {
+        return Err(bad_request(anyhow!(
+            "Channel size is larger than the bitcoin supply"
+        )));
+    }
+    let push_msat = fund_channel
+        .push_msat
+        .map(|x| x.parse::<u64>())
+        .transpose()
+        .map_err(bad_request)?
+        .unwrap_or_default();
+    if push_msat > channel_size_sat * 1000 {
+        return Err(bad_request(anyhow!(
+            "Can't push more than the channel size"
+        )));
+    }
+
+    let remote_reserve_proportional_millionths = lightning_interface
+        .user_config()
+        .channel_handshake_config
+        .their_channel_reserve_proportional_millionths;
+    let local_reserve_sat =
+        channel_reserve(channel_size_sat, DEFAULT_RESERVE_PROPORTIONAL_MILLIONTHS);
+    let remote_reserve_sat =
+        channel_reserve(channel_size_sat, remote_reserve_proportional_millionths);
+    let local_balance_msat = channel_size_sat * 1000 - push_msat;
+    let funding_fee_sat = wallet.estimate_funding_fee(fund_channel.fee_rate.unwrap_or_default());
+    let confirmed_balance_sat = wallet.balance().map_err(internal_server)?.confirmed;
+    Ok(Json(OpenChannelPreview {
+        channel_size_sat,
+        local_balance_msat,
+        remote_balance_msat: push_msat,
+        local_reserve_sat,
+        remote_reserve_sat,
+        spendable_msat: local_balance_msat.saturating_sub(local_reserve_sat * 1000),
+        receivable_msat: push_msat.saturating_sub(remote_reserve_sat * 1000),
+        dust_limit_sat: DUST_LIMIT_SATOSHIS,
+        funding_fee_sat,
+        confirmed_balance_sat,
+        sufficient_funds: confirmed_balance_sat
+            >= channel_size_sat
+                .saturating_add(funding_fee_sat)
+                .saturating_add(settings.wallet_reserve_sats),
+    }))
+}
+
+fn channel_reserve(channel_size_sat: u64, proportional_millionths: u32) -> u64 {
+    ((channel_size_sat as u128 * proportional_millionths as u128 / 1_000_000) as u64)
+        .max(MIN_CHANNEL_RESERVE_SATOSHIS)
+        .min(channel_size_sat)
+}
+
+pub(crate) async fn open_channel_psbt(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(open_channel): Json<OpenChannelPsbt>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    if lightning_interface.maintenance_mode() {
+        return Err(ApiError::ServiceUnavailable(
+            ErrorCode::MaintenanceMode,
+            "Node is in maintenance mode".to_string(),
+        ));
+    }
+
+    let (public_key, net_address) = match open_channel.id.split_once('@') {
+        Some((public_key, net_address)) => (
+            parse_pubkey(public_key)?,
+            Some(net_address.parse::<PeerAddress>().map_err(bad_request)?),
+        ),
+        None => (parse_pubkey(&open_channel.id)?, None),
+    };
+    lightning_interface
+        .connect_peer(public_key, net_address)
+        .await
+        .map_err(internal_server)?;
+
+    let value = open_channel.satoshis.parse::<u64>().map_err(bad_request)?;
+    let push_msat = open_channel
+        .push_msat
+        .map(|x| x.parse::<u64>())
+        .transpose()
+        .map_err(bad_request)?;
+    let mut user_config = lightning_interface.user_config();
+    if let Some(announce) = open_channel.announce {
+        user_config.channel_handshake_config.announced_channel = announce;
+    }
+
+    let result = lightning_interface
+        .open_channel_psbt(public_key, value, push_msat, Some(user_config))
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(OpenChannelPsbtResponse {
+        channel_id: result.channel_id.encode_hex(),
+        address: result.address.to_string(),
+        satoshis: value,
+        psbt: general_purpose::STANDARD.encode(serialize(&result.psbt)),
+    }))
+}
+
+pub(crate) async fn funding_psbt(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(funding_psbt): Json<FundingPsbt>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let psbt = general_purpose::STANDARD
+        .decode(&funding_psbt.psbt)
+        .map_err(|e| anyhow!(e))
+        .and_then(|bytes| Ok(deserialize::<PartiallySignedTransaction>(&bytes)?))
+        .map_err(|e| bad_request(anyhow!("Invalid PSBT: {e}")))?;
+    let channel = lightning_interface
+        .list_channels()
+        .into_iter()
+        .find(|c| c.channel_id.encode_hex::<String>() == funding_psbt.channel_id)
+        .ok_or_else(|| ApiError::NotFound(funding_psbt.channel_id.clone()))?;
+    let result = lightning_interface
+        .fund_channel_psbt(&channel.channel_id, psbt)
+        .await
+        .map_err(bad_request)?;
+    Ok(Json(to_fund_channel_response(result)))
+}
 
-    let response = FundChannelResponse {
+pub(crate) async fn get_pending_channel_open(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(pending_opens): Extension<Arc<PendingChannelOpens>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    pending_opens
+        .get(&id)
+        .map(Json)
+        .ok_or(ApiError::NotFound(id))
+}
+
+fn to_fund_channel_response(result: OpenChannelResult) -> FundChannelResponse {
+    FundChannelResponse {
         tx: result.transaction,
         txid: result.txid.to_string(),
         channel_id: result.channel_id.encode_hex(),
-    };
-    Ok(Json(response))
+    }
 }
 
 pub(crate) async fn set_channel_fee(
     macaroon: KldMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(settings): Extension<Arc<Settings>>,
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<SetChannelFeeParams>,
     Json(channel_fee): Json<ChannelFee>,
 ) -> Result<impl IntoResponse, ApiError> {
     macaroon_auth
         .verify_admin_macaroon(&macaroon.0)
         .map_err(unauthorized)?;
 
+    if !params.force.unwrap_or_default() {
+        check_fee_bounds(&settings, &channel_fee).map_err(bad_request)?;
+    }
+
     let mut updated_channels = vec![];
 
     if channel_fee.id == "all" {
@@ -159,20 +627,29 @@ pub(crate) async fn set_channel_fee(
             }
         }
         for (node_id, channels) in peer_channels {
-            let channel_ids: Vec<[u8; 32]> = channels.iter().map(|c| c.channel_id).collect();
-            let (base, ppm) = lightning_interface
-                .set_channel_fee(&node_id, &channel_ids, channel_fee.ppm, channel_fee.base)
-                .map_err(internal_server)?;
-            for channel in channels {
-                updated_channels.push(SetChannelFee {
-                    base,
-                    ppm,
-                    peer_id: node_id.to_string(),
-                    channel_id: channel.channel_id.encode_hex(),
-                    short_channel_id: to_string_empty!(channel.short_channel_id),
-                });
-            }
+            updated_channels.extend(set_peer_channel_fees(
+                lightning_interface.as_ref(),
+                &node_id,
+                &channels,
+                &channel_fee,
+            )?);
+        }
+    } else if channel_fee.id.len() == PUBLIC_KEY_SIZE * 2 {
+        let node_id = parse_pubkey(&channel_fee.id)?;
+        let channels: Vec<ChannelDetails> = lightning_interface
+            .list_channels()
+            .into_iter()
+            .filter(|c| c.counterparty.node_id == node_id)
+            .collect();
+        if channels.is_empty() {
+            return Err(ApiError::NotFound(channel_fee.id));
         }
+        updated_channels.extend(set_peer_channel_fees(
+            lightning_interface.as_ref(),
+            &node_id,
+            &channels,
+            &channel_fee,
+        )?);
     } else if let Some(channel) = lightning_interface.list_channels().iter().find(|c| {
         c.channel_id.encode_hex::<String>() == channel_fee.id
             || c.short_channel_id.unwrap_or_default().to_string() == channel_fee.id
@@ -199,6 +676,50 @@ pub(crate) async fn set_channel_fee(
     Ok(Json(SetChannelFeeResponse(updated_channels)))
 }
 
+// Update all the given channels with one peer in a single call.
+fn set_peer_channel_fees(
+    lightning_interface: &(dyn LightningInterface + Send + Sync),
+    node_id: &PublicKey,
+    channels: &[ChannelDetails],
+    channel_fee: &ChannelFee,
+) -> Result<Vec<SetChannelFee>, ApiError> {
+    let channel_ids: Vec<[u8; 32]> = channels.iter().map(|c| c.channel_id).collect();
+    let (base, ppm) = lightning_interface
+        .set_channel_fee(node_id, &channel_ids, channel_fee.ppm, channel_fee.base)
+        .map_err(internal_server)?;
+    Ok(channels
+        .iter()
+        .map(|channel| SetChannelFee {
+            base,
+            ppm,
+            peer_id: node_id.to_string(),
+            channel_id: channel.channel_id.encode_hex(),
+            short_channel_id: to_string_empty!(channel.short_channel_id),
+        })
+        .collect())
+}
+
+// Guard against fat-fingered fees that would make a channel unusable for routing.
+fn check_fee_bounds(settings: &Settings, channel_fee: &ChannelFee) -> anyhow::Result<()> {
+    if let Some(base) = channel_fee.base {
+        if base > settings.max_channel_fee_base_msat {
+            return Err(anyhow!(
+                "Base fee {base} msat exceeds the maximum of {} msat. Use force=true to set it anyway.",
+                settings.max_channel_fee_base_msat
+            ));
+        }
+    }
+    if let Some(ppm) = channel_fee.ppm {
+        if ppm > settings.max_channel_fee_ppm {
+            return Err(anyhow!(
+                "Fee rate {ppm} ppm exceeds the maximum of {} ppm. Use force=true to set it anyway.",
+                settings.max_channel_fee_ppm
+            ));
+        }
+    }
+    Ok(())
+}
+
 pub(crate) async fn close_channel(
     macaroon: KldMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
@@ -222,3 +743,50 @@ pub(crate) async fn close_channel(
         Err(ApiError::NotFound(channel_id))
     }
 }
+
+pub(crate) async fn abandon_channel(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+    Json(abandon_channel): Json<AbandonChannel>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let channel = lightning_interface
+        .list_channels()
+        .into_iter()
+        .find(|c| c.channel_id.encode_hex::<String>() == abandon_channel.id)
+        .ok_or_else(|| ApiError::NotFound(abandon_channel.id.clone()))?;
+    if channel.is_channel_ready {
+        return Err(bad_request(anyhow!(
+            "Channel {} is not pending",
+            abandon_channel.id
+        )));
+    }
+    // Abandon the funding transaction first. This fails if it could still confirm, in which case
+    // the channel must be left alone.
+    let freed_outpoints = match channel.funding_txo {
+        Some(funding_txo) => wallet
+            .abandon_transaction(funding_txo.txid)
+            .await
+            .map_err(internal_server)?,
+        None => vec![],
+    };
+    lightning_interface
+        .abandon_channel(&channel.channel_id, &channel.counterparty.node_id)
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(AbandonChannelResponse {
+        freed_outpoints: freed_outpoints.iter().map(|o| o.to_string()).collect(),
+    }))
+}
+
+#[test]
+fn test_channel_reserve() {
+    assert_eq!(1000, channel_reserve(20_000, 10_000));
+    assert_eq!(10_000, channel_reserve(1_000_000, 10_000));
+    assert_eq!(500, channel_reserve(500, 10_000));
+}
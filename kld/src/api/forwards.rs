@@ -0,0 +1,104 @@
+use std::{
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use api::{ExportForwardsParams, ListForwardsParams};
+use axum::{
+    body::StreamBody,
+    extract::Query,
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::IntoResponse,
+    Extension, Json,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use hex::ToHex;
+
+use crate::{database::forward::Forward, ldk::LightningInterface};
+
+use super::{internal_server, unauthorized, ApiError, KldMacaroon, MacaroonAuth};
+
+const CSV_HEADER: &str = "timestamp,in_scid,out_scid,amount_in_msat,amount_out_msat,fee_msat\n";
+
+pub(crate) async fn export_forwards(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ExportForwardsParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let to_time = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+    let forwards = lightning_interface
+        .forwards(params.since.map(to_time), params.until.map(to_time))
+        .await
+        .map_err(internal_server)?;
+    let rows = stream::once(async { Ok(CSV_HEADER.to_string()) })
+        .chain(forwards.map(|forward| forward.map(to_csv_row)));
+    Ok((
+        [
+            (CONTENT_TYPE, "text/csv"),
+            (CONTENT_DISPOSITION, "attachment; filename=\"forwards.csv\""),
+        ],
+        StreamBody::new(rows),
+    ))
+}
+
+pub(crate) async fn list_forwards(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ListForwardsParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let to_time = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+    let forwards: Vec<api::Forward> = lightning_interface
+        .forwards(params.since.map(to_time), params.until.map(to_time))
+        .await
+        .map_err(internal_server)?
+        .take(params.limit.unwrap_or(usize::MAX))
+        .map_ok(to_api_forward)
+        .try_collect()
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(forwards))
+}
+
+fn to_api_forward(forward: Forward) -> api::Forward {
+    api::Forward {
+        in_channel_id: forward.in_channel_id.map(|id| id.encode_hex()),
+        out_channel_id: forward.out_channel_id.map(|id| id.encode_hex()),
+        in_short_channel_id: forward.in_scid,
+        out_short_channel_id: forward.out_scid,
+        amount_in_msat: forward.amount_in_msat,
+        amount_out_msat: forward.amount_out_msat,
+        fee_msat: forward.fee_msat,
+        timestamp: forward
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    }
+}
+
+fn to_csv_row(forward: Forward) -> String {
+    let field = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+    format!(
+        "{},{},{},{},{},{}\n",
+        forward
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        field(forward.in_scid),
+        field(forward.out_scid),
+        field(forward.amount_in_msat),
+        field(forward.amount_out_msat),
+        field(forward.fee_msat)
+    )
+}
@@ -0,0 +1,117 @@
+use std::{sync::Arc, time::UNIX_EPOCH};
+
+use api::{FailHtlc, ForwardHtlc, Htlc, InterceptScid, InterceptedHtlc};
+use axum::{response::IntoResponse, Extension, Json};
+use hex::{FromHex, ToHex};
+
+use crate::ldk::LightningInterface;
+
+use super::{bad_request, internal_server, unauthorized, ApiError, KldMacaroon, MacaroonAuth};
+
+pub(crate) async fn get_intercept_scid(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    Ok(Json(InterceptScid {
+        short_channel_id: lightning_interface.intercept_scid(),
+    }))
+}
+
+pub(crate) async fn forward_htlc(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(forward_htlc): Json<ForwardHtlc>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let intercept_id = <[u8; 32]>::from_hex(&forward_htlc.intercept_id).map_err(bad_request)?;
+    let channel = lightning_interface
+        .list_channels()
+        .into_iter()
+        .find(|c| c.channel_id.encode_hex::<String>() == forward_htlc.channel_id)
+        .ok_or_else(|| ApiError::NotFound(forward_htlc.channel_id.clone()))?;
+    lightning_interface
+        .forward_intercepted_htlc(
+            intercept_id,
+            &channel.channel_id,
+            channel.counterparty.node_id,
+            forward_htlc.amount_msat,
+        )
+        .map_err(bad_request)?;
+    Ok(Json(()))
+}
+
+pub(crate) async fn fail_htlc(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(fail_htlc): Json<FailHtlc>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let intercept_id = <[u8; 32]>::from_hex(&fail_htlc.intercept_id).map_err(bad_request)?;
+    lightning_interface
+        .fail_intercepted_htlc(intercept_id)
+        .map_err(bad_request)?;
+    Ok(Json(()))
+}
+
+pub(crate) async fn list_intercepted_htlcs(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let htlcs: Vec<InterceptedHtlc> = lightning_interface
+        .list_intercepted_htlcs()
+        .into_iter()
+        .map(|htlc| InterceptedHtlc {
+            intercept_id: htlc.intercept_id.encode_hex(),
+            requested_next_hop_scid: htlc.requested_next_hop_scid,
+            payment_hash: htlc.payment_hash.0.encode_hex(),
+            inbound_amount_msat: htlc.inbound_amount_msat,
+            expected_outbound_amount_msat: htlc.expected_outbound_amount_msat,
+            intercepted_at: htlc
+                .intercepted_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        })
+        .collect();
+    Ok(Json(htlcs))
+}
+
+pub(crate) async fn list_htlcs(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let block_height = lightning_interface
+        .block_height()
+        .await
+        .map_err(internal_server)? as i64;
+    let htlcs: Vec<Htlc> = lightning_interface
+        .list_pending_htlcs()
+        .into_iter()
+        .map(|htlc| Htlc {
+            channel_id: htlc.channel_id.encode_hex(),
+            direction: htlc.direction.to_string(),
+            amount_sat: htlc.amount_sat,
+            cltv_expiry: htlc.cltv_expiry,
+            blocks_until_expiry: htlc.cltv_expiry as i64 - block_height,
+        })
+        .collect();
+    Ok(Json(htlcs))
+}
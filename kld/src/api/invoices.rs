@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use api::{GenerateInvoice, GenerateInvoiceResponse, ListPaymentsParams};
+use axum::{extract::Query, response::IntoResponse, Extension, Json};
+
+use crate::{database::payment::PaymentDirection, ldk::LightningInterface};
+
+use super::{
+    internal_server, payments::fetch_payments, unauthorized, ApiError, KldMacaroon, MacaroonAuth,
+};
+
+pub(crate) async fn generate_invoice(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(generate_invoice): Json<GenerateInvoice>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let invoice = lightning_interface
+        .generate_invoice(
+            generate_invoice.amount,
+            generate_invoice.description,
+            generate_invoice.expiry,
+            generate_invoice.fallback_address,
+        )
+        .await
+        .map_err(internal_server)?;
+    let expires_at = invoice
+        .duration_since_epoch()
+        .saturating_add(invoice.expiry_time())
+        .as_secs();
+    let response = GenerateInvoiceResponse {
+        payment_hash: invoice.payment_hash().to_string(),
+        expires_at,
+        bolt11: invoice.to_string(),
+    };
+    Ok(Json(response))
+}
+
+pub(crate) async fn list_invoices(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ListPaymentsParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let payments = fetch_payments(
+        lightning_interface.as_ref(),
+        PaymentDirection::Inbound,
+        params,
+    )
+    .await?;
+    Ok(Json(payments))
+}
@@ -5,6 +5,12 @@ use std::fs;
 #[cfg(test)]
 use test_utils::fake_fs as fs;
 
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
 use anyhow::Result;
 use axum::{
     async_trait,
@@ -13,8 +19,21 @@ use axum::{
 };
 use macaroon::{ByteString, Macaroon, MacaroonKey, Verifier};
 
+/// How long a successful verification is reused for.
+const CACHE_TTL: Duration = Duration::from_secs(10);
+/// Number of macaroon and role pairs to remember. Clients usually use one or two macaroons.
+const CACHE_SIZE: usize = 64;
+
 pub struct MacaroonAuth {
     key: MacaroonKey,
+    // Keyed by the serialized macaroon and the role it was verified for. The root key never
+    // changes for an instance, so entries only go stale by age.
+    verified: Mutex<HashMap<(String, &'static str), CachedVerification>>,
+}
+
+struct CachedVerification {
+    verified_at: Instant,
+    last_used: Instant,
 }
 
 impl MacaroonAuth {
@@ -24,6 +43,7 @@ impl MacaroonAuth {
 
         let admin_macaroon = Self::admin_macaroon(&key)?;
         let readonly_macaroon = Self::readonly_macaroon(&key)?;
+        let derivekey_macaroon = Self::derivekey_macaroon(&key)?;
 
         let mut buf = vec![];
         let base64 = admin_macaroon.serialize(macaroon::Format::V2)?;
@@ -41,20 +61,66 @@ impl MacaroonAuth {
             format!("{data_dir}/macaroons/readonly.macaroon"),
             readonly_macaroon.serialize(macaroon::Format::V2)?,
         )?;
+        // Not included in the admin macaroon, only for services which derive keys of the node.
+        fs::write(
+            format!("{data_dir}/macaroons/derivekey.macaroon"),
+            derivekey_macaroon.serialize(macaroon::Format::V2)?,
+        )?;
 
-        Ok(MacaroonAuth { key })
+        Ok(MacaroonAuth {
+            key,
+            verified: Mutex::new(HashMap::new()),
+        })
     }
 
     pub fn verify_admin_macaroon(&self, macaroon: &Macaroon) -> Result<()> {
-        let mut verifier = Verifier::default();
-        verifier.satisfy_general(|caveat| verify_role(caveat, "admin"));
-        Ok(verifier.verify(macaroon, &self.key, vec![])?)
+        self.verify_cached(macaroon, "admin")
     }
 
     pub fn verify_readonly_macaroon(&self, macaroon: &Macaroon) -> Result<()> {
+        self.verify_cached(macaroon, "readonly")
+    }
+
+    pub fn verify_derivekey_macaroon(&self, macaroon: &Macaroon) -> Result<()> {
+        self.verify_cached(macaroon, "derivekey")
+    }
+
+    fn verify_cached(&self, macaroon: &Macaroon, role: &'static str) -> Result<()> {
+        let cache_key = (macaroon.serialize(macaroon::Format::V2)?, role);
+        let now = Instant::now();
+        if let Some(cached) = self.verified.lock().unwrap().get_mut(&cache_key) {
+            if now.duration_since(cached.verified_at) < CACHE_TTL {
+                cached.last_used = now;
+                return Ok(());
+            }
+        }
         let mut verifier = Verifier::default();
-        verifier.satisfy_general(|caveat| verify_role(caveat, "readonly"));
-        Ok(verifier.verify(macaroon, &self.key, vec![])?)
+        verifier.satisfy_general(|caveat| verify_role(caveat, role));
+        verifier.verify(macaroon, &self.key, vec![])?;
+
+        let mut verified = self.verified.lock().unwrap();
+        if verified.len() >= CACHE_SIZE && !verified.contains_key(&cache_key) {
+            let least_recently_used = verified
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = least_recently_used {
+                verified.remove(&key);
+            }
+        }
+        verified.insert(
+            cache_key,
+            CachedVerification {
+                verified_at: now,
+                last_used: now,
+            },
+        );
+        Ok(())
+    }
+
+    /// The admin macaroon for requests on the local Unix socket.
+    pub(crate) fn local_admin_macaroon(&self) -> Result<Macaroon> {
+        Self::admin_macaroon(&self.key)
     }
 
     fn admin_macaroon(key: &MacaroonKey) -> Result<Macaroon> {
@@ -68,6 +134,12 @@ impl MacaroonAuth {
         macaroon.add_first_party_caveat("roles = readonly".into());
         Ok(macaroon)
     }
+
+    fn derivekey_macaroon(key: &MacaroonKey) -> Result<Macaroon> {
+        let mut macaroon = Macaroon::create(None, key, "derivekey".into())?;
+        macaroon.add_first_party_caveat("roles = derivekey".into());
+        Ok(macaroon)
+    }
 }
 
 fn verify_role(caveat: &ByteString, expected_role: &str) -> bool {
@@ -84,6 +156,11 @@ fn verify_role(caveat: &ByteString, expected_role: &str) -> bool {
 
 pub struct KldMacaroon(pub Macaroon);
 
+/// Attached to requests on the local Unix socket, where the permissions of the socket file take
+/// the place of the macaroon.
+#[derive(Clone)]
+pub(crate) struct UnixSocketMacaroon(pub Macaroon);
+
 #[async_trait]
 impl<S> FromRequestParts<S> for KldMacaroon
 where
@@ -93,6 +170,9 @@ where
 
     // May as well try to decode both base64 and hex macaroons.
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(UnixSocketMacaroon(macaroon)) = parts.extensions.get() {
+            return Ok(KldMacaroon(macaroon.clone()));
+        }
         let deserialize_err = (StatusCode::UNAUTHORIZED, "Unable to deserialize macaroon");
 
         let value = if let Some(value) = parts.headers.get(header::SEC_WEBSOCKET_PROTOCOL) {
@@ -145,3 +225,48 @@ fn test_admin_macaroon() {
         .verify_admin_macaroon(&admin_macaroon)
         .unwrap();
 }
+
+#[test]
+fn test_derivekey_macaroon() {
+    let macaroon_auth = MacaroonAuth::init(&[3u8; 32], "").unwrap();
+    let derivekey_macaroon = MacaroonAuth::derivekey_macaroon(&macaroon_auth.key).unwrap();
+    let admin_macaroon = MacaroonAuth::admin_macaroon(&macaroon_auth.key).unwrap();
+
+    macaroon_auth
+        .verify_derivekey_macaroon(&derivekey_macaroon)
+        .unwrap();
+    assert!(macaroon_auth
+        .verify_derivekey_macaroon(&admin_macaroon)
+        .is_err());
+    assert!(macaroon_auth
+        .verify_admin_macaroon(&derivekey_macaroon)
+        .is_err());
+}
+
+#[test]
+fn test_cached_macaroon_keeps_role() {
+    let macaroon_auth = MacaroonAuth::init(&[3u8; 32], "").unwrap();
+    let readonly_macaroon = MacaroonAuth::readonly_macaroon(&macaroon_auth.key).unwrap();
+
+    macaroon_auth
+        .verify_readonly_macaroon(&readonly_macaroon)
+        .unwrap();
+    macaroon_auth
+        .verify_readonly_macaroon(&readonly_macaroon)
+        .unwrap();
+    assert!(macaroon_auth
+        .verify_admin_macaroon(&readonly_macaroon)
+        .is_err());
+}
+
+#[test]
+fn test_macaroon_cache_is_bounded() {
+    let macaroon_auth = MacaroonAuth::init(&[3u8; 32], "").unwrap();
+    for i in 0..CACHE_SIZE + 1 {
+        let mut macaroon =
+            Macaroon::create(None, &macaroon_auth.key, format!("readonly{i}").into()).unwrap();
+        macaroon.add_first_party_caveat("roles = readonly".into());
+        macaroon_auth.verify_readonly_macaroon(&macaroon).unwrap();
+    }
+    assert_eq!(CACHE_SIZE, macaroon_auth.verified.lock().unwrap().len());
+}
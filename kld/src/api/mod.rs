@@ -1,32 +1,57 @@
+mod batch;
 mod channels;
+mod forwards;
+mod htlcs;
+mod invoices;
 mod macaroon_auth;
 mod network;
+mod payments;
 mod peers;
 mod utility;
 mod wallet;
+mod webhook;
 mod ws;
 
+use macaroon_auth::UnixSocketMacaroon;
 pub use macaroon_auth::{KldMacaroon, MacaroonAuth};
 use serde_json::json;
 
-use self::utility::get_info;
+use self::utility::{
+    announce, check_monitors, derive_key, export_state, get_info, list_pending_sweeps,
+    reset_scorer, set_alias, set_maintenance_mode, sign_message, verify_message,
+};
 use crate::{
     api::{
-        channels::{close_channel, list_channels, open_channel, set_channel_fee},
+        batch::{batch, BatchRouter},
+        channels::{
+            abandon_channel, close_channel, fee_report, funding_psbt, get_channel_earnings,
+            get_channel_stats, get_pending_channel_open, list_channels, list_closed_channels,
+            open_channel, open_channel_preview, open_channel_psbt, set_channel_fee,
+            PendingChannelOpens,
+        },
+        forwards::{export_forwards, list_forwards},
+        htlcs::{fail_htlc, forward_htlc, get_intercept_scid, list_htlcs, list_intercepted_htlcs},
+        invoices::{generate_invoice, list_invoices},
         network::{
-            get_network_channel, get_network_node, list_network_channels, list_network_nodes,
+            get_network_channel, get_network_node, get_network_stats, list_network_channels,
+            list_network_channels_between, list_network_nodes, prune_network_graph,
         },
-        peers::{connect_peer, disconnect_peer, list_peers},
-        wallet::{get_balance, new_address, transfer},
+        payments::{get_payment, list_payments, pay_lnurl},
+        peers::{connect_peer, disconnect_peer, forget_peer, list_peers},
+        wallet::{get_balance, import_descriptor, new_address, rescan, transfer},
+        webhook::list_pending_webhooks,
         ws::ws_handler,
     },
     ldk::LightningInterface,
     wallet::WalletInterface,
+    webhook::WebhookNotifier,
 };
-use anyhow::{Context, Result};
-use api::routes;
+use anyhow::{anyhow, bail, Context, Result};
+use api::{routes, CodedError, ErrorCode};
 use axum::{
     extract::Extension,
+    http::{Extensions, HeaderMap, Method, Request, Version},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::{delete, get, post},
     Json, Router,
@@ -35,78 +60,271 @@ use axum_server::{
     tls_rustls::{RustlsAcceptor, RustlsConfig},
     Handle, Server,
 };
-use futures::{future::Shared, Future};
-use hyper::StatusCode;
+use bitcoin::secp256k1::PublicKey;
+use futures::{
+    future::{try_join, try_join_all, Shared},
+    stream, Future, TryFutureExt,
+};
+use hyper::{server::accept, StatusCode};
 use log::{error, info, warn};
-use std::{net::SocketAddr, sync::Arc, time::Duration};
-use tower_http::cors::CorsLayer;
+use settings::Settings;
+use std::{
+    fs,
+    net::SocketAddr,
+    os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::net::UnixListener;
+use tower_http::{
+    compression::{
+        predicate::{DefaultPredicate, Predicate},
+        CompressionLayer,
+    },
+    cors::CorsLayer,
+};
 
 pub struct RestApi {
-    server: Server<RustlsAcceptor>,
+    servers: Vec<Server<RustlsAcceptor>>,
+    unix_listener: Option<UnixListener>,
 }
 
-pub async fn bind_api_server(listen_address: String, certs_dir: String) -> Result<RestApi> {
+/// Bind the REST API to every address in the comma separated `listen_address`, e.g. to serve
+/// both IPv4 and IPv6 on a multi-homed host. If `unix_socket` is not empty the API is also
+/// served on that Unix socket for local administration.
+pub async fn bind_api_server(
+    listen_address: String,
+    certs_dir: String,
+    unix_socket: String,
+) -> Result<RestApi> {
     let rustls_config = config(&certs_dir)
         .await
         .context("failed to load tls configuration")?;
-    let addr = listen_address.parse()?;
-    info!("Starting REST API on {addr}");
+    let mut servers = vec![];
+    for address in listen_address.split(',').map(str::trim) {
+        let addr: SocketAddr = address
+            .parse()
+            .with_context(|| format!("invalid REST API address {address}"))?;
+        info!("Starting REST API on {addr}");
+        servers.push(axum_server::bind_rustls(addr, rustls_config.clone()));
+    }
+    let unix_listener = if unix_socket.is_empty() {
+        None
+    } else {
+        info!("Starting REST API on {unix_socket}");
+        Some(bind_unix_socket(&unix_socket)?)
+    };
     Ok(RestApi {
-        server: axum_server::bind_rustls(addr, rustls_config),
+        servers,
+        unix_listener,
     })
 }
 
+fn bind_unix_socket(path: &str) -> Result<UnixListener> {
+    // Remove the socket left behind by a previous run, but never anything else.
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            bail!("{path} exists and is not a socket");
+        }
+        fs::remove_file(path)?;
+    }
+    // Anyone who can connect to the socket is an admin. So bind it in a private directory and only
+    // move it into place once its permissions are set.
+    let socket_path = Path::new(path);
+    let file_name = socket_path
+        .file_name()
+        .with_context(|| format!("{path} is not a file path"))?
+        .to_string_lossy();
+    let private_dir = socket_path.with_file_name(format!(".{file_name}.{}", std::process::id()));
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)
+        .with_context(|| format!("failed to create {}", private_dir.display()))?;
+    let private_path = private_dir.join(file_name.as_ref());
+    let result = UnixListener::bind(&private_path)
+        .with_context(|| format!("failed to bind socket {path}"))
+        .and_then(|listener| {
+            fs::set_permissions(&private_path, fs::Permissions::from_mode(0o600))?;
+            fs::rename(&private_path, socket_path)?;
+            Ok(listener)
+        });
+    let _ = fs::remove_dir_all(&private_dir);
+    result
+}
+
 impl RestApi {
     pub async fn serve(
         self,
+        settings: Arc<Settings>,
         lightning_api: Arc<dyn LightningInterface + Send + Sync>,
         wallet_api: Arc<dyn WalletInterface + Send + Sync>,
+        webhook: Arc<WebhookNotifier>,
         macaroon_auth: Arc<MacaroonAuth>,
         quit_signal: Shared<impl Future<Output = ()>>,
     ) -> Result<()> {
+        let unix_socket_macaroon = UnixSocketMacaroon(macaroon_auth.local_admin_macaroon()?);
         let cors = CorsLayer::permissive();
         let handle = Handle::new();
+        let shutdown_timeout = Duration::from_secs(settings.shutdown_timeout_secs);
 
-        let app = Router::new()
+        let api = Router::new()
             .route(routes::ROOT, get(root))
             .route(routes::GET_INFO, get(get_info))
             .route(routes::GET_BALANCE, get(get_balance))
             .route(routes::LIST_CHANNELS, get(list_channels))
+            .route(routes::LIST_CLOSED_CHANNELS, get(list_closed_channels))
             .route(routes::OPEN_CHANNEL, post(open_channel))
+            .route(routes::OPEN_CHANNEL_PREVIEW, post(open_channel_preview))
+            .route(routes::OPEN_CHANNEL_PSBT, post(open_channel_psbt))
+            .route(routes::FUNDING_PSBT, post(funding_psbt))
+            .route(
+                routes::GET_PENDING_CHANNEL_OPEN,
+                get(get_pending_channel_open),
+            )
             .route(routes::SET_CHANNEL_FEE, post(set_channel_fee))
             .route(routes::CLOSE_CHANNEL, delete(close_channel))
+            .route(routes::ABANDON_CHANNEL, post(abandon_channel))
+            .route(routes::GET_CHANNEL_STATS, get(get_channel_stats))
+            .route(routes::GET_CHANNEL_EARNINGS, get(get_channel_earnings))
+            .route(routes::FEE_REPORT, get(fee_report))
             .route(routes::NEW_ADDR, get(new_address))
             .route(routes::WITHDRAW, post(transfer))
+            .route(routes::WALLET_RESCAN, post(rescan))
+            .route(routes::IMPORT_DESCRIPTOR, post(import_descriptor))
+            .route(routes::GENERATE_INVOICE, post(generate_invoice))
+            .route(routes::LIST_INVOICES, get(list_invoices))
+            .route(routes::PAY_LNURL, post(pay_lnurl))
+            .route(routes::GET_PAYMENT, get(get_payment))
+            .route(routes::LIST_PAYMENTS, get(list_payments))
             .route(routes::LIST_PEERS, get(list_peers))
+            .route(routes::LIST_FORWARDS, get(list_forwards))
+            .route(routes::EXPORT_FORWARDS, get(export_forwards))
             .route(routes::CONNECT_PEER, post(connect_peer))
             .route(routes::DISCONNECT_PEER, delete(disconnect_peer))
+            .route(routes::FORGET_PEER, delete(forget_peer))
             .route(routes::LIST_NETWORK_NODE, get(get_network_node))
             .route(routes::LIST_NETWORK_NODES, get(list_network_nodes))
             .route(routes::LIST_NETWORK_CHANNEL, get(get_network_channel))
             .route(routes::LIST_NETWORK_CHANNELS, get(list_network_channels))
+            .route(
+                routes::LIST_NETWORK_CHANNELS_BETWEEN,
+                get(list_network_channels_between),
+            )
+            .route(routes::GET_NETWORK_STATS, get(get_network_stats))
+            .route(routes::PRUNE_NETWORK_GRAPH, post(prune_network_graph))
+            .route(routes::RESET_SCORER, post(reset_scorer))
+            .route(routes::CHECK_MONITORS, get(check_monitors))
+            .route(routes::PENDING_SWEEPS, get(list_pending_sweeps))
+            .route(routes::EXPORT_STATE, get(export_state))
+            .route(routes::SIGN_MESSAGE, post(sign_message))
+            .route(routes::DERIVE_KEY, post(derive_key))
+            .route(routes::VERIFY_MESSAGE, post(verify_message))
+            .route(routes::SET_ALIAS, post(set_alias))
+            .route(routes::ANNOUNCE, post(announce))
+            .route(routes::MAINTENANCE, post(set_maintenance_mode))
+            .route(routes::LIST_HTLCS, get(list_htlcs))
+            .route(routes::GET_INTERCEPT_SCID, get(get_intercept_scid))
+            .route(routes::FORWARD_HTLC, post(forward_htlc))
+            .route(routes::FAIL_HTLC, post(fail_htlc))
+            .route(routes::LIST_INTERCEPTED_HTLCS, get(list_intercepted_htlcs))
+            .route(routes::LIST_PENDING_WEBHOOKS, get(list_pending_webhooks))
             .route(routes::WEBSOCKET, get(ws_handler))
+            .route_layer(middleware::from_fn(require_ready))
             .fallback(handler_404)
-            .layer(cors)
+            .layer(Extension(settings))
             .layer(Extension(lightning_api))
             .layer(Extension(wallet_api))
-            .layer(Extension(macaroon_auth));
-
-        tokio::select!(
-            result = self.server.serve(app.into_make_service_with_connect_info::<SocketAddr>()) => {
-                    if let Err(e) = result {
-                        error!("API server shutdown unexpectedly: {}", e);
-                    } else {
-                        info!("API server shutdown successfully.");
-                    }
-                }
+            .layer(Extension(webhook))
+            .layer(Extension(macaroon_auth.clone()))
+            .layer(Extension(Arc::new(PendingChannelOpens::default())));
+        // The batch route dispatches to the other routes, so it is not part of them itself.
+        let app = Router::new()
+            .route(routes::BATCH, post(batch))
+            .layer(Extension(BatchRouter(api.clone())))
+            .layer(Extension(macaroon_auth))
+            .merge(api)
+            .layer(cors)
+            .layer(
+                CompressionLayer::new()
+                    .compress_when(DefaultPredicate::new().and(not_websocket_upgrade)),
+            );
+
+        // All servers share the handle so that a single shutdown drains every listener.
+        let tls_servers = try_join_all(self.servers.into_iter().map(|server| {
+            server.handle(handle.clone()).serve(
+                app.clone()
+                    .into_make_service_with_connect_info::<SocketAddr>(),
+            )
+        }))
+        .map_ok(|_| ())
+        .err_into();
+        let unix_server = serve_unix_socket(
+            self.unix_listener,
+            app.layer(Extension(unix_socket_macaroon)),
+            quit_signal.clone(),
+            shutdown_timeout,
+        );
+        let server = try_join(tls_servers, unix_server);
+        tokio::pin!(server);
+        let result = tokio::select!(
+            result = &mut server => result,
             _ = quit_signal => {
-                handle.graceful_shutdown(Some(Duration::from_secs(30)));
+                handle.graceful_shutdown(Some(shutdown_timeout));
+                // Keep serving until the open requests are done or the timeout is reached.
+                server.await
             }
         );
+        if let Err(e) = result {
+            error!("API server shutdown unexpectedly: {}", e);
+        } else {
+            info!("API server shutdown successfully.");
+        }
         Ok(())
     }
 }
 
+/// Serve the API without TLS on the Unix socket. Requests are authorised with the admin macaroon,
+/// so access is controlled by the file permissions of the socket.
+async fn serve_unix_socket<F: Future<Output = ()>>(
+    listener: Option<UnixListener>,
+    app: Router,
+    quit_signal: Shared<F>,
+    shutdown_timeout: Duration,
+) -> Result<()> {
+    let listener = match listener {
+        Some(listener) => listener,
+        None => return Ok(()),
+    };
+    let connections = stream::unfold(listener, |listener| async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => return Some((Ok::<_, std::io::Error>(stream), listener)),
+                // An error would end the server, so back off and keep accepting instead.
+                Err(e) => {
+                    warn!("Failed to accept connection on the REST API socket: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    });
+    let server = hyper::Server::builder(accept::from_stream(connections))
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(quit_signal.clone());
+    tokio::pin!(server);
+    tokio::select!(
+        result = &mut server => Ok(result?),
+        _ = async {
+            quit_signal.await;
+            tokio::time::sleep(shutdown_timeout).await
+        } => {
+            warn!("Open requests on the REST API socket did not finish before the shutdown timeout");
+            Ok(())
+        }
+    )
+}
+
 async fn root(
     macaroon: KldMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
@@ -117,6 +335,32 @@ async fn root(
     Ok(())
 }
 
+/// Requests that change state need LDK to be caught up with the chain, so reject them until the
+/// node is ready. Reading the node state is always allowed.
+async fn require_ready<B>(
+    Extension(lightning_api): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ApiError> {
+    if request.method() != Method::GET && !lightning_api.ready() {
+        return Err(ApiError::ServiceUnavailable(
+            ErrorCode::BitcoindSyncing,
+            "Node is synchronising with the chain".to_string(),
+        ));
+    }
+    Ok(next.run(request).await)
+}
+
+/// The websocket takes over the connection after the upgrade, so its response must not be encoded.
+fn not_websocket_upgrade(
+    status: StatusCode,
+    _version: Version,
+    _headers: &HeaderMap,
+    _extensions: &Extensions,
+) -> bool {
+    status != StatusCode::SWITCHING_PROTOCOLS
+}
+
 async fn handler_404() -> impl IntoResponse {
     ApiError::NotFound("No such method".to_string())
 }
@@ -132,8 +376,9 @@ async fn config(certs_dir: &str) -> Result<RustlsConfig> {
 pub enum ApiError {
     Unauthorized,
     NotFound(String),
-    BadRequest(Box<dyn std::error::Error>),
-    InternalServerError(Box<dyn std::error::Error>),
+    ServiceUnavailable(ErrorCode, String),
+    BadRequest(ErrorCode, Box<dyn std::error::Error>),
+    InternalServerError(ErrorCode, Box<dyn std::error::Error>),
 }
 
 impl IntoResponse for ApiError {
@@ -141,20 +386,27 @@ impl IntoResponse for ApiError {
         match self {
             ApiError::Unauthorized => build_api_error(
                 StatusCode::UNAUTHORIZED,
+                ErrorCode::Unauthorized,
                 "Failed to verify macaroon".to_string(),
             ),
-            ApiError::NotFound(s) => build_api_error(StatusCode::NOT_FOUND, s),
-            ApiError::BadRequest(e) => build_api_error(StatusCode::BAD_REQUEST, e.to_string()),
-            ApiError::InternalServerError(e) => {
-                build_api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            ApiError::NotFound(s) => build_api_error(StatusCode::NOT_FOUND, ErrorCode::NotFound, s),
+            ApiError::ServiceUnavailable(code, s) => {
+                build_api_error(StatusCode::SERVICE_UNAVAILABLE, code, s)
+            }
+            ApiError::BadRequest(code, e) => {
+                build_api_error(StatusCode::BAD_REQUEST, code, e.to_string())
+            }
+            ApiError::InternalServerError(code, e) => {
+                build_api_error(StatusCode::INTERNAL_SERVER_ERROR, code, e.to_string())
             }
         }
     }
 }
 
-fn build_api_error(status_code: StatusCode, detail: String) -> Response {
+fn build_api_error(status_code: StatusCode, code: ErrorCode, detail: String) -> Response {
     let error = api::Error {
         status: status_code.to_string(),
+        code,
         detail,
     };
     if let Ok(value) = serde_json::to_value(error) {
@@ -168,6 +420,19 @@ fn build_api_error(status_code: StatusCode, detail: String) -> Response {
     }
 }
 
+/// The code of the first error in the chain that has one.
+fn error_code(e: &anyhow::Error) -> Option<ErrorCode> {
+    e.chain().find_map(|cause| {
+        if let Some(coded) = cause.downcast_ref::<CodedError>() {
+            return Some(coded.code);
+        }
+        match cause.downcast_ref::<bdk::Error>() {
+            Some(bdk::Error::InsufficientFunds { .. }) => Some(ErrorCode::InsufficientFunds),
+            _ => None,
+        }
+    })
+}
+
 #[macro_export]
 macro_rules! to_string_empty {
     ($v: expr) => {
@@ -183,11 +448,18 @@ pub fn unauthorized(e: anyhow::Error) -> ApiError {
 pub fn internal_server(e: impl Into<anyhow::Error>) -> ApiError {
     let anyhow_err = e.into();
     warn!("{}", anyhow_err);
-    ApiError::InternalServerError(anyhow_err.into())
+    let code = error_code(&anyhow_err).unwrap_or(ErrorCode::InternalError);
+    ApiError::InternalServerError(code, anyhow_err.into())
+}
+
+/// Parse a hex encoded node public key so that every handler rejects malformed keys the same way.
+pub(crate) fn parse_pubkey(id: &str) -> Result<PublicKey, ApiError> {
+    PublicKey::from_str(id).map_err(|e| bad_request(anyhow!("Invalid public key {id}: {e}")))
 }
 
 pub fn bad_request(e: impl Into<anyhow::Error>) -> ApiError {
     let anyhow_err = e.into();
     info!("{}", anyhow_err);
-    ApiError::BadRequest(anyhow_err.into())
+    let code = error_code(&anyhow_err).unwrap_or(ErrorCode::InvalidRequest);
+    ApiError::BadRequest(code, anyhow_err.into())
 }
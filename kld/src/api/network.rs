@@ -1,12 +1,20 @@
-use api::{Address, NetworkChannel, NetworkNode};
-use axum::{extract::Path, response::IntoResponse, Extension, Json};
-use bitcoin::secp256k1::PublicKey;
+use api::{Address, NetworkChannel, NetworkNode, NetworkStats, PruneNetworkGraphResponse};
+use axum::{
+    extract::Path,
+    headers::{ETag, IfNoneMatch},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json, TypedHeader,
+};
 use hex::ToHex;
 use lightning::{
     ln::msgs::NetAddress,
     routing::gossip::{ChannelInfo, DirectedChannelInfo, NodeId, NodeInfo},
 };
+use serde::Serialize;
 use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
     net::{Ipv4Addr, Ipv6Addr},
     str::FromStr,
     sync::Arc,
@@ -14,22 +22,32 @@ use std::{
 
 use crate::ldk::LightningInterface;
 
-use super::{bad_request, unauthorized, ApiError, KldMacaroon, MacaroonAuth};
+use super::{
+    bad_request, internal_server, parse_pubkey, unauthorized, ApiError, KldMacaroon, MacaroonAuth,
+};
 
 pub(crate) async fn list_network_nodes(
     macaroon: KldMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
 ) -> Result<impl IntoResponse, ApiError> {
     macaroon_auth
         .verify_readonly_macaroon(&macaroon.0)
         .map_err(unauthorized)?;
-    let nodes: Vec<NetworkNode> = lightning_interface
-        .nodes()
-        .unordered_iter()
-        .filter_map(|(node_id, announcement)| to_api_node(node_id, announcement))
-        .collect();
-    Ok(Json(nodes))
+    let nodes = lightning_interface.nodes();
+    let etag = graph_etag(nodes.unordered_iter().map(|(node_id, node_info)| {
+        (
+            node_id,
+            node_info.announcement_info.as_ref().map(|a| a.last_update),
+        )
+    }));
+    Ok(conditional_json(if_none_match, etag, || {
+        nodes
+            .unordered_iter()
+            .filter_map(|(node_id, announcement)| to_api_node(node_id, announcement))
+            .collect::<Vec<NetworkNode>>()
+    }))
 }
 
 pub(crate) async fn get_network_node(
@@ -41,7 +59,7 @@ pub(crate) async fn get_network_node(
     macaroon_auth
         .verify_readonly_macaroon(&macaroon.0)
         .map_err(unauthorized)?;
-    let public_key = PublicKey::from_str(&id).map_err(bad_request)?;
+    let public_key = parse_pubkey(&id)?;
     let node_id = NodeId::from_pubkey(&public_key);
     if let Some(node_info) = lightning_interface.get_node(&node_id) {
         if let Some(node) = to_api_node(&node_id, &node_info) {
@@ -77,28 +95,159 @@ pub(crate) async fn list_network_channels(
     macaroon: KldMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
 ) -> Result<impl IntoResponse, ApiError> {
     macaroon_auth
         .verify_readonly_macaroon(&macaroon.0)
         .map_err(unauthorized)?;
-    let mut channels = vec![];
-    for (short_channel_id, channel_info) in lightning_interface.channels().unordered_iter() {
-        if let Some((directed_info, _)) = channel_info.as_directed_to(&channel_info.node_one) {
-            if let Some(api_channel) =
-                to_api_channel(short_channel_id, channel_info, &directed_info)
-            {
-                channels.push(api_channel);
-            }
+    let channels = lightning_interface.channels();
+    let etag = graph_etag(
+        channels
+            .unordered_iter()
+            .map(|(short_channel_id, channel_info)| {
+                (
+                    short_channel_id,
+                    channel_info.capacity_sats,
+                    channel_info.one_to_two.as_ref().map(|u| u.last_update),
+                    channel_info.two_to_one.as_ref().map(|u| u.last_update),
+                )
+            }),
+    );
+    Ok(conditional_json(if_none_match, etag, || {
+        channels
+            .unordered_iter()
+            .flat_map(|(short_channel_id, channel_info)| {
+                to_api_channels(short_channel_id, channel_info)
+            })
+            .collect::<Vec<NetworkChannel>>()
+    }))
+}
+
+pub(crate) async fn list_network_channels_between(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path((node1, node2)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let node1 = NodeId::from_pubkey(&parse_pubkey(&node1)?);
+    let node2 = NodeId::from_pubkey(&parse_pubkey(&node2)?);
+    let channels: Vec<NetworkChannel> = lightning_interface
+        .channels()
+        .unordered_iter()
+        .filter(|(_, channel_info)| {
+            (channel_info.node_one == node1 && channel_info.node_two == node2)
+                || (channel_info.node_one == node2 && channel_info.node_two == node1)
+        })
+        .flat_map(|(short_channel_id, channel_info)| {
+            to_api_channels(short_channel_id, channel_info)
+        })
+        .collect();
+    Ok(Json(channels))
+}
+
+pub(crate) async fn get_network_stats(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let channels = lightning_interface.channels();
+    let mut capacities = vec![];
+    let mut base_fees = vec![];
+    let mut fee_rates = vec![];
+    for (_, channel_info) in channels.unordered_iter() {
+        if let Some(capacity) = channel_info.capacity_sats {
+            capacities.push(capacity);
         }
-        if let Some((directed_info, _)) = channel_info.as_directed_to(&channel_info.node_two) {
-            if let Some(api_channel) =
-                to_api_channel(short_channel_id, channel_info, &directed_info)
-            {
-                channels.push(api_channel);
-            }
+        for update in [&channel_info.one_to_two, &channel_info.two_to_one]
+            .into_iter()
+            .flatten()
+        {
+            base_fees.push(update.fees.base_msat);
+            fee_rates.push(update.fees.proportional_millionths);
         }
     }
-    Ok(Json(channels))
+    let stats = NetworkStats {
+        num_nodes: lightning_interface.graph_num_nodes(),
+        num_channels: channels.len(),
+        total_capacity_sat: capacities.iter().sum(),
+        median_channel_size_sat: median(&mut capacities),
+        median_base_fee_msat: median(&mut base_fees),
+        median_fee_ppm: median(&mut fee_rates),
+    };
+    Ok(Json(stats))
+}
+
+pub(crate) async fn prune_network_graph(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let (removed_channels, removed_nodes) = lightning_interface
+        .prune_network_graph()
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(PruneNetworkGraphResponse {
+        removed_channels,
+        removed_nodes,
+    }))
+}
+
+/// Gossip messages which change a node or channel carry a newer timestamp, so hashing the keys with
+/// their timestamps identifies a listing without serializing it. Entries are combined independent
+/// of the iteration order of the graph.
+fn graph_etag<T: Hash>(entries: impl Iterator<Item = T>) -> ETag {
+    let mut count = 0u64;
+    let mut combined = 0u64;
+    for entry in entries {
+        let mut hasher = DefaultHasher::new();
+        entry.hash(&mut hasher);
+        combined = combined.wrapping_add(hasher.finish());
+        count += 1;
+    }
+    format!("\"{count:x}-{combined:016x}\"")
+        .parse()
+        .expect("valid etag")
+}
+
+/// Respond with 304 if the client already has the current version of the listing.
+fn conditional_json<T: Serialize>(
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    etag: ETag,
+    listing: impl FnOnce() -> T,
+) -> Response {
+    match if_none_match {
+        Some(TypedHeader(if_none_match)) if !if_none_match.precondition_passes(&etag) => {
+            (StatusCode::NOT_MODIFIED, TypedHeader(etag)).into_response()
+        }
+        _ => (TypedHeader(etag), Json(listing())).into_response(),
+    }
+}
+
+fn median<T: Ord + Copy + Default>(values: &mut [T]) -> T {
+    if values.is_empty() {
+        return T::default();
+    }
+    let mid = values.len() / 2;
+    *values.select_nth_unstable(mid).1
+}
+
+fn to_api_channels(short_channel_id: &u64, channel_info: &ChannelInfo) -> Vec<NetworkChannel> {
+    [&channel_info.node_one, &channel_info.node_two]
+        .into_iter()
+        .filter_map(|node| channel_info.as_directed_to(node))
+        .filter_map(|(directed_info, _)| {
+            to_api_channel(short_channel_id, channel_info, &directed_info)
+        })
+        .collect()
 }
 
 fn to_api_channel(
@@ -178,3 +327,23 @@ pub(crate) fn to_api_address(net_address: &NetAddress) -> Address {
         },
     }
 }
+
+#[test]
+fn test_graph_etag() {
+    let etag = graph_etag([(1u64, Some(10u32)), (2, None)].into_iter());
+    assert_eq!(
+        etag,
+        graph_etag([(2u64, None), (1, Some(10u32))].into_iter())
+    );
+    assert_ne!(
+        etag,
+        graph_etag([(1u64, Some(11u32)), (2, None)].into_iter())
+    );
+}
+
+#[test]
+fn test_median() {
+    assert_eq!(0u64, median(&mut []));
+    assert_eq!(3u32, median(&mut [5, 1, 3]));
+    assert_eq!(7u64, median(&mut [9, 1, 7, 3]));
+}
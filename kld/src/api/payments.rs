@@ -0,0 +1,153 @@
+use std::{
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use api::{ListPaymentsParams, PayLnurl, Payment, PaymentResponse};
+use axum::{
+    extract::{Path, Query},
+    response::IntoResponse,
+    Extension, Json,
+};
+use hex::{FromHex, ToHex};
+use lightning::ln::PaymentHash;
+
+use crate::{
+    database::{
+        self,
+        payment::{PaymentDirection, PaymentStatus},
+    },
+    ldk::LightningInterface,
+    lnurl,
+};
+
+use super::{bad_request, internal_server, unauthorized, ApiError, KldMacaroon, MacaroonAuth};
+
+pub(crate) async fn pay_lnurl(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(pay_lnurl): Json<PayLnurl>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let invoice = lnurl::fetch_invoice(&pay_lnurl.target, pay_lnurl.amount)
+        .await
+        .map_err(bad_request)?;
+    let max_fee_msat = max_fee_msat(
+        pay_lnurl.amount,
+        pay_lnurl.max_fee_msat,
+        pay_lnurl.max_fee_percent,
+    );
+    let payment = lightning_interface
+        .pay_invoice(invoice, max_fee_msat)
+        .await
+        .map_err(internal_server)?;
+    let response = PaymentResponse {
+        payment_hash: payment.payment_hash.0.encode_hex(),
+        payment_preimage: payment.preimage.0.encode_hex(),
+        amount_msat: payment.amount_msat,
+        fee_msat: payment.fee_paid_msat,
+    };
+    Ok(Json(response))
+}
+
+/// The smaller of the two limits, or 0.5% + 1 sat if neither is set.
+fn max_fee_msat(amount_msat: u64, max_fee_msat: Option<u64>, max_fee_percent: Option<f64>) -> u64 {
+    let percent_limit =
+        max_fee_percent.map(|percent| (amount_msat as f64 * percent / 100.0) as u64);
+    match (max_fee_msat, percent_limit) {
+        (Some(absolute), Some(percent)) => absolute.min(percent),
+        (Some(absolute), None) => absolute,
+        (None, Some(percent)) => percent,
+        (None, None) => amount_msat / 200 + 1000,
+    }
+}
+
+pub(crate) async fn get_payment(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(payment_hash): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let hash = PaymentHash(<[u8; 32]>::from_hex(&payment_hash).map_err(bad_request)?);
+    let payment = lightning_interface
+        .fetch_outbound_payment(hash)
+        .await
+        .map_err(internal_server)?
+        .ok_or_else(|| ApiError::NotFound(payment_hash))?;
+    Ok(Json(to_api_payment(payment)))
+}
+
+pub(crate) async fn list_payments(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ListPaymentsParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let payments = fetch_payments(
+        lightning_interface.as_ref(),
+        PaymentDirection::Outbound,
+        params,
+    )
+    .await?;
+    Ok(Json(payments))
+}
+
+pub(super) async fn fetch_payments(
+    lightning_interface: &(dyn LightningInterface + Send + Sync),
+    direction: PaymentDirection,
+    params: ListPaymentsParams,
+) -> Result<Vec<Payment>, ApiError> {
+    let status = params
+        .status
+        .as_deref()
+        .map(PaymentStatus::from_str)
+        .transpose()
+        .map_err(bad_request)?;
+    let to_time = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+    let payments = lightning_interface
+        .list_payments(
+            direction,
+            status,
+            params.since.map(to_time),
+            params.until.map(to_time),
+        )
+        .await
+        .map_err(internal_server)?;
+    Ok(payments.into_iter().map(to_api_payment).collect())
+}
+
+fn to_api_payment(payment: database::payment::Payment) -> Payment {
+    Payment {
+        payment_hash: payment.hash.0.encode_hex(),
+        status: payment.status.to_string(),
+        payment_preimage: payment.preimage.map(|p| p.0.encode_hex()),
+        amount_msat: payment.amount_msat,
+        fee_msat: payment.fee_msat,
+        failure_reason: payment.failure_reason,
+        settled_at: payment
+            .settled_at
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs()),
+    }
+}
+
+#[test]
+fn test_max_fee_msat() {
+    assert_eq!(6000, max_fee_msat(1_000_000, None, None));
+    assert_eq!(500, max_fee_msat(1_000_000, Some(500), None));
+    assert_eq!(10_000, max_fee_msat(1_000_000, None, Some(1.0)));
+    assert_eq!(500, max_fee_msat(1_000_000, Some(500), Some(1.0)));
+}
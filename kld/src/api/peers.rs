@@ -1,4 +1,4 @@
-use std::{str::FromStr, sync::Arc};
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
     api::{bad_request, network::to_api_address},
@@ -7,9 +7,9 @@ use crate::{
 use anyhow::Result;
 use api::Peer;
 use axum::{extract::Path, response::IntoResponse, Extension, Json};
-use bitcoin::{hashes::hex::ToHex, secp256k1::PublicKey};
+use bitcoin::hashes::hex::ToHex;
 
-use super::{internal_server, unauthorized, ApiError, KldMacaroon, MacaroonAuth};
+use super::{internal_server, parse_pubkey, unauthorized, ApiError, KldMacaroon, MacaroonAuth};
 
 pub(crate) async fn list_peers(
     macaroon: KldMacaroon,
@@ -20,16 +20,30 @@ pub(crate) async fn list_peers(
         .verify_readonly_macaroon(&macaroon.0)
         .map_err(unauthorized)?;
 
+    let mut channels = HashMap::new();
+    for channel in lightning_interface.list_channels() {
+        let (num_channels, capacity_sat) = channels
+            .entry(channel.counterparty.node_id)
+            .or_insert((0, 0));
+        *num_channels += 1;
+        *capacity_sat += channel.channel_value_satoshis;
+    }
     let peers: Vec<Peer> = lightning_interface
         .list_peers()
         .await
         .map_err(internal_server)?
         .iter()
-        .map(|p| Peer {
-            id: p.public_key.serialize().to_hex(),
-            connected: p.status == PeerStatus::Connected,
-            netaddr: p.net_address.as_ref().map(to_api_address),
-            alias: p.alias.clone(),
+        .map(|p| {
+            let (num_channels, capacity_sat) =
+                channels.get(&p.public_key).cloned().unwrap_or_default();
+            Peer {
+                id: p.public_key.serialize().to_hex(),
+                connected: p.status == PeerStatus::Connected,
+                netaddr: p.net_address.as_ref().map(to_api_address),
+                alias: p.alias.clone(),
+                num_channels,
+                capacity_sat,
+            }
         })
         .collect();
 
@@ -48,10 +62,10 @@ pub(crate) async fn connect_peer(
 
     let (public_key, net_address) = match id.split_once('@') {
         Some((public_key, net_address)) => (
-            PublicKey::from_str(public_key).map_err(bad_request)?,
+            parse_pubkey(public_key)?,
             Some(net_address.parse().map_err(bad_request)?),
         ),
-        None => (PublicKey::from_str(&id).map_err(bad_request)?, None),
+        None => (parse_pubkey(&id)?, None),
     };
     lightning_interface
         .connect_peer(public_key, net_address)
@@ -71,7 +85,7 @@ pub(crate) async fn disconnect_peer(
         .verify_admin_macaroon(&macaroon.0)
         .map_err(unauthorized)?;
 
-    let public_key = PublicKey::from_str(&id).map_err(bad_request)?;
+    let public_key = parse_pubkey(&id)?;
     lightning_interface
         .disconnect_peer(public_key)
         .await
@@ -79,3 +93,22 @@ pub(crate) async fn disconnect_peer(
 
     Ok(Json(()))
 }
+
+pub(crate) async fn forget_peer(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let public_key = parse_pubkey(&id)?;
+    lightning_interface
+        .forget_peer(public_key)
+        .await
+        .map_err(internal_server)?;
+
+    Ok(Json(()))
+}
@@ -1,21 +1,37 @@
+use anyhow::anyhow;
 use api::{Address, API_VERSION};
-use api::{Chain, GetInfo};
+use api::{
+    AnnounceResponse, Chain, DeriveKey, DeriveKeyResponse, ExportState, ExportStateParams,
+    ExportedPeer, GetInfo, Maintenance, MonitorCheck, MonitorDiscrepancy, PendingSweep,
+    ResetScorerResponse, SetAlias, SignMessage, SignMessageResponse, VerifyMessage,
+    VerifyMessageResponse,
+};
+use axum::extract::Query;
 use axum::Json;
 use axum::{response::IntoResponse, Extension};
+use bitcoin::util::bip32::DerivationPath;
 use bitcoin::Network;
+use hex::{FromHex, ToHex};
+use lightning::routing::gossip::NodeId;
+use lightning::util::message_signing;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::ldk::net_utils::PeerAddress;
 use crate::ldk::LightningInterface;
+use crate::wallet::WalletInterface;
 use crate::VERSION;
 
 use super::MacaroonAuth;
-use super::{internal_server, unauthorized};
+use super::{bad_request, internal_server, parse_pubkey, unauthorized};
 use super::{ApiError, KldMacaroon};
 
 pub(crate) async fn get_info(
     macaroon: KldMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
     Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
 ) -> Result<impl IntoResponse, ApiError> {
     macaroon_auth
         .verify_readonly_macaroon(&macaroon.0)
@@ -24,8 +40,9 @@ pub(crate) async fn get_info(
         .synced()
         .await
         .map_err(internal_server)?;
+    let identity_pubkey = lightning_interface.identity_pubkey();
     let info = GetInfo {
-        id: lightning_interface.identity_pubkey().to_string(),
+        id: identity_pubkey.to_string(),
         alias: lightning_interface.alias(),
         num_pending_channels: lightning_interface.num_pending_channels(),
         num_active_channels: lightning_interface.num_active_channels(),
@@ -43,7 +60,7 @@ pub(crate) async fn get_info(
         }],
         version: VERSION.to_string(),
         api_version: API_VERSION.to_string(),
-        color: "".to_string(),
+        color: lightning_interface.color().encode_hex(),
         network: lightning_interface.network().to_string(),
         address: lightning_interface
             .public_addresses()
@@ -55,6 +72,256 @@ pub(crate) async fn get_info(
                 port: a.1.parse().unwrap_or_default(),
             })
             .collect(),
+        uris: lightning_interface
+            .public_addresses()
+            .iter()
+            .map(|address| format!("{identity_pubkey}@{address}"))
+            .collect(),
+        maintenance_mode: lightning_interface.maintenance_mode(),
+        watch_only_balance: wallet
+            .watch_only_balance()
+            .map_err(internal_server)?
+            .get_total(),
     };
     Ok(Json(info))
 }
+
+pub(crate) async fn set_maintenance_mode(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(maintenance): Json<Maintenance>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    lightning_interface.set_maintenance_mode(maintenance.enabled);
+    Ok(Json(Maintenance {
+        enabled: lightning_interface.maintenance_mode(),
+    }))
+}
+
+pub(crate) async fn set_alias(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(set_alias): Json<SetAlias>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    if set_alias.alias.len() > 32 {
+        return Err(bad_request(anyhow!(
+            "Alias can not be longer than 32 bytes"
+        )));
+    }
+    let color = set_alias
+        .color
+        .map(|color| <[u8; 3]>::from_hex(color.trim_start_matches('#')))
+        .transpose()
+        .map_err(bad_request)?;
+    lightning_interface
+        .set_alias(set_alias.alias, color)
+        .map_err(internal_server)?;
+    Ok(Json(SetAlias {
+        alias: lightning_interface.alias(),
+        color: Some(lightning_interface.color().encode_hex()),
+    }))
+}
+
+pub(crate) async fn announce(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    Ok(Json(AnnounceResponse {
+        announced: lightning_interface.announce_node(),
+    }))
+}
+
+pub(crate) async fn reset_scorer(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(internal_server)?
+        .as_secs();
+    lightning_interface
+        .reset_scorer()
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(ResetScorerResponse {
+        status: "reset".to_string(),
+        timestamp,
+    }))
+}
+
+pub(crate) async fn check_monitors(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let check = lightning_interface
+        .check_channel_monitors()
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(MonitorCheck {
+        checked_monitors: check.checked,
+        corrupt_monitors: check.corrupt,
+        discrepancies: check
+            .discrepancies
+            .into_iter()
+            .map(|d| MonitorDiscrepancy {
+                funding_txo: format!("{}:{}", d.funding_txo.txid, d.funding_txo.index),
+                persisted_update_id: d.persisted_update_id,
+                in_memory_update_id: d.in_memory_update_id,
+            })
+            .collect(),
+    }))
+}
+
+pub(crate) async fn list_pending_sweeps(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let sweeps: Vec<PendingSweep> = lightning_interface
+        .pending_sweeps()
+        .await
+        .map_err(internal_server)?
+        .into_iter()
+        .map(|output| PendingSweep {
+            outpoint: output.outpoint().to_string(),
+            value_sats: output.value_sats(),
+            status: output.status.to_string(),
+            sweep_txid: output.sweep_txid.map(|txid| txid.to_string()),
+            created_at: output
+                .created_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        })
+        .collect();
+    Ok(Json(sweeps))
+}
+
+/// Version of the state export format, increased whenever its layout changes.
+const EXPORT_STATE_VERSION: u32 = 1;
+
+pub(crate) async fn export_state(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ExportStateParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    if !params.confirm.unwrap_or_default() {
+        return Err(bad_request(anyhow!(
+            "The export contains the full channel state. Importing it into a node with the same keys while this node is running loses the funds in its channels. Set confirm=true to export it anyway"
+        )));
+    }
+    let exported_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(internal_server)?
+        .as_secs();
+    let state = lightning_interface
+        .export_state()
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(ExportState {
+        version: EXPORT_STATE_VERSION,
+        node_id: lightning_interface.identity_pubkey().to_string(),
+        network: lightning_interface.network().to_string(),
+        exported_at,
+        channel_monitors: state
+            .channel_monitors
+            .iter()
+            .map(|monitor| monitor.encode_hex())
+            .collect(),
+        network_graph: state.network_graph.map(|graph| graph.encode_hex()),
+        peers: state
+            .peers
+            .into_iter()
+            .map(|(id, address)| ExportedPeer {
+                id: id.to_string(),
+                address: PeerAddress(address).to_string(),
+            })
+            .collect(),
+    }))
+}
+
+pub(crate) async fn sign_message(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(sign_message): Json<SignMessage>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let signature = lightning_interface
+        .sign_message(sign_message.message.as_bytes())
+        .map_err(internal_server)?;
+    Ok(Json(SignMessageResponse { signature }))
+}
+
+pub(crate) async fn derive_key(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(derive_key): Json<DeriveKey>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_derivekey_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let path = DerivationPath::from_str(&derive_key.path).map_err(bad_request)?;
+    let public_key = lightning_interface
+        .derive_public_key(&path)
+        .map_err(bad_request)?;
+    Ok(Json(DeriveKeyResponse {
+        path: path.to_string(),
+        public_key: public_key.to_string(),
+    }))
+}
+
+pub(crate) async fn verify_message(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(verify_message): Json<VerifyMessage>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+    let signer =
+        message_signing::recover_pk(verify_message.message.as_bytes(), &verify_message.signature)
+            .map_err(|e| bad_request(anyhow!("Invalid signature: {e}")))?;
+    let known_node = lightning_interface
+        .get_node(&NodeId::from_pubkey(&signer))
+        .is_some();
+    let verified = match verify_message.pubkey {
+        Some(pubkey) => parse_pubkey(&pubkey)? == signer,
+        None => known_node,
+    };
+    Ok(Json(VerifyMessageResponse {
+        verified,
+        pubkey: signer.to_string(),
+        known_node,
+    }))
+}
@@ -1,15 +1,24 @@
 use anyhow::anyhow;
+use api::ImportDescriptor;
 use api::NewAddress;
 use api::NewAddressResponse;
 use api::WalletBalance;
+use api::WalletRescan;
+use api::WalletRescanParams;
+use api::WalletRescanResponse;
 use api::WalletTransfer;
 use api::WalletTransferResponse;
+use axum::extract::Query;
+use axum::http::StatusCode;
 use axum::{response::IntoResponse, Extension, Json};
+use bdk::miniscript::{Descriptor, DescriptorPublicKey};
 use bitcoin::consensus::encode;
 use bitcoin::Address;
+use log::error;
 use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::ldk::LightningInterface;
 use crate::wallet::WalletInterface;
 
 use super::bad_request;
@@ -23,6 +32,7 @@ pub(crate) async fn get_balance(
     macaroon: KldMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
     Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
 ) -> Result<impl IntoResponse, ApiError> {
     macaroon_auth
         .verify_readonly_macaroon(&macaroon.0)
@@ -30,11 +40,27 @@ pub(crate) async fn get_balance(
 
     let balance = wallet.balance().map_err(internal_server)?;
     let unconf_balance = balance.untrusted_pending + balance.trusted_pending;
-    let total_balance = unconf_balance + balance.confirmed;
+    // The funding transaction has left the wallet but the channel can't be used yet.
+    let reserved_balance = lightning_interface
+        .list_channels()
+        .iter()
+        .filter(|channel| {
+            channel.is_outbound && channel.funding_txo.is_some() && !channel.is_channel_ready
+        })
+        .map(|channel| channel.channel_value_satoshis)
+        .sum();
+    let total_balance = unconf_balance + balance.confirmed + reserved_balance;
+    let watch_only_balance = wallet
+        .watch_only_balance()
+        .map_err(internal_server)?
+        .get_total();
     let result = WalletBalance {
         total_balance,
         conf_balance: balance.confirmed,
         unconf_balance,
+        reserved_balance,
+        immature_balance: balance.immature,
+        watch_only_balance,
     };
     Ok(Json(result))
 }
@@ -88,3 +114,57 @@ pub(crate) async fn transfer(
     };
     Ok(Json(response))
 }
+
+pub(crate) async fn rescan(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+    Query(params): Query<WalletRescanParams>,
+    Json(wallet_rescan): Json<WalletRescan>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    if wallet.rescan_progress().is_some() {
+        return Err(bad_request(anyhow!("Wallet rescan is already in progress")));
+    }
+    if params.run_async.unwrap_or_default() {
+        tokio::spawn(async move {
+            if let Err(e) = wallet.rescan(wallet_rescan.birthday_height).await {
+                error!("Wallet rescan failed: {e}");
+            }
+        });
+        let response = WalletRescanResponse {
+            status: "started".to_string(),
+        };
+        return Ok((StatusCode::ACCEPTED, Json(response)));
+    }
+    wallet
+        .rescan(wallet_rescan.birthday_height)
+        .await
+        .map_err(internal_server)?;
+    let response = WalletRescanResponse {
+        status: "complete".to_string(),
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+pub(crate) async fn import_descriptor(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+    Json(import_descriptor): Json<ImportDescriptor>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_admin_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    Descriptor::<DescriptorPublicKey>::from_str(&import_descriptor.descriptor)
+        .map_err(bad_request)?;
+    wallet
+        .import_descriptor(import_descriptor.descriptor)
+        .await
+        .map_err(internal_server)?;
+    Ok(Json(()))
+}
@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use api::PendingWebhook;
+use axum::{response::IntoResponse, Extension, Json};
+
+use crate::webhook::WebhookNotifier;
+
+use super::{unauthorized, ApiError, KldMacaroon, MacaroonAuth};
+
+pub(crate) async fn list_pending_webhooks(
+    macaroon: KldMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(webhook): Extension<Arc<WebhookNotifier>>,
+) -> Result<impl IntoResponse, ApiError> {
+    macaroon_auth
+        .verify_readonly_macaroon(&macaroon.0)
+        .map_err(unauthorized)?;
+
+    let pending: Vec<PendingWebhook> = webhook
+        .pending()
+        .into_iter()
+        .map(|p| PendingWebhook {
+            event: p.event,
+            timestamp: p.timestamp,
+            attempts: p.attempts,
+            last_error: p.last_error,
+        })
+        .collect();
+    Ok(Json(pending))
+}
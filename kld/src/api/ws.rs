@@ -9,14 +9,15 @@ use axum::{
     response::IntoResponse,
     Extension, TypedHeader,
 };
-use futures::StreamExt;
-use log::{debug, info};
+use futures::{SinkExt, StreamExt};
+use log::{debug, info, warn};
+use tokio::sync::broadcast::{self, error::RecvError};
 
-use crate::api::unauthorized;
+use crate::{api::unauthorized, webhook::WebhookNotifier};
 
 use super::{ApiError, KldMacaroon, MacaroonAuth};
 
-/// This is WIP. Just connects and checks macaroon at the moment.
+/// Clients receive the same JSON events as the webhook. Messages from clients are only logged.
 
 /// The handler for the HTTP request (this gets called when the HTTP GET lands at the start
 /// of websocket negotiation. After this completes, the actual switching from HTTP to
@@ -26,9 +27,11 @@ use super::{ApiError, KldMacaroon, MacaroonAuth};
 pub async fn ws_handler(
     macaroon: KldMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(webhook): Extension<Arc<WebhookNotifier>>,
     ws: WebSocketUpgrade,
     user_agent: Option<TypedHeader<UserAgent>>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // There is no address for connections on the Unix socket.
+    connect_info: Option<ConnectInfo<SocketAddr>>,
 ) -> Result<impl IntoResponse, ApiError> {
     macaroon_auth
         .verify_admin_macaroon(&macaroon.0)
@@ -37,16 +40,24 @@ pub async fn ws_handler(
         .map(|a| a.to_string())
         .unwrap_or_else(|| "Unknown client".to_string());
 
-    info!("`{}` at {} connected.", user_agent, addr.to_string());
+    let addr = connect_info
+        .map(|ConnectInfo(addr)| addr.to_string())
+        .unwrap_or_else(|| "unix socket".to_string());
+    info!("`{}` at {} connected.", user_agent, addr);
+    let events = webhook.subscribe();
     // finalize the upgrade process by returning upgrade callback.
     // we can customize the callback by sending additional info such as address.
     Ok(ws
         .protocols(["hex"])
-        .on_upgrade(move |socket| handle_socket(socket, addr)))
+        .on_upgrade(move |socket| handle_socket(socket, addr, events)))
 }
 
 /// Actual websocket statemachine (one will be spawned per connection)
-async fn handle_socket(mut socket: WebSocket, who: SocketAddr) {
+async fn handle_socket(
+    mut socket: WebSocket,
+    who: String,
+    mut events: broadcast::Receiver<String>,
+) {
     //send a ping (unsupported by some browsers) just to kick things off and get a response
     if socket.send(Message::Ping(vec![])).await.is_ok() {
         debug!("Pinged {}...", who);
@@ -64,7 +75,25 @@ async fn handle_socket(mut socket: WebSocket, who: SocketAddr) {
 
     // By splitting socket we can send and receive at the same time. In this example we will send
     // unsolicited messages to client based on some sort of server's internal event (i.e .timer).
-    let (mut _sender, mut receiver) = socket.split();
+    let (mut sender, mut receiver) = socket.split();
+
+    // This task forwards node events to the client.
+    let send_who = who.clone();
+    let mut send_task = tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if sender.send(Message::Text(event)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("Websocket client {send_who} missed {missed} events");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
 
     /* To close connection.
         debug!("Sending close to {}...", who);
@@ -80,23 +109,28 @@ async fn handle_socket(mut socket: WebSocket, who: SocketAddr) {
     });*/
 
     // This second task will receive messages from client and print them on server console
-    let recv_task = tokio::spawn(async move {
+    let recv_who = who.clone();
+    let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
             // print message and break if instructed to do so
-            if process_message(msg, who).is_break() {
+            if process_message(msg, &recv_who).is_break() {
                 break;
             }
         }
     });
 
-    recv_task.await.unwrap();
+    // If either task exits the other one is aborted.
+    tokio::select! {
+        _ = &mut send_task => recv_task.abort(),
+        _ = &mut recv_task => send_task.abort(),
+    }
 
     // returning from the handler closes the websocket connection
     info!("Websocket context {} destroyed", who);
 }
 
 /// helper to print contents of messages to stdout. Has special treatment for Close.
-fn process_message(msg: Message, who: SocketAddr) -> ControlFlow<(), ()> {
+fn process_message(msg: Message, who: &str) -> ControlFlow<(), ()> {
     match msg {
         Message::Text(t) => {
             info!(">>> {} sent str: {:?}", who, t);
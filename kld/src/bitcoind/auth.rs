@@ -0,0 +1,177 @@
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose, Engine};
+use lightning_block_sync::{http::HttpEndpoint, rpc::RpcClient};
+use log::{info, warn};
+use settings::Settings;
+
+/// How to authenticate with the bitcoind RPC interface.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BitcoindAuth {
+    Cookie(String),
+    UserPass { user: String, password: String },
+}
+
+impl BitcoindAuth {
+    /// The cookie is preferred. User and password are only used if no cookie path is configured
+    /// or if the cookie file doesn't exist.
+    pub fn from_settings(settings: &Settings) -> Result<BitcoindAuth> {
+        let cookie_path = &settings.bitcoin_cookie_path;
+        let has_cookie = !cookie_path.is_empty();
+        let has_user = !settings.bitcoind_rpc_user.is_empty();
+        if has_cookie && (!has_user || Path::new(cookie_path).exists()) {
+            Ok(BitcoindAuth::Cookie(cookie_path.clone()))
+        } else if has_user {
+            Ok(BitcoindAuth::UserPass {
+                user: settings.bitcoind_rpc_user.clone(),
+                password: settings.bitcoind_rpc_password.clone(),
+            })
+        } else {
+            bail!(
+                "No bitcoind credentials, set either the cookie path or the RPC user and password"
+            )
+        }
+    }
+
+    fn credentials(&self) -> Result<String> {
+        let credentials = match self {
+            BitcoindAuth::Cookie(path) => std::fs::read(path)
+                .with_context(|| format!("Failed to read bitcoin cookie {path}"))?,
+            BitcoindAuth::UserPass { user, password } => format!("{user}:{password}").into_bytes(),
+        };
+        Ok(general_purpose::STANDARD.encode(credentials))
+    }
+}
+
+impl fmt::Display for BitcoindAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitcoindAuth::Cookie(path) => write!(f, "cookie {path}"),
+            BitcoindAuth::UserPass { user, .. } => write!(f, "user {user}"),
+        }
+    }
+}
+
+/// RPC client which picks up the new cookie when bitcoind rotates it on restart. The cookie is
+/// only read again after a call failed in a way a new cookie could explain.
+pub(crate) struct RpcConnection {
+    auth: BitcoindAuth,
+    endpoint: HttpEndpoint,
+    current: Mutex<(String, Arc<RpcClient>)>,
+}
+
+impl RpcConnection {
+    pub fn new(auth: BitcoindAuth, endpoint: HttpEndpoint) -> Result<RpcConnection> {
+        let credentials = auth.credentials()?;
+        let client = Arc::new(
+            RpcClient::new(&credentials, endpoint.clone())
+                .context("failed to create rpc client")?,
+        );
+        Ok(RpcConnection {
+            auth,
+            endpoint,
+            current: Mutex::new((credentials, client)),
+        })
+    }
+
+    pub fn client(&self) -> Arc<RpcClient> {
+        self.current.lock().unwrap().1.clone()
+    }
+
+    /// Call after an RPC failed. bitcoind writes a new cookie when it restarts, so the old one is
+    /// refused with 401 or the connection fails while it is down.
+    pub fn check_error(&self, error: &io::Error) {
+        let needs_reload = match error.kind() {
+            ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::TimedOut
+            | ErrorKind::UnexpectedEof => true,
+            // lightning-block-sync only reports the HTTP status in the error message.
+            ErrorKind::Other => error.to_string().contains("401"),
+            _ => false,
+        };
+        if needs_reload {
+            self.reload_cookie();
+        }
+    }
+
+    pub fn reload_cookie(&self) {
+        let path = match &self.auth {
+            BitcoindAuth::Cookie(path) => path,
+            BitcoindAuth::UserPass { .. } => return,
+        };
+        let credentials = match self.auth.credentials() {
+            Ok(credentials) => credentials,
+            // bitcoind removes the cookie while it restarts.
+            Err(e) => {
+                warn!("{e}");
+                return;
+            }
+        };
+        let mut current = self.current.lock().unwrap();
+        if credentials == current.0 {
+            return;
+        }
+        match RpcClient::new(&credentials, self.endpoint.clone()) {
+            Ok(client) => {
+                info!("Bitcoin cookie {path} changed, reconnecting");
+                *current = (credentials, Arc::new(client));
+            }
+            Err(e) => warn!("Failed to create rpc client: {e}"),
+        }
+    }
+}
+
+#[test]
+fn test_auth_from_settings() {
+    let mut settings = Settings::default();
+    settings.bitcoin_cookie_path = "/does/not/exist/.cookie".to_string();
+    assert_eq!(
+        BitcoindAuth::Cookie(settings.bitcoin_cookie_path.clone()),
+        BitcoindAuth::from_settings(&settings).unwrap()
+    );
+
+    settings.bitcoind_rpc_user = "kld".to_string();
+    settings.bitcoind_rpc_password = "secret".to_string();
+    assert_eq!(
+        BitcoindAuth::UserPass {
+            user: "kld".to_string(),
+            password: "secret".to_string()
+        },
+        BitcoindAuth::from_settings(&settings).unwrap()
+    );
+
+    settings.bitcoin_cookie_path = String::new();
+    settings.bitcoind_rpc_user = String::new();
+    assert!(BitcoindAuth::from_settings(&settings).is_err());
+}
+
+#[test]
+fn test_reload_cookie_after_error() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("kld-test-cookie-{}", std::process::id()));
+    std::fs::write(&path, "__cookie__:first")?;
+    let connection = RpcConnection::new(
+        BitcoindAuth::Cookie(path.to_string_lossy().to_string()),
+        HttpEndpoint::for_host("127.0.0.1".to_string()),
+    )?;
+    let client = connection.client();
+    std::fs::write(&path, "__cookie__:second")?;
+
+    connection.check_error(&io::Error::new(ErrorKind::Other, "RPC error -8"));
+    assert!(Arc::ptr_eq(&client, &connection.client()));
+
+    connection.check_error(&io::Error::new(ErrorKind::ConnectionRefused, "refused"));
+    assert!(!Arc::ptr_eq(&client, &connection.client()));
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
@@ -9,14 +9,13 @@ use std::{
 use anyhow::{anyhow, bail, Context, Result};
 
 use async_trait::async_trait;
-use base64::{engine::general_purpose, Engine};
-use bitcoin::{consensus::encode, Address, BlockHash, Transaction, Txid};
+use bitcoin::{consensus::encode, Address, BlockHash, OutPoint, Transaction, Txid};
 use bitcoincore_rpc_json::{EstimateMode, EstimateSmartFeeResult, GetBlockchainInfoResult};
 use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
 use lightning_block_sync::{
     http::{HttpEndpoint, JsonResponse},
-    rpc::RpcClient,
-    AsyncBlockSourceResult, BlockData, BlockHeaderData, BlockSource,
+    AsyncBlockSourceResult, BlockData, BlockHeaderData, BlockSource, BlockSourceErrorKind,
+    BlockSourceResult,
 };
 use log::{error, info};
 use serde::Deserialize;
@@ -25,30 +24,36 @@ use settings::Settings;
 
 use crate::{ldk::MIN_FEERATE, quit_signal};
 
-use super::Synchronised;
+use super::{
+    auth::{BitcoindAuth, RpcConnection},
+    Synchronised,
+};
 
 pub struct BitcoindClient {
-    client: Arc<RpcClient>,
+    rpc: Arc<RpcConnection>,
     priorities: Arc<Priorities>,
 }
 
 impl BitcoindClient {
     pub async fn new(settings: &Settings) -> Result<BitcoindClient> {
-        let cookie = std::fs::read(&settings.bitcoin_cookie_path)
-            .context("Failed to read bitcoin cookie")?;
-        let credentials = general_purpose::STANDARD.encode(cookie);
+        let auth = BitcoindAuth::from_settings(settings)?;
         let http_endpoint = HttpEndpoint::for_host(settings.bitcoind_rpc_host.clone())
             .with_port(settings.bitcoind_rpc_port);
-        let client = Arc::new(
-            RpcClient::new(&credentials, http_endpoint).context("failed to create rpc client")?,
-        );
+        let rpc = Arc::new(RpcConnection::new(auth.clone(), http_endpoint)?);
 
         let priorities = Arc::new(Priorities::new());
-        let bitcoind_client = BitcoindClient { client, priorities };
+        let bitcoind_client = BitcoindClient { rpc, priorities };
 
+        // Fail early if bitcoind can't be reached or doesn't accept the credentials.
+        let blockchain_info = bitcoind_client.get_blockchain_info().await.with_context(|| {
+            format!(
+                "Failed to call bitcoind at {}:{} with {auth}. Check that it is running and accepts the credentials",
+                settings.bitcoind_rpc_host, settings.bitcoind_rpc_port
+            )
+        })?;
         // Check that the bitcoind we've connected to is running the network we expect
-        let bitcoind_chain = bitcoind_client.get_blockchain_info().await?.chain;
-        if bitcoind_chain != settings.bitcoin_network.to_string() {
+        let bitcoind_chain = blockchain_info.chain;
+        if bitcoind_chain != settings.bitcoin_network.bitcoind_chain() {
             bail!(
                 "Chain argument ({}) didn't match bitcoind chain ({bitcoind_chain})",
                 settings.bitcoin_network,
@@ -74,22 +79,20 @@ impl BitcoindClient {
 
     pub async fn send_transaction(&self, tx: &Transaction) -> Result<Txid> {
         let tx_serialized = json!(encode::serialize_hex(tx));
-        BitcoindClient::send_transaction_with_client(self.client.clone(), tx_serialized).await
+        BitcoindClient::send_transaction_with_client(&self.rpc, tx_serialized).await
     }
 
     async fn send_transaction_with_client(
-        client: Arc<RpcClient>,
+        rpc: &RpcConnection,
         tx_serialized: Value,
     ) -> Result<Txid> {
-        client
-            .call_method::<JsonString>("sendrawtransaction", &[tx_serialized])
+        call_method(rpc, "sendrawtransaction", &[tx_serialized])
             .await?
             .deserialize()
     }
 
     pub async fn get_blockchain_info(&self) -> Result<GetBlockchainInfoResult> {
-        self.client
-            .call_method::<JsonString>("getblockchaininfo", &[])
+        call_method(&self.rpc, "getblockchaininfo", &[])
             .await?
             .deserialize()
     }
@@ -99,39 +102,53 @@ impl BitcoindClient {
         n_blocks: u64,
         address: &Address,
     ) -> Result<Vec<BlockHash>> {
-        self.client
-            .call_method::<JsonString>("generatetoaddress", &[json!(n_blocks), json!(address)])
-            .await?
-            .deserialize()
+        call_method(
+            &self.rpc,
+            "generatetoaddress",
+            &[json!(n_blocks), json!(address)],
+        )
+        .await?
+        .deserialize()
     }
 
     pub async fn get_block_hash(&self, height: u32) -> Result<BlockHash> {
-        self.client
-            .call_method::<JsonString>("getblockhash", &[json!(height)])
+        call_method(&self.rpc, "getblockhash", &[json!(height)])
             .await?
             .deserialize()
     }
 
+    /// Whether the output is unspent, optionally taking spends in the mempool into account.
+    pub async fn is_unspent(&self, outpoint: &OutPoint, include_mempool: bool) -> Result<bool> {
+        let tx_out: Option<Value> = call_method(
+            &self.rpc,
+            "gettxout",
+            &[
+                json!(outpoint.txid),
+                json!(outpoint.vout),
+                json!(include_mempool),
+            ],
+        )
+        .await?
+        .deserialize()?;
+        Ok(tx_out.is_some())
+    }
+
     pub fn poll_for_fee_estimates(&self) {
-        let client = self.client.clone();
+        let rpc = self.rpc.clone();
         let priorities = self.priorities.clone();
         tokio::spawn(async move {
             loop {
                 BitcoindClient::estimate_fee(
                     priorities.clone(),
-                    client.clone(),
+                    &rpc,
                     ConfirmationTarget::Background,
                 )
                 .await;
+                BitcoindClient::estimate_fee(priorities.clone(), &rpc, ConfirmationTarget::Normal)
+                    .await;
                 BitcoindClient::estimate_fee(
                     priorities.clone(),
-                    client.clone(),
-                    ConfirmationTarget::Normal,
-                )
-                .await;
-                BitcoindClient::estimate_fee(
-                    priorities.clone(),
-                    client.clone(),
+                    &rpc,
                     ConfirmationTarget::HighPriority,
                 )
                 .await;
@@ -142,17 +159,17 @@ impl BitcoindClient {
 
     async fn estimate_fee(
         priorities: Arc<Priorities>,
-        client: Arc<RpcClient>,
+        rpc: &RpcConnection,
         conf_target: ConfirmationTarget,
     ) {
         let priority = priorities.priority_of(&conf_target);
-        match client
-            .call_method::<JsonString>(
-                "estimatesmartfee",
-                &[json!(priority.n_blocks), json!(priority.estimate_mode)],
-            )
-            .await
-            .map(|r| serde_json::from_str::<EstimateSmartFeeResult>(&r.0))
+        match call_method(
+            rpc,
+            "estimatesmartfee",
+            &[json!(priority.n_blocks), json!(priority.estimate_mode)],
+        )
+        .await
+        .map(|r| serde_json::from_str::<EstimateSmartFeeResult>(&r.0))
         {
             Ok(Ok(result)) => {
                 // Bitcoind returns fee in BTC/kB.
@@ -218,10 +235,10 @@ impl BroadcasterInterface for BitcoindClient {
     fn broadcast_transaction(&self, tx: &Transaction) {
         // This may error due to RL calling `broadcast_transaction` with the same transaction
         // multiple times, but the error is safe to ignore.
-        let client = self.client.clone();
+        let rpc = self.rpc.clone();
         let tx_serialized = json!(encode::serialize_hex(tx));
         tokio::spawn(async move {
-            match BitcoindClient::send_transaction_with_client(client, tx_serialized).await {
+            match BitcoindClient::send_transaction_with_client(&rpc, tx_serialized).await {
                 Ok(txid) => {
                     info!("Broadcast transaction {txid}");
                 }
@@ -248,19 +265,56 @@ impl BlockSource for BitcoindClient {
         header_hash: &'a BlockHash,
         height_hint: Option<u32>,
     ) -> AsyncBlockSourceResult<'a, BlockHeaderData> {
-        Box::pin(async move { self.client.get_header(header_hash, height_hint).await })
+        Box::pin(async move {
+            let result = self.rpc.client().get_header(header_hash, height_hint).await;
+            self.check_block_source_result(&result);
+            result
+        })
     }
 
     fn get_block<'a>(
         &'a self,
         header_hash: &'a BlockHash,
     ) -> AsyncBlockSourceResult<'a, BlockData> {
-        Box::pin(async move { self.client.get_block(header_hash).await })
+        Box::pin(async move {
+            let result = self.rpc.client().get_block(header_hash).await;
+            self.check_block_source_result(&result);
+            result
+        })
     }
 
     fn get_best_block(&self) -> AsyncBlockSourceResult<(BlockHash, Option<u32>)> {
-        Box::pin(async move { self.client.get_best_block().await })
+        Box::pin(async move {
+            let result = self.rpc.client().get_best_block().await;
+            self.check_block_source_result(&result);
+            result
+        })
+    }
+}
+
+impl BitcoindClient {
+    fn check_block_source_result<T>(&self, result: &BlockSourceResult<T>) {
+        // The underlying io::Error is not accessible, but persistent errors are about the data
+        // and not about the connection.
+        if let Err(e) = result {
+            if e.kind() == BlockSourceErrorKind::Transient {
+                self.rpc.reload_cookie();
+            }
+        }
+    }
+}
+
+/// Call the RPC and pick up a new cookie if bitcoind has rotated it.
+async fn call_method(
+    rpc: &RpcConnection,
+    method: &str,
+    params: &[Value],
+) -> std::io::Result<JsonString> {
+    let result = rpc.client().call_method::<JsonString>(method, params).await;
+    if let Err(e) = &result {
+        rpc.check_error(e);
     }
+    result
 }
 
 struct Priority {
@@ -1,10 +1,14 @@
+mod auth;
 mod bitcoind_client;
 mod utxo_lookup;
+mod zmq;
 
 use anyhow::Result;
 use async_trait::async_trait;
+pub use auth::BitcoindAuth;
 pub use bitcoind_client::BitcoindClient;
 pub use utxo_lookup::BitcoindUtxoLookup;
+pub use zmq::notify_on_new_block;
 
 #[cfg(test)]
 pub mod mock;
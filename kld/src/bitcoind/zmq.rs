@@ -0,0 +1,30 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use log::{info, warn};
+use tokio::sync::Notify;
+use zeromq::{Socket, SocketRecv, SubSocket};
+
+/// Subscribe to the `hashblock` notifications of bitcoind at the ZMQ endpoint
+/// (e.g. tcp://127.0.0.1:28332) and wake up `new_block` for every block.
+pub fn notify_on_new_block(endpoint: String, new_block: Arc<Notify>) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = subscribe(&endpoint, &new_block).await {
+                warn!("ZMQ subscription to {endpoint} failed: {e}");
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    });
+}
+
+async fn subscribe(endpoint: &str, new_block: &Notify) -> Result<()> {
+    let mut socket = SubSocket::new();
+    socket.connect(endpoint).await?;
+    socket.subscribe("hashblock").await?;
+    info!("Subscribed to new blocks at {endpoint}");
+    loop {
+        socket.recv().await?;
+        new_block.notify_one();
+    }
+}
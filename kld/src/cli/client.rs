@@ -1,30 +1,47 @@
 use std::{fs::File, io::Read};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use api::{
-    routes, Channel, ChannelFee, FeeRate, FundChannel, FundChannelResponse, GetInfo,
-    NetworkChannel, NetworkNode, NewAddress, NewAddressResponse, Peer, SetChannelFeeResponse,
-    WalletBalance, WalletTransfer, WalletTransferResponse,
+    routes, AbandonChannel, AbandonChannelResponse, AnnounceResponse, Channel, ChannelEarnings,
+    ChannelEarningsParams, ChannelFee, ChannelFeeReport, ChannelStats, ClosedChannel, DeriveKey,
+    DeriveKeyResponse, ExportForwardsParams, ExportState, ExportStateParams, FailHtlc, FeeRate,
+    Forward, ForwardHtlc, FundChannel, FundChannelResponse, FundingPsbt, GenerateInvoice,
+    GenerateInvoiceResponse, GetInfo, Htlc, ImportDescriptor, InterceptScid, InterceptedHtlc,
+    ListForwardsParams, ListPaymentsParams, Maintenance, MonitorCheck, NetworkChannel, NetworkNode,
+    NetworkStats, NewAddress, NewAddressResponse, OpenChannelPreview, OpenChannelPsbt,
+    OpenChannelPsbtResponse, PayLnurl, Payment, PaymentResponse, Peer, PendingChannelOpen,
+    PendingSweep, PendingWebhook, PruneNetworkGraphResponse, ResetScorerResponse, SetAlias,
+    SetChannelFeeResponse, SignMessage, SignMessageResponse, VerifyMessage, VerifyMessageResponse,
+    WalletBalance, WalletRescan, WalletRescanResponse, WalletTransfer, WalletTransferResponse,
 };
 use bitcoin::secp256k1::PublicKey;
+use hyper::Body;
 use reqwest::{
-    blocking::{Client, ClientBuilder, RequestBuilder, Response},
-    header::{HeaderValue, CONTENT_TYPE},
-    Certificate, Method,
+    blocking::{Client, ClientBuilder, Request, RequestBuilder},
+    header::{HeaderValue, CONTENT_TYPE, HOST},
+    Certificate, Method, StatusCode,
 };
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::to_string_pretty;
+use tokio::net::UnixStream;
+
+use crate::table::{OutputFormat, Table};
 
 pub struct Api {
-    host: String,
     client: Client,
-    macaroon: Vec<u8>,
+    transport: Transport,
+    output: OutputFormat,
+}
+
+enum Transport {
+    Https { host: String, macaroon: Vec<u8> },
+    Unix { path: String },
 }
 
 impl Api {
-    pub fn new(host: &str, cert_path: &str, macaroon_path: &str) -> Result<Api> {
-        let macaroon = read_file(macaroon_path)?;
-        let cert = Certificate::from_pem(&read_file(cert_path)?)?;
+    /// Talk to the node over HTTPS with the PEM encoded `cert` and a hex or base64 encoded `macaroon`.
+    pub fn new(host: &str, cert: &[u8], macaroon: Vec<u8>, output: OutputFormat) -> Result<Api> {
+        let cert = Certificate::from_pem(cert)?;
         // Rustls does not support IP addresses (hostnames only) so we need to use native tls (openssl). Also turn off SNI as this requires host names as well.
         let client = ClientBuilder::new()
             .tls_sni(false)
@@ -33,9 +50,25 @@ impl Api {
             .timeout(None)
             .build()?;
         Ok(Api {
-            host: host.to_string(),
             client,
-            macaroon,
+            transport: Transport::Https {
+                host: host.to_string(),
+                macaroon,
+            },
+            output,
+        })
+    }
+
+    /// Talk to a local node over its Unix socket. The node authorises these requests as admin.
+    pub fn unix(path: &str, output: OutputFormat) -> Result<Api> {
+        // The client is only used to build the requests.
+        let client = ClientBuilder::new().timeout(None).build()?;
+        Ok(Api {
+            client,
+            transport: Transport::Unix {
+                path: path.to_string(),
+            },
+            output,
         })
     }
 
@@ -46,7 +79,7 @@ impl Api {
 
     pub fn get_balance(&self) -> Result<String> {
         let response = self.request(Method::GET, routes::GET_BALANCE).send()?;
-        deserialize::<WalletBalance>(response)
+        self.deserialize_table::<WalletBalance>(response)
     }
 
     pub fn new_address(&self) -> Result<String> {
@@ -75,14 +108,129 @@ impl Api {
         deserialize::<WalletTransferResponse>(response)
     }
 
+    pub fn rescan_wallet(&self, birthday_height: Option<u32>, run_async: bool) -> Result<String> {
+        let wallet_rescan = WalletRescan { birthday_height };
+        let route = if run_async {
+            format!("{}?async=true", routes::WALLET_RESCAN)
+        } else {
+            routes::WALLET_RESCAN.to_string()
+        };
+        let response = self
+            .request_with_body(Method::POST, &route, wallet_rescan)
+            .send()?;
+        deserialize::<WalletRescanResponse>(response)
+    }
+
+    pub fn import_descriptor(&self, descriptor: String) -> Result<String> {
+        let response = self
+            .request_with_body(
+                Method::POST,
+                routes::IMPORT_DESCRIPTOR,
+                ImportDescriptor { descriptor },
+            )
+            .send()?;
+        deserialize::<()>(response)
+    }
+
     pub fn list_channels(&self) -> Result<String> {
         let response = self.request(Method::GET, routes::LIST_CHANNELS).send()?;
-        deserialize::<Vec<Channel>>(response)
+        self.deserialize_table::<Vec<Channel>>(response)
+    }
+
+    pub fn list_closed_channels(&self) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::LIST_CLOSED_CHANNELS)
+            .send()?;
+        deserialize::<Vec<ClosedChannel>>(response)
+    }
+
+    pub fn generate_invoice(
+        &self,
+        amount: Option<u64>,
+        description: String,
+        expiry: Option<u32>,
+        fallback_address: bool,
+    ) -> Result<String> {
+        let generate_invoice = GenerateInvoice {
+            amount,
+            description,
+            expiry,
+            fallback_address,
+        };
+        let response = self
+            .request_with_body(Method::POST, routes::GENERATE_INVOICE, generate_invoice)
+            .send()?;
+        deserialize::<GenerateInvoiceResponse>(response)
+    }
+
+    pub fn list_invoices(&self, params: ListPaymentsParams) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::LIST_INVOICES)
+            .query(&params)
+            .send()?;
+        deserialize::<Vec<Payment>>(response)
+    }
+
+    pub fn pay_lnurl(
+        &self,
+        target: String,
+        amount: u64,
+        max_fee_msat: Option<u64>,
+        max_fee_percent: Option<f64>,
+    ) -> Result<String> {
+        let pay_lnurl = PayLnurl {
+            target,
+            amount,
+            max_fee_msat,
+            max_fee_percent,
+        };
+        let response = self
+            .request_with_body(Method::POST, routes::PAY_LNURL, pay_lnurl)
+            .send()?;
+        deserialize::<PaymentResponse>(response)
+    }
+
+    pub fn get_payment(&self, payment_hash: String) -> Result<String> {
+        let response = self
+            .request(
+                Method::GET,
+                &routes::GET_PAYMENT.replace(":payment_hash", &payment_hash),
+            )
+            .send()?;
+        deserialize::<Payment>(response)
+    }
+
+    pub fn list_payments(&self, params: ListPaymentsParams) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::LIST_PAYMENTS)
+            .query(&params)
+            .send()?;
+        deserialize::<Vec<Payment>>(response)
+    }
+
+    pub fn list_forwards(&self, params: ListForwardsParams) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::LIST_FORWARDS)
+            .query(&params)
+            .send()?;
+        deserialize::<Vec<Forward>>(response)
+    }
+
+    pub fn export_forwards(&self, params: ExportForwardsParams) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::EXPORT_FORWARDS)
+            .query(&params)
+            .send()?;
+        if response.status().is_success() {
+            Ok(response.text()?)
+        } else {
+            Ok(to_string_pretty(&response.json::<api::Error>()?)?)
+        }
     }
 
     pub fn list_peers(&self) -> Result<String> {
         let response = self.request(Method::GET, routes::LIST_PEERS).send()?;
-        deserialize::<Vec<Peer>>(response)
+        self.deserialize_table::<Vec<Peer>>(response)
     }
 
     pub fn connect_peer(&self, id: String) -> Result<String> {
@@ -99,6 +247,39 @@ impl Api {
         deserialize::<()>(response)
     }
 
+    pub fn forget_peer(&self, id: String) -> Result<String> {
+        let response = self
+            .request(Method::DELETE, &routes::FORGET_PEER.replace(":id", &id))
+            .send()?;
+        deserialize::<()>(response)
+    }
+
+    pub fn open_channel_preview(
+        &self,
+        id: String,
+        satoshis: String,
+        push_msat: Option<String>,
+        fee_rate: Option<FeeRate>,
+    ) -> Result<String> {
+        let preview = FundChannel {
+            id,
+            satoshis,
+            fee_rate,
+            announce: None,
+            min_conf: None,
+            utxos: vec![],
+            push_msat,
+            close_to: None,
+            request_amt: None,
+            compact_lease: None,
+            minimum_depth: None,
+        };
+        let response = self
+            .request_with_body(Method::POST, routes::OPEN_CHANNEL_PREVIEW, preview)
+            .send()?;
+        deserialize::<OpenChannelPreview>(response)
+    }
+
     pub fn open_channel(
         &self,
         id: String,
@@ -106,6 +287,7 @@ impl Api {
         push_msat: Option<String>,
         announce: Option<bool>,
         fee_rate: Option<FeeRate>,
+        run_async: bool,
     ) -> Result<String> {
         let open_channel = FundChannel {
             id,
@@ -118,22 +300,73 @@ impl Api {
             close_to: None,
             request_amt: None,
             compact_lease: None,
+            minimum_depth: None,
         };
+        if run_async {
+            let route = format!("{}?async=true", routes::OPEN_CHANNEL);
+            let response = self
+                .request_with_body(Method::POST, &route, open_channel)
+                .send()?;
+            return deserialize::<PendingChannelOpen>(response);
+        }
         let response = self
             .request_with_body(Method::POST, routes::OPEN_CHANNEL, open_channel)
             .send()?;
         deserialize::<FundChannelResponse>(response)
     }
 
+    pub fn open_channel_psbt(
+        &self,
+        id: String,
+        satoshis: String,
+        push_msat: Option<String>,
+        announce: Option<bool>,
+    ) -> Result<String> {
+        let open_channel = OpenChannelPsbt {
+            id,
+            satoshis,
+            announce,
+            push_msat,
+        };
+        let response = self
+            .request_with_body(Method::POST, routes::OPEN_CHANNEL_PSBT, open_channel)
+            .send()?;
+        deserialize::<OpenChannelPsbtResponse>(response)
+    }
+
+    pub fn funding_psbt(&self, channel_id: String, psbt: String) -> Result<String> {
+        let funding = FundingPsbt { channel_id, psbt };
+        let response = self
+            .request_with_body(Method::POST, routes::FUNDING_PSBT, funding)
+            .send()?;
+        deserialize::<FundChannelResponse>(response)
+    }
+
+    pub fn pending_channel_open(&self, id: String) -> Result<String> {
+        let response = self
+            .request(
+                Method::GET,
+                &routes::GET_PENDING_CHANNEL_OPEN.replace(":id", &id),
+            )
+            .send()?;
+        deserialize::<PendingChannelOpen>(response)
+    }
+
     pub fn set_channel_fee(
         &self,
         id: String,
         base: Option<u32>,
         ppm: Option<u32>,
+        force: bool,
     ) -> Result<String> {
         let fee_request = ChannelFee { id, base, ppm };
+        let route = if force {
+            format!("{}?force=true", routes::SET_CHANNEL_FEE)
+        } else {
+            routes::SET_CHANNEL_FEE.to_string()
+        };
         let response = self
-            .request_with_body(Method::POST, routes::SET_CHANNEL_FEE, fee_request)
+            .request_with_body(Method::POST, &route, fee_request)
             .send()?;
         deserialize::<SetChannelFeeResponse>(response)
     }
@@ -145,6 +378,36 @@ impl Api {
         deserialize::<()>(response)
     }
 
+    pub fn abandon_channel(&self, id: String) -> Result<String> {
+        let response = self
+            .request_with_body(Method::POST, routes::ABANDON_CHANNEL, AbandonChannel { id })
+            .send()?;
+        deserialize::<AbandonChannelResponse>(response)
+    }
+
+    pub fn channel_stats(&self, id: String) -> Result<String> {
+        let response = self
+            .request(Method::GET, &routes::GET_CHANNEL_STATS.replace(":id", &id))
+            .send()?;
+        deserialize::<ChannelStats>(response)
+    }
+
+    pub fn channel_earnings(&self, id: String, bucket: Option<String>) -> Result<String> {
+        let response = self
+            .request(
+                Method::GET,
+                &routes::GET_CHANNEL_EARNINGS.replace(":id", &id),
+            )
+            .query(&ChannelEarningsParams { bucket })
+            .send()?;
+        deserialize::<Vec<ChannelEarnings>>(response)
+    }
+
+    pub fn fee_report(&self) -> Result<String> {
+        let response = self.request(Method::GET, routes::FEE_REPORT).send()?;
+        deserialize::<Vec<ChannelFeeReport>>(response)
+    }
+
     pub fn list_network_nodes(&self, id: Option<String>) -> Result<String> {
         let response = if let Some(id) = id {
             self.request(Method::GET, &routes::LIST_NETWORK_NODE.replace(":id", &id))
@@ -170,29 +433,273 @@ impl Api {
         deserialize::<Vec<NetworkChannel>>(response)
     }
 
-    fn request_builder(&self, method: Method, route: &str) -> RequestBuilder {
-        self.client
-            .request(method, format!("https://{}{}", self.host, route))
-            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
-            .header("macaroon", self.macaroon.clone())
+    pub fn network_stats(&self) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::GET_NETWORK_STATS)
+            .send()?;
+        deserialize::<NetworkStats>(response)
     }
 
-    fn request(&self, method: Method, route: &str) -> RequestBuilder {
-        self.request_builder(method, route)
+    pub fn prune_network_graph(&self) -> Result<String> {
+        let response = self
+            .request(Method::POST, routes::PRUNE_NETWORK_GRAPH)
+            .send()?;
+        deserialize::<PruneNetworkGraphResponse>(response)
+    }
+
+    pub fn set_alias(&self, alias: String, color: Option<String>) -> Result<String> {
+        let response = self
+            .request_with_body(Method::POST, routes::SET_ALIAS, SetAlias { alias, color })
+            .send()?;
+        deserialize::<SetAlias>(response)
+    }
+
+    pub fn announce(&self) -> Result<String> {
+        let response = self.request(Method::POST, routes::ANNOUNCE).send()?;
+        deserialize::<AnnounceResponse>(response)
+    }
+
+    pub fn set_maintenance_mode(&self, enabled: bool) -> Result<String> {
+        let response = self
+            .request_with_body(Method::POST, routes::MAINTENANCE, Maintenance { enabled })
+            .send()?;
+        deserialize::<Maintenance>(response)
+    }
+
+    pub fn reset_scorer(&self) -> Result<String> {
+        let response = self.request(Method::POST, routes::RESET_SCORER).send()?;
+        deserialize::<ResetScorerResponse>(response)
+    }
+
+    pub fn check_monitors(&self) -> Result<String> {
+        let response = self.request(Method::GET, routes::CHECK_MONITORS).send()?;
+        deserialize::<MonitorCheck>(response)
+    }
+
+    pub fn list_pending_sweeps(&self) -> Result<String> {
+        let response = self.request(Method::GET, routes::PENDING_SWEEPS).send()?;
+        deserialize::<Vec<PendingSweep>>(response)
+    }
+
+    pub fn export_state(&self, confirm: bool) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::EXPORT_STATE)
+            .query(&ExportStateParams {
+                confirm: Some(confirm),
+            })
+            .send()?;
+        deserialize::<ExportState>(response)
+    }
+
+    pub fn sign_message(&self, message: String) -> Result<String> {
+        let response = self
+            .request_with_body(Method::POST, routes::SIGN_MESSAGE, SignMessage { message })
+            .send()?;
+        deserialize::<SignMessageResponse>(response)
+    }
+
+    pub fn derive_key(&self, path: String) -> Result<String> {
+        let response = self
+            .request_with_body(Method::POST, routes::DERIVE_KEY, DeriveKey { path })
+            .send()?;
+        deserialize::<DeriveKeyResponse>(response)
+    }
+
+    pub fn verify_message(
+        &self,
+        message: String,
+        signature: String,
+        pubkey: Option<String>,
+    ) -> Result<String> {
+        let verify_message = VerifyMessage {
+            message,
+            signature,
+            pubkey,
+        };
+        let response = self
+            .request_with_body(Method::POST, routes::VERIFY_MESSAGE, verify_message)
+            .send()?;
+        deserialize::<VerifyMessageResponse>(response)
+    }
+
+    pub fn list_pending_webhooks(&self) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::LIST_PENDING_WEBHOOKS)
+            .send()?;
+        deserialize::<Vec<PendingWebhook>>(response)
+    }
+
+    pub fn list_htlcs(&self) -> Result<String> {
+        let response = self.request(Method::GET, routes::LIST_HTLCS).send()?;
+        deserialize::<Vec<Htlc>>(response)
     }
 
-    fn request_with_body<T: Serialize>(
+    pub fn intercept_scid(&self) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::GET_INTERCEPT_SCID)
+            .send()?;
+        deserialize::<InterceptScid>(response)
+    }
+
+    pub fn forward_htlc(
         &self,
-        method: Method,
-        route: &str,
-        body: T,
-    ) -> RequestBuilder {
+        intercept_id: String,
+        channel_id: String,
+        amount_msat: u64,
+    ) -> Result<String> {
+        let forward_htlc = ForwardHtlc {
+            intercept_id,
+            channel_id,
+            amount_msat,
+        };
+        let response = self
+            .request_with_body(Method::POST, routes::FORWARD_HTLC, forward_htlc)
+            .send()?;
+        deserialize::<()>(response)
+    }
+
+    pub fn fail_htlc(&self, intercept_id: String) -> Result<String> {
+        let response = self
+            .request_with_body(Method::POST, routes::FAIL_HTLC, FailHtlc { intercept_id })
+            .send()?;
+        deserialize::<()>(response)
+    }
+
+    pub fn list_intercepted_htlcs(&self) -> Result<String> {
+        let response = self
+            .request(Method::GET, routes::LIST_INTERCEPTED_HTLCS)
+            .send()?;
+        deserialize::<Vec<InterceptedHtlc>>(response)
+    }
+
+    fn deserialize_table<T: DeserializeOwned + Serialize + Table>(
+        &self,
+        response: ApiResponse,
+    ) -> Result<String> {
+        if self.output == OutputFormat::Table && response.status().is_success() {
+            Ok(response.json::<T>()?.to_table())
+        } else {
+            deserialize::<T>(response)
+        }
+    }
+
+    fn request_builder(&self, method: Method, route: &str) -> ApiRequest {
+        let builder = match &self.transport {
+            Transport::Https { host, macaroon } => self
+                .client
+                .request(method, format!("https://{host}{route}"))
+                .header("macaroon", macaroon.clone()),
+            Transport::Unix { .. } => self
+                .client
+                .request(method, format!("http://localhost{route}")),
+        };
+        ApiRequest {
+            api: self,
+            builder: builder.header(CONTENT_TYPE, HeaderValue::from_static("application/json")),
+        }
+    }
+
+    fn request(&self, method: Method, route: &str) -> ApiRequest {
+        self.request_builder(method, route)
+    }
+
+    fn request_with_body<T: Serialize>(&self, method: Method, route: &str, body: T) -> ApiRequest {
         let body = serde_json::to_string(&body).unwrap();
-        self.request_builder(method, route).body(body)
+        let request = self.request_builder(method, route);
+        ApiRequest {
+            api: request.api,
+            builder: request.builder.body(body),
+        }
+    }
+}
+
+struct ApiRequest<'a> {
+    api: &'a Api,
+    builder: RequestBuilder,
+}
+
+impl<'a> ApiRequest<'a> {
+    fn query<T: Serialize + ?Sized>(self, query: &T) -> ApiRequest<'a> {
+        ApiRequest {
+            api: self.api,
+            builder: self.builder.query(query),
+        }
+    }
+
+    fn send(self) -> Result<ApiResponse> {
+        let request = self.builder.build()?;
+        match &self.api.transport {
+            Transport::Https { .. } => {
+                let response = self.api.client.execute(request)?;
+                Ok(ApiResponse {
+                    status: response.status(),
+                    body: response.bytes()?.to_vec(),
+                })
+            }
+            Transport::Unix { path } => send_unix(path, request),
+        }
     }
 }
 
-fn deserialize<T: DeserializeOwned + Serialize>(response: Response) -> Result<String> {
+struct ApiResponse {
+    status: StatusCode,
+    body: Vec<u8>,
+}
+
+impl ApiResponse {
+    fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    fn text(self) -> Result<String> {
+        Ok(String::from_utf8(self.body)?)
+    }
+
+    fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// Plain HTTP over the Unix socket. Reqwest can't do this so the request is sent with hyper.
+fn send_unix(path: &str, request: Request) -> Result<ApiResponse> {
+    let uri = match request.url().query() {
+        Some(query) => format!("{}?{query}", request.url().path()),
+        None => request.url().path().to_string(),
+    };
+    let mut builder = hyper::Request::builder()
+        .method(request.method().clone())
+        .uri(uri)
+        .header(HOST, "localhost");
+    for (name, value) in request.headers() {
+        builder = builder.header(name, value);
+    }
+    let body = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .unwrap_or_default()
+        .to_vec();
+    let hyper_request = builder.body(Body::from(body))?;
+
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(async {
+            let stream = UnixStream::connect(path)
+                .await
+                .with_context(|| format!("failed to connect to {path}"))?;
+            let (mut sender, connection) = hyper::client::conn::handshake(stream).await?;
+            tokio::spawn(connection);
+            let response = sender.send_request(hyper_request).await?;
+            let status = response.status();
+            let body = hyper::body::to_bytes(response.into_body()).await?;
+            Ok(ApiResponse {
+                status,
+                body: body.to_vec(),
+            })
+        })
+}
+
+fn deserialize<T: DeserializeOwned + Serialize>(response: ApiResponse) -> Result<String> {
     if response.status().is_success() {
         Ok(to_string_pretty(&response.json::<T>()?)?)
     } else {
@@ -200,7 +707,7 @@ fn deserialize<T: DeserializeOwned + Serialize>(response: Response) -> Result<St
     }
 }
 
-fn read_file(path: &str) -> Result<Vec<u8>> {
+pub fn read_file(path: &str) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
     match File::open(path) {
         Ok(mut file) => match file.read_to_end(&mut buf) {
@@ -0,0 +1,53 @@
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Defaults for the connection flags, read from ~/.kld/cli.toml unless --config is given.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub target: Option<String>,
+    pub cert_path: Option<String>,
+    pub macaroon_path: Option<String>,
+    pub socket: Option<String>,
+}
+
+impl Config {
+    /// Load the config file. A missing default file is the same as an empty one, but a file
+    /// passed explicitly must exist.
+    pub fn load(path: Option<&str>) -> Result<Config> {
+        let (path, required) = match path {
+            Some(path) => (PathBuf::from(path), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound && !required => return Ok(Config::default()),
+            Err(e) => return Err(e).with_context(|| format!("{}", path.display())),
+        };
+        toml::from_str(&contents).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".kld/cli.toml"))
+}
+
+#[test]
+fn test_parse_config() {
+    let config: Config = toml::from_str(
+        r#"
+        target = "127.0.0.1:2244"
+        cert-path = "/var/lib/kld/certs/kld.crt"
+        macaroon-path = "/var/lib/kld/macaroons/admin.macaroon"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(Some("127.0.0.1:2244".to_string()), config.target);
+    assert_eq!(None, config.socket);
+    assert!(toml::from_str::<Config>("unknown = 1").is_err());
+}
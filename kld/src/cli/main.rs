@@ -1,22 +1,41 @@
 mod client;
+mod config;
+mod table;
 
-use crate::client::Api;
-use anyhow::Result;
-use api::FeeRate;
+use std::env;
+
+use crate::{
+    client::{read_file, Api},
+    config::Config,
+    table::OutputFormat,
+};
+use anyhow::{Context, Result};
+use api::{ExportForwardsParams, FeeRate, ListForwardsParams, ListPaymentsParams};
 use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// IP address or hostname of the target machine.
+    #[arg(short, long, env = "KLD_TARGET")]
+    target: Option<String>,
+    /// Path to the TLS cert of the target API. Alternatively set KLD_CERT to the PEM encoded cert.
     #[arg(short, long)]
-    target: String,
-    /// Path to the TLS cert of the target API.
-    #[arg(short, long)]
-    cert_path: String,
-    /// Path to the macaroon for authenticating with the API.
+    cert_path: Option<String>,
+    /// Path to the macaroon for authenticating with the API. Alternatively set KLD_MACAROON to the
+    /// hex or base64 encoded macaroon.
     #[arg(short, long)]
-    macaroon_path: String,
+    macaroon_path: Option<String>,
+    /// Path to the Unix socket of a node on this host. Used instead of target, cert and macaroon.
+    #[arg(short, long, conflicts_with_all = ["target", "cert_path", "macaroon_path"])]
+    socket: Option<String>,
+    /// TOML file with defaults for target, cert-path, macaroon-path and socket [default: ~/.kld/cli.toml].
+    /// Flags take precedence over environment variables, which take precedence over the config file.
+    #[arg(long)]
+    config: Option<String>,
+    /// Output format of the response.
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Json)]
+    output: OutputFormat,
     /// Command to run.
     #[clap(subcommand)]
     command: Command,
@@ -42,6 +61,102 @@ enum Command {
         #[arg(long)]
         fee_rate: Option<FeeRate>,
     },
+    /// Rescan the blockchain for on-chain wallet transactions.
+    RescanWallet {
+        /// Block height to start scanning from. Scans from genesis if not set.
+        #[arg(long)]
+        birthday_height: Option<u32>,
+        /// Return immediately instead of waiting for the rescan to complete.
+        #[arg(long)]
+        run_async: bool,
+    },
+    /// Watch an external descriptor. Its balance is reported separately and never spent.
+    ImportDescriptor {
+        /// Output descriptor with public keys only, e.g. wpkh(xpub.../0/*).
+        #[arg(long)]
+        descriptor: String,
+    },
+    /// Create an invoice for receiving a payment.
+    GenerateInvoice {
+        /// Amount in millisatoshis. Leave empty to accept any amount.
+        #[arg(long)]
+        amount: Option<u64>,
+        /// Description of the payment.
+        #[arg(long)]
+        description: String,
+        /// Seconds until the invoice expires.
+        #[arg(long)]
+        expiry: Option<u32>,
+        /// Add an on-chain fallback address to the invoice.
+        #[arg(long)]
+        fallback_address: bool,
+    },
+    /// List invoices created by this node.
+    ListInvoices {
+        /// Only list invoices with this status (pending, succeeded or failed).
+        #[arg(long)]
+        status: Option<String>,
+        /// Only list invoices paid at or after this unix timestamp.
+        #[arg(long)]
+        since: Option<u64>,
+        /// Only list invoices paid before this unix timestamp.
+        #[arg(long)]
+        until: Option<u64>,
+    },
+    /// Pay to a LNURL or lightning address.
+    PayLnurl {
+        /// Bech32 encoded LNURL or lightning address (user@domain).
+        #[arg(long)]
+        target: String,
+        /// Amount in millisatoshis.
+        #[arg(long)]
+        amount: u64,
+        /// Largest total routing fee in millisatoshis.
+        #[arg(long)]
+        max_fee_msat: Option<u64>,
+        /// Largest total routing fee as a percentage of the amount. Defaults to 0.5% + 1 sat if no limit is given.
+        #[arg(long)]
+        max_fee_percent: Option<f64>,
+    },
+    /// Get the status of an outbound payment.
+    GetPayment {
+        /// Hex encoded payment hash.
+        #[arg(long)]
+        payment_hash: String,
+    },
+    /// List payments sent by this node.
+    ListPayments {
+        /// Only list payments with this status (pending, succeeded or failed).
+        #[arg(long)]
+        status: Option<String>,
+        /// Only list payments settled at or after this unix timestamp.
+        #[arg(long)]
+        since: Option<u64>,
+        /// Only list payments settled before this unix timestamp.
+        #[arg(long)]
+        until: Option<u64>,
+    },
+    /// List payments forwarded by this node, oldest first.
+    ListForwards {
+        /// Only list forwards at or after this unix timestamp.
+        #[arg(long)]
+        since: Option<u64>,
+        /// Only list forwards before this unix timestamp.
+        #[arg(long)]
+        until: Option<u64>,
+        /// Maximum number of forwards to list.
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Export the forwarding history as CSV.
+    ExportForwards {
+        /// Only export forwards at or after this unix timestamp.
+        #[arg(long)]
+        since: Option<u64>,
+        /// Only export forwards before this unix timestamp.
+        #[arg(long)]
+        until: Option<u64>,
+    },
     /// Fetch a list of this nodes peers.
     ListPeers,
     /// Connect with a network peer.
@@ -56,8 +171,16 @@ enum Command {
         #[arg(long)]
         public_key: String,
     },
+    /// Delete the stored addresses of a peer without disconnecting.
+    ForgetPeer {
+        /// The public key of the node to forget.
+        #[arg(long)]
+        public_key: String,
+    },
     /// Fetch a list of this nodes open channels.
     ListChannels,
+    /// List closed channels with their closing and sweep transactions.
+    ListClosedChannels,
     /// Open a channel with another node.
     OpenChannel {
         /// The public key of the node to open a channel with. Optionally provide host and port [id@host:port].
@@ -69,16 +192,64 @@ enum Command {
         /// The number of satoshis to push to the other node side of the channel.
         #[arg(long)]
         push_msat: Option<String>,
-        /// Whether to announce the channel to the rest of the network (public - default) or not (private).
+        /// Whether to announce the channel to the rest of the network (public) or not (private). Defaults to the node setting.
         #[arg(long)]
         announce: Option<bool>,
         /// Fee rate [urgent/normal/slow/<sats>perkw/<sats>perkb]
         #[arg(long)]
         fee_rate: Option<FeeRate>,
+        /// Return a tracking id immediately instead of waiting for the funding transaction.
+        #[arg(long)]
+        run_async: bool,
+    },
+    /// Show the reserves, balances and funding fee of a channel open without opening it.
+    OpenChannelPreview {
+        /// The public key of the node to open a channel with.
+        #[arg(long)]
+        public_key: String,
+        /// Amount of satoshis to commit to the channel.
+        #[arg(long)]
+        sats: String,
+        /// The number of satoshis to push to the other node side of the channel.
+        #[arg(long)]
+        push_msat: Option<String>,
+        /// Fee rate [urgent/normal/slow/<sats>perkw/<sats>perkb]
+        #[arg(long)]
+        fee_rate: Option<FeeRate>,
+    },
+    /// Open a channel funded by an external wallet. Prints a PSBT paying the funding output.
+    OpenChannelPsbt {
+        /// The public key of the node to open a channel with. Optionally provide host and port [id@host:port].
+        #[arg(long)]
+        public_key: String,
+        /// Amount of satoshis to commit to the channel.
+        #[arg(long)]
+        sats: String,
+        /// The number of satoshis to push to the other node side of the channel.
+        #[arg(long)]
+        push_msat: Option<String>,
+        /// Whether to announce the channel to the rest of the network (public) or not (private). Defaults to the node setting.
+        #[arg(long)]
+        announce: Option<bool>,
+    },
+    /// Complete a channel open started with open-channel-psbt.
+    FundingPsbt {
+        /// Temporary channel ID returned by open-channel-psbt.
+        #[arg(long)]
+        channel_id: String,
+        /// The signed and finalized PSBT (base64).
+        #[arg(long)]
+        psbt: String,
+    },
+    /// Get the status of a channel open started with --run-async.
+    PendingChannelOpen {
+        /// Tracking id returned by open-channel.
+        #[arg(long)]
+        id: String,
     },
     /// Set channel fees.
     SetChannelFee {
-        /// Channel ID, short channel ID or "all" for all channels.
+        /// Channel ID, short channel ID, peer public key for all channels with a peer or "all" for all channels.
         #[arg(long)]
         id: String,
         /// Optional value in msats added as base fee to any routed payment.
@@ -87,6 +258,9 @@ enum Command {
         /// Optional value that is added proportionally per-millionths to any routed payment volume in satoshi
         #[arg(long)]
         ppm_fee: Option<u32>,
+        /// Set the fee even if it exceeds the maximum configured on the node.
+        #[arg(long)]
+        force: bool,
     },
     /// Close a channel.
     CloseChannel {
@@ -94,6 +268,29 @@ enum Command {
         #[arg(long)]
         id: String,
     },
+    /// Abandon a pending channel whose funding transaction will never confirm.
+    AbandonChannel {
+        /// Channel ID of the pending channel.
+        #[arg(long)]
+        id: String,
+    },
+    /// Fetch forwarding statistics of a channel.
+    ChannelStats {
+        /// Channel ID or short channel ID.
+        #[arg(long)]
+        id: String,
+    },
+    /// Fees earned by forwarding through a channel, grouped by time.
+    ChannelEarnings {
+        /// Channel ID or short channel ID.
+        #[arg(long)]
+        id: String,
+        /// Group the fees by hour, day (default) or week.
+        #[arg(long)]
+        bucket: Option<String>,
+    },
+    /// Show our and our peers' forwarding policies for every channel.
+    FeeReport,
     /// Get node information from the network graph.
     NetworkNodes {
         /// Provide Node ID to get info about a single node.
@@ -106,6 +303,90 @@ enum Command {
         #[arg(long)]
         id: Option<String>,
     },
+    /// Get aggregate statistics of the network graph.
+    NetworkStats,
+    /// Remove stale channels and the nodes left without channels from the network graph.
+    PruneNetworkGraph,
+    /// Reset the probabilistic scorer used for pathfinding.
+    ResetScorer,
+    /// Check that the persisted channel monitors deserialize and match the ones in memory.
+    CheckMonitors,
+    /// List outputs of closed channels which have not been swept into the wallet yet.
+    PendingSweeps,
+    /// Export channel monitors, network graph and peers for migration tooling. Importing them
+    /// into a node with the same keys while this node is running loses the funds in its channels.
+    ExportState {
+        /// Confirm that you understand the risk of the exported state.
+        #[arg(long)]
+        confirm: bool,
+    },
+    /// Sign a message with the node key to prove ownership of the node.
+    SignMessage {
+        /// The message to sign.
+        #[arg(long)]
+        message: String,
+    },
+    /// Derive a public key from the node seed. Needs the derivekey macaroon.
+    DeriveKey {
+        /// BIP32 derivation path, e.g. m/138'/0.
+        #[arg(long)]
+        path: String,
+    },
+    /// Verify a signed message and show whether the signer is a known node.
+    VerifyMessage {
+        /// The signed message.
+        #[arg(long)]
+        message: String,
+        /// zbase32 encoded signature.
+        #[arg(long)]
+        signature: String,
+        /// The public key of the node expected to have signed the message.
+        #[arg(long)]
+        pubkey: Option<String>,
+    },
+    /// Change the node alias and color until the next restart.
+    SetAlias {
+        /// New alias of the node (at most 32 bytes).
+        #[arg(long)]
+        alias: String,
+        /// New color as hex, e.g. ff9900.
+        #[arg(long)]
+        color: Option<String>,
+    },
+    /// Broadcast the node announcement now.
+    Announce,
+    /// Reject new channels while existing channels keep routing.
+    Maintenance {
+        /// Enable maintenance mode. Leave out to disable it.
+        #[arg(long)]
+        enabled: bool,
+    },
+    /// Fetch webhook events which have not been delivered yet.
+    ListPendingWebhooks,
+    /// List unresolved HTLCs of all channels, closest to expiry first. Dust HTLCs are not included.
+    ListHtlcs,
+    /// Get a short channel id for route hints whose HTLCs are intercepted.
+    InterceptScid,
+    /// Forward an intercepted HTLC through a channel.
+    ForwardHtlc {
+        /// The intercept id (hex) from the htlc_intercepted event.
+        #[arg(long)]
+        intercept_id: String,
+        /// The channel id (hex) to forward through.
+        #[arg(long)]
+        channel_id: String,
+        /// The amount to forward in msats.
+        #[arg(long)]
+        amount_msat: u64,
+    },
+    /// Fail an intercepted HTLC back to the sender.
+    FailHtlc {
+        /// The intercept id (hex) from the htlc_intercepted event.
+        #[arg(long)]
+        intercept_id: String,
+    },
+    /// List intercepted HTLCs which have not been forwarded or failed yet.
+    ListInterceptedHtlcs,
 }
 
 fn main() {
@@ -118,7 +399,27 @@ fn main() {
 }
 
 fn run_command(args: Args) -> Result<()> {
-    let api = Api::new(&args.target, &args.cert_path, &args.macaroon_path)?;
+    let config = Config::load(args.config.as_deref())?;
+    // A target on the command line wins over a socket in the config file.
+    let socket = if args.target.is_some() {
+        None
+    } else {
+        args.socket.or(config.socket)
+    };
+    let api = match socket {
+        Some(socket) => Api::unix(&socket, args.output)?,
+        None => Api::new(
+            &args.target.or(config.target).context("Missing --target")?,
+            &credential(args.cert_path, "KLD_CERT", config.cert_path, "--cert-path")?,
+            credential(
+                args.macaroon_path,
+                "KLD_MACAROON",
+                config.macaroon_path,
+                "--macaroon-path",
+            )?,
+            args.output,
+        )?,
+    };
 
     let output = match args.command {
         Command::GetInfo => api.get_info()?,
@@ -129,28 +430,143 @@ fn run_command(args: Args) -> Result<()> {
             satoshis,
             fee_rate,
         } => api.withdraw(address, satoshis, fee_rate)?,
+        Command::RescanWallet {
+            birthday_height,
+            run_async,
+        } => api.rescan_wallet(birthday_height, run_async)?,
+        Command::ImportDescriptor { descriptor } => api.import_descriptor(descriptor)?,
         Command::ListChannels => api.list_channels()?,
+        Command::ListClosedChannels => api.list_closed_channels()?,
+        Command::GenerateInvoice {
+            amount,
+            description,
+            expiry,
+            fallback_address,
+        } => api.generate_invoice(amount, description, expiry, fallback_address)?,
+        Command::ListInvoices {
+            status,
+            since,
+            until,
+        } => api.list_invoices(ListPaymentsParams {
+            status,
+            since,
+            until,
+        })?,
+        Command::PayLnurl {
+            target,
+            amount,
+            max_fee_msat,
+            max_fee_percent,
+        } => api.pay_lnurl(target, amount, max_fee_msat, max_fee_percent)?,
+        Command::GetPayment { payment_hash } => api.get_payment(payment_hash)?,
+        Command::ListPayments {
+            status,
+            since,
+            until,
+        } => api.list_payments(ListPaymentsParams {
+            status,
+            since,
+            until,
+        })?,
+        Command::ListForwards {
+            since,
+            until,
+            limit,
+        } => api.list_forwards(ListForwardsParams {
+            since,
+            until,
+            limit,
+        })?,
+        Command::ExportForwards { since, until } => {
+            api.export_forwards(ExportForwardsParams { since, until })?
+        }
         Command::ListPeers => api.list_peers()?,
         Command::ConnectPeer { public_key } => api.connect_peer(public_key)?,
         Command::DisconnectPeer { public_key } => api.disconnect_peer(public_key)?,
+        Command::ForgetPeer { public_key } => api.forget_peer(public_key)?,
         Command::OpenChannel {
             public_key,
             sats: satoshis,
             push_msat,
             announce,
             fee_rate,
-        } => api.open_channel(public_key, satoshis, push_msat, announce, fee_rate)?,
+            run_async,
+        } => api.open_channel(
+            public_key, satoshis, push_msat, announce, fee_rate, run_async,
+        )?,
+        Command::OpenChannelPreview {
+            public_key,
+            sats: satoshis,
+            push_msat,
+            fee_rate,
+        } => api.open_channel_preview(public_key, satoshis, push_msat, fee_rate)?,
+        Command::OpenChannelPsbt {
+            public_key,
+            sats: satoshis,
+            push_msat,
+            announce,
+        } => api.open_channel_psbt(public_key, satoshis, push_msat, announce)?,
+        Command::FundingPsbt { channel_id, psbt } => api.funding_psbt(channel_id, psbt)?,
+        Command::PendingChannelOpen { id } => api.pending_channel_open(id)?,
         Command::SetChannelFee {
             id,
             base_fee,
             ppm_fee,
-        } => api.set_channel_fee(id, base_fee, ppm_fee)?,
+            force,
+        } => api.set_channel_fee(id, base_fee, ppm_fee, force)?,
         Command::CloseChannel { id } => api.close_channel(id)?,
+        Command::AbandonChannel { id } => api.abandon_channel(id)?,
+        Command::ChannelStats { id } => api.channel_stats(id)?,
+        Command::ChannelEarnings { id, bucket } => api.channel_earnings(id, bucket)?,
+        Command::FeeReport => api.fee_report()?,
         Command::NetworkNodes { id } => api.list_network_nodes(id)?,
         Command::NetworkChannels { id } => api.list_network_channels(id)?,
+        Command::NetworkStats => api.network_stats()?,
+        Command::PruneNetworkGraph => api.prune_network_graph()?,
+        Command::ResetScorer => api.reset_scorer()?,
+        Command::CheckMonitors => api.check_monitors()?,
+        Command::PendingSweeps => api.list_pending_sweeps()?,
+        Command::ExportState { confirm } => api.export_state(confirm)?,
+        Command::SignMessage { message } => api.sign_message(message)?,
+        Command::DeriveKey { path } => api.derive_key(path)?,
+        Command::VerifyMessage {
+            message,
+            signature,
+            pubkey,
+        } => api.verify_message(message, signature, pubkey)?,
+        Command::SetAlias { alias, color } => api.set_alias(alias, color)?,
+        Command::Announce => api.announce()?,
+        Command::Maintenance { enabled } => api.set_maintenance_mode(enabled)?,
+        Command::ListPendingWebhooks => api.list_pending_webhooks()?,
+        Command::ListHtlcs => api.list_htlcs()?,
+        Command::InterceptScid => api.intercept_scid()?,
+        Command::ForwardHtlc {
+            intercept_id,
+            channel_id,
+            amount_msat,
+        } => api.forward_htlc(intercept_id, channel_id, amount_msat)?,
+        Command::FailHtlc { intercept_id } => api.fail_htlc(intercept_id)?,
+        Command::ListInterceptedHtlcs => api.list_intercepted_htlcs()?,
     };
     if output != "null" {
         println!("{output}");
     }
     Ok(())
 }
+
+/// Read a credential from the file given on the command line, the environment variable or the
+/// file given in the config, in that order.
+fn credential(
+    path: Option<String>,
+    env_var: &str,
+    config_path: Option<String>,
+    flag: &str,
+) -> Result<Vec<u8>> {
+    if let Some(path) = path {
+        return read_file(&path);
+    }
+    if let Ok(value) = env::var(env_var) {
+        return Ok(value.trim().as_bytes().to_vec());
+    }
+    read_file(&config_path.with_context(|| format!("Missing {flag} or {env_var}"))?)
+}
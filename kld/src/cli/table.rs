@@ -0,0 +1,140 @@
+use api::{Channel, Peer, WalletBalance};
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Pretty printed JSON for scripting.
+    #[default]
+    Json,
+    /// Aligned columns for reading. Only channels, peers and balances have a table, everything
+    /// else is printed as JSON.
+    Table,
+}
+
+/// Responses which can be printed as a table.
+pub trait Table {
+    fn header() -> Vec<&'static str>;
+    fn rows(&self) -> Vec<Vec<String>>;
+
+    fn to_table(&self) -> String {
+        render(&Self::header(), &self.rows())
+    }
+}
+
+impl Table for Vec<Channel> {
+    fn header() -> Vec<&'static str> {
+        vec![
+            "SHORT CHANNEL ID",
+            "PEER",
+            "ALIAS",
+            "STATE",
+            "CONNECTED",
+            "PRIVATE",
+            "TO US (MSAT)",
+            "TOTAL (MSAT)",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.iter()
+            .map(|channel| {
+                vec![
+                    channel.short_channel_id.clone(),
+                    channel.id.clone(),
+                    channel.alias.clone(),
+                    channel.state.clone(),
+                    channel.connected.clone(),
+                    channel.private.clone(),
+                    channel.msatoshi_to_us.clone(),
+                    channel.msatoshi_total.clone(),
+                ]
+            })
+            .collect()
+    }
+}
+
+impl Table for Vec<Peer> {
+    fn header() -> Vec<&'static str> {
+        vec![
+            "ID",
+            "ALIAS",
+            "CONNECTED",
+            "ADDRESS",
+            "CHANNELS",
+            "CAPACITY",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.iter()
+            .map(|peer| {
+                vec![
+                    peer.id.clone(),
+                    peer.alias.clone(),
+                    peer.connected.to_string(),
+                    peer.netaddr
+                        .as_ref()
+                        .map(|a| format!("{}:{}", a.address, a.port))
+                        .unwrap_or_default(),
+                    peer.num_channels.to_string(),
+                    peer.capacity_sat.to_string(),
+                ]
+            })
+            .collect()
+    }
+}
+
+impl Table for WalletBalance {
+    fn header() -> Vec<&'static str> {
+        vec!["BALANCE", "SATS"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        [
+            ("total", self.total_balance),
+            ("confirmed", self.conf_balance),
+            ("unconfirmed", self.unconf_balance),
+            ("reserved", self.reserved_balance),
+            ("immature", self.immature_balance),
+            ("watch only", self.watch_only_balance),
+        ]
+        .into_iter()
+        .map(|(name, sats)| vec![name.to_string(), sats.to_string()])
+        .collect()
+    }
+}
+
+fn render(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    std::iter::once(&header)
+        .chain(rows)
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<String>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[test]
+fn test_render() {
+    let rows = vec![
+        vec!["a".to_string(), "1".to_string()],
+        vec!["long name".to_string(), "1000".to_string()],
+    ];
+    assert_eq!(
+        "NAME       SATS\na          1\nlong name  1000",
+        render(&["NAME", "SATS"], &rows)
+    );
+}
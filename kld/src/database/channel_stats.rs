@@ -0,0 +1,8 @@
+/// Forwarding counters for a single channel.
+#[derive(PartialEq, Eq, Debug, Default, Clone)]
+pub struct ChannelStats {
+    pub channel_id: [u8; 32],
+    pub htlcs_attempted: u64,
+    pub htlcs_succeeded: u64,
+    pub htlcs_failed: u64,
+}
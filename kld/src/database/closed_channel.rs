@@ -0,0 +1,17 @@
+use std::time::SystemTime;
+
+use bitcoin::{secp256k1::PublicKey, OutPoint, Txid};
+
+/// A closed channel with the transaction that closed it and the one that swept our funds back
+/// into the wallet. The closing transaction is recorded once it confirms, so it can be set
+/// before the reason is known.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ClosedChannel {
+    pub channel_id: [u8; 32],
+    pub counterparty_node_id: Option<PublicKey>,
+    pub funding_txo: Option<OutPoint>,
+    pub reason: Option<String>,
+    pub closing_txid: Option<Txid>,
+    pub sweep_txid: Option<Txid>,
+    pub closed_at: SystemTime,
+}
@@ -0,0 +1,58 @@
+use std::{fmt::Display, str::FromStr, time::SystemTime};
+
+use anyhow::bail;
+
+/// A payment routed through this node.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Forward {
+    pub in_channel_id: Option<[u8; 32]>,
+    pub out_channel_id: Option<[u8; 32]>,
+    pub in_scid: Option<u64>,
+    pub out_scid: Option<u64>,
+    pub amount_in_msat: Option<u64>,
+    pub amount_out_msat: Option<u64>,
+    pub fee_msat: Option<u64>,
+    pub timestamp: SystemTime,
+}
+
+/// Length of the time buckets which forwarding fees are summed over.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EarningsBucket {
+    Hour,
+    Day,
+    Week,
+}
+
+impl Display for EarningsBucket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EarningsBucket::Hour => write!(f, "hour"),
+            EarningsBucket::Day => write!(f, "day"),
+            EarningsBucket::Week => write!(f, "week"),
+        }
+    }
+}
+
+impl FromStr for EarningsBucket {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hour" => Ok(EarningsBucket::Hour),
+            "day" => Ok(EarningsBucket::Day),
+            "week" => Ok(EarningsBucket::Week),
+            _ => bail!("Unknown bucket {s}, expected hour, day or week"),
+        }
+    }
+}
+
+/// Fees a channel earned within one time bucket. Inbound fees are from payments which entered
+/// the node through the channel, outbound fees from payments which left through it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ChannelEarnings {
+    pub bucket_start: SystemTime,
+    pub inbound_fee_msat: u64,
+    pub outbound_fee_msat: u64,
+    pub inbound_forwards: u64,
+    pub outbound_forwards: u64,
+}
@@ -1,11 +1,13 @@
 use crate::logger::KldLogger;
-use crate::to_i64;
+use crate::{from_i64, from_maybe_i64, to_i64};
 
-use super::{connection, Client};
-use anyhow::{anyhow, bail, Result};
+use super::lease::safety_margin;
+use super::{connection, connection_with_retry, execute_retrying, query_retrying, CachedClient};
+use anyhow::{anyhow, Result};
 use bitcoin::hashes::Hash;
 use bitcoin::secp256k1::PublicKey;
 use bitcoin::{BlockHash, Txid};
+use futures::stream::{BoxStream, StreamExt};
 use lightning::chain::chaininterface::{BroadcasterInterface, FeeEstimator};
 use lightning::chain::chainmonitor::MonitorUpdateId;
 use lightning::chain::channelmonitor::{ChannelMonitor, ChannelMonitorUpdate};
@@ -16,6 +18,7 @@ use lightning::chain::transaction::OutPoint;
 use lightning::chain::{self, ChannelMonitorUpdateStatus, Watch};
 use lightning::ln::channelmanager::{ChannelManager, ChannelManagerReadArgs};
 use lightning::ln::msgs::NetAddress;
+use lightning::ln::PaymentHash;
 use lightning::routing::gossip::NetworkGraph;
 use lightning::routing::router::Router;
 use lightning::routing::scoring::{
@@ -25,42 +28,47 @@ use lightning::util::logger::Logger;
 use lightning::util::persist::Persister;
 use lightning::util::ser::ReadableArgs;
 use lightning::util::ser::Writeable;
-use log::{debug, info};
+use log::{debug, error, info};
 use settings::Settings;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::io::Cursor;
 use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use std::{fs, io};
 use tokio::runtime::Handle;
 use tokio::sync::RwLock;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{GenericClient, Row};
 
-use super::peer::Peer;
+use super::channel_stats::ChannelStats;
+use super::closed_channel::ClosedChannel;
+use super::forward::{ChannelEarnings, EarningsBucket, Forward};
+use super::payment::{Payment, PaymentDirection, PaymentStatus};
+use super::peer::{deserialize_address, Peer};
+use super::spendable_output::{SpendableOutput, SweepStatus};
 
 // This gets called from a background thread in LDK so need a handle to the runtime.
 macro_rules! block_in_place {
     ($statement: literal, $params: expr, $self: expr) => {
         tokio::task::block_in_place(move || {
-            $self.runtime.block_on(async move {
-                $self
-                    .client()
-                    .await
-                    .unwrap()
-                    .read()
-                    .await
-                    .execute($statement, $params)
-                    .await
-                    .unwrap()
-            })
+            $self
+                .runtime
+                .block_on(async move { $self.execute_cached($statement, $params).await.unwrap() })
         })
     };
 }
 
 pub struct LdkDatabase {
     settings: Settings,
-    client: Arc<RwLock<Client>>,
+    client: Arc<RwLock<CachedClient>>,
+    read_replica: Option<(Settings, Arc<RwLock<CachedClient>>)>,
     runtime: Handle,
+    corrupt_channel_monitors: AtomicUsize,
+    fenced: AtomicBool,
 }
 
 impl LdkDatabase {
@@ -69,29 +77,98 @@ impl LdkDatabase {
             "Connecting LDK to Cockroach database {} at {}:{}",
             settings.database_name, settings.database_host, settings.database_port
         );
-        let client = connection(settings).await?;
-        let client = Arc::new(RwLock::new(client));
+        // This is the first connection at startup, the migrations only run once we have the lease.
+        let client = connection_with_retry(settings).await?;
+        let client = Arc::new(RwLock::new(CachedClient::new(client)));
+
+        let read_replica = if settings.database_read_host.is_empty() {
+            None
+        } else {
+            let mut read_settings = settings.clone();
+            read_settings.database_host = settings.database_read_host.clone();
+            info!(
+                "Connecting to Cockroach read replica at {}:{}",
+                read_settings.database_host, read_settings.database_port
+            );
+            let read_client = connection(&read_settings).await?;
+            Some((
+                read_settings,
+                Arc::new(RwLock::new(CachedClient::new(read_client))),
+            ))
+        };
 
         Ok(LdkDatabase {
             settings: settings.clone(),
             client,
+            read_replica,
             runtime: Handle::current(),
+            corrupt_channel_monitors: AtomicUsize::new(0),
+            fenced: AtomicBool::new(false),
         })
     }
 
+    /// Refuse all further database access, because another process might have taken over the
+    /// node. The node state which LDK persists when it stops is dropped instead.
+    pub fn fence(&self) {
+        self.fenced.store(true, Ordering::Release);
+    }
+
+    fn check_fenced(&self) -> Result<(), io::Error> {
+        if self.fenced.load(Ordering::Acquire) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Database is fenced because the lease was lost",
+            ));
+        }
+        Ok(())
+    }
+
     /// Try to reconnect to the database if the connection has been dropped.
     /// If this is not possible one of the callers of this function should shut the node down.
-    async fn client(&self) -> Result<Arc<RwLock<Client>>> {
+    async fn client(&self) -> Result<Arc<RwLock<CachedClient>>> {
+        self.check_fenced()?;
         if self.client.read().await.is_closed() {
             let mut guard = self.client.write().await;
             if guard.is_closed() {
                 let client = connection(&self.settings).await?;
-                *guard = client;
+                *guard = CachedClient::new(client);
             }
         }
         Ok(self.client.clone())
     }
 
+    /// The connection for large reads which can be slightly stale, the read replica if there is
+    /// one and the primary otherwise.
+    async fn read_client(&self) -> Result<Arc<RwLock<CachedClient>>> {
+        let (settings, client) = match &self.read_replica {
+            Some(read_replica) => read_replica,
+            None => return self.client().await,
+        };
+        if client.read().await.is_closed() {
+            let mut guard = client.write().await;
+            if guard.is_closed() {
+                *guard = CachedClient::new(connection(settings).await?);
+            }
+        }
+        Ok(client.clone())
+    }
+
+    async fn execute_cached(
+        &self,
+        query: &'static str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64> {
+        execute_retrying(&*self.client().await?, &self.settings, query, params).await
+    }
+
+    async fn query_cached(
+        &self,
+        query: &'static str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Row>> {
+        query_retrying(&*self.client().await?, &self.settings, query, params).await
+    }
+
     pub async fn is_first_start(&self) -> Result<bool> {
         Ok(self
             .client()
@@ -103,54 +180,108 @@ impl LdkDatabase {
             .is_none())
     }
 
-    pub async fn persist_peer(&self, peer: &Peer) -> Result<()> {
+    /// The lease is taken before the migrations run, so that a standby doesn't change the schema
+    /// under the leader. Creates the lease table on a new database, the same as the migration.
+    pub async fn create_lease_table(&self) -> Result<()> {
         self.client()
             .await?
             .read()
             .await
-            .execute(
-                "UPSERT INTO peers (public_key, address) \
-            VALUES ($1, $2)",
-                &[&peer.public_key.encode(), &peer.net_address.encode()],
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS node_lease ( \
+                id INT PRIMARY KEY DEFAULT 1 CHECK (id = 1), \
+                holder STRING NOT NULL, \
+                expires_at TIMESTAMP NOT NULL \
+            )",
             )
             .await?;
         Ok(())
     }
 
-    pub async fn fetch_peer(&self, public_key: &PublicKey) -> Result<Option<Peer>> {
-        debug!("Fetching peer from database");
+    /// Take the lease on the database for the holder, or extend it if the holder has it already.
+    /// Returns false if another holder has a lease which hasn't expired a safety margin ago, so
+    /// the previous holder has stopped writing before we take over. The database clock is used
+    /// for expiry so clock skew between the processes doesn't matter.
+    pub async fn acquire_lease(&self, holder: &str, duration: Duration) -> Result<bool> {
+        let rows = self
+            .client()
+            .await?
+            .read()
+            .await
+            .query(
+                "INSERT INTO node_lease (id, holder, expires_at) \
+            VALUES (1, $1, current_timestamp() + $2::INT * INTERVAL '1 millisecond') \
+            ON CONFLICT (id) DO UPDATE SET holder = excluded.holder, expires_at = excluded.expires_at \
+            WHERE node_lease.holder = excluded.holder \
+            OR node_lease.expires_at < current_timestamp() - $3::INT * INTERVAL '1 millisecond' \
+            RETURNING holder",
+                &[
+                    &holder,
+                    &to_i64!(duration.as_millis()),
+                    &to_i64!(safety_margin(duration).as_millis()),
+                ],
+            )
+            .await?;
+        Ok(!rows.is_empty())
+    }
+
+    pub async fn release_lease(&self, holder: &str) -> Result<()> {
         self.client()
             .await?
             .read()
             .await
-            .query_opt(
-                "SELECT * FROM peers WHERE public_key = $1",
+            .execute("DELETE FROM node_lease WHERE holder = $1", &[&holder])
+            .await?;
+        Ok(())
+    }
+
+    /// Remember that we connected to the peer with this address just now. Other addresses of the
+    /// peer are kept to try if this one stops working.
+    pub async fn persist_peer(
+        &self,
+        public_key: &PublicKey,
+        net_address: &NetAddress,
+    ) -> Result<()> {
+        self.execute_cached(
+            "UPSERT INTO peers (public_key, address, connected_at) \
+            VALUES ($1, $2, current_timestamp())",
+            &[&public_key.encode(), &net_address.encode()],
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_peer(&self, public_key: &PublicKey) -> Result<Option<Peer>> {
+        debug!("Fetching peer from database");
+        let net_addresses: Vec<Vec<u8>> = self
+            .query_cached(
+                "SELECT address FROM peers WHERE public_key = $1 ORDER BY connected_at DESC",
                 &[&public_key.encode()],
             )
             .await?
-            .map(|row| {
-                let public_key: Vec<u8> = row.get("public_key");
-                let net_address: Vec<u8> = row.get("address");
-                Peer::deserialize(public_key, net_address)
-            })
-            .transpose()
+            .iter()
+            .map(|row| row.get("address"))
+            .collect();
+        if net_addresses.is_empty() {
+            return Ok(None);
+        }
+        Peer::deserialize(public_key.encode(), net_addresses).map(Some)
     }
 
-    pub async fn fetch_peers(&self) -> Result<HashMap<PublicKey, NetAddress>> {
+    /// All peers with their addresses, the most recently connected first.
+    pub async fn fetch_peers(&self) -> Result<HashMap<PublicKey, Vec<NetAddress>>> {
         debug!("Fetching peers from database");
-        let mut peers = HashMap::new();
+        let mut peers: HashMap<PublicKey, Vec<NetAddress>> = HashMap::new();
         for row in self
-            .client()
-            .await?
-            .read()
-            .await
-            .query("SELECT * FROM peers", &[])
+            .query_cached("SELECT * FROM peers ORDER BY connected_at DESC", &[])
             .await?
         {
             let public_key: Vec<u8> = row.get("public_key");
             let net_address: Vec<u8> = row.get("address");
-            let peer = Peer::deserialize(public_key, net_address)?;
-            peers.insert(peer.public_key, peer.net_address);
+            peers
+                .entry(PublicKey::from_slice(&public_key)?)
+                .or_default()
+                .push(deserialize_address(net_address)?);
         }
         debug!("Fetched {} peers", peers.len());
         Ok(peers)
@@ -170,12 +301,381 @@ impl LdkDatabase {
         Ok(())
     }
 
+    /// Count a forwarded HTLC against the channel it was routed through.
+    pub async fn record_channel_forward(
+        &self,
+        channel_id: &[u8; 32],
+        succeeded: bool,
+    ) -> Result<()> {
+        let (succeeded, failed): (i64, i64) = if succeeded { (1, 0) } else { (0, 1) };
+        self.execute_cached(
+                "INSERT INTO channel_stats (channel_id, htlcs_attempted, htlcs_succeeded, htlcs_failed) \
+            VALUES ($1, 1, $2, $3) \
+            ON CONFLICT (channel_id) DO UPDATE SET \
+            htlcs_attempted = channel_stats.htlcs_attempted + 1, \
+            htlcs_succeeded = channel_stats.htlcs_succeeded + excluded.htlcs_succeeded, \
+            htlcs_failed = channel_stats.htlcs_failed + excluded.htlcs_failed, \
+            timestamp = CURRENT_TIMESTAMP",
+                &[&channel_id.as_slice(), &succeeded, &failed],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_channel_stats(&self) -> Result<Vec<ChannelStats>> {
+        debug!("Fetching channel stats from database");
+        let mut stats = vec![];
+        for row in self
+            .read_client()
+            .await?
+            .read()
+            .await
+            .query(
+                "SELECT channel_id, htlcs_attempted, htlcs_succeeded, htlcs_failed \
+            FROM channel_stats",
+                &[],
+            )
+            .await?
+        {
+            let channel_id: Vec<u8> = row.get("channel_id");
+            stats.push(ChannelStats {
+                channel_id: channel_id
+                    .try_into()
+                    .map_err(|_| anyhow!("Invalid channel id in channel_stats"))?,
+                htlcs_attempted: from_i64!(row, "htlcs_attempted"),
+                htlcs_succeeded: from_i64!(row, "htlcs_succeeded"),
+                htlcs_failed: from_i64!(row, "htlcs_failed"),
+            });
+        }
+        Ok(stats)
+    }
+
+    pub async fn persist_channel_closed(
+        &self,
+        channel_id: &[u8; 32],
+        counterparty_node_id: Option<PublicKey>,
+        funding_txo: Option<bitcoin::OutPoint>,
+        reason: &str,
+    ) -> Result<()> {
+        self.client()
+            .await?
+            .read()
+            .await
+            .execute(
+                "INSERT INTO closed_channels (channel_id, counterparty_node_id, funding_txid, funding_vout, reason) \
+            VALUES ($1, $2, $3, $4, $5) \
+            ON CONFLICT (channel_id) DO UPDATE SET \
+            counterparty_node_id = excluded.counterparty_node_id, \
+            funding_txid = excluded.funding_txid, \
+            funding_vout = excluded.funding_vout, \
+            reason = excluded.reason, \
+            closed_at = CURRENT_TIMESTAMP",
+                &[
+                    &channel_id.as_slice(),
+                    &counterparty_node_id.map(|id| id.serialize().to_vec()),
+                    &funding_txo.map(|txo| txo.txid.as_ref().to_vec()),
+                    &funding_txo.map(|txo| txo.vout as i64),
+                    &reason,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn persist_closing_txid(&self, channel_id: &[u8; 32], txid: &Txid) -> Result<()> {
+        self.client()
+            .await?
+            .read()
+            .await
+            .execute(
+                "INSERT INTO closed_channels (channel_id, closing_txid) VALUES ($1, $2) \
+            ON CONFLICT (channel_id) DO UPDATE SET closing_txid = excluded.closing_txid",
+                &[&channel_id.as_slice(), &txid.as_ref()],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Record the sweep of the outputs and of the closed channels they came from in one
+    /// transaction. A partial write would leave outputs pending whose sweep is already broadcast,
+    /// and they would be swept again.
+    pub async fn persist_sweep(
+        &self,
+        outputs: &[SpendableOutput],
+        sweep_txid: &Txid,
+    ) -> Result<()> {
+        let closing_txids: Vec<Txid> = outputs
+            .iter()
+            .map(|output| output.outpoint().txid)
+            .collect();
+        let closing_txids: Vec<&[u8]> = closing_txids.iter().map(|txid| txid.as_ref()).collect();
+        let client = self.client().await?;
+        let mut client = client.write().await;
+        let transaction = client.transaction().await?;
+        for output in outputs {
+            upsert_spendable_output(&transaction, output).await?;
+        }
+        transaction
+            .execute(
+                "UPDATE closed_channels SET sweep_txid = $1 WHERE closing_txid = ANY($2)",
+                &[&sweep_txid.as_ref(), &closing_txids],
+            )
+            .await?;
+        // Dropping the transaction on an error above rolls it back.
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn fetch_closed_channels(&self) -> Result<Vec<ClosedChannel>> {
+        debug!("Fetching closed channels from database");
+        self.read_client()
+            .await?
+            .read()
+            .await
+            .query("SELECT * FROM closed_channels ORDER BY closed_at", &[])
+            .await?
+            .iter()
+            .map(closed_channel_from_row)
+            .collect()
+    }
+
+    pub async fn persist_spendable_output(&self, output: &SpendableOutput) -> Result<()> {
+        let client = self.client().await?;
+        let client = client.read().await;
+        upsert_spendable_output(&**client, output).await
+    }
+
+    /// Fetch the outputs which have not been swept yet.
+    pub async fn fetch_unswept_outputs(&self) -> Result<Vec<SpendableOutput>> {
+        debug!("Fetching unswept outputs from database");
+        self.client()
+            .await?
+            .read()
+            .await
+            .query(
+                "SELECT * FROM spendable_outputs WHERE status <> $1 ORDER BY created_at",
+                &[&SweepStatus::Swept.to_string()],
+            )
+            .await?
+            .iter()
+            .map(spendable_output_from_row)
+            .collect()
+    }
+
+    pub async fn persist_payment(&self, payment: &Payment) -> Result<()> {
+        self.execute_cached(
+                "UPSERT INTO payments (payment_hash, direction, status, preimage, amount_msat, fee_msat, failure_reason, settled_at) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                &[
+                    &payment.hash.0.as_slice(),
+                    &payment.direction.to_string(),
+                    &payment.status.to_string(),
+                    &payment.preimage.as_ref().map(|p| p.0.as_slice()),
+                    &payment.amount_msat.map(|a| to_i64!(a)),
+                    &payment.fee_msat.map(|f| to_i64!(f)),
+                    &payment.failure_reason,
+                    &payment.settled_at,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Record a new outbound payment as pending. An earlier attempt for the same hash is only
+    /// replaced if it failed, so returns false if the payment is in progress or already paid.
+    pub async fn insert_pending_payment(&self, payment: &Payment) -> Result<bool> {
+        let inserted = self
+            .execute_cached(
+                "INSERT INTO payments (payment_hash, direction, status, amount_msat) \
+            VALUES ($1, $2, $3, $4) \
+            ON CONFLICT (payment_hash, direction) DO UPDATE SET \
+            status = excluded.status, preimage = NULL, amount_msat = excluded.amount_msat, \
+            fee_msat = NULL, failure_reason = NULL, created_at = current_timestamp(), settled_at = NULL \
+            WHERE payments.status = $5",
+                &[
+                    &payment.hash.0.as_slice(),
+                    &payment.direction.to_string(),
+                    &PaymentStatus::Pending.to_string(),
+                    &payment.amount_msat.map(|a| to_i64!(a)),
+                    &PaymentStatus::Failed.to_string(),
+                ],
+            )
+            .await?;
+        Ok(inserted == 1)
+    }
+
+    pub async fn fetch_payment(
+        &self,
+        payment_hash: &PaymentHash,
+        direction: PaymentDirection,
+    ) -> Result<Option<Payment>> {
+        debug!("Fetching payment from database");
+        self.query_cached(
+            "SELECT * FROM payments WHERE payment_hash = $1 AND direction = $2",
+            &[&payment_hash.0.as_slice(), &direction.to_string()],
+        )
+        .await?
+        .first()
+        .map(payment_from_row)
+        .transpose()
+    }
+
+    /// Outbound payments which are still pending, read from the primary as they are acted on.
+    pub async fn fetch_pending_payments(&self) -> Result<Vec<Payment>> {
+        debug!("Fetching pending payments from database");
+        self.client()
+            .await?
+            .read()
+            .await
+            .query(
+                "SELECT * FROM payments WHERE direction = $1 AND status = $2 ORDER BY created_at",
+                &[
+                    &PaymentDirection::Outbound.to_string(),
+                    &PaymentStatus::Pending.to_string(),
+                ],
+            )
+            .await?
+            .iter()
+            .map(payment_from_row)
+            .collect()
+    }
+
+    /// Payments in one direction, optionally filtered by status and settlement time, ordered by
+    /// settlement time. Unsettled payments come last. Read from the read replica if there is one.
+    pub async fn fetch_payments(
+        &self,
+        direction: PaymentDirection,
+        status: Option<PaymentStatus>,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> Result<Vec<Payment>> {
+        debug!("Fetching payments from database");
+        self.read_client()
+            .await?
+            .read()
+            .await
+            .query(
+                "SELECT * FROM payments \
+            WHERE direction = $1 \
+            AND ($2::STRING IS NULL OR status = $2) \
+            AND ($3::TIMESTAMP IS NULL OR settled_at >= $3) \
+            AND ($4::TIMESTAMP IS NULL OR settled_at < $4) \
+            ORDER BY settled_at IS NULL, settled_at, created_at",
+                &[
+                    &direction.to_string(),
+                    &status.map(|s| s.to_string()),
+                    &since,
+                    &until,
+                ],
+            )
+            .await?
+            .iter()
+            .map(payment_from_row)
+            .collect()
+    }
+
+    pub async fn persist_forward(&self, forward: &Forward) -> Result<()> {
+        self.execute_cached(
+                "INSERT INTO forwarded_payments (in_channel_id, out_channel_id, in_scid, out_scid, amount_in_msat, amount_out_msat, fee_msat, timestamp) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                &[
+                    &forward.in_channel_id.as_ref().map(|id| id.as_slice()),
+                    &forward.out_channel_id.as_ref().map(|id| id.as_slice()),
+                    &forward.in_scid.map(|scid| to_i64!(scid)),
+                    &forward.out_scid.map(|scid| to_i64!(scid)),
+                    &forward.amount_in_msat.map(|a| to_i64!(a)),
+                    &forward.amount_out_msat.map(|a| to_i64!(a)),
+                    &forward.fee_msat.map(|f| to_i64!(f)),
+                    &forward.timestamp,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Stream the forwarding history in chronological order so that large histories don't
+    /// have to be loaded into memory.
+    pub async fn fetch_forwards(
+        &self,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> Result<BoxStream<'static, Result<Forward>>> {
+        debug!("Fetching forwards from database");
+        let params: [&(dyn ToSql + Sync); 2] = [&since, &until];
+        let rows = self
+            .read_client()
+            .await?
+            .read()
+            .await
+            .query_raw(
+                "SELECT * FROM forwarded_payments \
+            WHERE ($1::TIMESTAMP IS NULL OR timestamp >= $1) \
+            AND ($2::TIMESTAMP IS NULL OR timestamp < $2) \
+            ORDER BY timestamp",
+                params,
+            )
+            .await?;
+        Ok(rows.map(|row| forward_from_row(&row?)).boxed())
+    }
+
+    /// Sum the fees earned through a channel per time bucket, oldest bucket first.
+    pub async fn fetch_channel_earnings(
+        &self,
+        short_channel_id: u64,
+        bucket: EarningsBucket,
+    ) -> Result<Vec<ChannelEarnings>> {
+        let rows = self
+            .read_client()
+            .await?
+            .read()
+            .await
+            .query(
+                "SELECT date_trunc($2::STRING, timestamp) AS bucket_start, \
+            SUM(CASE WHEN in_scid = $1 THEN COALESCE(fee_msat, 0) ELSE 0 END)::INT AS inbound_fee_msat, \
+            SUM(CASE WHEN out_scid = $1 THEN COALESCE(fee_msat, 0) ELSE 0 END)::INT AS outbound_fee_msat, \
+            SUM(CASE WHEN in_scid = $1 THEN 1 ELSE 0 END)::INT AS inbound_forwards, \
+            SUM(CASE WHEN out_scid = $1 THEN 1 ELSE 0 END)::INT AS outbound_forwards \
+            FROM forwarded_payments \
+            WHERE in_scid = $1 OR out_scid = $1 \
+            GROUP BY bucket_start \
+            ORDER BY bucket_start",
+                &[&to_i64!(short_channel_id), &bucket.to_string()],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| ChannelEarnings {
+                bucket_start: row.get("bucket_start"),
+                inbound_fee_msat: from_i64!(row, "inbound_fee_msat"),
+                outbound_fee_msat: from_i64!(row, "outbound_fee_msat"),
+                inbound_forwards: from_i64!(row, "inbound_forwards"),
+                outbound_forwards: from_i64!(row, "outbound_forwards"),
+            })
+            .collect())
+    }
+
+    /// Fetch the channel monitors to start the node with and record how many were corrupt.
     pub async fn fetch_channel_monitors<ES: EntropySource, SP: SignerProvider>(
+        &self,
+        entropy_source: &ES,
+        signer_provider: &SP,
+    ) -> Result<Vec<(BlockHash, ChannelMonitor<SP::Signer>)>> {
+        let (monitors, corrupt) = self
+            .read_channel_monitors(entropy_source, signer_provider)
+            .await?;
+        self.corrupt_channel_monitors
+            .store(corrupt, Ordering::Relaxed);
+        Ok(monitors)
+    }
+
+    /// Read the channel monitors without touching the counter reported by corrupt_channel_monitors.
+    /// Also returns the number of corrupt monitors which were skipped.
+    pub async fn read_channel_monitors<ES: EntropySource, SP: SignerProvider>(
         &self,
         entropy_source: &ES,
         signer_provider: &SP, //		broadcaster: &B,
                               //		fee_estimator: &F,
-    ) -> Result<Vec<(BlockHash, ChannelMonitor<SP::Signer>)>>
+    ) -> Result<(Vec<(BlockHash, ChannelMonitor<SP::Signer>)>, usize)>
 where
         //      B::Target: BroadcasterInterface,
         //		F::Target: FeeEstimator,
@@ -192,6 +692,8 @@ where
             )
             .await?;
         let mut monitors: Vec<(BlockHash, ChannelMonitor<SP::Signer>)> = vec![];
+        // A single corrupt monitor shouldn't keep the node and all other channels down.
+        let mut corrupt = 0;
         for row in rows {
             let out_point: Vec<u8> = row.get("out_point");
 
@@ -209,7 +711,15 @@ where
                     if channel_monitor.get_funding_txo().0.txid != txid
                         || channel_monitor.get_funding_txo().0.index != index
                     {
-                        bail!("Unable to find ChannelMonitor for: {}:{}", txid, index);
+                        error!(
+                            "Skipping ChannelMonitor stored for {}:{} which belongs to {}:{}",
+                            txid,
+                            index,
+                            channel_monitor.get_funding_txo().0.txid,
+                            channel_monitor.get_funding_txo().0.index
+                        );
+                        corrupt += 1;
+                        continue;
                     }
                     /*
                                         let update_rows = self
@@ -241,10 +751,22 @@ where
                     */
                     monitors.push((blockhash, channel_monitor));
                 }
-                Err(e) => bail!("Failed to deserialize ChannelMonitor: {}", e),
+                Err(e) => {
+                    error!(
+                        "Skipping ChannelMonitor for {}:{} which failed to deserialize: {}",
+                        txid, index, e
+                    );
+                    corrupt += 1;
+                }
             }
         }
-        Ok(monitors)
+        Ok((monitors, corrupt))
+    }
+
+    /// Number of channel monitors skipped when the node started because they were corrupt. A
+    /// corrupt monitor of an open channel still fails the start in fetch_channel_manager.
+    pub fn corrupt_channel_monitors(&self) -> usize {
+        self.corrupt_channel_monitors.load(Ordering::Relaxed)
     }
 
     pub async fn fetch_channel_manager<
@@ -301,6 +823,16 @@ where
         }
     }
 
+    // Network graph could get very large so just write it to disk for now.
+    pub fn persist_network_graph<L: Deref>(&self, network_graph: &NetworkGraph<L>) -> io::Result<()>
+    where
+        L::Target: Logger,
+    {
+        let mut buf = vec![];
+        network_graph.write(&mut buf)?;
+        fs::write(format!("{}/network_graph", self.settings.data_dir), &buf)
+    }
+
     pub async fn fetch_scorer(
         &self,
         params: ProbabilisticScoringParameters,
@@ -324,6 +856,22 @@ where
             });
         Ok(scorer)
     }
+
+    pub async fn persist_scorer(&self, scorer: &impl Writeable) -> Result<()> {
+        let mut buf = vec![];
+        scorer.write(&mut buf)?;
+        self.client()
+            .await?
+            .read()
+            .await
+            .execute(
+                "UPSERT INTO scorer (id, scorer, timestamp) \
+            VALUES ('scorer', $1, CURRENT_TIMESTAMP)",
+                &[&buf],
+            )
+            .await?;
+        Ok(())
+    }
 }
 
 impl<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, S>
@@ -343,6 +891,7 @@ where
         &self,
         channel_manager: &ChannelManager<M, T, ES, NS, SP, F, R, L>,
     ) -> Result<(), io::Error> {
+        self.check_fenced()?;
         let mut buf = vec![];
         channel_manager.write(&mut buf)?;
         block_in_place!(
@@ -354,17 +903,16 @@ where
         Ok(())
     }
 
-    // Network graph could get very large so just write it to disk for now.
     fn persist_graph(
         &self,
         network_graph: &lightning::routing::gossip::NetworkGraph<L>,
     ) -> Result<(), io::Error> {
-        let mut buf = vec![];
-        network_graph.write(&mut buf)?;
-        fs::write(format!("{}/network_graph", self.settings.data_dir), &buf)
+        self.check_fenced()?;
+        self.persist_network_graph(network_graph)
     }
 
     fn persist_scorer(&self, scorer: &S) -> Result<(), io::Error> {
+        self.check_fenced()?;
         let mut buf = vec![];
         scorer.write(&mut buf)?;
         block_in_place!(
@@ -377,6 +925,11 @@ where
     }
 }
 
+// Monitor updates are written one by one and never coalesced. Returning Completed promises LDK
+// that the update is durable, so it may send the matching commitment_signed or revoke_and_ack
+// right away. Waiting for a flush window would need InProgress and a later call to
+// ChainMonitor::channel_monitor_updated, which LDK 0.0.114 doesn't handle reliably yet (see
+// update_persisted_channel). Losing an update that was reported as completed can lose funds.
 impl<ChannelSigner: WriteableEcdsaChannelSigner> chain::chainmonitor::Persist<ChannelSigner>
     for LdkDatabase
 {
@@ -393,6 +946,11 @@ impl<ChannelSigner: WriteableEcdsaChannelSigner> chain::chainmonitor::Persist<Ch
             monitor.get_latest_update_id()
         );
 
+        // Never report the update as durable, the node is stopping anyway.
+        if self.check_fenced().is_err() {
+            return ChannelMonitorUpdateStatus::InProgress;
+        }
+
         let mut out_point_buf = vec![];
         funding_txo.write(&mut out_point_buf).unwrap();
 
@@ -480,3 +1038,102 @@ impl<ChannelSigner: WriteableEcdsaChannelSigner> chain::chainmonitor::Persist<Ch
         */
     }
 }
+
+// Takes a GenericClient so it can be part of a transaction.
+async fn upsert_spendable_output(
+    client: &impl GenericClient,
+    output: &SpendableOutput,
+) -> Result<()> {
+    let outpoint = output.outpoint();
+    client
+        .execute(
+            "UPSERT INTO spendable_outputs (txid, vout, value_sats, descriptor, status, sweep_txid, created_at) \
+        VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            &[
+                &outpoint.txid.as_ref(),
+                &(outpoint.vout as i64),
+                &to_i64!(output.value_sats()),
+                &output.serialize_descriptor(),
+                &output.status.to_string(),
+                &output.sweep_txid.as_ref().map(|txid| txid.as_ref()),
+                &output.created_at,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+fn payment_from_row(row: &Row) -> Result<Payment> {
+    let status: String = row.get("status");
+    let direction: String = row.get("direction");
+    Ok(Payment {
+        hash: Payment::deserialize_hash(row.get("payment_hash"))?,
+        direction: PaymentDirection::from_str(&direction)?,
+        status: PaymentStatus::from_str(&status)?,
+        preimage: Payment::deserialize_preimage(row.get("preimage"))?,
+        amount_msat: from_maybe_i64!(row, "amount_msat"),
+        fee_msat: from_maybe_i64!(row, "fee_msat"),
+        failure_reason: row.get("failure_reason"),
+        settled_at: row.get("settled_at"),
+    })
+}
+
+fn closed_channel_from_row(row: &Row) -> Result<ClosedChannel> {
+    let txid = |name: &str| {
+        row.get::<&str, Option<Vec<u8>>>(name)
+            .map(|txid| Txid::from_slice(&txid))
+            .transpose()
+    };
+    let channel_id: Vec<u8> = row.get("channel_id");
+    let funding_vout: Option<i64> = row.get("funding_vout");
+    Ok(ClosedChannel {
+        channel_id: channel_id
+            .try_into()
+            .map_err(|_| anyhow!("Invalid channel id in closed_channels"))?,
+        counterparty_node_id: row
+            .get::<&str, Option<Vec<u8>>>("counterparty_node_id")
+            .map(|id| PublicKey::from_slice(&id))
+            .transpose()?,
+        funding_txo: txid("funding_txid")?
+            .zip(funding_vout)
+            .map(|(txid, vout)| bitcoin::OutPoint::new(txid, vout as u32)),
+        reason: row.get("reason"),
+        closing_txid: txid("closing_txid")?,
+        sweep_txid: txid("sweep_txid")?,
+        closed_at: row.get("closed_at"),
+    })
+}
+
+fn spendable_output_from_row(row: &Row) -> Result<SpendableOutput> {
+    let status: String = row.get("status");
+    Ok(SpendableOutput {
+        descriptor: SpendableOutput::deserialize_descriptor(row.get("descriptor"))?,
+        status: SweepStatus::from_str(&status)?,
+        sweep_txid: row
+            .get::<&str, Option<Vec<u8>>>("sweep_txid")
+            .map(|txid| Txid::from_slice(&txid))
+            .transpose()?,
+        created_at: row.get("created_at"),
+    })
+}
+
+fn forward_from_row(row: &Row) -> Result<Forward> {
+    let channel_id = |name: &str| {
+        row.get::<&str, Option<Vec<u8>>>(name)
+            .map(|id| {
+                id.try_into()
+                    .map_err(|_| anyhow!("Invalid channel id in forwarded_payments"))
+            })
+            .transpose()
+    };
+    Ok(Forward {
+        in_channel_id: channel_id("in_channel_id")?,
+        out_channel_id: channel_id("out_channel_id")?,
+        in_scid: from_maybe_i64!(row, "in_scid"),
+        out_scid: from_maybe_i64!(row, "out_scid"),
+        amount_in_msat: from_maybe_i64!(row, "amount_in_msat"),
+        amount_out_msat: from_maybe_i64!(row, "amount_out_msat"),
+        fee_msat: from_maybe_i64!(row, "fee_msat"),
+        timestamp: row.get("timestamp"),
+    })
+}
@@ -0,0 +1,126 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use log::{info, warn};
+use settings::Settings;
+
+use super::LdkDatabase;
+
+/// Makes sure that only one kld process uses a database. Two processes with the same keys and
+/// channel state would send conflicting channel updates to peers and could lose funds.
+///
+/// The process holding the lease is the leader. Standby processes with the same mnemonic wait
+/// for the lease and take over once the leader stops renewing it. Other processes wait only as
+/// long as a lease lasts, so that a node which crashed can be restarted right away.
+pub struct NodeLease {
+    database: Arc<LdkDatabase>,
+    holder: String,
+    duration: Duration,
+    acquired_at: Instant,
+}
+
+impl NodeLease {
+    pub async fn acquire(database: Arc<LdkDatabase>, settings: &Settings) -> Result<NodeLease> {
+        let holder = format!(
+            "{}-{}",
+            settings.node_id,
+            hex::encode(rand::random::<[u8; 8]>())
+        );
+        let duration = Duration::from_secs(settings.database_lease_secs);
+        database.create_lease_table().await?;
+        let acquired_at = Instant::now();
+        if database.acquire_lease(&holder, duration).await? {
+            info!("Acquired database lease as {holder}");
+            return Ok(NodeLease {
+                database,
+                holder,
+                duration,
+                acquired_at,
+            });
+        }
+        // A crashed process which is restarted right away finds its own old lease. It can only
+        // be taken over once it has expired, so wait that long before refusing to start.
+        let give_up_at = acquired_at + duration + safety_margin(duration) + duration / 3;
+        if settings.standby {
+            info!("Another kld process is the leader, waiting as standby {holder}");
+        } else {
+            info!("Another kld process holds the database lease, waiting for it to expire");
+        }
+        let mut interval = tokio::time::interval(duration / 3);
+        interval.tick().await;
+        let acquired_at = loop {
+            interval.tick().await;
+            if !settings.standby && Instant::now() >= give_up_at {
+                bail!(
+                    "Another kld process is using database {}. Refusing to start.",
+                    settings.database_name
+                );
+            }
+            let started = Instant::now();
+            match database.acquire_lease(&holder, duration).await {
+                Ok(true) => break started,
+                Ok(false) => (),
+                Err(e) => warn!("Failed to acquire database lease: {e}"),
+            }
+        };
+        info!("Leader is gone, taking over as {holder}");
+        Ok(NodeLease {
+            database,
+            holder,
+            duration,
+            acquired_at,
+        })
+    }
+
+    /// Renew the lease until it is lost. Returns an error when another process might have taken
+    /// over, after fencing the database so the node stops without writing anything else to it.
+    ///
+    /// A renewal extends the lease from some point after it was sent, so the lease is only
+    /// counted from the time before sending it. The node stops a safety margin before the lease
+    /// can have expired, also when a renewal hangs.
+    pub async fn hold(&self) -> Result<()> {
+        let result = self.renew().await;
+        self.database.fence();
+        result
+    }
+
+    async fn renew(&self) -> Result<()> {
+        let mut renewed_at = self.acquired_at;
+        loop {
+            let deadline = renewed_at + self.duration - safety_margin(self.duration);
+            let next_renewal = Instant::now() + self.duration / 3;
+            tokio::time::sleep_until(next_renewal.min(deadline).into()).await;
+            if Instant::now() >= deadline {
+                bail!("Database lease expired");
+            }
+            let started = Instant::now();
+            match tokio::time::timeout_at(
+                deadline.into(),
+                self.database.acquire_lease(&self.holder, self.duration),
+            )
+            .await
+            {
+                Ok(Ok(true)) => renewed_at = started,
+                Ok(Ok(false)) => bail!("Database lease has been taken by another process"),
+                Ok(Err(e)) => warn!("Failed to renew database lease: {e}"),
+                Err(_) => bail!("Database lease expired while renewing it"),
+            }
+        }
+    }
+
+    /// Give up the lease so that another process can start right away.
+    pub async fn release(&self) -> Result<()> {
+        self.database.release_lease(&self.holder).await?;
+        info!("Released database lease");
+        Ok(())
+    }
+}
+
+/// Time before the lease expires at which the leader stops, to allow for the time a renewal takes
+/// to reach the database. A standby waits as long after the lease expired before it takes over.
+pub(crate) fn safety_margin(duration: Duration) -> Duration {
+    duration / 6
+}
@@ -1,8 +1,19 @@
+pub mod channel_stats;
+pub mod closed_channel;
+pub mod forward;
 mod ldk_database;
+pub mod lease;
+pub mod payment;
 pub mod peer;
+pub mod spendable_output;
 mod wallet_database;
 
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+    time::Duration,
+};
 
 pub use ldk_database::LdkDatabase;
 pub use wallet_database::WalletDatabase;
@@ -11,7 +22,8 @@ use anyhow::{Context, Result};
 use log::{error, info, warn};
 use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
 use postgres_openssl::MakeTlsConnector;
-use tokio_postgres::Client;
+use tokio::sync::RwLock;
+use tokio_postgres::{error::SqlState, types::ToSql, Client, Error, Row, Statement};
 
 use settings::Settings;
 
@@ -37,6 +49,115 @@ macro_rules! from_maybe_i64 {
     };
 }
 
+/// A database connection which prepares statements once and reuses them, so the database doesn't
+/// parse and plan the frequently run queries on every call. Prepared statements belong to the
+/// connection, so a new CachedClient has to be created when reconnecting.
+pub struct CachedClient {
+    client: Client,
+    statements: Mutex<HashMap<&'static str, Statement>>,
+}
+
+impl CachedClient {
+    pub fn new(client: Client) -> CachedClient {
+        CachedClient {
+            client,
+            statements: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn prepare_cached(&self, query: &'static str) -> Result<Statement, Error> {
+        if let Some(statement) = self.statements.lock().unwrap().get(query) {
+            return Ok(statement.clone());
+        }
+        let statement = self.client.prepare(query).await?;
+        self.statements
+            .lock()
+            .unwrap()
+            .insert(query, statement.clone());
+        Ok(statement)
+    }
+
+    pub async fn execute_cached(
+        &self,
+        query: &'static str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, Error> {
+        let statement = self.prepare_cached(query).await?;
+        self.client.execute(&statement, params).await
+    }
+
+    pub async fn query_cached(
+        &self,
+        query: &'static str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Row>, Error> {
+        let statement = self.prepare_cached(query).await?;
+        self.client.query(&statement, params).await
+    }
+}
+
+/// Execute a cached statement. If it hits the statement timeout it is retried once on a new
+/// connection, as a hanging connection is more likely than a query that is always too slow.
+pub(crate) async fn execute_retrying(
+    client: &RwLock<CachedClient>,
+    settings: &Settings,
+    query: &'static str,
+    params: &[&(dyn ToSql + Sync)],
+) -> Result<u64> {
+    let result = client.read().await.execute_cached(query, params).await;
+    match result {
+        Err(e) if is_statement_timeout(&e) => {
+            warn!("Database statement timed out, retrying on a new connection: {e}");
+            reconnect(client, settings).await?;
+            Ok(client.read().await.execute_cached(query, params).await?)
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Query with a cached statement, retrying once on a new connection like execute_retrying.
+pub(crate) async fn query_retrying(
+    client: &RwLock<CachedClient>,
+    settings: &Settings,
+    query: &'static str,
+    params: &[&(dyn ToSql + Sync)],
+) -> Result<Vec<Row>> {
+    let result = client.read().await.query_cached(query, params).await;
+    match result {
+        Err(e) if is_statement_timeout(&e) => {
+            warn!("Database statement timed out, retrying on a new connection: {e}");
+            reconnect(client, settings).await?;
+            Ok(client.read().await.query_cached(query, params).await?)
+        }
+        result => Ok(result?),
+    }
+}
+
+async fn reconnect(client: &RwLock<CachedClient>, settings: &Settings) -> Result<()> {
+    let new_client = connection(settings).await?;
+    *client.write().await = CachedClient::new(new_client);
+    Ok(())
+}
+
+/// True if the statement was cancelled because it ran longer than the statement timeout.
+pub fn is_statement_timeout(error: &Error) -> bool {
+    error.code() == Some(&SqlState::QUERY_CANCELED)
+}
+
+impl Deref for CachedClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl DerefMut for CachedClient {
+    fn deref_mut(&mut self) -> &mut Client {
+        &mut self.client
+    }
+}
+
 pub async fn connection(settings: &Settings) -> Result<Client> {
     let log_safe_params = format!(
         "host={} port={} user={} dbname={}",
@@ -54,14 +175,27 @@ pub async fn connection(settings: &Settings) -> Result<Client> {
         .set_private_key_file(&settings.database_client_key_path, SslFiletype::PEM)
         .expect("Database private key");
     let connector = MakeTlsConnector::new(builder.build());
-    let (client, connection) = tokio_postgres::connect(&log_safe_params, connector)
-        .await
-        .with_context(|| format!("could not connect to database ({log_safe_params})"))?;
+    let (client, connection) = tokio::time::timeout(
+        Duration::from_secs(settings.database_connect_timeout_secs),
+        tokio_postgres::connect(&log_safe_params, connector),
+    )
+    .await
+    .with_context(|| format!("timed out connecting to database ({log_safe_params})"))?
+    .with_context(|| format!("could not connect to database ({log_safe_params})"))?;
     tokio::spawn(async move {
         if let Err(e) = connection.await {
             error!("Database connection error: {}", e);
         }
     });
+    if settings.database_statement_timeout_ms > 0 {
+        client
+            .batch_execute(&format!(
+                "SET statement_timeout = {}",
+                settings.database_statement_timeout_ms
+            ))
+            .await
+            .context("could not set statement timeout")?;
+    }
     Ok(client)
 }
 
@@ -70,25 +204,47 @@ mod embedded {
     embed_migrations!("src/database/sql");
 }
 
-pub async fn migrate_database(settings: &Settings) {
-    let delay = 5;
+const MAX_CONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// Connect to the database, retrying with an exponential backoff up to
+/// database_connect_attempts times. Used at startup when the database might not be up yet.
+async fn connection_with_retry(settings: &Settings) -> Result<Client> {
+    let mut delay = Duration::from_secs(1);
+    let mut attempt = 1;
     loop {
         match connection(settings).await {
-            Ok(mut client) => {
-                info!("Running database migrations");
-                embedded::migrations::runner()
-                    .run_async(&mut client)
-                    .await
-                    .expect("failed to run migrations");
-                return;
-            }
-            Err(e) => {
+            Ok(client) => return Ok(client),
+            Err(e) if attempt < settings.database_connect_attempts => {
                 warn!(
-                    "Cannot connect to database '{}': {e}. Retrying in {delay}s...",
-                    settings.database_name
+                    "Cannot connect to database '{}' (attempt {attempt}/{}): {e:#}. Retrying in {}s...",
+                    settings.database_name,
+                    settings.database_connect_attempts,
+                    delay.as_secs()
                 );
             }
+            Err(e) => {
+                return Err(e.context(format!(
+                    "giving up connecting to database after {attempt} attempts"
+                )))
+            }
         }
-        tokio::time::sleep(Duration::from_secs(delay)).await;
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_CONNECT_DELAY);
+        attempt += 1;
     }
 }
+
+pub async fn migrate_database(settings: &Settings) -> Result<()> {
+    let mut client = connection_with_retry(settings).await?;
+    info!("Running database migrations");
+    // Schema changes can take much longer than the queries the timeout is meant for.
+    client
+        .batch_execute("SET statement_timeout = 0")
+        .await
+        .context("failed to disable statement timeout")?;
+    embedded::migrations::runner()
+        .run_async(&mut client)
+        .await
+        .context("failed to run migrations")?;
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use std::{fmt::Display, str::FromStr, time::SystemTime};
+
+use anyhow::{anyhow, bail, Result};
+use lightning::ln::{PaymentHash, PaymentPreimage};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaymentDirection {
+    Inbound,
+    Outbound,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaymentStatus {
+    Pending,
+    Succeeded,
+    Failed,
+}
+
+/// A lightning payment sent or received by this node.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Payment {
+    pub hash: PaymentHash,
+    pub direction: PaymentDirection,
+    pub status: PaymentStatus,
+    pub preimage: Option<PaymentPreimage>,
+    pub amount_msat: Option<u64>,
+    pub fee_msat: Option<u64>,
+    pub failure_reason: Option<String>,
+    pub settled_at: Option<SystemTime>,
+}
+
+impl Payment {
+    pub fn new_outbound(hash: PaymentHash, amount_msat: Option<u64>) -> Payment {
+        Payment {
+            hash,
+            direction: PaymentDirection::Outbound,
+            status: PaymentStatus::Pending,
+            preimage: None,
+            amount_msat,
+            fee_msat: None,
+            failure_reason: None,
+            settled_at: None,
+        }
+    }
+
+    pub fn new_inbound(hash: PaymentHash, amount_msat: Option<u64>) -> Payment {
+        Payment {
+            direction: PaymentDirection::Inbound,
+            ..Payment::new_outbound(hash, amount_msat)
+        }
+    }
+
+    pub fn received(&mut self, preimage: Option<PaymentPreimage>, amount_msat: u64) {
+        self.status = PaymentStatus::Succeeded;
+        self.preimage = preimage;
+        self.amount_msat = Some(amount_msat);
+        self.settled_at = Some(SystemTime::now());
+    }
+
+    pub fn succeeded(&mut self, preimage: PaymentPreimage, fee_msat: Option<u64>) {
+        self.status = PaymentStatus::Succeeded;
+        self.preimage = Some(preimage);
+        self.fee_msat = fee_msat;
+        self.settled_at = Some(SystemTime::now());
+    }
+
+    pub fn failed(&mut self, reason: String) {
+        self.status = PaymentStatus::Failed;
+        self.failure_reason = Some(reason);
+        self.settled_at = Some(SystemTime::now());
+    }
+
+    pub fn deserialize_hash(bytes: Vec<u8>) -> Result<PaymentHash> {
+        Ok(PaymentHash(
+            bytes
+                .try_into()
+                .map_err(|_| anyhow!("Invalid payment hash"))?,
+        ))
+    }
+
+    pub fn deserialize_preimage(bytes: Option<Vec<u8>>) -> Result<Option<PaymentPreimage>> {
+        bytes
+            .map(|b| {
+                Ok(PaymentPreimage(
+                    b.try_into()
+                        .map_err(|_| anyhow!("Invalid payment preimage"))?,
+                ))
+            })
+            .transpose()
+    }
+}
+
+impl Display for PaymentDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaymentDirection::Inbound => write!(f, "inbound"),
+            PaymentDirection::Outbound => write!(f, "outbound"),
+        }
+    }
+}
+
+impl FromStr for PaymentDirection {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inbound" => Ok(PaymentDirection::Inbound),
+            "outbound" => Ok(PaymentDirection::Outbound),
+            _ => bail!("Unknown payment direction {s}"),
+        }
+    }
+}
+
+impl Display for PaymentStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaymentStatus::Pending => write!(f, "pending"),
+            PaymentStatus::Succeeded => write!(f, "succeeded"),
+            PaymentStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl FromStr for PaymentStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(PaymentStatus::Pending),
+            "succeeded" => Ok(PaymentStatus::Succeeded),
+            "failed" => Ok(PaymentStatus::Failed),
+            _ => bail!("Unknown payment status {s}"),
+        }
+    }
+}
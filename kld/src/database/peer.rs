@@ -5,19 +5,61 @@ use lightning::{ln::msgs::NetAddress, util::ser::MaybeReadable};
 #[derive(PartialEq, Eq, Debug)]
 pub struct Peer {
     pub public_key: PublicKey,
-    pub net_address: NetAddress,
+    /// Addresses we connected to the peer with, the most recent connection first.
+    pub net_addresses: Vec<NetAddress>,
 }
 
 impl Peer {
-    pub fn deserialize(public_key: Vec<u8>, net_address: Vec<u8>) -> Result<Peer> {
+    pub fn deserialize(public_key: Vec<u8>, net_addresses: Vec<Vec<u8>>) -> Result<Peer> {
         let public_key = PublicKey::from_slice(&public_key)?;
-        let net_address = NetAddress::read(&mut net_address.as_slice())
-            .map_err(|e| anyhow!("{}", e))?
-            .ok_or(anyhow!("Error parsing address"))?;
+        let net_addresses = net_addresses
+            .into_iter()
+            .map(deserialize_address)
+            .collect::<Result<Vec<NetAddress>>>()?;
 
         Ok(Peer {
             public_key,
-            net_address,
+            net_addresses,
         })
     }
 }
+
+pub fn deserialize_address(net_address: Vec<u8>) -> Result<NetAddress> {
+    NetAddress::read(&mut net_address.as_slice())
+        .map_err(|e| anyhow!("{}", e))?
+        .ok_or(anyhow!("Error parsing address"))
+}
+
+#[test]
+fn test_deserialize_all_address_types() -> Result<()> {
+    use lightning::util::ser::{Hostname, Writeable};
+
+    let public_key = PublicKey::from_slice(&[2; 33])?;
+    let net_addresses = vec![
+        NetAddress::IPv4 {
+            addr: [127, 0, 0, 1],
+            port: 9735,
+        },
+        NetAddress::IPv6 {
+            addr: [1; 16],
+            port: 9735,
+        },
+        NetAddress::OnionV2([2; 12]),
+        NetAddress::OnionV3 {
+            ed25519_pubkey: [3; 32],
+            checksum: 1,
+            version: 3,
+            port: 9735,
+        },
+        NetAddress::Hostname {
+            hostname: Hostname::try_from("node.example.com".to_string()).unwrap(),
+            port: 9735,
+        },
+    ];
+    let peer = Peer::deserialize(
+        public_key.serialize().to_vec(),
+        net_addresses.iter().map(|a| a.encode()).collect(),
+    )?;
+    assert_eq!(net_addresses, peer.net_addresses);
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::{fmt::Display, str::FromStr, time::SystemTime};
+
+use anyhow::{anyhow, bail, Result};
+use bitcoin::{OutPoint, Txid};
+use lightning::{
+    chain::keysinterface::SpendableOutputDescriptor,
+    util::ser::{Readable, Writeable},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SweepStatus {
+    /// Waiting for a sweep transaction to be built.
+    Pending,
+    /// A sweep transaction has been broadcast but has not confirmed yet.
+    Sweeping,
+    /// The output has been spent on chain.
+    Swept,
+}
+
+/// An output of a closed channel which LDK handed us to sweep into the wallet. LDK only reports
+/// each output once, so it is kept here until the sweep has confirmed.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SpendableOutput {
+    pub descriptor: SpendableOutputDescriptor,
+    pub status: SweepStatus,
+    pub sweep_txid: Option<Txid>,
+    pub created_at: SystemTime,
+}
+
+impl SpendableOutput {
+    pub fn new(descriptor: SpendableOutputDescriptor) -> SpendableOutput {
+        SpendableOutput {
+            descriptor,
+            status: SweepStatus::Pending,
+            sweep_txid: None,
+            created_at: SystemTime::now(),
+        }
+    }
+
+    pub fn outpoint(&self) -> OutPoint {
+        match &self.descriptor {
+            SpendableOutputDescriptor::StaticOutput { outpoint, .. } => {
+                outpoint.into_bitcoin_outpoint()
+            }
+            SpendableOutputDescriptor::DelayedPaymentOutput(descriptor) => {
+                descriptor.outpoint.into_bitcoin_outpoint()
+            }
+            SpendableOutputDescriptor::StaticPaymentOutput(descriptor) => {
+                descriptor.outpoint.into_bitcoin_outpoint()
+            }
+        }
+    }
+
+    pub fn value_sats(&self) -> u64 {
+        match &self.descriptor {
+            SpendableOutputDescriptor::StaticOutput { output, .. } => output.value,
+            SpendableOutputDescriptor::DelayedPaymentOutput(descriptor) => descriptor.output.value,
+            SpendableOutputDescriptor::StaticPaymentOutput(descriptor) => descriptor.output.value,
+        }
+    }
+
+    pub fn sweeping(&mut self, sweep_txid: Txid) {
+        self.status = SweepStatus::Sweeping;
+        self.sweep_txid = Some(sweep_txid);
+    }
+
+    pub fn serialize_descriptor(&self) -> Vec<u8> {
+        self.descriptor.encode()
+    }
+
+    pub fn deserialize_descriptor(bytes: Vec<u8>) -> Result<SpendableOutputDescriptor> {
+        SpendableOutputDescriptor::read(&mut bytes.as_slice())
+            .map_err(|e| anyhow!("Error parsing spendable output descriptor: {e}"))
+    }
+}
+
+impl Display for SweepStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SweepStatus::Pending => write!(f, "pending"),
+            SweepStatus::Sweeping => write!(f, "sweeping"),
+            SweepStatus::Swept => write!(f, "swept"),
+        }
+    }
+}
+
+impl FromStr for SweepStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(SweepStatus::Pending),
+            "sweeping" => Ok(SweepStatus::Sweeping),
+            "swept" => Ok(SweepStatus::Swept),
+            _ => bail!("Unknown sweep status {s}"),
+        }
+    }
+}
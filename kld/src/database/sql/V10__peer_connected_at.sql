@@ -0,0 +1 @@
+ALTER TABLE peers ADD COLUMN connected_at TIMESTAMP NOT NULL DEFAULT current_timestamp();
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS node_lease (
+    id              INT PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+    holder          STRING NOT NULL,
+    expires_at      TIMESTAMP NOT NULL
+);
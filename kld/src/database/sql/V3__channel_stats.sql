@@ -0,0 +1,7 @@
+CREATE TABLE channel_stats (
+    channel_id      BYTES PRIMARY KEY,
+    htlcs_attempted INT NOT NULL DEFAULT 0,
+    htlcs_succeeded INT NOT NULL DEFAULT 0,
+    htlcs_failed    INT NOT NULL DEFAULT 0,
+    timestamp       TIMESTAMP NOT NULL DEFAULT current_timestamp()
+);
@@ -0,0 +1,4 @@
+CREATE TABLE wallet_watch_descriptors (
+    descriptor TEXT PRIMARY KEY,
+    sync_time  INT NOT NULL DEFAULT 0
+);
@@ -0,0 +1,12 @@
+CREATE TABLE payments (
+    payment_hash    BYTES NOT NULL,
+    direction       STRING NOT NULL,
+    status          STRING NOT NULL,
+    preimage        BYTES,
+    amount_msat     INT,
+    fee_msat        INT,
+    failure_reason  STRING,
+    created_at      TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    settled_at      TIMESTAMP,
+    PRIMARY KEY (payment_hash, direction)
+);
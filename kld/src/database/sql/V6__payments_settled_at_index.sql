@@ -0,0 +1 @@
+CREATE INDEX payments_settled_at ON payments (direction, settled_at);
@@ -0,0 +1,12 @@
+CREATE TABLE forwarded_payments (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    in_channel_id   BYTES,
+    out_channel_id  BYTES,
+    in_scid         INT,
+    out_scid        INT,
+    amount_in_msat  INT,
+    amount_out_msat INT,
+    fee_msat        INT,
+    timestamp       TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    INDEX (timestamp)
+);
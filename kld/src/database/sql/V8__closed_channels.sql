@@ -0,0 +1,11 @@
+CREATE TABLE closed_channels (
+    channel_id              BYTES PRIMARY KEY,
+    counterparty_node_id    BYTES,
+    funding_txid            BYTES,
+    funding_vout            INT,
+    reason                  STRING,
+    closing_txid            BYTES,
+    sweep_txid              BYTES,
+    closed_at               TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    INDEX (closing_txid)
+);
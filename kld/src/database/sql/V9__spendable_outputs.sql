@@ -0,0 +1,11 @@
+CREATE TABLE spendable_outputs (
+    txid            BYTES NOT NULL,
+    vout            INT NOT NULL,
+    value_sats      INT NOT NULL,
+    descriptor      BYTES NOT NULL,
+    status          STRING NOT NULL,
+    sweep_txid      BYTES,
+    created_at      TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    PRIMARY KEY (txid, vout),
+    INDEX (status)
+);
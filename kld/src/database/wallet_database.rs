@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
-use super::{connection, Client};
-use crate::to_i64;
+use super::{connection, CachedClient};
+use crate::{to_i64, wallet::WatchDescriptorDatabase};
 use anyhow::Result;
 use bdk::{
     database::{BatchDatabase, BatchOperations, Database, SyncTime},
@@ -23,7 +23,7 @@ macro_rules! execute_blocking {
                     .map_err(|e| Error::Generic(e.to_string()))?
                     .read()
                     .await
-                    .execute($statement, $params)
+                    .execute_cached($statement, $params)
                     .await
                     .map_err(|e| Error::Generic(e.to_string()))
             })
@@ -41,7 +41,7 @@ macro_rules! query_blocking {
                     .map_err(|e| Error::Generic(e.to_string()))?
                     .read()
                     .await
-                    .query($statement, $params)
+                    .query_cached($statement, $params)
                     .await
                     .map_err(|e| Error::Generic(e.to_string()))
             })
@@ -52,7 +52,7 @@ macro_rules! query_blocking {
 #[derive(Clone)]
 pub struct WalletDatabase {
     settings: Settings,
-    client: Arc<RwLock<Client>>,
+    client: Arc<RwLock<CachedClient>>,
 }
 
 impl WalletDatabase {
@@ -64,18 +64,20 @@ impl WalletDatabase {
         let client = connection(settings).await?;
         Ok(WalletDatabase {
             settings: settings.clone(),
-            client: Arc::new(RwLock::new(client)),
+            client: Arc::new(RwLock::new(CachedClient::new(client))),
         })
     }
 
     /// Try to reconnect to the database if the connection has been dropped.
     /// If this is not possible one of the callers of this function should shut the node down.
-    async fn client(&self) -> Result<Arc<RwLock<Client>>> {
+    /// Unlike in LdkDatabase, statements which time out are not retried on a new connection as
+    /// they can be part of a batch whose transaction would be lost with the old connection.
+    async fn client(&self) -> Result<Arc<RwLock<CachedClient>>> {
         if self.client.read().await.is_closed() {
             let mut guard = self.client.write().await;
             if guard.is_closed() {
                 let client = connection(&self.settings).await?;
-                *guard = client;
+                *guard = CachedClient::new(client);
             }
         }
         Ok(self.client.clone())
@@ -885,6 +887,32 @@ impl Database for WalletDatabase {
     }
 }
 
+impl WatchDescriptorDatabase for WalletDatabase {
+    fn watch_descriptors(&self) -> Result<Vec<(String, u64)>, Error> {
+        let rows = query_blocking!(
+            "SELECT descriptor, sync_time FROM wallet_watch_descriptors",
+            &[],
+            self
+        )?;
+        let mut descriptors = vec![];
+        for row in rows {
+            let descriptor: String = row.get(0);
+            let sync_time: u64 = row.get::<usize, i64>(1).try_into().unwrap();
+            descriptors.push((descriptor, sync_time));
+        }
+        Ok(descriptors)
+    }
+
+    fn persist_watch_descriptor(&self, descriptor: &str, sync_time: u64) -> Result<(), Error> {
+        execute_blocking!(
+            "UPSERT INTO wallet_watch_descriptors (descriptor, sync_time) VALUES ($1, $2)",
+            &[&descriptor, &to_i64!(sync_time)],
+            self
+        )
+        .map(|_| ())
+    }
+}
+
 impl BatchDatabase for WalletDatabase {
     type Batch = WalletDatabase;
 
@@ -0,0 +1,103 @@
+//! Raise the fees of channels which are running low on outbound liquidity and lower them again
+//! once the channel is balanced, so that routing pushes liquidity back towards us.
+
+use std::{sync::Arc, time::Duration};
+
+use hex::ToHex;
+use lightning::ln::channelmanager::ChannelDetails;
+use log::{error, info};
+use settings::Settings;
+
+use super::{ldk_error, ChannelManager};
+
+/// Adjust fees periodically until the process exits. Does nothing unless enabled in the settings.
+pub(crate) fn start(channel_manager: Arc<ChannelManager>, settings: Arc<Settings>) {
+    if !settings.auto_fees {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(settings.auto_fees_interval_secs));
+        loop {
+            interval.tick().await;
+            adjust_fees(&channel_manager, &settings);
+        }
+    });
+}
+
+fn adjust_fees(channel_manager: &ChannelManager, settings: &Settings) {
+    for channel in channel_manager.list_channels() {
+        let mut config = match channel.config {
+            Some(config) if channel.is_usable => config,
+            _ => continue,
+        };
+        let current_ppm = config.forwarding_fee_proportional_millionths;
+        let outbound_percent = outbound_percent(&channel);
+        let ppm = match next_fee_ppm(current_ppm, outbound_percent, settings) {
+            Some(ppm) => ppm,
+            None => continue,
+        };
+        config.forwarding_fee_proportional_millionths = ppm;
+        match channel_manager.update_channel_config(
+            &channel.counterparty.node_id,
+            &[channel.channel_id],
+            &config,
+        ) {
+            Ok(()) => info!(
+                "Changed fee of channel {} from {current_ppm} to {ppm} ppm with {outbound_percent}% outbound liquidity",
+                channel.channel_id.encode_hex::<String>()
+            ),
+            Err(e) => error!(
+                "Failed to change fee of channel {}: {}",
+                channel.channel_id.encode_hex::<String>(),
+                ldk_error(e)
+            ),
+        }
+    }
+}
+
+fn outbound_percent(channel: &ChannelDetails) -> u64 {
+    let capacity_msat = channel.channel_value_satoshis * 1000;
+    if capacity_msat == 0 {
+        return 0;
+    }
+    channel.balance_msat * 100 / capacity_msat
+}
+
+fn next_fee_ppm(current_ppm: u32, outbound_percent: u64, settings: &Settings) -> Option<u32> {
+    if outbound_percent < settings.auto_fees_low_outbound_percent.into()
+        && current_ppm < settings.auto_fees_max_ppm
+    {
+        Some(
+            current_ppm
+                .saturating_add(settings.auto_fees_ppm_step)
+                .min(settings.auto_fees_max_ppm),
+        )
+    } else if outbound_percent >= settings.auto_fees_balanced_outbound_percent.into()
+        && current_ppm > settings.auto_fees_min_ppm
+    {
+        Some(
+            current_ppm
+                .saturating_sub(settings.auto_fees_ppm_step)
+                .max(settings.auto_fees_min_ppm),
+        )
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_next_fee_ppm() {
+    let settings = Settings::default();
+    // Low on outbound liquidity.
+    assert_eq!(Some(550), next_fee_ppm(500, 10, &settings));
+    assert_eq!(Some(2000), next_fee_ppm(1990, 10, &settings));
+    assert_eq!(None, next_fee_ppm(2000, 10, &settings));
+    // Between the thresholds the fee stays.
+    assert_eq!(None, next_fee_ppm(500, 30, &settings));
+    // Balanced.
+    assert_eq!(Some(450), next_fee_ppm(500, 50, &settings));
+    assert_eq!(Some(100), next_fee_ppm(120, 50, &settings));
+    assert_eq!(None, next_fee_ppm(100, 50, &settings));
+    assert_eq!(None, next_fee_ppm(0, 50, &settings));
+}
@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use bitcoin::{BlockHeader, Txid};
+use hex::ToHex;
+use lightning::chain::{self, channelmonitor::ANTI_REORG_DELAY, transaction::TransactionData};
+use log::{error, info, warn};
+use serde_json::json;
+
+use crate::{database::LdkDatabase, webhook::WebhookNotifier};
+
+use super::{ChainMonitor, ChannelManager};
+
+/// Chain reorganisations seen since startup.
+#[derive(Default)]
+pub(crate) struct Reorgs {
+    pub count: AtomicU64,
+    pub last_depth: AtomicU32,
+}
+
+/// Passes blocks from the chain poller on to LDK, reports reorgs and records the transactions
+/// which close our channels. A reorg shows up as one or more disconnected blocks followed by the
+/// blocks of the new chain.
+pub(crate) struct ChainListener {
+    chain_monitor: Arc<ChainMonitor>,
+    channel_manager: Arc<ChannelManager>,
+    database: Arc<LdkDatabase>,
+    webhook: Arc<WebhookNotifier>,
+    reorgs: Arc<Reorgs>,
+    disconnected: AtomicU32,
+}
+
+impl ChainListener {
+    pub fn new(
+        chain_monitor: Arc<ChainMonitor>,
+        channel_manager: Arc<ChannelManager>,
+        database: Arc<LdkDatabase>,
+        webhook: Arc<WebhookNotifier>,
+        reorgs: Arc<Reorgs>,
+    ) -> ChainListener {
+        ChainListener {
+            chain_monitor,
+            channel_manager,
+            database,
+            webhook,
+            reorgs,
+            disconnected: AtomicU32::new(0),
+        }
+    }
+
+    fn reorg_finished(&self, depth: u32, height: u32) {
+        self.reorgs.count.fetch_add(1, Ordering::Relaxed);
+        self.reorgs.last_depth.store(depth, Ordering::Relaxed);
+        self.webhook.notify(
+            "chain_reorg",
+            json!({
+                "depth": depth,
+                "height": height,
+            }),
+        );
+        if depth < ANTI_REORG_DELAY {
+            warn!("Chain reorg of {depth} blocks, new chain continues at height {height}");
+            return;
+        }
+        error!("Deep chain reorg of {depth} blocks, new chain continues at height {height}");
+        for channel in self.channel_manager.list_channels() {
+            if channel.funding_txo.is_some() && channel.confirmations.unwrap_or_default() <= depth {
+                error!(
+                    "Funding of channel {} with {} may have been affected by the reorg",
+                    channel.channel_id.encode_hex::<String>(),
+                    channel.counterparty.node_id
+                );
+            }
+        }
+    }
+
+    /// Transactions in the block which spend the funding output of one of our channels.
+    fn find_closing_transactions(&self, txdata: &TransactionData) {
+        let funding_outpoints: HashMap<bitcoin::OutPoint, [u8; 32]> = self
+            .chain_monitor
+            .list_monitors()
+            .into_iter()
+            .map(|outpoint| (outpoint.into_bitcoin_outpoint(), outpoint.to_channel_id()))
+            .collect();
+        for (_, tx) in txdata {
+            for input in &tx.input {
+                if let Some(channel_id) = funding_outpoints.get(&input.previous_output) {
+                    self.closing_transaction_confirmed(*channel_id, tx.txid());
+                }
+            }
+        }
+    }
+
+    fn closing_transaction_confirmed(&self, channel_id: [u8; 32], txid: Txid) {
+        info!(
+            "Closing transaction {txid} of channel {} confirmed",
+            channel_id.encode_hex::<String>()
+        );
+        let database = self.database.clone();
+        tokio::spawn(async move {
+            if let Err(e) = database.persist_closing_txid(&channel_id, &txid).await {
+                error!("Failed to persist closing transaction {txid}: {e}");
+            }
+        });
+    }
+}
+
+impl chain::Listen for ChainListener {
+    fn filtered_block_connected(
+        &self,
+        header: &BlockHeader,
+        txdata: &TransactionData,
+        height: u32,
+    ) {
+        let depth = self.disconnected.swap(0, Ordering::AcqRel);
+        if depth > 0 {
+            self.reorg_finished(depth, height);
+        }
+        self.find_closing_transactions(txdata);
+        self.chain_monitor
+            .filtered_block_connected(header, txdata, height);
+        self.channel_manager
+            .filtered_block_connected(header, txdata, height);
+    }
+
+    fn block_disconnected(&self, header: &BlockHeader, height: u32) {
+        self.disconnected.fetch_add(1, Ordering::AcqRel);
+        self.chain_monitor.block_disconnected(header, height);
+        self.channel_manager.block_disconnected(header, height);
+    }
+}
@@ -1,51 +1,82 @@
-use crate::bitcoind::{BitcoindClient, BitcoindUtxoLookup, Synchronised};
+use crate::bitcoind::{notify_on_new_block, BitcoindClient, BitcoindUtxoLookup, Synchronised};
 use crate::wallet::{Wallet, WalletInterface};
-
-use crate::database::{LdkDatabase, WalletDatabase};
+use crate::webhook::WebhookNotifier;
+
+use crate::database::{
+    channel_stats::ChannelStats,
+    closed_channel::ClosedChannel,
+    forward::{ChannelEarnings, EarningsBucket, Forward},
+    payment::{Payment, PaymentDirection, PaymentStatus},
+    spendable_output::SpendableOutput,
+    LdkDatabase, WalletDatabase,
+};
 use anyhow::{anyhow, bail, Context, Result};
-use api::FeeRate;
+use api::{CodedError, ErrorCode, FeeRate};
 use async_trait::async_trait;
-use bitcoin::secp256k1::PublicKey;
-use bitcoin::{BlockHash, Network, Transaction};
-use lightning::chain::channelmonitor::ChannelMonitor;
-use lightning::chain::keysinterface::{InMemorySigner, KeysManager};
+use bitcoin::bech32::ToBase32;
+use bitcoin::hashes::{sha256, Hash as _};
+use bitcoin::secp256k1::{PublicKey, Secp256k1};
+use bitcoin::util::address::Payload;
+use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{Address, BlockHash, Network, PackedLockTime, Script, Transaction, TxOut};
+use futures::stream::BoxStream;
+use hex::ToHex;
+use lightning::chain::channelmonitor::{Balance, ChannelMonitor};
+use lightning::chain::keysinterface::{InMemorySigner, KeysManager, NodeSigner, Recipient};
+use lightning::chain::transaction::OutPoint;
 use lightning::chain::BestBlock;
 use lightning::chain::{self, ChannelMonitorUpdateStatus};
 use lightning::chain::{chainmonitor, Watch};
-use lightning::ln::channelmanager::{self, ChannelDetails};
-use lightning::ln::channelmanager::{ChainParameters, ChannelManagerReadArgs};
-use lightning::ln::msgs::NetAddress;
+use lightning::ln::channelmanager::{self, ChannelDetails, MIN_FINAL_CLTV_EXPIRY_DELTA};
+use lightning::ln::channelmanager::{ChainParameters, ChannelManagerReadArgs, InterceptId};
+use lightning::ln::channelmanager::{RecentPaymentDetails, Retry, RetryableSendFailure};
 use lightning::ln::peer_handler::{IgnoringMessageHandler, MessageHandler};
-use lightning::routing::gossip::{ChannelInfo, NodeId, NodeInfo, P2PGossipSync};
-use lightning::routing::router::DefaultRouter;
+use lightning::ln::{PaymentHash, PaymentPreimage};
+use lightning::routing::gossip::{ChannelInfo, NodeId, NodeInfo, P2PGossipSync, RoutingFees};
+use lightning::routing::router::{
+    DefaultRouter, PaymentParameters, RouteHint, RouteHintHop, RouteParameters, Router,
+};
 use lightning::routing::scoring::{ProbabilisticScorer, ProbabilisticScoringParameters};
 use lightning::util::config::UserConfig;
 
 use crate::logger::KldLogger;
 use lightning::util::indexed_map::IndexedMap;
+use lightning::util::message_signing;
+use lightning::util::ser::Writeable;
 use lightning_background_processor::{BackgroundProcessor, GossipSync};
 use lightning_block_sync::SpvClient;
 use lightning_block_sync::UnboundedCache;
 use lightning_block_sync::{init, BlockSourceResult};
 use lightning_block_sync::{poll, BlockSource};
+use lightning_invoice::payment::{pay_invoice, PaymentError};
+use lightning_invoice::{Fallback, Invoice, InvoiceBuilder};
 use log::{error, info, warn};
 use rand::random;
 use settings::Settings;
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 use tokio::runtime::Handle;
 use tokio::sync::oneshot::{self, Receiver, Sender};
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, RwLock};
+use tracing::{field, instrument, Span};
 
+use super::auto_fees;
+use super::chain_listener::{ChainListener, Reorgs};
 use super::event_handler::EventHandler;
+use super::graph_pruner;
 use super::net_utils::PeerAddress;
-use super::payment_info::PaymentInfoStorage;
 use super::peer_manager::PeerManager;
+use super::remote_signer::{HttpRemoteSigner, RemoteSigner};
+use super::sweeper::{self, Sweeper};
 use super::{
-    ldk_error, ChainMonitor, ChannelManager, LdkPeerManager, LightningInterface, NetworkGraph,
-    OnionMessenger, OpenChannelResult, Peer, PeerStatus,
+    check_channel_minimum_depth, ldk_error, ChainMonitor, ChannelManager, InterceptedHtlc,
+    KldRouter, LdkPeerManager, LightningInterface, MonitorCheck, MonitorDiscrepancy, NetworkGraph,
+    NodeState, OnionMessenger, OpenChannelPsbtResult, OpenChannelResult, PaymentResult, Peer,
+    PeerStatus, PendingHtlc, Scorer, DEFAULT_INVOICE_EXPIRY_SECS,
 };
 
 #[async_trait]
@@ -88,7 +119,21 @@ impl LightningInterface for Controller {
     }
 
     fn alias(&self) -> String {
-        self.settings.node_name.clone()
+        self.peer_manager.appearance().alias
+    }
+
+    fn color(&self) -> [u8; 3] {
+        self.peer_manager.appearance().color
+    }
+
+    fn announce_node(&self) -> bool {
+        self.peer_manager.broadcast_node_announcement()
+    }
+
+    fn set_alias(&self, alias: String, color: Option<[u8; 3]>) -> Result<()> {
+        self.peer_manager.set_appearance(alias, color)?;
+        info!("Changed the node alias");
+        Ok(())
     }
 
     async fn block_height(&self) -> Result<u64> {
@@ -130,6 +175,17 @@ impl LightningInterface for Controller {
         self.channel_manager.list_channels()
     }
 
+    async fn list_closed_channels(&self) -> Result<Vec<ClosedChannel>> {
+        self.database.fetch_closed_channels().await
+    }
+
+    async fn pending_sweeps(&self) -> Result<Vec<SpendableOutput>> {
+        self.database.fetch_unswept_outputs().await
+    }
+
+    // The event handler enters a span with the same name and user_channel_id for the funding
+    // events, so the whole channel open can be followed.
+    #[instrument(skip(self, fee_rate, override_config), fields(user_channel_id))]
     async fn open_channel(
         &self,
         their_network_key: PublicKey,
@@ -142,9 +198,13 @@ impl LightningInterface for Controller {
             bail!("Bitcoind is syncronising blockchain")
         }
         if !self.peer_manager.is_connected(&their_network_key) {
-            return Err(anyhow!("Peer not connected"));
+            bail!(CodedError::new(
+                ErrorCode::PeerNotConnected,
+                "Peer not connected"
+            ));
         }
         let user_channel_id: u128 = random();
+        Span::current().record("user_channel_id", field::display(user_channel_id));
         let channel_id = self
             .channel_manager
             .create_channel(
@@ -169,6 +229,98 @@ impl LightningInterface for Controller {
         })
     }
 
+    async fn open_channel_psbt(
+        &self,
+        their_network_key: PublicKey,
+        channel_value_satoshis: u64,
+        push_msat: Option<u64>,
+        override_config: Option<UserConfig>,
+    ) -> Result<OpenChannelPsbtResult> {
+        if !self.bitcoind_client.is_synchronised().await? {
+            bail!("Bitcoind is syncronising blockchain")
+        }
+        if !self.peer_manager.is_connected(&their_network_key) {
+            bail!(CodedError::new(
+                ErrorCode::PeerNotConnected,
+                "Peer not connected"
+            ));
+        }
+        let user_channel_id: u128 = random();
+        let channel_id = self
+            .channel_manager
+            .create_channel(
+                their_network_key,
+                channel_value_satoshis,
+                push_msat.unwrap_or_default(),
+                user_channel_id,
+                override_config,
+            )
+            .map_err(ldk_error)?;
+        let receiver = self
+            .async_api_requests
+            .funding_psbts
+            .insert(user_channel_id, ())
+            .await;
+        let output_script = receiver.await??;
+        let address = Address::from_script(&output_script, self.network())
+            .context("Funding output has no address")?;
+        let psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut {
+                value: channel_value_satoshis,
+                script_pubkey: output_script,
+            }],
+        })?;
+        Ok(OpenChannelPsbtResult {
+            channel_id,
+            address,
+            psbt,
+        })
+    }
+
+    async fn fund_channel_psbt(
+        &self,
+        channel_id: &[u8; 32],
+        psbt: PartiallySignedTransaction,
+    ) -> Result<OpenChannelResult> {
+        let channel = self
+            .channel_manager
+            .list_channels()
+            .into_iter()
+            .find(|c| &c.channel_id == channel_id)
+            .context("Channel not found")?;
+        if channel.funding_txo.is_some() {
+            bail!("Channel is already funded");
+        }
+        if psbt
+            .inputs
+            .iter()
+            .any(|input| input.final_script_sig.is_none() && input.final_script_witness.is_none())
+        {
+            bail!("PSBT is not finalized");
+        }
+        let transaction = psbt.extract_tx();
+        self.channel_manager
+            .funding_transaction_generated(
+                channel_id,
+                &channel.counterparty.node_id,
+                transaction.clone(),
+            )
+            .map_err(ldk_error)?;
+        let txid = transaction.txid();
+        info!(
+            "Channel {} funded externally with txid {txid}",
+            hex::encode(channel_id)
+        );
+        Ok(OpenChannelResult {
+            transaction,
+            txid,
+            channel_id: *channel_id,
+        })
+    }
+
     async fn close_channel(
         &self,
         channel_id: &[u8; 32],
@@ -182,6 +334,100 @@ impl LightningInterface for Controller {
             .map_err(ldk_error)
     }
 
+    async fn abandon_channel(
+        &self,
+        channel_id: &[u8; 32],
+        counterparty_node_id: &PublicKey,
+    ) -> Result<()> {
+        self.channel_manager
+            .force_close_without_broadcasting_txn(channel_id, counterparty_node_id)
+            .map_err(ldk_error)
+    }
+
+    fn intercept_scid(&self) -> u64 {
+        self.channel_manager.get_intercept_scid()
+    }
+
+    fn forward_intercepted_htlc(
+        &self,
+        intercept_id: [u8; 32],
+        channel_id: &[u8; 32],
+        counterparty_node_id: PublicKey,
+        amount_msat: u64,
+    ) -> Result<()> {
+        self.channel_manager
+            .forward_intercepted_htlc(
+                InterceptId(intercept_id),
+                channel_id,
+                counterparty_node_id,
+                amount_msat,
+            )
+            .map_err(ldk_error)?;
+        self.intercepted_htlcs.lock().unwrap().remove(&intercept_id);
+        Ok(())
+    }
+
+    fn fail_intercepted_htlc(&self, intercept_id: [u8; 32]) -> Result<()> {
+        self.channel_manager
+            .fail_intercepted_htlc(InterceptId(intercept_id))
+            .map_err(ldk_error)?;
+        self.intercepted_htlcs.lock().unwrap().remove(&intercept_id);
+        Ok(())
+    }
+
+    fn list_intercepted_htlcs(&self) -> Vec<InterceptedHtlc> {
+        let mut htlcs: Vec<InterceptedHtlc> = self
+            .intercepted_htlcs
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        htlcs.sort_by_key(|htlc| htlc.intercepted_at);
+        htlcs
+    }
+
+    fn list_pending_htlcs(&self) -> Vec<PendingHtlc> {
+        let mut htlcs = vec![];
+        for funding_txo in self.chain_monitor.list_monitors() {
+            let monitor = match self.chain_monitor.get_monitor(funding_txo) {
+                Ok(monitor) => monitor,
+                Err(_) => continue,
+            };
+            for balance in monitor.get_claimable_balances() {
+                let (direction, amount_sats, cltv_expiry) = match balance {
+                    Balance::MaybeTimeoutClaimableHTLC {
+                        claimable_amount_satoshis,
+                        claimable_height,
+                        ..
+                    } => (
+                        PaymentDirection::Outbound,
+                        claimable_amount_satoshis,
+                        claimable_height,
+                    ),
+                    Balance::MaybePreimageClaimableHTLC {
+                        claimable_amount_satoshis,
+                        expiry_height,
+                        ..
+                    } => (
+                        PaymentDirection::Inbound,
+                        claimable_amount_satoshis,
+                        expiry_height,
+                    ),
+                    _ => continue,
+                };
+                htlcs.push(PendingHtlc {
+                    channel_id: funding_txo.to_channel_id(),
+                    direction,
+                    amount_sat: amount_sats,
+                    cltv_expiry,
+                });
+            }
+        }
+        htlcs.sort_by_key(|htlc| htlc.cltv_expiry);
+        htlcs
+    }
+
     fn set_channel_fee(
         &self,
         counterparty_node_id: &PublicKey,
@@ -205,6 +451,20 @@ impl LightningInterface for Controller {
         ))
     }
 
+    async fn channel_stats(&self) -> Result<Vec<ChannelStats>> {
+        self.database.fetch_channel_stats().await
+    }
+
+    async fn channel_earnings(
+        &self,
+        short_channel_id: u64,
+        bucket: EarningsBucket,
+    ) -> Result<Vec<ChannelEarnings>> {
+        self.database
+            .fetch_channel_earnings(short_channel_id, bucket)
+            .await
+    }
+
     fn alias_of(&self, public_key: &PublicKey) -> Option<String> {
         self.network_graph
             .read_only()
@@ -254,6 +514,7 @@ impl LightningInterface for Controller {
         Ok(response)
     }
 
+    #[instrument(skip(self, peer_address))]
     async fn connect_peer(
         &self,
         public_key: PublicKey,
@@ -264,15 +525,10 @@ impl LightningInterface for Controller {
                 .connect_peer(public_key, net_address)
                 .await
         } else {
-            let addresses: Vec<PeerAddress> = self
-                .network_graph
-                .read_only()
-                .get_addresses(&public_key)
-                .context("No addresses found for node")?
-                .into_iter()
-                .filter(|a| matches!(a, NetAddress::IPv4 { addr: _, port: _ }))
-                .map(PeerAddress)
-                .collect();
+            let addresses = self.peer_manager.known_addresses(&public_key).await?;
+            if addresses.is_empty() {
+                bail!("No addresses found for node");
+            }
             for address in addresses {
                 if let Err(e) = self
                     .peer_manager
@@ -289,7 +545,12 @@ impl LightningInterface for Controller {
     }
 
     async fn disconnect_peer(&self, public_key: PublicKey) -> Result<()> {
-        self.peer_manager.disconnect_by_node_id(public_key).await
+        self.peer_manager.disconnect_by_node_id(public_key);
+        Ok(())
+    }
+
+    async fn forget_peer(&self, public_key: PublicKey) -> Result<()> {
+        self.peer_manager.forget_peer(&public_key).await
     }
 
     fn public_addresses(&self) -> Vec<String> {
@@ -316,16 +577,353 @@ impl LightningInterface for Controller {
     fn user_config(&self) -> UserConfig {
         *self.channel_manager.get_current_default_configuration()
     }
+
+    fn maintenance_mode(&self) -> bool {
+        self.maintenance_mode.load(Ordering::Acquire)
+    }
+
+    fn set_maintenance_mode(&self, enabled: bool) {
+        self.maintenance_mode.store(enabled, Ordering::Release);
+        info!(
+            "Maintenance mode {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+    }
+
+    fn ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    fn num_reorgs(&self) -> u64 {
+        self.reorgs.count.load(Ordering::Relaxed)
+    }
+
+    fn last_reorg_depth(&self) -> u32 {
+        self.reorgs.last_depth.load(Ordering::Relaxed)
+    }
+
+    fn corrupt_channel_monitors(&self) -> usize {
+        self.database.corrupt_channel_monitors()
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<String> {
+        Ok(message_signing::sign(
+            message,
+            &self.keys_manager.get_node_secret_key(),
+        )?)
+    }
+
+    fn derive_public_key(&self, path: &DerivationPath) -> Result<PublicKey> {
+        let secp = Secp256k1::new();
+        let key = self.master_key.derive_priv(&secp, path)?;
+        Ok(ExtendedPubKey::from_priv(&secp, &key).public_key)
+    }
+
+    async fn check_channel_monitors(&self) -> Result<MonitorCheck> {
+        let (monitors, corrupt) = self
+            .database
+            .read_channel_monitors(self.keys_manager.as_ref(), self.keys_manager.as_ref())
+            .await?;
+        let persisted: HashMap<OutPoint, u64> = monitors
+            .iter()
+            .map(|(_, monitor)| (monitor.get_funding_txo().0, monitor.get_latest_update_id()))
+            .collect();
+        let in_memory = self.chain_monitor.list_monitors();
+        let mut discrepancies = vec![];
+        for funding_txo in &in_memory {
+            // Updates which are being persisted right now can show up here as well.
+            let in_memory_update_id = self
+                .chain_monitor
+                .get_monitor(*funding_txo)
+                .ok()
+                .map(|monitor| monitor.get_latest_update_id());
+            let persisted_update_id = persisted.get(funding_txo).copied();
+            if persisted_update_id != in_memory_update_id {
+                discrepancies.push(MonitorDiscrepancy {
+                    funding_txo: *funding_txo,
+                    persisted_update_id,
+                    in_memory_update_id,
+                });
+            }
+        }
+        for (funding_txo, update_id) in &persisted {
+            if !in_memory.contains(funding_txo) {
+                discrepancies.push(MonitorDiscrepancy {
+                    funding_txo: *funding_txo,
+                    persisted_update_id: Some(*update_id),
+                    in_memory_update_id: None,
+                });
+            }
+        }
+        Ok(MonitorCheck {
+            checked: persisted.len(),
+            corrupt,
+            discrepancies,
+        })
+    }
+
+    async fn export_state(&self) -> Result<NodeState> {
+        let channel_monitors = self
+            .database
+            .read_channel_monitors(self.keys_manager.as_ref(), self.keys_manager.as_ref())
+            .await?
+            .0
+            .iter()
+            .map(|(_, monitor)| monitor.encode())
+            .collect();
+        let network_graph = self
+            .database
+            .fetch_graph()
+            .await?
+            .map(|graph| graph.encode());
+        let peers = self
+            .database
+            .fetch_peers()
+            .await?
+            .into_iter()
+            .flat_map(|(public_key, addresses)| {
+                addresses
+                    .into_iter()
+                    .map(move |address| (public_key, address))
+            })
+            .collect();
+        Ok(NodeState {
+            channel_monitors,
+            network_graph,
+            peers,
+        })
+    }
+
+    async fn reset_scorer(&self) -> Result<()> {
+        // The router and background processor share this mutex so they pick up the new scorer.
+        *self.scorer.lock().unwrap() = ProbabilisticScorer::new(
+            ProbabilisticScoringParameters::default(),
+            self.network_graph.clone(),
+            KldLogger::global(),
+        );
+        info!("Scorer has been reset");
+        self.database.persist_scorer(self.scorer.as_ref()).await
+    }
+
+    async fn prune_network_graph(&self) -> Result<(usize, usize)> {
+        graph_pruner::prune(&self.network_graph, &self.database)
+    }
+
+    async fn generate_invoice(
+        &self,
+        amount_msat: Option<u64>,
+        description: String,
+        expiry_secs: Option<u32>,
+        fallback_address: bool,
+    ) -> Result<Invoice> {
+        let expiry_secs = expiry_secs.unwrap_or(DEFAULT_INVOICE_EXPIRY_SECS);
+        let (payment_hash, payment_secret) = self
+            .channel_manager
+            .create_inbound_payment(amount_msat, expiry_secs, None)
+            .map_err(|_| anyhow!("Invoice amount is too large"))?;
+        let mut builder = InvoiceBuilder::new(self.network().into())
+            .description(description)
+            .payment_hash(sha256::Hash::from_slice(&payment_hash.0)?)
+            .payment_secret(payment_secret)
+            .duration_since_epoch(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?)
+            .min_final_cltv_expiry_delta(MIN_FINAL_CLTV_EXPIRY_DELTA.into())
+            .expiry_time(Duration::from_secs(expiry_secs.into()))
+            .basic_mpp();
+        if let Some(amount_msat) = amount_msat {
+            builder = builder.amount_milli_satoshis(amount_msat);
+        }
+        for route_hint in self.route_hints() {
+            builder = builder.private_route(route_hint);
+        }
+        if fallback_address {
+            let address = self.wallet.new_address()?.address;
+            builder = builder.fallback(to_fallback(address.payload)?);
+        }
+        let raw_invoice = builder.build_raw().map_err(|e| anyhow!("{e}"))?;
+        let hrp = raw_invoice.hrp.to_string();
+        let data = raw_invoice.data.to_base32();
+        let signed_invoice = raw_invoice
+            .sign::<_, ()>(|_| {
+                self.keys_manager
+                    .sign_invoice(hrp.as_bytes(), &data, Recipient::Node)
+            })
+            .map_err(|_| anyhow!("Failed to sign invoice"))?;
+        let invoice = Invoice::from_signed(signed_invoice).map_err(|e| anyhow!("{e}"))?;
+        self.database
+            .persist_payment(&Payment::new_inbound(payment_hash, amount_msat))
+            .await?;
+        Ok(invoice)
+    }
+
+    // Like open_channel, the payment events are handled in a span with the same payment_hash.
+    #[instrument(skip(self, invoice), fields(payment_hash = %invoice.payment_hash()))]
+    async fn pay_invoice(&self, invoice: Invoice, max_fee_msat: u64) -> Result<PaymentResult> {
+        let payment_hash = PaymentHash(invoice.payment_hash().into_inner());
+        self.check_route_fee(&invoice, max_fee_msat)?;
+        // Paying the same invoice twice would send the money twice. The insert only succeeds for
+        // one caller, even if several try to pay the invoice at the same time.
+        let mut payment = Payment::new_outbound(payment_hash, invoice.amount_milli_satoshis());
+        if !self.database.insert_pending_payment(&payment).await? {
+            match self
+                .database
+                .fetch_payment(&payment_hash, PaymentDirection::Outbound)
+                .await?
+                .map(|payment| payment.status)
+            {
+                Some(PaymentStatus::Succeeded) => bail!("Invoice has already been paid"),
+                _ => bail!("Payment is already in progress"),
+            }
+        }
+        let receiver = self
+            .async_api_requests
+            .payments
+            .insert(payment_hash, ())
+            .await;
+        if let Err(e) = pay_invoice(
+            &invoice,
+            Retry::Timeout(Duration::from_secs(60)),
+            self.channel_manager.as_ref(),
+        ) {
+            self.async_api_requests.payments.get(&payment_hash).await;
+            if matches!(
+                e,
+                PaymentError::Sending(RetryableSendFailure::DuplicatePayment)
+            ) {
+                // LDK is still working on the payment, its events will settle it.
+                bail!("Payment is already in progress");
+            }
+            payment.failed(format!("{e:?}"));
+            self.database.persist_payment(&payment).await?;
+            if matches!(
+                e,
+                PaymentError::Sending(RetryableSendFailure::RouteNotFound)
+            ) {
+                bail!(CodedError::new(
+                    ErrorCode::NoRoute,
+                    format!("Failed to send payment: {e:?}")
+                ));
+            }
+            bail!("Failed to send payment: {e:?}");
+        }
+        let (preimage, fee_paid_msat) = receiver.await??;
+        Ok(PaymentResult {
+            payment_hash,
+            preimage,
+            amount_msat: invoice.amount_milli_satoshis(),
+            fee_paid_msat,
+        })
+    }
+
+    async fn fetch_outbound_payment(&self, payment_hash: PaymentHash) -> Result<Option<Payment>> {
+        self.database
+            .fetch_payment(&payment_hash, PaymentDirection::Outbound)
+            .await
+    }
+
+    async fn list_payments(
+        &self,
+        direction: PaymentDirection,
+        status: Option<PaymentStatus>,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> Result<Vec<Payment>> {
+        self.database
+            .fetch_payments(direction, status, since, until)
+            .await
+    }
+
+    async fn forwards(
+        &self,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> Result<BoxStream<'static, Result<Forward>>> {
+        self.database.fetch_forwards(since, until).await
+    }
+}
+
+fn to_fallback(payload: Payload) -> Result<Fallback> {
+    Ok(match payload {
+        Payload::WitnessProgram { version, program } => {
+            Fallback::SegWitProgram { version, program }
+        }
+        Payload::PubkeyHash(hash) => Fallback::PubKeyHash(hash.into_inner()),
+        Payload::ScriptHash(hash) => Fallback::ScriptHash(hash.into_inner()),
+    })
+}
+
+/// HTLCs held by interception by intercept id, added by the event handler.
+pub(crate) type InterceptedHtlcs = Mutex<HashMap<[u8; 32], InterceptedHtlc>>;
+
+/// Channels keep the config they were opened with, so a changed limit has to be applied to each
+/// of them.
+fn apply_dust_exposure_limit(channel_manager: &ChannelManager, max_dust_htlc_exposure_msat: u64) {
+    for channel in channel_manager.list_channels() {
+        let mut config = match channel.config {
+            Some(config) if config.max_dust_htlc_exposure_msat != max_dust_htlc_exposure_msat => {
+                config
+            }
+            _ => continue,
+        };
+        config.max_dust_htlc_exposure_msat = max_dust_htlc_exposure_msat;
+        if let Err(e) = channel_manager.update_channel_config(
+            &channel.counterparty.node_id,
+            &[channel.channel_id],
+            &config,
+        ) {
+            error!(
+                "Failed to set dust HTLC exposure limit of channel {}: {}",
+                channel.channel_id.encode_hex::<String>(),
+                ldk_error(e)
+            );
+        }
+    }
+}
+
+/// Settle the payments which are still pending in the database after a restart. Payments that
+/// LDK still tracks are resolved by the payment events as usual. LDK rebuilds its pending
+/// payments from the channel monitors when it loads, so the others never left the node and are
+/// marked as failed, which allows paying the invoice again. This runs before the node is ready,
+/// so no new payment can be pending but not yet sent.
+pub async fn reconcile_pending_payments(
+    database: &LdkDatabase,
+    recent_payments: &[RecentPaymentDetails],
+) -> Result<()> {
+    for mut payment in database.fetch_pending_payments().await? {
+        let in_flight = recent_payments.iter().any(|recent| match recent {
+            RecentPaymentDetails::Pending { payment_hash, .. }
+            | RecentPaymentDetails::Abandoned { payment_hash } => *payment_hash == payment.hash,
+            RecentPaymentDetails::Fulfilled { payment_hash } => *payment_hash == Some(payment.hash),
+        });
+        if in_flight {
+            info!(
+                "Payment {} is still in flight",
+                payment.hash.0.encode_hex::<String>()
+            );
+        } else {
+            warn!(
+                "Payment {} was never sent before the node stopped, marking it as failed",
+                payment.hash.0.encode_hex::<String>()
+            );
+            payment.failed("Payment was not sent before the node stopped".to_string());
+            database.persist_payment(&payment).await?;
+        }
+    }
+    Ok(())
 }
 
 pub(crate) struct AsyncAPIRequests {
     pub funding_transactions: AsyncSenders<u128, FeeRate, Result<Transaction>>,
+    /// Channel opens waiting for the funding output, which is then paid by an external wallet.
+    pub funding_psbts: AsyncSenders<u128, (), Result<Script>>,
+    pub payments: AsyncSenders<PaymentHash, (), Result<(PaymentPreimage, Option<u64>)>>,
 }
 
 impl AsyncAPIRequests {
     fn new() -> AsyncAPIRequests {
         AsyncAPIRequests {
             funding_transactions: AsyncSenders::new(),
+            funding_psbts: AsyncSenders::new(),
+            payments: AsyncSenders::new(),
         }
     }
 }
@@ -372,22 +970,113 @@ pub struct Controller {
     settings: Arc<Settings>,
     database: Arc<LdkDatabase>,
     bitcoind_client: Arc<BitcoindClient>,
+    keys_manager: Arc<KeysManager>,
+    master_key: ExtendedPrivKey,
+    chain_monitor: Arc<ChainMonitor>,
     channel_manager: Arc<ChannelManager>,
     peer_manager: Arc<PeerManager>,
     network_graph: Arc<NetworkGraph>,
+    scorer: Arc<Mutex<Scorer>>,
+    router: Arc<KldRouter>,
     wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
     async_api_requests: Arc<AsyncAPIRequests>,
+    intercepted_htlcs: Arc<InterceptedHtlcs>,
+    maintenance_mode: Arc<AtomicBool>,
+    ready: Arc<AtomicBool>,
+    reorgs: Arc<Reorgs>,
     background_processor: Arc<Mutex<Option<BackgroundProcessor>>>,
 }
 
 impl Controller {
-    pub fn stop(&self) {
+    /// Fail if the cheapest route to the payee costs more than max_fee_msat. LDK 0.0.114 can't
+    /// limit the fee of the routes it retries with, so this only checks the route found now.
+    fn check_route_fee(&self, invoice: &Invoice, max_fee_msat: u64) -> Result<()> {
+        let amount_msat = invoice
+            .amount_milli_satoshis()
+            .context("Invoice has no amount")?;
+        let final_cltv_expiry_delta = invoice.min_final_cltv_expiry_delta() as u32;
+        let mut payment_params = PaymentParameters::from_node_id(
+            invoice.recover_payee_pub_key(),
+            final_cltv_expiry_delta,
+        )
+        .with_route_hints(invoice.route_hints());
+        if let Some(features) = invoice.features() {
+            payment_params = payment_params.with_features(features.clone());
+        }
+        let route_params = RouteParameters {
+            payment_params,
+            final_value_msat: amount_msat,
+            final_cltv_expiry_delta,
+        };
+        let first_hops = self.channel_manager.list_usable_channels();
+        let route = self
+            .router
+            .find_route(
+                &self.identity_pubkey(),
+                &route_params,
+                Some(&first_hops.iter().collect::<Vec<_>>()),
+                &self.channel_manager.compute_inflight_htlcs(),
+            )
+            .map_err(|e| {
+                CodedError::new(
+                    ErrorCode::NoRoute,
+                    format!("Failed to find a route: {}", e.err),
+                )
+            })?;
+        let fee_msat = route.get_total_fees();
+        if fee_msat > max_fee_msat {
+            bail!(
+                "The cheapest route costs {fee_msat} msat in fees, more than the limit of {max_fee_msat} msat"
+            );
+        }
+        Ok(())
+    }
+
+    /// Hints for the payer to reach us through our private channels.
+    fn route_hints(&self) -> Vec<RouteHint> {
+        self.channel_manager
+            .list_usable_channels()
+            .into_iter()
+            .filter(|channel| !channel.is_public)
+            .filter_map(|channel| {
+                let forwarding_info = channel.counterparty.forwarding_info.as_ref()?;
+                Some(RouteHint(vec![RouteHintHop {
+                    src_node_id: channel.counterparty.node_id,
+                    short_channel_id: channel.get_inbound_payment_scid()?,
+                    fees: RoutingFees {
+                        base_msat: forwarding_info.fee_base_msat,
+                        proportional_millionths: forwarding_info.fee_proportional_millionths,
+                    },
+                    cltv_expiry_delta: forwarding_info.cltv_expiry_delta,
+                    htlc_minimum_msat: channel.inbound_htlc_minimum_msat,
+                    htlc_maximum_msat: channel.inbound_htlc_maximum_msat,
+                }]))
+            })
+            .collect()
+    }
+
+    /// Disconnect all peers and stop the background processor, which persists the final channel
+    /// manager, network graph and scorer. Call this after the API stopped taking requests.
+    pub async fn shutdown(&self) -> Result<()> {
+        info!("Stopping lightning node");
+        self.peer_manager.disconnect_all_peers();
+        let background_processor = self.background_processor.lock().unwrap().take();
+        if let Some(bgp) = background_processor {
+            tokio::task::block_in_place(|| bgp.stop())
+                .context("Background processor did not stop cleanly")?;
+        }
+        Ok(())
+    }
+
+    fn stop(&self) {
         // Disconnect our peers and stop accepting new connections. This ensures we don't continue
         // updating our channel data after we've stopped the background processor.
         self.peer_manager.disconnect_all_peers();
         if let Some(bgp) = self.background_processor.lock().unwrap().take() {
-            bgp.stop()
-                .expect("Background processor did not stop cleanly");
+            // Fails without writing anything when the database lease has been lost.
+            if let Err(e) = bgp.stop() {
+                error!("Background processor did not stop cleanly: {e}");
+            }
         }
     }
 
@@ -396,6 +1085,7 @@ impl Controller {
         database: Arc<LdkDatabase>,
         bitcoind_client: Arc<BitcoindClient>,
         wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+        webhook: Arc<WebhookNotifier>,
         seed: &[u8; 32],
     ) -> Result<Controller> {
         // BitcoindClient implements the FeeEstimator trait, so it'll act as our fee estimator.
@@ -419,6 +1109,16 @@ impl Controller {
             .is_first_start()
             .await
             .context("could not check if database has been initialized")?;
+        if settings.signer_mode == "remote" {
+            // The channel manager and peer manager are bound to the KeysManager, so only check
+            // that the signer is reachable until they are generic over the signer.
+            let signer = HttpRemoteSigner::new(&settings.remote_signer_url)?;
+            let node_id = signer
+                .node_id()
+                .await
+                .context("Could not reach remote signer")?;
+            bail!("Remote signer {node_id} is reachable but signing with it is not supported yet");
+        }
         // Initialize the KeysManager
         // The key seed that we use to derive the node privkey (that corresponds to the node pubkey) and
         // other secret key material.
@@ -426,6 +1126,8 @@ impl Controller {
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap();
         let keys_manager = Arc::new(KeysManager::new(seed, cur.as_secs(), cur.subsec_nanos()));
+        // The same master key as the KeysManager derives its keys from.
+        let master_key = ExtendedPrivKey::new_master(network, seed)?;
 
         let network_graph = Arc::new(
             database
@@ -461,11 +1163,28 @@ impl Controller {
         let mut channelmonitors = database
             .fetch_channel_monitors(keys_manager.as_ref(), keys_manager.as_ref())
             .await?;
+        check_channel_minimum_depth(settings.channel_minimum_depth)?;
         let mut user_config = UserConfig::default();
         user_config
             .channel_handshake_limits
             .force_announced_channel_preference = false;
-        user_config.channel_handshake_config.announced_channel = true;
+        user_config.channel_handshake_config.announced_channel =
+            settings.announce_channels_by_default;
+        user_config.channel_handshake_config.minimum_depth = settings.channel_minimum_depth;
+        // So inbound channels can be rejected in maintenance mode.
+        user_config.manually_accept_inbound_channels = true;
+        user_config.accept_intercept_htlcs = settings.intercept_htlcs;
+        user_config.channel_config.max_dust_htlc_exposure_msat =
+            settings.max_dust_htlc_exposure_msat;
+        // TODO: negotiate anchor output channels. In this LDK version
+        // negotiate_anchors_zero_fee_htlc_tx and the BumpTransaction event only exist when LDK is
+        // built with --cfg anchors, so anchors need an LDK upgrade first. Once enabled the wallet
+        // has to keep a UTXO reserve for fee bumping at force close.
+        // TODO: offer simple taproot channels to peers that support them behind an experimental
+        // setting once LDK supports them, and regular channels to all others.
+        // TODO: let LSPs accept underpaying HTLCs with channel_config.accept_underpaying_htlcs
+        // once LDK supports it and record the skimmed fee of PaymentForwarded events in the
+        // forwarding history.
 
         let (channel_manager_blockhash, channel_manager) = {
             if is_first_start {
@@ -481,7 +1200,7 @@ impl Controller {
                     fee_estimator.clone(),
                     chain_monitor.clone(),
                     broadcaster.clone(),
-                    router,
+                    router.clone(),
                     KldLogger::global(),
                     keys_manager.clone(),
                     keys_manager.clone(),
@@ -500,7 +1219,7 @@ impl Controller {
                     fee_estimator.clone(),
                     chain_monitor.clone(),
                     broadcaster.clone(),
-                    router,
+                    router.clone(),
                     KldLogger::global(),
                     user_config,
                     channel_monitor_mut_refs,
@@ -512,6 +1231,7 @@ impl Controller {
             }
         };
         let channel_manager: Arc<ChannelManager> = Arc::new(channel_manager);
+        apply_dust_exposure_limit(&channel_manager, settings.max_dust_htlc_exposure_msat);
 
         let gossip_sync = Arc::new_cyclic(|u| {
             let utxo_lookup = Arc::new(BitcoindUtxoLookup::new(
@@ -554,24 +1274,36 @@ impl Controller {
         let peer_manager = Arc::new(PeerManager::new(
             ldk_peer_manager.clone(),
             channel_manager.clone(),
+            network_graph.clone(),
             database.clone(),
             settings.clone(),
         )?);
 
         let async_api_requests = Arc::new(AsyncAPIRequests::new());
+        let intercepted_htlcs = Arc::new(InterceptedHtlcs::default());
         // Handle LDK Events
-        // TODO: persist payment info to disk
-        let inbound_payments: PaymentInfoStorage = Arc::new(Mutex::new(HashMap::new()));
-        let outbound_payments: PaymentInfoStorage = Arc::new(Mutex::new(HashMap::new()));
+        let maintenance_mode = Arc::new(AtomicBool::new(false));
+        let sweeper = Arc::new(Sweeper::new(
+            &settings,
+            keys_manager.clone(),
+            bitcoind_client.clone(),
+            wallet.clone(),
+            database.clone(),
+        )?);
         let event_handler = EventHandler::new(
             channel_manager.clone(),
-            bitcoind_client.clone(),
-            keys_manager.clone(),
-            inbound_payments,
-            outbound_payments,
+            chain_monitor.clone(),
             network_graph.clone(),
             wallet.clone(),
+            sweeper.clone(),
+            peer_manager.clone(),
+            database.clone(),
+            webhook.clone(),
             async_api_requests.clone(),
+            intercepted_htlcs.clone(),
+            maintenance_mode.clone(),
+            settings.max_channels_per_peer,
+            settings.max_intercepted_htlc_msat,
             Handle::current(),
         );
 
@@ -584,12 +1316,34 @@ impl Controller {
             GossipSync::p2p(gossip_sync),
             ldk_peer_manager.clone(),
             KldLogger::global(),
-            Some(scorer),
+            Some(scorer.clone()),
         );
 
         let bitcoind_client_clone = bitcoind_client.clone();
+        let chain_monitor_clone = chain_monitor.clone();
         let channel_manager_clone = channel_manager.clone();
         let peer_manager_clone = peer_manager.clone();
+        let auto_fees_channel_manager = channel_manager.clone();
+        let pruner_network_graph = network_graph.clone();
+        let pruner_database = database.clone();
+        let reconcile_channel_manager = channel_manager.clone();
+        let reconcile_database = database.clone();
+        let settings_clone = settings.clone();
+        let ready = Arc::new(AtomicBool::new(false));
+        let ready_clone = ready.clone();
+        let chain_poll_interval = Duration::from_millis(settings.chain_poll_interval_ms);
+        let reorgs = Arc::new(Reorgs::default());
+        let chain_listener = ChainListener::new(
+            chain_monitor.clone(),
+            channel_manager.clone(),
+            database.clone(),
+            webhook,
+            reorgs.clone(),
+        );
+        let new_block = Arc::new(Notify::new());
+        if !settings.bitcoind_zmq_block.is_empty() {
+            notify_on_new_block(settings.bitcoind_zmq_block.clone(), new_block.clone());
+        }
         tokio::spawn(async move {
             bitcoind_client_clone
                 .wait_for_blockchain_synchronisation()
@@ -601,28 +1355,56 @@ impl Controller {
                 channel_manager_blockhash,
                 channel_manager_clone,
                 channelmonitors,
+                chain_listener,
+                chain_poll_interval,
+                new_block,
             )
             .await
             .unwrap();
+            if let Err(e) = reconcile_pending_payments(
+                &reconcile_database,
+                &reconcile_channel_manager.list_recent_payments(),
+            )
+            .await
+            {
+                error!("Failed to reconcile pending payments: {e}");
+            }
+            ready_clone.store(true, Ordering::Release);
 
             peer_manager_clone.listen().await;
+            peer_manager_clone.connect_bootstrap_peers();
+            peer_manager_clone.connect_dns_seed_peers();
             peer_manager_clone.keep_channel_peers_connected();
+            peer_manager_clone.keep_peers_pinged();
             peer_manager_clone.regularly_broadcast_node_announcement();
+            auto_fees::start(auto_fees_channel_manager, settings_clone.clone());
+            graph_pruner::start(pruner_network_graph, pruner_database, settings_clone);
+            sweeper::start(sweeper);
         });
 
         Ok(Controller {
             settings,
             database,
             bitcoind_client,
+            keys_manager,
+            master_key,
+            chain_monitor: chain_monitor_clone,
             channel_manager,
             peer_manager,
             network_graph,
+            scorer,
+            router,
             wallet,
             async_api_requests,
+            intercepted_htlcs,
+            maintenance_mode,
+            ready,
+            reorgs,
             background_processor: Arc::new(Mutex::new(Some(background_processor))),
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn sync_to_chain_tip(
         network: Network,
         bitcoind_client: Arc<BitcoindClient>,
@@ -630,6 +1412,9 @@ impl Controller {
         channel_manager_blockhash: BlockHash,
         channel_manager: Arc<ChannelManager>,
         channelmonitors: Vec<(BlockHash, ChannelMonitor<InMemorySigner>)>,
+        chain_listener: ChainListener,
+        poll_interval: Duration,
+        new_block: Arc<Notify>,
     ) -> BlockSourceResult<()> {
         // Sync ChannelMonitors and ChannelManager to chain tip
         let mut chain_listener_channel_monitors = Vec::new();
@@ -679,14 +1464,17 @@ impl Controller {
         // Connect and Disconnect Blocks
         tokio::spawn(async move {
             let chain_poller = poll::ChainPoller::new(bitcoind_client, network);
-            let chain_listener = (chain_monitor, channel_manager);
             let mut spv_client =
                 SpvClient::new(chain_tip, chain_poller, &mut cache, &chain_listener);
             loop {
                 if let Err(e) = spv_client.poll_best_tip().await {
                     error!("{}", e.into_inner())
                 }
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                // Poll right away when bitcoind announces a block, otherwise on the next tick.
+                tokio::select! {
+                    _ = tokio::time::sleep(poll_interval) => {}
+                    _ = new_block.notified() => {}
+                }
             }
         });
 
@@ -0,0 +1,77 @@
+use anyhow::{anyhow, bail, Context, Result};
+use bech32::FromBase32;
+use bitcoin::secp256k1::PublicKey;
+use lightning::{ln::msgs::NetAddress, util::ser::Hostname};
+use settings::Network;
+use trust_dns_resolver::TokioAsyncResolver;
+
+use super::net_utils::PeerAddress;
+
+/// The lightning DNS seeds (BOLT 10) of a network.
+pub fn dns_seeds(network: Network) -> &'static [&'static str] {
+    match network {
+        Network::Main => &["nodes.lightning.directory", "lseed.bitcoinstats.com"],
+        Network::Testnet => &["test.nodes.lightning.directory"],
+        Network::Signet | Network::Regtest => &[],
+    }
+}
+
+/// Look up the SRV records of a DNS seed. Each record points to a hostname starting with the
+/// bech32 encoded node id, which resolves to the address of the node.
+pub async fn query_dns_seed(seed: &str) -> Result<Vec<(PublicKey, PeerAddress)>> {
+    let resolver = TokioAsyncResolver::tokio_from_system_conf()?;
+    let records = resolver.srv_lookup(seed_service_name(seed)).await?;
+    Ok(records
+        .iter()
+        .filter_map(|srv| parse_seed_record(&srv.target().to_utf8(), srv.port()).ok())
+        .collect())
+}
+
+/// Seeds serve the SRV records for the nodes service, like other implementations query them.
+fn seed_service_name(seed: &str) -> String {
+    format!("_nodes._tcp.{seed}")
+}
+
+fn parse_seed_record(target: &str, port: u16) -> Result<(PublicKey, PeerAddress)> {
+    let hostname = target.trim_end_matches('.');
+    let label = hostname.split('.').next().context("Empty seed record")?;
+    let (hrp, data, _) = bech32::decode(label)?;
+    if hrp != "ln" {
+        bail!("Unexpected node id prefix {hrp}");
+    }
+    let public_key = PublicKey::from_slice(&Vec::<u8>::from_base32(&data)?)?;
+    let hostname = Hostname::try_from(hostname.to_string())
+        .map_err(|_| anyhow!("Invalid hostname {hostname}"))?;
+    Ok((
+        public_key,
+        PeerAddress(NetAddress::Hostname { hostname, port }),
+    ))
+}
+
+#[test]
+fn test_parse_seed_record() {
+    use bech32::{ToBase32, Variant};
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+
+    let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+    let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+    let node_id =
+        bech32::encode("ln", public_key.serialize().to_base32(), Variant::Bech32).unwrap();
+    let target = format!("{node_id}.nodes.lightning.directory.");
+
+    let (parsed_key, address) = parse_seed_record(&target, 9735).unwrap();
+    assert_eq!(parsed_key, public_key);
+    assert_eq!(
+        address.to_string(),
+        format!("{node_id}.nodes.lightning.directory:9735")
+    );
+    assert!(parse_seed_record("nodes.lightning.directory.", 9735).is_err());
+}
+
+#[test]
+fn test_seed_service_name() {
+    assert_eq!(
+        seed_service_name("nodes.lightning.directory"),
+        "_nodes._tcp.nodes.lightning.directory"
+    );
+}
@@ -1,66 +1,81 @@
-use std::collections::hash_map::Entry;
-
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use anyhow::anyhow;
 
-use bitcoin::secp256k1::Secp256k1;
-
-use crate::database::WalletDatabase;
+use crate::database::forward::Forward;
+use crate::database::payment::{Payment, PaymentDirection};
+use crate::database::{LdkDatabase, WalletDatabase};
 use hex::ToHex;
-use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
-use lightning::chain::keysinterface::KeysManager;
 use lightning::routing::gossip::NodeId;
-use lightning::util::events::{Event, PaymentPurpose};
+use lightning::util::events::{Event, HTLCDestination, PaymentPurpose};
 use log::{error, info};
 use rand::{thread_rng, Rng};
+use serde_json::json;
 use tokio::runtime::Handle;
+use tracing::{info_span, Instrument, Span};
 
 use crate::bitcoind::BitcoindClient;
 use crate::ldk::ldk_error;
-use crate::ldk::payment_info::{HTLCStatus, MillisatAmount, PaymentInfo};
-use crate::wallet::{Wallet, WalletInterface};
+use crate::ldk::payment_info::MillisatAmount;
+use crate::wallet::Wallet;
+use crate::webhook::WebhookNotifier;
 
-use super::controller::AsyncAPIRequests;
-use super::payment_info::PaymentInfoStorage;
-use super::{ChannelManager, NetworkGraph};
+use super::controller::{AsyncAPIRequests, InterceptedHtlcs};
+use super::peer_manager::PeerManager;
+use super::sweeper::Sweeper;
+use super::{ChainMonitor, ChannelManager, InterceptedHtlc, NetworkGraph};
 
 pub(crate) struct EventHandler {
     channel_manager: Arc<ChannelManager>,
-    bitcoind_client: Arc<BitcoindClient>,
-    keys_manager: Arc<KeysManager>,
-    inbound_payments: PaymentInfoStorage,
-    outbound_payments: PaymentInfoStorage,
+    chain_monitor: Arc<ChainMonitor>,
     network_graph: Arc<NetworkGraph>,
     wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+    sweeper: Arc<Sweeper>,
+    peer_manager: Arc<PeerManager>,
+    database: Arc<LdkDatabase>,
+    webhook: Arc<WebhookNotifier>,
     async_api_requests: Arc<AsyncAPIRequests>,
+    intercepted_htlcs: Arc<InterceptedHtlcs>,
+    maintenance_mode: Arc<AtomicBool>,
+    max_channels_per_peer: usize,
+    max_intercepted_htlc_msat: u64,
     runtime_handle: Handle,
 }
 
 impl EventHandler {
-    // TODO remove when payments storage is in database
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         channel_manager: Arc<ChannelManager>,
-        bitcoind_client: Arc<BitcoindClient>,
-        keys_manager: Arc<KeysManager>,
-        inbound_payments: PaymentInfoStorage,
-        outbound_payments: PaymentInfoStorage,
+        chain_monitor: Arc<ChainMonitor>,
         network_graph: Arc<NetworkGraph>,
         wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+        sweeper: Arc<Sweeper>,
+        peer_manager: Arc<PeerManager>,
+        database: Arc<LdkDatabase>,
+        webhook: Arc<WebhookNotifier>,
         async_api_requests: Arc<AsyncAPIRequests>,
+        intercepted_htlcs: Arc<InterceptedHtlcs>,
+        maintenance_mode: Arc<AtomicBool>,
+        max_channels_per_peer: usize,
+        max_intercepted_htlc_msat: u64,
         runtime_handle: Handle,
     ) -> EventHandler {
         EventHandler {
             channel_manager,
-            bitcoind_client,
-            keys_manager,
-            inbound_payments,
-            outbound_payments,
+            chain_monitor,
             network_graph,
             wallet,
+            sweeper,
+            peer_manager,
+            database,
+            webhook,
             async_api_requests,
+            intercepted_htlcs,
+            maintenance_mode,
+            max_channels_per_peer,
+            max_intercepted_htlc_msat,
             runtime_handle,
         }
     }
@@ -68,12 +83,31 @@ impl EventHandler {
 
 impl lightning::util::events::EventHandler for EventHandler {
     fn handle_event(&self, event: lightning::util::events::Event) {
+        let span = event_span(&event);
         tokio::task::block_in_place(move || {
-            self.runtime_handle.block_on(self.handle_event_async(event))
+            self.runtime_handle
+                .block_on(self.handle_event_async(event).instrument(span))
         })
     }
 }
 
+/// Events which complete an API request are handled in a span named like the span of the request
+/// in the controller, with the same id field.
+fn event_span(event: &Event) -> Span {
+    match event {
+        Event::FundingGenerationReady {
+            user_channel_id, ..
+        } => info_span!("open_channel", user_channel_id = %user_channel_id),
+        Event::PaymentSent { payment_hash, .. } | Event::PaymentFailed { payment_hash, .. } => {
+            info_span!(
+                "pay_invoice",
+                payment_hash = %payment_hash.0.encode_hex::<String>()
+            )
+        }
+        _ => Span::none(),
+    }
+}
+
 impl EventHandler {
     pub async fn handle_event_async(&self, event: lightning::util::events::Event) {
         match event {
@@ -92,6 +126,16 @@ impl EventHandler {
                 {
                     Some(fee_rate) => fee_rate,
                     None => {
+                        if let Some(((), respond)) = self
+                            .async_api_requests
+                            .funding_psbts
+                            .get(&user_channel_id)
+                            .await
+                        {
+                            info!("EVENT: Channel with user channel id {user_channel_id} waits for external funding");
+                            respond(Ok(output_script));
+                            return;
+                        }
                         error!(
                             "Can't find funding transaction for user_channel_id {user_channel_id}"
                         );
@@ -139,6 +183,25 @@ impl EventHandler {
                     channel_id.encode_hex::<String>(),
                     counterparty_node_id
                 );
+                self.webhook.notify(
+                    "channel_ready",
+                    json!({
+                        "channel_id": channel_id.encode_hex::<String>(),
+                        "counterparty_node_id": counterparty_node_id.to_string(),
+                    }),
+                );
+                // Don't make the network wait for the next regular broadcast to learn about our
+                // node once the first public channel is ready.
+                let public_channels: Vec<_> = self
+                    .channel_manager
+                    .list_channels()
+                    .into_iter()
+                    .filter(|c| c.is_public && c.is_channel_ready)
+                    .collect();
+                if public_channels.len() == 1 && public_channels[0].channel_id == channel_id {
+                    info!("EVENT: Announcing node with its first public channel");
+                    self.peer_manager.broadcast_node_announcement();
+                }
             }
             Event::ChannelClosed {
                 channel_id,
@@ -149,6 +212,13 @@ impl EventHandler {
                     "EVENT: Channel {}: {reason}.",
                     channel_id.encode_hex::<String>()
                 );
+                self.webhook.notify(
+                    "channel_closed",
+                    json!({
+                        "channel_id": channel_id.encode_hex::<String>(),
+                        "reason": reason.to_string(),
+                    }),
+                );
                 self.async_api_requests
                     .funding_transactions
                     .respond(
@@ -156,6 +226,41 @@ impl EventHandler {
                         Err(anyhow!("Channel closed due to {reason}")),
                     )
                     .await;
+                self.async_api_requests
+                    .funding_psbts
+                    .respond(
+                        &user_channel_id,
+                        Err(anyhow!("Channel closed due to {reason}")),
+                    )
+                    .await;
+                // The monitor outlives the channel, channels which were never funded have none.
+                let (funding_txo, counterparty_node_id) = match self
+                    .chain_monitor
+                    .list_monitors()
+                    .into_iter()
+                    .find(|outpoint| outpoint.to_channel_id() == channel_id)
+                {
+                    Some(outpoint) => (
+                        Some(outpoint.into_bitcoin_outpoint()),
+                        self.chain_monitor
+                            .get_monitor(outpoint)
+                            .ok()
+                            .and_then(|monitor| monitor.get_counterparty_node_id()),
+                    ),
+                    None => (None, None),
+                };
+                if let Err(e) = self
+                    .database
+                    .persist_channel_closed(
+                        &channel_id,
+                        counterparty_node_id,
+                        funding_txo,
+                        &reason.to_string(),
+                    )
+                    .await
+                {
+                    error!("Failed to persist closed channel: {e}");
+                }
             }
             Event::DiscardFunding {
                 channel_id,
@@ -167,9 +272,50 @@ impl EventHandler {
                     transaction.txid()
                 )
             }
-            Event::OpenChannelRequest { .. } => {
-                // Unreachable, we don't set manually_accept_inbound_channels
+            Event::OpenChannelRequest {
+                temporary_channel_id,
+                counterparty_node_id,
+                funding_satoshis,
+                ..
+            } => {
+                // The requested channel is already listed while it waits for us to accept it.
+                let num_channels = self
+                    .channel_manager
+                    .list_channels()
+                    .iter()
+                    .filter(|c| {
+                        c.counterparty.node_id == counterparty_node_id
+                            && c.channel_id != temporary_channel_id
+                    })
+                    .count();
+                let result = if self.maintenance_mode.load(Ordering::Acquire) {
+                    info!("EVENT: Rejecting inbound channel from {counterparty_node_id} in maintenance mode");
+                    self.channel_manager.force_close_without_broadcasting_txn(
+                        &temporary_channel_id,
+                        &counterparty_node_id,
+                    )
+                } else if num_channels >= self.max_channels_per_peer {
+                    info!("EVENT: Rejecting inbound channel from {counterparty_node_id} which already has {num_channels} channels with us");
+                    self.channel_manager.force_close_without_broadcasting_txn(
+                        &temporary_channel_id,
+                        &counterparty_node_id,
+                    )
+                } else {
+                    info!("EVENT: Accepting inbound channel of {funding_satoshis} sats from {counterparty_node_id}");
+                    let user_channel_id: u128 = thread_rng().gen();
+                    self.channel_manager.accept_inbound_channel(
+                        &temporary_channel_id,
+                        &counterparty_node_id,
+                        user_channel_id,
+                    )
+                };
+                if let Err(e) = result {
+                    error!("Event::OpenChannelRequest: {}", ldk_error(e));
+                }
             }
+            // TODO: surface custom TLV records of keysend payments once we are on LDK 0.0.116+.
+            // This version neither exposes received custom TLVs here nor lets us attach them
+            // to outbound spontaneous payments (RecipientOnionFields::custom_tlvs).
             Event::PaymentClaimable {
                 payment_hash,
                 purpose,
@@ -204,30 +350,35 @@ impl EventHandler {
                     payment_hash.0.encode_hex::<String>(),
                     amount_msat,
                 );
-                let (payment_preimage, payment_secret) = match purpose {
+                self.webhook.notify(
+                    "payment_received",
+                    json!({
+                        "payment_hash": payment_hash.0.encode_hex::<String>(),
+                        "amount_msat": amount_msat,
+                    }),
+                );
+                let payment_preimage = match purpose {
                     PaymentPurpose::InvoicePayment {
-                        payment_preimage,
-                        payment_secret,
-                        ..
-                    } => (payment_preimage, Some(payment_secret)),
-                    PaymentPurpose::SpontaneousPayment(preimage) => (Some(preimage), None),
+                        payment_preimage, ..
+                    } => payment_preimage,
+                    PaymentPurpose::SpontaneousPayment(preimage) => Some(preimage),
                 };
-                let mut payments = self.inbound_payments.lock().unwrap();
-                match payments.entry(payment_hash) {
-                    Entry::Occupied(mut e) => {
-                        let payment = e.get_mut();
-                        payment.status = HTLCStatus::Succeeded;
-                        payment.preimage = payment_preimage;
-                        payment.secret = payment_secret;
+                let mut payment = match self
+                    .database
+                    .fetch_payment(&payment_hash, PaymentDirection::Inbound)
+                    .await
+                {
+                    Ok(payment) => {
+                        payment.unwrap_or_else(|| Payment::new_inbound(payment_hash, None))
                     }
-                    Entry::Vacant(e) => {
-                        e.insert(PaymentInfo {
-                            preimage: payment_preimage,
-                            secret: payment_secret,
-                            status: HTLCStatus::Succeeded,
-                            amt_msat: MillisatAmount(Some(amount_msat)),
-                        });
+                    Err(e) => {
+                        error!("Failed to fetch payment: {e}");
+                        return;
                     }
+                };
+                payment.received(payment_preimage, amount_msat);
+                if let Err(e) = self.database.persist_payment(&payment).await {
+                    error!("Failed to persist payment: {e}");
                 }
             }
             Event::PaymentSent {
@@ -236,23 +387,46 @@ impl EventHandler {
                 fee_paid_msat,
                 ..
             } => {
-                let mut payments = self.outbound_payments.lock().unwrap();
-                if let Some(payment) = payments.get_mut(&payment_hash) {
-                    payment.preimage = Some(payment_preimage);
-                    payment.status = HTLCStatus::Succeeded;
-                    info!(
-                        "EVENT: successfully sent payment of {} millisatoshis{} from \
-								 payment hash {} with preimage {}",
-                        payment.amt_msat,
-                        if let Some(fee) = fee_paid_msat {
-                            format!(" (fee {fee} msat)")
-                        } else {
-                            "".to_string()
-                        },
-                        payment_hash.0.encode_hex::<String>(),
-                        payment_preimage.0.encode_hex::<String>()
-                    );
+                self.webhook.notify(
+                    "payment_sent",
+                    json!({
+                        "payment_hash": payment_hash.0.encode_hex::<String>(),
+                        "fee_paid_msat": fee_paid_msat,
+                    }),
+                );
+                self.async_api_requests
+                    .payments
+                    .respond(&payment_hash, Ok((payment_preimage, fee_paid_msat)))
+                    .await;
+                let mut payment = match self
+                    .database
+                    .fetch_payment(&payment_hash, PaymentDirection::Outbound)
+                    .await
+                {
+                    Ok(payment) => {
+                        payment.unwrap_or_else(|| Payment::new_outbound(payment_hash, None))
+                    }
+                    Err(e) => {
+                        error!("Failed to fetch payment: {e}");
+                        return;
+                    }
+                };
+                payment.succeeded(payment_preimage, fee_paid_msat);
+                if let Err(e) = self.database.persist_payment(&payment).await {
+                    error!("Failed to persist payment: {e}");
                 }
+                info!(
+                    "EVENT: successfully sent payment of {} millisatoshis{} from \
+								 payment hash {} with preimage {}",
+                    MillisatAmount(payment.amount_msat),
+                    if let Some(fee) = fee_paid_msat {
+                        format!(" (fee {fee} msat)")
+                    } else {
+                        "".to_string()
+                    },
+                    payment_hash.0.encode_hex::<String>(),
+                    payment_preimage.0.encode_hex::<String>()
+                );
             }
             Event::PaymentPathSuccessful { .. } => {}
             Event::PaymentPathFailed { .. } => {}
@@ -264,9 +438,27 @@ impl EventHandler {
 				payment_hash.0.encode_hex::<String>()
 			);
 
-                let mut payments = self.outbound_payments.lock().unwrap();
-                if let Some(payment) = payments.get_mut(&payment_hash) {
-                    payment.status = HTLCStatus::Failed;
+                self.async_api_requests
+                    .payments
+                    .respond(
+                        &payment_hash,
+                        Err(anyhow!("Payment failed after exhausting retry attempts")),
+                    )
+                    .await;
+                match self
+                    .database
+                    .fetch_payment(&payment_hash, PaymentDirection::Outbound)
+                    .await
+                {
+                    Ok(Some(mut payment)) => {
+                        payment
+                            .failed("Payment failed after exhausting retry attempts".to_string());
+                        if let Err(e) = self.database.persist_payment(&payment).await {
+                            error!("Failed to persist payment: {e}");
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => error!("Failed to fetch payment: {e}"),
                 }
             }
             Event::PaymentForwarded {
@@ -331,6 +523,29 @@ impl EventHandler {
                         from_prev_str, to_next_str, from_onchain_str
                     );
                 }
+                for channel_id in [prev_channel_id, next_channel_id].iter().flatten() {
+                    self.record_channel_forward(channel_id, true).await;
+                }
+                let scid = |channel_id: Option<[u8; 32]>| {
+                    channels
+                        .iter()
+                        .find(|c| Some(c.channel_id) == channel_id)
+                        .and_then(|c| c.short_channel_id)
+                };
+                // LDK doesn't report the forwarded amounts in this version.
+                let forward = Forward {
+                    in_channel_id: prev_channel_id,
+                    out_channel_id: next_channel_id,
+                    in_scid: scid(prev_channel_id),
+                    out_scid: scid(next_channel_id),
+                    amount_in_msat: None,
+                    amount_out_msat: None,
+                    fee_msat: fee_earned_msat,
+                    timestamp: SystemTime::now(),
+                };
+                if let Err(e) = self.database.persist_forward(&forward).await {
+                    error!("Failed to persist forward: {e}");
+                }
             }
             Event::HTLCHandlingFailed {
                 prev_channel_id,
@@ -341,6 +556,33 @@ impl EventHandler {
                     prev_channel_id.encode_hex::<String>(),
                     failed_next_destination
                 );
+                // LDK fails intercepted HTLCs which are held until close to their expiry, but the
+                // event doesn't say which. Parts of a payment to the same fake short channel id
+                // expire together so forget all of them.
+                if let HTLCDestination::InvalidForward {
+                    requested_forward_scid,
+                } = failed_next_destination
+                {
+                    self.intercepted_htlcs
+                        .lock()
+                        .unwrap()
+                        .retain(|_, htlc| htlc.requested_next_hop_scid != requested_forward_scid);
+                }
+                // Failures of payments to us are not forwarding activity.
+                if matches!(
+                    failed_next_destination,
+                    HTLCDestination::FailedPayment { .. }
+                ) {
+                    return;
+                }
+                self.record_channel_forward(&prev_channel_id, false).await;
+                if let HTLCDestination::NextHopChannel {
+                    channel_id: Some(next_channel_id),
+                    ..
+                } = failed_next_destination
+                {
+                    self.record_channel_forward(&next_channel_id, false).await;
+                }
             }
             Event::PendingHTLCsForwardable { time_forwardable } => {
                 let forwarding_channel_manager = self.channel_manager.clone();
@@ -352,43 +594,73 @@ impl EventHandler {
                 });
             }
             Event::SpendableOutputs { outputs } => {
-                let destination_address = match self.wallet.new_address() {
-                    Ok(a) => a,
-                    Err(e) => {
-                        error!("Could not get new address: {}", e);
-                        return;
-                    }
-                };
-                let output_descriptors = &outputs.iter().collect::<Vec<_>>();
-                let tx_feerate = self
-                    .bitcoind_client
-                    .get_est_sat_per_1000_weight(ConfirmationTarget::Normal);
-                match self.keys_manager.spend_spendable_outputs(
-                    output_descriptors,
-                    Vec::new(),
-                    destination_address.script_pubkey(),
-                    tx_feerate,
-                    &Secp256k1::new(),
-                ) {
-                    Ok(spending_tx) => {
-                        info!(
-                            "EVENT: Sending spendable output to {}",
-                            destination_address.address
-                        );
-                        self.bitcoind_client.broadcast_transaction(&spending_tx)
-                    }
-                    Err(_) => {
-                        error!("Failed to build spending transaction");
-                    }
-                };
+                self.sweeper.add_outputs(outputs).await;
             }
+            // TODO: CPFP anchor commitment transactions with wallet UTXOs on
+            // Event::BumpTransaction. The event only exists in LDK builds with --cfg anchors and
+            // there is no BumpTransactionEventHandler before LDK 0.0.116, so this has to wait for
+            // the upgrade that enables anchor channels.
             Event::HTLCIntercepted {
-                intercept_id: _,
-                requested_next_hop_scid: _,
-                payment_hash: _,
-                inbound_amount_msat: _,
-                expected_outbound_amount_msat: _,
-            } => {}
+                intercept_id,
+                requested_next_hop_scid,
+                payment_hash,
+                inbound_amount_msat,
+                expected_outbound_amount_msat,
+            } => {
+                info!(
+                    "EVENT: Intercepted HTLC {} for short channel id {requested_next_hop_scid}",
+                    intercept_id.0.encode_hex::<String>()
+                );
+                if self.max_intercepted_htlc_msat > 0
+                    && inbound_amount_msat > self.max_intercepted_htlc_msat
+                {
+                    info!(
+                        "Failing HTLC {} of {inbound_amount_msat} msat, above the maximum of {} msat",
+                        intercept_id.0.encode_hex::<String>(),
+                        self.max_intercepted_htlc_msat
+                    );
+                    // LDK fails it back with unknown_next_peer, it doesn't take an onion error.
+                    if let Err(e) = self.channel_manager.fail_intercepted_htlc(intercept_id) {
+                        error!("Event::HTLCIntercepted: {}", ldk_error(e));
+                    }
+                    return;
+                }
+                self.intercepted_htlcs.lock().unwrap().insert(
+                    intercept_id.0,
+                    InterceptedHtlc {
+                        intercept_id: intercept_id.0,
+                        requested_next_hop_scid,
+                        payment_hash,
+                        inbound_amount_msat,
+                        expected_outbound_amount_msat,
+                        intercepted_at: SystemTime::now(),
+                    },
+                );
+                // An external policy decides whether to forward or fail it through the API.
+                self.webhook.notify(
+                    "htlc_intercepted",
+                    json!({
+                        "intercept_id": intercept_id.0.encode_hex::<String>(),
+                        "requested_next_hop_scid": requested_next_hop_scid,
+                        "payment_hash": payment_hash.0.encode_hex::<String>(),
+                        "inbound_amount_msat": inbound_amount_msat,
+                        "expected_outbound_amount_msat": expected_outbound_amount_msat,
+                    }),
+                );
+            }
+        }
+    }
+
+    async fn record_channel_forward(&self, channel_id: &[u8; 32], succeeded: bool) {
+        if let Err(e) = self
+            .database
+            .record_channel_forward(channel_id, succeeded)
+            .await
+        {
+            error!(
+                "Failed to record forward for channel {}: {e}",
+                channel_id.encode_hex::<String>()
+            );
         }
     }
 }
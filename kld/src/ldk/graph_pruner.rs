@@ -0,0 +1,56 @@
+//! Remove channels from the network graph which haven't been updated for two weeks, and the nodes
+//! left without channels, so the persisted graph doesn't grow forever on long running nodes.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use log::{error, info};
+use settings::Settings;
+
+use crate::database::LdkDatabase;
+
+use super::NetworkGraph;
+
+/// Prune the graph periodically until the process exits. Does nothing if the interval is 0.
+pub(crate) fn start(
+    network_graph: Arc<NetworkGraph>,
+    database: Arc<LdkDatabase>,
+    settings: Arc<Settings>,
+) {
+    if settings.network_graph_prune_interval_secs == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            settings.network_graph_prune_interval_secs,
+        ));
+        loop {
+            interval.tick().await;
+            if let Err(e) = prune(&network_graph, &database) {
+                error!("Failed to prune network graph: {e}");
+            }
+        }
+    });
+}
+
+/// Remove stale channels and persist the pruned graph. Returns the number of removed channels
+/// and nodes.
+pub(crate) fn prune(
+    network_graph: &NetworkGraph,
+    database: &LdkDatabase,
+) -> Result<(usize, usize)> {
+    let (channels, nodes) = graph_size(network_graph);
+    network_graph.remove_stale_channels_and_tracking();
+    let (remaining_channels, remaining_nodes) = graph_size(network_graph);
+    database.persist_network_graph(network_graph)?;
+    // Gossip may add channels while pruning so the graph could have grown in the meantime.
+    let removed_channels = channels.saturating_sub(remaining_channels);
+    let removed_nodes = nodes.saturating_sub(remaining_nodes);
+    info!("Pruned {removed_channels} channels and {removed_nodes} nodes from the network graph");
+    Ok((removed_channels, removed_nodes))
+}
+
+fn graph_size(network_graph: &NetworkGraph) -> (usize, usize) {
+    let graph = network_graph.read_only();
+    (graph.channels().len(), graph.nodes().len())
+}
@@ -1,12 +1,29 @@
 use anyhow::Result;
 use api::FeeRate;
 use async_trait::async_trait;
-use bitcoin::{secp256k1::PublicKey, Network, Transaction, Txid};
+use bitcoin::{
+    secp256k1::PublicKey,
+    util::{bip32::DerivationPath, psbt::PartiallySignedTransaction},
+    Address, Network, Transaction, Txid,
+};
+use futures::stream::BoxStream;
 use lightning::{
-    ln::{channelmanager::ChannelDetails, msgs::NetAddress},
+    chain::transaction::OutPoint,
+    ln::{channelmanager::ChannelDetails, msgs::NetAddress, PaymentHash, PaymentPreimage},
     routing::gossip::{ChannelInfo, NodeId, NodeInfo},
     util::{config::UserConfig, indexed_map::IndexedMap},
 };
+use lightning_invoice::Invoice;
+
+use std::time::SystemTime;
+
+use crate::database::{
+    channel_stats::ChannelStats,
+    closed_channel::ClosedChannel,
+    forward::{ChannelEarnings, EarningsBucket, Forward},
+    payment::{Payment, PaymentDirection, PaymentStatus},
+    spendable_output::SpendableOutput,
+};
 
 use super::net_utils::PeerAddress;
 
@@ -14,6 +31,16 @@ use super::net_utils::PeerAddress;
 pub trait LightningInterface {
     fn alias(&self) -> String;
 
+    /// Color of the node in the node announcement.
+    fn color(&self) -> [u8; 3];
+
+    /// Change alias and color (if given) until the next restart and announce them to the network.
+    fn set_alias(&self, alias: String, color: Option<[u8; 3]>) -> Result<()>;
+
+    /// Broadcast the node announcement now. Returns false if there is no public channel to
+    /// announce the node with.
+    fn announce_node(&self) -> bool;
+
     async fn block_height(&self) -> Result<u64>;
 
     fn identity_pubkey(&self) -> PublicKey;
@@ -38,6 +65,11 @@ pub trait LightningInterface {
 
     fn list_channels(&self) -> Vec<ChannelDetails>;
 
+    async fn list_closed_channels(&self) -> Result<Vec<ClosedChannel>>;
+
+    /// Outputs of closed channels which have not been swept into the wallet yet.
+    async fn pending_sweeps(&self) -> Result<Vec<SpendableOutput>>;
+
     fn set_channel_fee(
         &self,
         counterparty_node_id: &PublicKey,
@@ -46,6 +78,16 @@ pub trait LightningInterface {
         forwarding_fee_base_msat: Option<u32>,
     ) -> Result<(u32, u32)>;
 
+    /// Forwarding counters for every channel that has routed an HTLC.
+    async fn channel_stats(&self) -> Result<Vec<ChannelStats>>;
+
+    /// Fees earned by forwarding through the channel, summed per time bucket.
+    async fn channel_earnings(
+        &self,
+        short_channel_id: u64,
+        bucket: EarningsBucket,
+    ) -> Result<Vec<ChannelEarnings>>;
+
     fn alias_of(&self, node_id: &PublicKey) -> Option<String>;
 
     fn public_addresses(&self) -> Vec<String>;
@@ -58,8 +100,12 @@ pub trait LightningInterface {
         socket_addr: Option<PeerAddress>,
     ) -> Result<()>;
 
+    /// Close the connection. The peer is reconnected if we have a channel with it.
     async fn disconnect_peer(&self, public_key: PublicKey) -> Result<()>;
 
+    /// Delete the stored addresses of the peer without disconnecting.
+    async fn forget_peer(&self, public_key: PublicKey) -> Result<()>;
+
     async fn open_channel(
         &self,
         their_network_key: PublicKey,
@@ -69,12 +115,60 @@ pub trait LightningInterface {
         override_config: Option<UserConfig>,
     ) -> Result<OpenChannelResult>;
 
+    /// Start opening a channel which is funded by an external wallet. Returns once the funding
+    /// output has been negotiated with the peer.
+    async fn open_channel_psbt(
+        &self,
+        their_network_key: PublicKey,
+        channel_value_satoshis: u64,
+        push_msat: Option<u64>,
+        override_config: Option<UserConfig>,
+    ) -> Result<OpenChannelPsbtResult>;
+
+    /// Finish a channel open started with open_channel_psbt. The PSBT must be finalized and pay
+    /// the funding output. LDK broadcasts the transaction once the peer has signed.
+    async fn fund_channel_psbt(
+        &self,
+        channel_id: &[u8; 32],
+        psbt: PartiallySignedTransaction,
+    ) -> Result<OpenChannelResult>;
+
     async fn close_channel(
         &self,
         channel_id: &[u8; 32],
         counterparty_node_id: &PublicKey,
     ) -> Result<()>;
 
+    /// Close a channel without broadcasting our commitment transaction. Only safe if the funding
+    /// transaction never confirmed.
+    async fn abandon_channel(
+        &self,
+        channel_id: &[u8; 32],
+        counterparty_node_id: &PublicKey,
+    ) -> Result<()>;
+
+    /// A new fake short channel id. HTLCs for it raise an htlc_intercepted event if interception
+    /// is enabled.
+    fn intercept_scid(&self) -> u64;
+
+    /// Forward an intercepted HTLC to the counterparty of the channel.
+    fn forward_intercepted_htlc(
+        &self,
+        intercept_id: [u8; 32],
+        channel_id: &[u8; 32],
+        counterparty_node_id: PublicKey,
+        amount_msat: u64,
+    ) -> Result<()>;
+
+    /// Fail an intercepted HTLC back to the previous hop.
+    fn fail_intercepted_htlc(&self, intercept_id: [u8; 32]) -> Result<()>;
+
+    /// Intercepted HTLCs which have not been forwarded or failed yet, oldest first.
+    fn list_intercepted_htlcs(&self) -> Vec<InterceptedHtlc>;
+
+    /// HTLCs in the latest commitment transactions of all channels, closest to expiry first.
+    fn list_pending_htlcs(&self) -> Vec<PendingHtlc>;
+
     fn get_node(&self, node_id: &NodeId) -> Option<NodeInfo>;
 
     fn nodes(&self) -> IndexedMap<NodeId, NodeInfo>;
@@ -84,6 +178,80 @@ pub trait LightningInterface {
     fn channels(&self) -> IndexedMap<u64, ChannelInfo>;
 
     fn user_config(&self) -> UserConfig;
+
+    /// In maintenance mode new channels are rejected while existing channels keep routing.
+    fn maintenance_mode(&self) -> bool;
+
+    fn set_maintenance_mode(&self, enabled: bool);
+
+    /// True once LDK has caught up with the chain after startup. The API rejects requests that
+    /// change state until then. It is never reset, so opening and closing channels still checks
+    /// that bitcoind is synchronised.
+    fn ready(&self) -> bool;
+
+    /// Number of chain reorgs seen since startup.
+    fn num_reorgs(&self) -> u64;
+
+    /// Number of blocks replaced by the last reorg.
+    fn last_reorg_depth(&self) -> u32;
+
+    /// Number of channel monitors which were skipped when loading because they were corrupt,
+    /// shown in /health. Only monitors of closed channels can be skipped: a corrupt monitor of an
+    /// open channel still stops the node from starting when the channel manager is read.
+    fn corrupt_channel_monitors(&self) -> usize;
+
+    /// Sign the message with the node key in the zbase32 format used by LND and CLN.
+    fn sign_message(&self, message: &[u8]) -> Result<String>;
+
+    /// Derive a public key from the seed of the node. The private key never leaves the node.
+    fn derive_public_key(&self, path: &DerivationPath) -> Result<PublicKey>;
+
+    /// Replace the current scorer with a new one and persist it.
+    async fn reset_scorer(&self) -> Result<()>;
+
+    /// Remove stale channels from the network graph and persist it. Returns the number of removed
+    /// channels and nodes.
+    async fn prune_network_graph(&self) -> Result<(usize, usize)>;
+
+    /// Read all channel monitors from the database and check that they deserialize and are at
+    /// the same update as the monitors in memory.
+    async fn check_channel_monitors(&self) -> Result<MonitorCheck>;
+
+    /// Persisted channel monitors, network graph and peers in their serialized form.
+    async fn export_state(&self) -> Result<NodeState>;
+
+    /// Create an invoice for receiving a payment. When fallback_address is set a new address
+    /// from the on-chain wallet is added for payers that can't find a route.
+    async fn generate_invoice(
+        &self,
+        amount_msat: Option<u64>,
+        description: String,
+        expiry_secs: Option<u32>,
+        fallback_address: bool,
+    ) -> Result<Invoice>;
+
+    /// Pay the invoice and wait until the payment has either succeeded or failed. Fails without
+    /// sending anything if the cheapest route costs more than max_fee_msat in fees.
+    async fn pay_invoice(&self, invoice: Invoice, max_fee_msat: u64) -> Result<PaymentResult>;
+
+    /// Look up a payment sent by this node.
+    async fn fetch_outbound_payment(&self, payment_hash: PaymentHash) -> Result<Option<Payment>>;
+
+    /// List payments, optionally filtered by status and settlement time (since inclusive, until exclusive).
+    async fn list_payments(
+        &self,
+        direction: PaymentDirection,
+        status: Option<PaymentStatus>,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> Result<Vec<Payment>>;
+
+    /// Stream the forwarding history in chronological order (since inclusive, until exclusive).
+    async fn forwards(
+        &self,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> Result<BoxStream<'static, Result<Forward>>>;
 }
 
 pub struct Peer {
@@ -115,8 +283,65 @@ impl ToString for PeerStatus {
     }
 }
 
+pub struct PaymentResult {
+    pub payment_hash: PaymentHash,
+    pub preimage: PaymentPreimage,
+    pub amount_msat: Option<u64>,
+    pub fee_paid_msat: Option<u64>,
+}
+
 pub struct OpenChannelResult {
     pub transaction: Transaction,
     pub txid: Txid,
     pub channel_id: [u8; 32],
 }
+
+/// Result of comparing the persisted channel monitors with the ones the chain monitor uses.
+pub struct MonitorCheck {
+    pub checked: usize,
+    pub corrupt: usize,
+    pub discrepancies: Vec<MonitorDiscrepancy>,
+}
+
+/// A channel monitor whose persisted state doesn't match the chain monitor. An update id of None
+/// means the monitor is missing on that side.
+pub struct MonitorDiscrepancy {
+    pub funding_txo: OutPoint,
+    pub persisted_update_id: Option<u64>,
+    pub in_memory_update_id: Option<u64>,
+}
+
+/// An HTLC held by interception until it is forwarded or failed.
+#[derive(Clone)]
+pub struct InterceptedHtlc {
+    pub intercept_id: [u8; 32],
+    pub requested_next_hop_scid: u64,
+    pub payment_hash: PaymentHash,
+    pub inbound_amount_msat: u64,
+    pub expected_outbound_amount_msat: u64,
+    pub intercepted_at: SystemTime,
+}
+
+/// An HTLC which hasn't been resolved yet, as reported by the channel monitor. LDK 0.0.114 only
+/// reports the amount in satoshis and not the payment hash. Dust HTLCs and inbound HTLCs which
+/// we are claiming already are left out.
+pub struct PendingHtlc {
+    pub channel_id: [u8; 32],
+    pub direction: PaymentDirection,
+    pub amount_sat: u64,
+    pub cltv_expiry: u32,
+}
+
+/// Full state of the node for migration tooling. Never import it into another node with the
+/// same keys while this one is running.
+pub struct NodeState {
+    pub channel_monitors: Vec<Vec<u8>>,
+    pub network_graph: Option<Vec<u8>>,
+    pub peers: Vec<(PublicKey, NetAddress)>,
+}
+
+pub struct OpenChannelPsbtResult {
+    pub channel_id: [u8; 32],
+    pub address: Address,
+    pub psbt: PartiallySignedTransaction,
+}
@@ -1,34 +1,63 @@
+mod auto_fees;
+mod chain_listener;
 pub mod channel_utils;
 pub mod controller;
+mod dns_seed;
 mod event_handler;
+mod graph_pruner;
 pub mod lightning_interface;
 pub mod net_utils;
 mod payment_info;
 mod peer_manager;
+pub mod remote_signer;
+mod sweeper;
 
 use std::sync::Arc;
 
+use anyhow::{bail, Result};
+
 use crate::database::LdkDatabase;
 use crate::logger::KldLogger;
 use lightning::{
     chain::{chainmonitor, keysinterface::InMemorySigner, Filter},
     ln::{channelmanager::SimpleArcChannelManager, peer_handler::SimpleArcPeerManager},
     onion_message::SimpleArcOnionMessenger,
-    routing::gossip,
+    routing::{gossip, router::DefaultRouter, scoring::ProbabilisticScorer},
     util::errors::APIError,
 };
 use lightning_net_tokio::SocketDescriptor;
 
 pub use controller::Controller;
-pub use lightning_interface::{LightningInterface, OpenChannelResult, Peer, PeerStatus};
+pub use lightning_interface::{
+    InterceptedHtlc, LightningInterface, MonitorCheck, MonitorDiscrepancy, NodeState,
+    OpenChannelPsbtResult, OpenChannelResult, PaymentResult, Peer, PeerStatus, PendingHtlc,
+};
 
 use crate::bitcoind::{BitcoindClient, BitcoindUtxoLookup};
 
 /// The minimum feerate we are allowed to send, as specify by LDK (sats/kwu).
 pub static MIN_FEERATE: u32 = 253;
 
+/// Expiry of invoices if the caller doesn't specify one.
+pub static DEFAULT_INVOICE_EXPIRY_SECS: u32 = 3600;
+
+/// Largest channel minimum depth we allow. Matches the default max_minimum_depth LDK peers accept.
+pub static MAX_CHANNEL_MINIMUM_DEPTH: u32 = 144;
+
+pub fn check_channel_minimum_depth(minimum_depth: u32) -> Result<()> {
+    if minimum_depth == 0 || minimum_depth > MAX_CHANNEL_MINIMUM_DEPTH {
+        bail!("Channel minimum depth must be between 1 and {MAX_CHANNEL_MINIMUM_DEPTH}");
+    }
+    Ok(())
+}
+
 pub type NetworkGraph = gossip::NetworkGraph<Arc<KldLogger>>;
 
+pub(crate) type Scorer = ProbabilisticScorer<Arc<NetworkGraph>, Arc<KldLogger>>;
+
+pub(crate) type KldRouter =
+    DefaultRouter<Arc<NetworkGraph>, Arc<KldLogger>, Arc<std::sync::Mutex<Scorer>>>;
+
 pub(crate) type LdkPeerManager = SimpleArcPeerManager<
     SocketDescriptor,
     ChainMonitor,
@@ -70,3 +99,11 @@ pub fn ldk_error(error: APIError) -> anyhow::Error {
         }
     })
 }
+
+#[test]
+fn test_check_channel_minimum_depth() {
+    assert!(check_channel_minimum_depth(0).is_err());
+    assert!(check_channel_minimum_depth(1).is_ok());
+    assert!(check_channel_minimum_depth(MAX_CHANNEL_MINIMUM_DEPTH).is_ok());
+    assert!(check_channel_minimum_depth(MAX_CHANNEL_MINIMUM_DEPTH + 1).is_err());
+}
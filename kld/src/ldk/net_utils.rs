@@ -1,12 +1,21 @@
 use std::{
+    collections::HashMap,
     fmt::Display,
     net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
     str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
 };
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use hex::ToHex;
-use lightning::ln::msgs::NetAddress;
+use lightning::{ln::msgs::NetAddress, util::ser::Hostname};
+use once_cell::sync::Lazy;
+
+const DNS_CACHE_TTL: Duration = Duration::from_secs(60);
+
+static DNS_CACHE: Lazy<Mutex<HashMap<String, (Instant, Vec<SocketAddr>)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct PeerAddress(pub NetAddress);
@@ -29,27 +38,79 @@ impl Display for PeerAddress {
     }
 }
 
+impl PeerAddress {
+    /// The socket addresses to try when connecting. Hostnames are resolved with DNS and the
+    /// result is cached for a minute. There is no proxy support so lookups go to the system
+    /// resolver directly.
+    pub async fn resolve(&self) -> anyhow::Result<Vec<SocketAddr>> {
+        let (hostname, port) = match &self.0 {
+            NetAddress::Hostname { hostname, port } => (hostname.as_str(), *port),
+            _ => return Ok(vec![SocketAddr::try_from(self.clone())?]),
+        };
+        let key = format!("{hostname}:{port}");
+        if let Some((resolved_at, addresses)) = DNS_CACHE.lock().unwrap().get(&key) {
+            if resolved_at.elapsed() < DNS_CACHE_TTL {
+                return Ok(addresses.clone());
+            }
+        }
+        let addresses: Vec<SocketAddr> = tokio::net::lookup_host((hostname, port))
+            .await
+            .with_context(|| format!("Could not resolve hostname {hostname}"))?
+            .collect();
+        if addresses.is_empty() {
+            return Err(anyhow!(
+                "Hostname {hostname} did not resolve to any address"
+            ));
+        }
+        DNS_CACHE
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), addresses.clone()));
+        Ok(addresses)
+    }
+}
+
 impl FromStr for PeerAddress {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        if let Some((ip, port)) = s.rsplit_once(':') {
-            if let Ok(ipv4) = Ipv4Addr::from_str(ip) {
+        if let Some((host, port)) = s.rsplit_once(':') {
+            // IPv6 addresses may be written in brackets: [::1]:9735
+            let host = host
+                .strip_prefix('[')
+                .and_then(|h| h.strip_suffix(']'))
+                .unwrap_or(host);
+            if let Ok(ipv4) = Ipv4Addr::from_str(host) {
                 return Ok(PeerAddress(NetAddress::IPv4 {
                     addr: ipv4.octets(),
                     port: port.parse()?,
                 }));
-            } else if let Ok(ipv6) = Ipv6Addr::from_str(ip) {
+            } else if let Ok(ipv6) = Ipv6Addr::from_str(host) {
                 return Ok(PeerAddress(NetAddress::IPv6 {
                     addr: ipv6.octets(),
                     port: port.parse()?,
                 }));
+            } else if is_hostname(host) {
+                if let Ok(hostname) = Hostname::try_from(host.to_string()) {
+                    return Ok(PeerAddress(NetAddress::Hostname {
+                        hostname,
+                        port: port.parse()?,
+                    }));
+                }
             }
         }
         Err(anyhow!("Invalid network address:port"))
     }
 }
 
+fn is_hostname(host: &str) -> bool {
+    !host.is_empty()
+        && host.chars().any(|c| c.is_ascii_alphabetic())
+        && host.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
 impl TryFrom<PeerAddress> for SocketAddr {
     type Error = anyhow::Error;
 
@@ -95,3 +156,44 @@ fn test_ipv6_net_address() -> anyhow::Result<()> {
     assert_eq!(ipv6_address_str.parse::<PeerAddress>()?, ipv6_address);
     Ok(())
 }
+
+#[test]
+fn test_ipv6_bracketed_net_address() -> anyhow::Result<()> {
+    let ipv6_address = PeerAddress(NetAddress::IPv6 {
+        addr: Ipv6Addr::LOCALHOST.octets(),
+        port: 9735,
+    });
+    assert_eq!("[::1]:9735".parse::<PeerAddress>()?, ipv6_address);
+    Ok(())
+}
+
+#[test]
+fn test_hostname_net_address() -> anyhow::Result<()> {
+    let hostname_address = PeerAddress(NetAddress::Hostname {
+        hostname: Hostname::try_from("node.example.com".to_string()).unwrap(),
+        port: 9735,
+    });
+    assert_eq!(&hostname_address.to_string(), "node.example.com:9735");
+    assert_eq!(
+        "node.example.com:9735".parse::<PeerAddress>()?,
+        hostname_address
+    );
+    assert!("node..example.com:9735".parse::<PeerAddress>().is_err());
+    assert!("1.2.3:9735".parse::<PeerAddress>().is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_resolve_net_address() -> anyhow::Result<()> {
+    let ipv4_socket = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 5050));
+    let ipv4_address = "127.0.0.1:5050".parse::<PeerAddress>()?;
+    assert_eq!(vec![ipv4_socket], ipv4_address.resolve().await?);
+
+    let localhost = "localhost:5050".parse::<PeerAddress>()?;
+    assert!(localhost
+        .resolve()
+        .await?
+        .iter()
+        .all(|a| a.ip().is_loopback() && a.port() == 5050));
+    Ok(())
+}
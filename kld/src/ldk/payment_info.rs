@@ -1,22 +1,4 @@
-use std::{
-    collections::HashMap,
-    fmt,
-    sync::{Arc, Mutex},
-};
-
-use lightning::ln::{PaymentHash, PaymentPreimage, PaymentSecret};
-
-pub(crate) enum HTLCStatus {
-    Succeeded,
-    Failed,
-}
-
-pub(crate) struct PaymentInfo {
-    pub preimage: Option<PaymentPreimage>,
-    pub secret: Option<PaymentSecret>,
-    pub status: HTLCStatus,
-    pub amt_msat: MillisatAmount,
-}
+use std::fmt;
 
 pub(crate) struct MillisatAmount(pub Option<u64>);
 
@@ -28,5 +10,3 @@ impl fmt::Display for MillisatAmount {
         }
     }
 }
-
-pub(crate) type PaymentInfoStorage = Arc<Mutex<HashMap<PaymentHash, PaymentInfo>>>;
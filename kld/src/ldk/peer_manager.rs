@@ -1,33 +1,63 @@
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    net::{Shutdown, SocketAddr},
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
-use crate::database::{peer::Peer, LdkDatabase};
+use crate::database::LdkDatabase;
 use anyhow::{anyhow, bail, Context, Result};
 use bitcoin::secp256k1::PublicKey;
 use lightning::ln::msgs::NetAddress;
-use log::{error, info};
+use log::{error, info, warn};
 use settings::Settings;
-use tokio::task::JoinHandle;
+use tokio::{
+    net::{TcpListener, TcpSocket},
+    sync::Semaphore,
+    task::JoinHandle,
+};
 
-use super::{net_utils::PeerAddress, ChannelManager, LdkPeerManager};
+use super::{
+    dns_seed::{dns_seeds, query_dns_seed},
+    net_utils::PeerAddress,
+    ChannelManager, LdkPeerManager, NetworkGraph,
+};
 
 pub struct PeerManager {
     ldk_peer_manager: Arc<LdkPeerManager>,
     channel_manager: Arc<ChannelManager>,
+    network_graph: Arc<NetworkGraph>,
     database: Arc<LdkDatabase>,
     settings: Arc<Settings>,
     addresses: Vec<PeerAddress>,
+    // Starts out with the alias from the settings and can be changed at runtime.
+    appearance: RwLock<NodeAppearance>,
+}
+
+/// How the node presents itself in the node announcement.
+#[derive(Clone)]
+pub struct NodeAppearance {
+    pub alias: String,
+    pub color: [u8; 3],
+}
+
+impl NodeAppearance {
+    fn announced_alias(&self) -> [u8; 32] {
+        let mut alias = [0; 32];
+        alias[..self.alias.len()].copy_from_slice(self.alias.as_bytes());
+        alias
+    }
 }
 
 impl PeerManager {
     pub fn new(
         ldk_peer_manager: Arc<LdkPeerManager>,
         channel_manager: Arc<ChannelManager>,
+        network_graph: Arc<NetworkGraph>,
         database: Arc<LdkDatabase>,
         settings: Arc<Settings>,
     ) -> Result<PeerManager> {
-        if settings.node_name.len() > 32 {
-            bail!("Node Alias can not be longer than 32 bytes");
-        }
+        check_alias(&settings.node_name)?;
         let mut addresses = vec![];
         for address in &settings.public_addresses {
             addresses.push(address.parse::<PeerAddress>().unwrap());
@@ -35,30 +65,91 @@ impl PeerManager {
         Ok(PeerManager {
             ldk_peer_manager,
             channel_manager,
+            network_graph,
             database,
+            appearance: RwLock::new(NodeAppearance {
+                alias: settings.node_name.clone(),
+                color: [0; 3],
+            }),
             settings,
             addresses,
         })
     }
 
     pub async fn listen(&self) {
-        let listener =
-            tokio::net::TcpListener::bind(format!("0.0.0.0:{}", self.settings.peer_port))
-                .await
-                .context("Failed to bind to listen port")
-                .unwrap();
+        let listener = bind_listener(self.settings.peer_port, self.settings.peer_listen_backlog)
+            .context("Failed to bind to listen port")
+            .unwrap();
         let ldk_peer_manager = self.ldk_peer_manager.clone();
+        let handshakes = Arc::new(Semaphore::new(self.settings.max_inbound_handshakes));
         tokio::spawn(async move {
+            let mut accept_errors = 0;
             loop {
+                // Stop accepting while too many handshakes are in progress, so that a flood of
+                // connections waits in the backlog instead of spawning tasks.
+                let permit = match handshakes.clone().acquire_owned().await {
+                    Ok(permit) => permit,
+                    Err(_) => return,
+                };
+                let (tcp_stream, socket_addr) = match listener.accept().await {
+                    Ok(connection) => {
+                        accept_errors = 0;
+                        connection
+                    }
+                    Err(e) => {
+                        // For example when running out of file descriptors. Back off so the loop
+                        // doesn't spin until connections are closed.
+                        accept_errors += 1;
+                        warn!(
+                            "Failed to accept inbound peer connection {accept_errors} times: {e}"
+                        );
+                        tokio::time::sleep(accept_error_backoff(accept_errors)).await;
+                        continue;
+                    }
+                };
+                let std_stream = match tcp_stream.into_std() {
+                    Ok(std_stream) => std_stream,
+                    Err(e) => {
+                        warn!("Failed to set up inbound peer connection from {socket_addr}: {e}");
+                        continue;
+                    }
+                };
+                // Kept to close connections which don't complete the handshake in time.
+                let shutdown_stream = match std_stream.try_clone() {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!("Failed to set up inbound peer connection from {socket_addr}: {e}");
+                        continue;
+                    }
+                };
                 let peer_mgr = ldk_peer_manager.clone();
-                let (tcp_stream, socket_addr) = listener.accept().await.unwrap();
                 tokio::spawn(async move {
-                    let disconnected = lightning_net_tokio::setup_inbound(
-                        peer_mgr.clone(),
-                        tcp_stream.into_std().unwrap(),
-                    );
+                    let disconnected =
+                        lightning_net_tokio::setup_inbound(peer_mgr.clone(), std_stream);
+                    tokio::pin!(disconnected);
                     info!("Inbound peer connection from {socket_addr}");
-                    disconnected.await;
+                    let handshake = tokio::time::timeout(
+                        HANDSHAKE_TIMEOUT,
+                        handshake_completed(&peer_mgr, socket_addr),
+                    );
+                    tokio::select! {
+                        _ = &mut disconnected => {}
+                        result = handshake => {
+                            match result {
+                                Ok(()) => {
+                                    drop(permit);
+                                    drop(shutdown_stream);
+                                }
+                                Err(_) => {
+                                    warn!("Inbound peer {socket_addr} did not complete the handshake in time");
+                                    if let Err(e) = shutdown_stream.shutdown(Shutdown::Both) {
+                                        warn!("Failed to close connection from {socket_addr}: {e}");
+                                    }
+                                }
+                            }
+                            disconnected.await;
+                        }
+                    }
                     info!("Inbound peer disconnected from {socket_addr}");
                 });
             }
@@ -87,10 +178,80 @@ impl PeerManager {
         }
     }
 
+    /// Connect to the bootstrap peers from the settings in the background.
+    pub fn connect_bootstrap_peers(self: &Arc<Self>) {
+        for peer in &self.settings.bootstrap_peers {
+            let (public_key, address) = match parse_bootstrap_peer(peer) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    warn!("Invalid bootstrap peer {peer}: {e}");
+                    continue;
+                }
+            };
+            let peer_manager = self.clone();
+            tokio::spawn(async move {
+                match peer_manager.connect_peer(public_key, address.clone()).await {
+                    Ok(()) => info!("Connected to bootstrap peer {public_key}@{address}"),
+                    Err(e) => {
+                        warn!("Failed to connect to bootstrap peer {public_key}@{address}: {e}")
+                    }
+                }
+            });
+        }
+    }
+
+    /// Connect to a few peers from the DNS seeds in the background if enabled and the node has no
+    /// channels yet. Connection attempts are spaced out so we don't hammer the seeded nodes.
+    pub fn connect_dns_seed_peers(self: &Arc<Self>) {
+        if !self.settings.use_dns_seeds || !self.channel_manager.list_channels().is_empty() {
+            return;
+        }
+        let seeds = dns_seeds(self.settings.bitcoin_network);
+        if seeds.is_empty() {
+            warn!("No DNS seeds for network {}", self.settings.bitcoin_network);
+            return;
+        }
+        let peer_manager = self.clone();
+        tokio::spawn(async move {
+            let mut connected = 0;
+            let mut attempts = 0;
+            for seed in seeds {
+                let peers = match query_dns_seed(seed).await {
+                    Ok(peers) => peers,
+                    Err(e) => {
+                        warn!("Failed to query DNS seed {seed}: {e}");
+                        continue;
+                    }
+                };
+                for (public_key, address) in peers {
+                    if connected >= DNS_SEED_PEERS || attempts >= DNS_SEED_MAX_ATTEMPTS {
+                        return;
+                    }
+                    if peer_manager.is_connected(&public_key) {
+                        continue;
+                    }
+                    attempts += 1;
+                    match peer_manager.connect_peer(public_key, address.clone()).await {
+                        Ok(()) => {
+                            info!("Connected to DNS seed peer {public_key}@{address}");
+                            connected += 1;
+                        }
+                        Err(e) => {
+                            warn!("Failed to connect to DNS seed peer {public_key}@{address}: {e}")
+                        }
+                    }
+                    tokio::time::sleep(DNS_SEED_CONNECT_INTERVAL).await;
+                }
+            }
+        });
+    }
+
     pub fn keep_channel_peers_connected(&self) {
         let database = self.database.clone();
         let ldk_peer_manager = self.ldk_peer_manager.clone();
         let channel_manager = self.channel_manager.clone();
+        let network_graph = self.network_graph.clone();
+        let reconnect_interval = Duration::from_secs(self.settings.peer_reconnect_interval_secs);
         tokio::spawn(async move {
             loop {
                 let connected_node_ids = ldk_peer_manager.get_peer_node_ids();
@@ -100,43 +261,114 @@ impl PeerManager {
                     .map(|chan| chan.counterparty.node_id)
                     .filter(|id| !connected_node_ids.iter().any(|(pk, _)| pk == id))
                 {
-                    match database.fetch_peer(&unconnected_node_id).await {
-                        Ok(Some(peer)) => {
-                            let _ = connect_peer(
-                                ldk_peer_manager.clone(),
-                                database.clone(),
-                                peer.public_key,
-                                PeerAddress(peer.net_address),
-                            )
-                            .await;
+                    let addresses = match known_addresses(
+                        &database,
+                        &network_graph,
+                        &unconnected_node_id,
+                    )
+                    .await
+                    {
+                        Ok(addresses) => addresses,
+                        Err(e) => {
+                            error!("{}", e);
+                            continue;
+                        }
+                    };
+                    for address in addresses {
+                        if connect_peer(
+                            ldk_peer_manager.clone(),
+                            database.clone(),
+                            unconnected_node_id,
+                            address,
+                        )
+                        .await
+                        .is_ok()
+                        {
+                            break;
                         }
-                        Err(e) => error!("{}", e),
-                        _ => (),
                     }
                 }
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                tokio::time::sleep(reconnect_interval).await;
+            }
+        });
+    }
+
+    // The background processor ticks the peer manager every 10 seconds which sends pings and
+    // disconnects peers that did not respond to the previous one. Ticking more often makes this
+    // more aggressive.
+    pub fn keep_peers_pinged(&self) {
+        if self.settings.peer_ping_interval_secs == 0 {
+            return;
+        }
+        let ldk_peer_manager = self.ldk_peer_manager.clone();
+        let ping_interval = Duration::from_secs(self.settings.peer_ping_interval_secs);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ping_interval);
+            loop {
+                interval.tick().await;
+                ldk_peer_manager.timer_tick_occurred();
             }
         });
     }
 
     // Regularly broadcast our node_announcement. This is only required (or possible) if we have
     // some public channels, and is only useful if we have public listen address(es) to announce.
-    // In a production environment, this should occur only after the announcement of new channels
-    // to avoid churn in the global network graph.
-    pub fn regularly_broadcast_node_announcement(&self) {
-        let mut alias = [0; 32];
-        alias[..self.settings.node_name.len()].copy_from_slice(self.settings.node_name.as_bytes());
-        let peer_manager = self.ldk_peer_manager.clone();
-        let addresses: Vec<NetAddress> = self.addresses.iter().map(|a| a.0.clone()).collect();
+    // Nodes with only private channels skip the broadcast so they are not advertised.
+    pub fn regularly_broadcast_node_announcement(self: &Arc<Self>) {
+        let peer_manager = self.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(60));
             loop {
                 interval.tick().await;
-                peer_manager.broadcast_node_announcement([0; 3], alias, addresses.clone());
+                peer_manager.broadcast_node_announcement();
             }
         });
     }
 
+    /// Announce the node with the current alias, color and addresses. Returns false without
+    /// broadcasting if there is no public channel yet.
+    pub fn broadcast_node_announcement(&self) -> bool {
+        if !self
+            .channel_manager
+            .list_channels()
+            .iter()
+            .any(|channel| channel.is_public && channel.is_channel_ready)
+        {
+            return false;
+        }
+        let appearance = self.appearance();
+        let addresses: Vec<NetAddress> = self.addresses.iter().map(|a| a.0.clone()).collect();
+        self.ldk_peer_manager.broadcast_node_announcement(
+            appearance.color,
+            appearance.announced_alias(),
+            addresses,
+        );
+        true
+    }
+
+    pub fn appearance(&self) -> NodeAppearance {
+        self.appearance.read().unwrap().clone()
+    }
+
+    /// Change the alias and color until the next restart and announce them right away.
+    pub fn set_appearance(&self, alias: String, color: Option<[u8; 3]>) -> Result<()> {
+        check_alias(&alias)?;
+        {
+            let mut appearance = self.appearance.write().unwrap();
+            appearance.alias = alias;
+            if let Some(color) = color {
+                appearance.color = color;
+            }
+        }
+        self.broadcast_node_announcement();
+        Ok(())
+    }
+
+    /// Addresses to try for connecting to the node, see known_addresses.
+    pub async fn known_addresses(&self, public_key: &PublicKey) -> Result<Vec<PeerAddress>> {
+        known_addresses(&self.database, &self.network_graph, public_key).await
+    }
+
     pub fn get_connected_peers(&self) -> Vec<(PublicKey, Option<NetAddress>)> {
         self.ldk_peer_manager.get_peer_node_ids()
     }
@@ -148,9 +380,14 @@ impl PeerManager {
             .any(|p| p.0 == *public_key)
     }
 
-    pub async fn disconnect_by_node_id(&self, node_id: PublicKey) -> Result<()> {
+    /// Channel peers are reconnected by keep_channel_peers_connected.
+    pub fn disconnect_by_node_id(&self, node_id: PublicKey) {
         self.ldk_peer_manager.disconnect_by_node_id(node_id);
-        self.database.delete_peer(&node_id).await
+    }
+
+    /// Delete the stored addresses of the peer. The connection, if any, stays open.
+    pub async fn forget_peer(&self, node_id: &PublicKey) -> Result<()> {
+        self.database.delete_peer(node_id).await
     }
 
     pub fn disconnect_all_peers(&self) {
@@ -158,26 +395,135 @@ impl PeerManager {
     }
 }
 
+/// The addresses we connected to the node with, the most recent first, followed by the other IP
+/// and DNS addresses the node announced. Onion addresses need a proxy, which is not supported.
+/// connect_peer stores the address that works, so a fresher one from the graph takes the place
+/// of a stale one.
+async fn known_addresses(
+    database: &LdkDatabase,
+    network_graph: &NetworkGraph,
+    public_key: &PublicKey,
+) -> Result<Vec<PeerAddress>> {
+    let mut addresses: Vec<PeerAddress> = database
+        .fetch_peer(public_key)
+        .await?
+        .map(|peer| peer.net_addresses)
+        .unwrap_or_default()
+        .into_iter()
+        .map(PeerAddress)
+        .collect();
+    let graph_addresses = network_graph
+        .read_only()
+        .get_addresses(public_key)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|a| {
+            matches!(
+                a,
+                NetAddress::IPv4 { .. } | NetAddress::IPv6 { .. } | NetAddress::Hostname { .. }
+            )
+        })
+        .map(PeerAddress);
+    for address in graph_addresses {
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+    Ok(addresses)
+}
+
+const DNS_SEED_PEERS: usize = 3;
+const DNS_SEED_MAX_ATTEMPTS: usize = 10;
+const DNS_SEED_CONNECT_INTERVAL: Duration = Duration::from_secs(2);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const MIN_ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_ACCEPT_ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+fn parse_bootstrap_peer(peer: &str) -> Result<(PublicKey, PeerAddress)> {
+    let (public_key, address) = peer.split_once('@').context("Expected pubkey@host:port")?;
+    Ok((PublicKey::from_str(public_key)?, address.parse()?))
+}
+
+fn accept_error_backoff(accept_errors: u32) -> Duration {
+    MIN_ACCEPT_ERROR_BACKOFF
+        .saturating_mul(2u32.saturating_pow(accept_errors.saturating_sub(1)))
+        .min(MAX_ACCEPT_ERROR_BACKOFF)
+}
+
+fn bind_listener(port: u16, backlog: u32) -> std::io::Result<TcpListener> {
+    let socket = TcpSocket::new_v4()?;
+    // Like TcpListener::bind, so that a restarted node can listen while old connections linger.
+    socket.set_reuseaddr(true)?;
+    socket.bind(SocketAddr::from(([0, 0, 0, 0], port)))?;
+    socket.listen(backlog)
+}
+
+/// Returns once LDK knows the node id of the peer at the address.
+async fn handshake_completed(ldk_peer_manager: &LdkPeerManager, socket_addr: SocketAddr) {
+    loop {
+        let completed = ldk_peer_manager
+            .get_peer_node_ids()
+            .into_iter()
+            .filter_map(|(_, address)| SocketAddr::try_from(PeerAddress(address?)).ok())
+            .any(|address| address == socket_addr);
+        if completed {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+fn check_alias(alias: &str) -> Result<()> {
+    if alias.len() > 32 {
+        bail!("Node Alias can not be longer than 32 bytes");
+    }
+    Ok(())
+}
+
 async fn connect_peer(
     ldk_peer_manager: Arc<LdkPeerManager>,
     database: Arc<LdkDatabase>,
     public_key: PublicKey,
     peer_address: PeerAddress,
 ) -> Result<JoinHandle<()>> {
-    let socket_addr = SocketAddr::try_from(peer_address.clone())?;
-    let connection_closed =
-        lightning_net_tokio::connect_outbound(ldk_peer_manager, public_key, socket_addr)
-            .await
-            .context("Could not connect to peer {public_key}@{peer_addr}")?;
-    database
-        .persist_peer(&Peer {
+    for socket_addr in peer_address.resolve().await? {
+        let connection_closed = match lightning_net_tokio::connect_outbound(
+            ldk_peer_manager.clone(),
             public_key,
-            net_address: peer_address.0,
-        })
-        .await?;
-    info!("Connected to peer {public_key}@{socket_addr}");
-    Ok(tokio::spawn(async move {
-        connection_closed.await;
-        info!("Disconnected from peer {public_key}@{socket_addr}");
-    }))
+            socket_addr,
+        )
+        .await
+        {
+            Some(connection_closed) => connection_closed,
+            None => {
+                info!("Could not connect to peer {public_key}@{socket_addr}");
+                continue;
+            }
+        };
+        database.persist_peer(&public_key, &peer_address.0).await?;
+        info!("Connected to peer {public_key}@{socket_addr}");
+        return Ok(tokio::spawn(async move {
+            connection_closed.await;
+            info!("Disconnected from peer {public_key}@{socket_addr}");
+        }));
+    }
+    bail!("Could not connect to peer {public_key}@{peer_address}")
+}
+
+#[test]
+fn test_accept_error_backoff() {
+    assert_eq!(Duration::from_millis(10), accept_error_backoff(1));
+    assert_eq!(Duration::from_millis(80), accept_error_backoff(4));
+    assert_eq!(MAX_ACCEPT_ERROR_BACKOFF, accept_error_backoff(40));
+}
+
+#[test]
+fn test_parse_bootstrap_peer() {
+    let public_key = "02a1633cafcc01ebfb6d78e39f687a1f0995c62fc95f51ead10a02ee0be551b5dc";
+    let (parsed_key, address) =
+        parse_bootstrap_peer(&format!("{public_key}@127.0.0.1:9735")).unwrap();
+    assert_eq!(public_key, parsed_key.to_string());
+    assert_eq!("127.0.0.1:9735", address.to_string());
+    assert!(parse_bootstrap_peer(public_key).is_err());
+    assert!(parse_bootstrap_peer(&format!("{public_key}@127.0.0.1")).is_err());
 }
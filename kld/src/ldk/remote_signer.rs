@@ -0,0 +1,155 @@
+//! Interface to a signer which holds the node secret outside of the kld process. Every operation
+//! is a JSON POST to `<remote_signer_url>/<operation>` with hex encoded fields.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use bitcoin::{
+    bech32::u5,
+    secp256k1::{
+        ecdh::SharedSecret,
+        ecdsa::{RecoverableSignature, RecoveryId, Signature},
+        PublicKey, Scalar,
+    },
+};
+use hex::{FromHex, ToHex};
+use reqwest::Client;
+use serde_json::{json, Value};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The operations of LDK's NodeSigner which need the node secret.
+#[async_trait]
+pub trait RemoteSigner {
+    /// The node public key of the signer.
+    async fn node_id(&self) -> Result<PublicKey>;
+
+    /// ECDH of the node secret, tweaked if set, with another key. Used for the peer handshake
+    /// and to decrypt onions.
+    async fn ecdh(&self, other_key: &PublicKey, tweak: Option<&Scalar>) -> Result<SharedSecret>;
+
+    /// Sign the double sha256 hash of a node or channel announcement with the node key.
+    async fn sign_gossip_message(&self, message_hash: &[u8; 32]) -> Result<Signature>;
+
+    /// Sign a BOLT 11 invoice given its human readable part and data.
+    async fn sign_invoice(
+        &self,
+        hrp_bytes: &[u8],
+        invoice_data: &[u5],
+    ) -> Result<RecoverableSignature>;
+}
+
+pub struct HttpRemoteSigner {
+    url: String,
+    client: Client,
+}
+
+impl HttpRemoteSigner {
+    pub fn new(url: &str) -> Result<HttpRemoteSigner> {
+        Ok(HttpRemoteSigner {
+            url: url.trim_end_matches('/').to_string(),
+            client: Client::builder().timeout(TIMEOUT).build()?,
+        })
+    }
+
+    async fn request(&self, operation: &str, body: Value) -> Result<Value> {
+        let response = self
+            .client
+            .post(format!("{}/{operation}", self.url))
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+}
+
+#[async_trait]
+impl RemoteSigner for HttpRemoteSigner {
+    async fn node_id(&self) -> Result<PublicKey> {
+        let response = self.request("node_id", json!({})).await?;
+        Ok(PublicKey::from_slice(&hex_field(&response, "nodeId")?)?)
+    }
+
+    async fn ecdh(&self, other_key: &PublicKey, tweak: Option<&Scalar>) -> Result<SharedSecret> {
+        let response = self
+            .request(
+                "ecdh",
+                json!({
+                    "publicKey": other_key.serialize().encode_hex::<String>(),
+                    "tweak": tweak.map(|t| t.to_be_bytes().encode_hex::<String>()),
+                }),
+            )
+            .await?;
+        let bytes = <[u8; 32]>::try_from(hex_field(&response, "sharedSecret")?.as_slice())
+            .map_err(|_| anyhow!("Shared secret must be 32 bytes"))?;
+        Ok(SharedSecret::from_bytes(bytes))
+    }
+
+    async fn sign_gossip_message(&self, message_hash: &[u8; 32]) -> Result<Signature> {
+        let response = self
+            .request(
+                "sign_gossip_message",
+                json!({ "messageHash": message_hash.encode_hex::<String>() }),
+            )
+            .await?;
+        Ok(Signature::from_compact(&hex_field(
+            &response,
+            "signature",
+        )?)?)
+    }
+
+    async fn sign_invoice(
+        &self,
+        hrp_bytes: &[u8],
+        invoice_data: &[u5],
+    ) -> Result<RecoverableSignature> {
+        let invoice_data: Vec<u8> = invoice_data.iter().map(|d| d.to_u8()).collect();
+        let response = self
+            .request(
+                "sign_invoice",
+                json!({
+                    "hrp": hrp_bytes.encode_hex::<String>(),
+                    "invoiceData": invoice_data.encode_hex::<String>(),
+                }),
+            )
+            .await?;
+        parse_recoverable_signature(&hex_field(&response, "signature")?)
+    }
+}
+
+fn hex_field(response: &Value, name: &str) -> Result<Vec<u8>> {
+    let field = response[name]
+        .as_str()
+        .with_context(|| format!("Remote signer response is missing {name}"))?;
+    Ok(Vec::from_hex(field)?)
+}
+
+/// The 64 byte compact signature followed by the recovery id.
+fn parse_recoverable_signature(bytes: &[u8]) -> Result<RecoverableSignature> {
+    if bytes.len() != 65 {
+        return Err(anyhow!("Recoverable signature must be 65 bytes"));
+    }
+    let recovery_id = RecoveryId::from_i32(bytes[64] as i32)?;
+    Ok(RecoverableSignature::from_compact(
+        &bytes[..64],
+        recovery_id,
+    )?)
+}
+
+#[test]
+fn test_parse_recoverable_signature() {
+    use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+
+    let secp = Secp256k1::new();
+    let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+    let signature =
+        secp.sign_ecdsa_recoverable(&Message::from_slice(&[2; 32]).unwrap(), &secret_key);
+    let (recovery_id, compact) = signature.serialize_compact();
+    let mut bytes = compact.to_vec();
+    bytes.push(recovery_id.to_i32() as u8);
+
+    assert_eq!(signature, parse_recoverable_signature(&bytes).unwrap());
+    assert!(parse_recoverable_signature(&bytes[..64]).is_err());
+}
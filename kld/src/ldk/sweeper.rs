@@ -0,0 +1,236 @@
+//! Sweep the outputs of closed channels, which LDK hands us with `Event::SpendableOutputs`, into
+//! the wallet. LDK reports each output only once, so they are persisted before the sweep and
+//! retried until the sweep has confirmed.
+
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use api::FeeRate;
+use bitcoin::{secp256k1::Secp256k1, Address, Network, Txid};
+use lightning::chain::{
+    chaininterface::{ConfirmationTarget, FeeEstimator},
+    keysinterface::{KeysManager, SpendableOutputDescriptor},
+};
+use log::{error, info, warn};
+use settings::Settings;
+
+use crate::{
+    bitcoind::BitcoindClient,
+    database::{
+        spendable_output::{SpendableOutput, SweepStatus},
+        LdkDatabase, WalletDatabase,
+    },
+    wallet::{Wallet, WalletInterface},
+};
+
+use super::MIN_FEERATE;
+
+/// How often unconfirmed sweeps are checked and failed ones retried.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(600);
+
+pub(crate) struct Sweeper {
+    keys_manager: Arc<KeysManager>,
+    bitcoind_client: Arc<BitcoindClient>,
+    wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+    database: Arc<LdkDatabase>,
+    destination: Option<Address>,
+    fee_rate: FeeRate,
+}
+
+impl Sweeper {
+    pub fn new(
+        settings: &Settings,
+        keys_manager: Arc<KeysManager>,
+        bitcoind_client: Arc<BitcoindClient>,
+        wallet: Arc<Wallet<WalletDatabase, BitcoindClient>>,
+        database: Arc<LdkDatabase>,
+    ) -> Result<Sweeper> {
+        Ok(Sweeper {
+            keys_manager,
+            bitcoind_client,
+            wallet,
+            database,
+            destination: sweep_destination(
+                &settings.sweep_address,
+                settings.bitcoin_network.into(),
+            )?,
+            fee_rate: FeeRate::from_str(&settings.sweep_fee_rate)
+                .context("Invalid sweep fee rate")?,
+        })
+    }
+
+    /// Persist new outputs and try to sweep them straight away.
+    pub async fn add_outputs(&self, descriptors: Vec<SpendableOutputDescriptor>) {
+        let outputs: Vec<SpendableOutput> =
+            descriptors.into_iter().map(SpendableOutput::new).collect();
+        for output in &outputs {
+            if let Err(e) = self.database.persist_spendable_output(output).await {
+                error!(
+                    "Failed to persist spendable output {}: {e}",
+                    output.outpoint()
+                );
+            }
+        }
+        if let Err(e) = self.sweep(outputs).await {
+            error!("Failed to sweep spendable outputs, will retry: {e}");
+        }
+    }
+
+    /// Mark confirmed sweeps as done and sweep outputs again which are still unspent.
+    async fn retry_sweeps(&self) -> Result<()> {
+        let mut to_sweep = vec![];
+        for mut output in self.database.fetch_unswept_outputs().await? {
+            let outpoint = output.outpoint();
+            match output.status {
+                SweepStatus::Pending => {
+                    // Spent by a sweep which was broadcast but could not be recorded.
+                    if !self.bitcoind_client.is_unspent(&outpoint, false).await? {
+                        info!("Output {outpoint} has already been spent");
+                        output.status = SweepStatus::Swept;
+                        self.database.persist_spendable_output(&output).await?;
+                    } else if self.bitcoind_client.is_unspent(&outpoint, true).await? {
+                        to_sweep.push(output);
+                    }
+                }
+                SweepStatus::Sweeping => {
+                    if !self.bitcoind_client.is_unspent(&outpoint, false).await? {
+                        info!(
+                            "Output {outpoint} has been swept in {}",
+                            output
+                                .sweep_txid
+                                .map(|txid| txid.to_string())
+                                .unwrap_or_default()
+                        );
+                        output.status = SweepStatus::Swept;
+                        self.database.persist_spendable_output(&output).await?;
+                    } else if self.bitcoind_client.is_unspent(&outpoint, true).await? {
+                        warn!("Sweep of output {outpoint} is no longer in the mempool");
+                        to_sweep.push(output);
+                    }
+                }
+                SweepStatus::Swept => {}
+            }
+        }
+        if !to_sweep.is_empty() {
+            self.sweep(to_sweep).await?;
+        }
+        Ok(())
+    }
+
+    /// Sweep the outputs in one transaction. If that can't be broadcast they are swept one by
+    /// one, so a single output which can't be spent doesn't hold up the others.
+    async fn sweep(&self, outputs: Vec<SpendableOutput>) -> Result<()> {
+        if outputs.len() > 1 {
+            match self.broadcast_sweep(&outputs).await {
+                Ok(sweep_txid) => return self.record_sweep(outputs, sweep_txid).await,
+                Err(e) => warn!(
+                    "Failed to sweep {} outputs together, sweeping them one by one: {e}",
+                    outputs.len()
+                ),
+            }
+        }
+        let mut failed = 0;
+        for output in outputs {
+            let outpoint = output.outpoint();
+            let result = match self.broadcast_sweep(std::slice::from_ref(&output)).await {
+                Ok(sweep_txid) => self.record_sweep(vec![output], sweep_txid).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                error!("Failed to sweep output {outpoint}: {e}");
+                failed += 1;
+            }
+        }
+        if failed > 0 {
+            bail!("{failed} outputs could not be swept");
+        }
+        Ok(())
+    }
+
+    async fn broadcast_sweep(&self, outputs: &[SpendableOutput]) -> Result<Txid> {
+        let destination = match &self.destination {
+            Some(address) => address.clone(),
+            None => self.wallet.new_address()?.address,
+        };
+        let descriptors: Vec<&SpendableOutputDescriptor> =
+            outputs.iter().map(|output| &output.descriptor).collect();
+        let sweep_tx = self
+            .keys_manager
+            .spend_spendable_outputs(
+                &descriptors,
+                Vec::new(),
+                destination.script_pubkey(),
+                self.sat_per_1000_weight(),
+                &Secp256k1::new(),
+            )
+            .map_err(|_| anyhow!("Failed to build sweep transaction"))?;
+        let sweep_txid = self.bitcoind_client.send_transaction(&sweep_tx).await?;
+        let value_sats: u64 = outputs.iter().map(|output| output.value_sats()).sum();
+        info!(
+            "Sweeping {} spendable outputs with {value_sats} sats to {destination} in {sweep_txid}",
+            outputs.len()
+        );
+        Ok(sweep_txid)
+    }
+
+    async fn record_sweep(
+        &self,
+        mut outputs: Vec<SpendableOutput>,
+        sweep_txid: Txid,
+    ) -> Result<()> {
+        for output in &mut outputs {
+            output.sweeping(sweep_txid);
+        }
+        self.database.persist_sweep(&outputs, &sweep_txid).await
+    }
+
+    fn sat_per_1000_weight(&self) -> u32 {
+        let estimate = |target| self.bitcoind_client.get_est_sat_per_1000_weight(target);
+        let feerate = match self.fee_rate {
+            FeeRate::Urgent => estimate(ConfirmationTarget::HighPriority),
+            FeeRate::Normal => estimate(ConfirmationTarget::Normal),
+            FeeRate::Slow => estimate(ConfirmationTarget::Background),
+            FeeRate::PerKw(sats) => sats,
+            // A virtual byte is four weight units.
+            FeeRate::PerKb(sats) => sats / 4,
+        };
+        feerate.max(MIN_FEERATE)
+    }
+}
+
+/// Parse the configured sweep address. None means funds are swept into the wallet.
+fn sweep_destination(address: &str, network: Network) -> Result<Option<Address>> {
+    if address.is_empty() {
+        return Ok(None);
+    }
+    let address = Address::from_str(address).context("Invalid sweep address")?;
+    if !address.is_valid_for_network(network) {
+        bail!("Sweep address {address} is not valid for {network}");
+    }
+    Ok(Some(address))
+}
+
+/// Retry sweeps periodically until the process exits.
+pub(crate) fn start(sweeper: Arc<Sweeper>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = sweeper.retry_sweeps().await {
+                error!("Failed to retry sweeps: {e}");
+            }
+        }
+    });
+}
+
+#[test]
+fn test_sweep_destination() {
+    assert_eq!(None, sweep_destination("", Network::Regtest).unwrap());
+    assert!(
+        sweep_destination("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt", Network::Regtest)
+            .unwrap()
+            .is_some()
+    );
+    assert!(sweep_destination("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt", Network::Bitcoin).is_err());
+    assert!(sweep_destination("not an address", Network::Regtest).is_err());
+}
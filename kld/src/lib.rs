@@ -5,9 +5,11 @@ pub mod bitcoind;
 pub mod database;
 pub mod key_generator;
 pub mod ldk;
+pub mod lnurl;
 pub mod logger;
 pub mod prometheus;
 pub mod wallet;
+pub mod webhook;
 
 pub const VERSION: &str = concat!("KLD v", env!("CARGO_PKG_VERSION"));
 
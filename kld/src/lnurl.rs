@@ -0,0 +1,134 @@
+//! Client side of LNURL-pay (LUD-06) and lightning addresses (LUD-16).
+
+use std::{str::FromStr, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use bech32::FromBase32;
+use bitcoin::hashes::{sha256, Hash};
+use lightning_invoice::{Invoice, InvoiceDescription};
+use reqwest::Client;
+use serde_json::Value;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RESPONSE_SIZE: usize = 64 * 1024;
+
+/// Resolve a bech32 encoded LNURL or a lightning address (user@domain) and request an invoice
+/// for the amount from the recipient's service.
+pub async fn fetch_invoice(target: &str, amount_msat: u64) -> Result<Invoice> {
+    let url = pay_request_url(target)?;
+    let client = Client::builder().timeout(TIMEOUT).build()?;
+
+    let pay_request = get_json(&client, &url).await?;
+    if pay_request["tag"] != "payRequest" {
+        bail!("{target} is not a LNURL-pay target");
+    }
+    let callback = pay_request["callback"]
+        .as_str()
+        .context("LNURL-pay response is missing the callback")?;
+    let min_sendable = pay_request["minSendable"].as_u64().unwrap_or_default();
+    let max_sendable = pay_request["maxSendable"].as_u64().unwrap_or(u64::MAX);
+    if amount_msat < min_sendable || amount_msat > max_sendable {
+        bail!("Amount must be between {min_sendable} and {max_sendable} msat");
+    }
+    let metadata = pay_request["metadata"].as_str().unwrap_or_default();
+
+    let separator = if callback.contains('?') { '&' } else { '?' };
+    let response = get_json(
+        &client,
+        &format!("{callback}{separator}amount={amount_msat}"),
+    )
+    .await?;
+    let invoice = Invoice::from_str(
+        response["pr"]
+            .as_str()
+            .context("LNURL-pay callback did not return an invoice")?,
+    )
+    .map_err(|e| anyhow!("Invalid invoice from LNURL-pay callback: {e}"))?;
+    validate_invoice(&invoice, amount_msat, metadata)?;
+    Ok(invoice)
+}
+
+fn pay_request_url(target: &str) -> Result<String> {
+    let target = target.trim();
+    let target = target
+        .strip_prefix("lightning:")
+        .or_else(|| target.strip_prefix("LIGHTNING:"))
+        .unwrap_or(target);
+    if let Some((user, domain)) = target.split_once('@') {
+        if user.is_empty() || domain.is_empty() || domain.contains('/') {
+            bail!("Invalid lightning address {target}");
+        }
+        let scheme = if domain.ends_with(".onion") {
+            "http"
+        } else {
+            "https"
+        };
+        return Ok(format!(
+            "{scheme}://{domain}/.well-known/lnurlp/{}",
+            user.to_lowercase()
+        ));
+    }
+    let (hrp, data, _) = bech32::decode(target).map_err(|e| anyhow!("Invalid LNURL: {e}"))?;
+    if hrp != "lnurl" {
+        bail!("Invalid LNURL: unexpected prefix {hrp}");
+    }
+    let url = String::from_utf8(Vec::<u8>::from_base32(&data)?)?;
+    Ok(url)
+}
+
+fn validate_invoice(invoice: &Invoice, amount_msat: u64, metadata: &str) -> Result<()> {
+    if invoice.amount_milli_satoshis() != Some(amount_msat) {
+        bail!("Invoice amount does not match the requested amount");
+    }
+    match invoice.description() {
+        InvoiceDescription::Hash(hash) if hash.0 == sha256::Hash::hash(metadata.as_bytes()) => {
+            Ok(())
+        }
+        _ => bail!("Invoice description hash does not match the LNURL metadata"),
+    }
+}
+
+async fn get_json(client: &Client, url: &str) -> Result<Value> {
+    let mut response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        bail!("{url} responded with {}", response.status());
+    }
+    let mut body = vec![];
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > MAX_RESPONSE_SIZE {
+            bail!("Response from {url} is too large");
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let json: Value = serde_json::from_slice(&body)?;
+    if json["status"] == "ERROR" {
+        bail!(
+            "LNURL service returned an error: {}",
+            json["reason"].as_str().unwrap_or_default()
+        );
+    }
+    Ok(json)
+}
+
+#[test]
+fn test_lightning_address_url() {
+    assert_eq!(
+        "https://example.com/.well-known/lnurlp/satoshi",
+        pay_request_url("Satoshi@example.com").unwrap()
+    );
+    assert_eq!(
+        "http://abc.onion/.well-known/lnurlp/satoshi",
+        pay_request_url("lightning:satoshi@abc.onion").unwrap()
+    );
+    assert!(pay_request_url("@example.com").is_err());
+}
+
+#[test]
+fn test_lnurl_url() {
+    // LUD-01 example
+    assert_eq!(
+        "https://service.com/api?q=3fc3645b439ce8e7f2553a69e5267081d96dcd340693afabe04be7b0ccd178df",
+        pay_request_url("LNURL1DP68GURN8GHJ7UM9WFMXJCM99E3K7MF0V9CXJ0M385EKVCENXC6R2C35XVUKXEFCV5MKVV34X5EKZD3EV56NYD3HXQURZEPEXEJXXEPNXSCRVWFNV9NXZCN9XQ6XYEFHVGCXXCMYXYMNSERXFQ5FNS").unwrap()
+    );
+    assert!(pay_request_url("lnbc1invalid").is_err());
+}
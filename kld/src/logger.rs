@@ -1,7 +1,9 @@
+use anyhow::Result;
 use lightning::util::logger::{Level, Logger};
 use log::{logger, LevelFilter, Log, Metadata, MetadataBuilder, Record};
 use once_cell::sync::OnceCell;
 use std::{process, sync::Arc};
+use tracing_log::{AsTrace, LogTracer};
 
 /// A logger instance for logfmt format (https://www.brandur.org/logfmt)
 #[derive(Debug)]
@@ -23,6 +25,23 @@ impl KldLogger {
         let _ = log::set_logger(logger).map(|()| log::set_max_level(level_filter));
     }
 
+    /// Use a tracing subscriber instead, which prints the spans an event happened in. Records of
+    /// the log crate, including those of LDK, are forwarded to it.
+    pub fn init_tracing(node_id: &str, level_filter: LevelFilter) -> Result<()> {
+        // LDK still needs the global instance to log through.
+        KLD_LOGGER.get_or_init(|| {
+            Arc::new(KldLogger {
+                node_id: node_id.to_string(),
+            })
+        });
+        LogTracer::init_with_filter(level_filter)?;
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(level_filter.as_trace())
+            .finish();
+        tracing::subscriber::set_global_default(subscriber)?;
+        Ok(())
+    }
+
     pub fn global() -> Arc<KldLogger> {
         KLD_LOGGER.get().expect("logger is not initialized").clone()
     }
@@ -1,25 +1,36 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use futures::FutureExt;
 use kld::api::{bind_api_server, MacaroonAuth};
 use kld::bitcoind::BitcoindClient;
-use kld::database::{migrate_database, LdkDatabase, WalletDatabase};
+use kld::database::{lease::NodeLease, migrate_database, LdkDatabase, WalletDatabase};
 use kld::key_generator::KeyGenerator;
 use kld::ldk::Controller;
 use kld::logger::KldLogger;
 use kld::prometheus::start_prometheus_exporter;
 use kld::wallet::Wallet;
+use kld::webhook::WebhookNotifier;
 use kld::{quit_signal, VERSION};
-use log::{error, info};
+use log::{error, info, warn};
 use settings::Settings;
 use std::sync::Arc;
 use std::time::Duration;
 
 pub fn main() -> Result<()> {
     let settings = Arc::new(Settings::load());
-    KldLogger::init(
-        &settings.node_id,
-        settings.log_level.parse().context("Invalid log level")?,
-    );
+    // The logger needs a valid log level, so the problems are reported before it is set up.
+    if let Err(problems) = settings.validate() {
+        for problem in &problems {
+            eprintln!("Invalid setting: {problem}");
+        }
+        bail!("Found {} invalid settings", problems.len());
+    }
+
+    let log_level = settings.log_level.parse().context("Invalid log level")?;
+    if settings.log_format == "tracing" {
+        KldLogger::init_tracing(&settings.node_id, log_level)?;
+    } else {
+        KldLogger::init(&settings.node_id, log_level);
+    }
 
     info!("Starting {VERSION}");
 
@@ -43,8 +54,6 @@ pub fn main() -> Result<()> {
 async fn run_kld(settings: Arc<Settings>) -> Result<()> {
     let quit_signal = quit_signal().shared();
 
-    migrate_database(&settings).await;
-
     let key_generator = Arc::new(
         KeyGenerator::init(&settings.mnemonic_path).context("cannot initialize key generator")?,
     );
@@ -54,6 +63,18 @@ async fn run_kld(settings: Arc<Settings>) -> Result<()> {
             .await
             .context("cannot connect to ldk database")?,
     );
+    // A standby waits here until it becomes the leader, before it touches the schema or any node
+    // state.
+    let lease = tokio::select!(
+        lease = NodeLease::acquire(database.clone(), &settings) => lease?,
+        _ = quit_signal.clone() => {
+            info!("Received quit signal.");
+            return Ok(());
+        }
+    );
+    migrate_database(&settings)
+        .await
+        .context("cannot migrate database")?;
     let wallet_database = WalletDatabase::new(&settings)
         .await
         .context("cannot connect to wallet database")?;
@@ -72,11 +93,15 @@ async fn run_kld(settings: Arc<Settings>) -> Result<()> {
     );
     wallet.keep_sync_with_chain()?;
 
+    let webhook = Arc::new(WebhookNotifier::new(&settings));
+    webhook.start();
+
     let controller = Controller::start_ldk(
         settings.clone(),
         database,
         bitcoind_client,
         wallet.clone(),
+        webhook.clone(),
         &key_generator.lightning_seed(),
     )
     .await
@@ -91,19 +116,46 @@ async fn run_kld(settings: Arc<Settings>) -> Result<()> {
     let server = bind_api_server(
         settings.rest_api_address.clone(),
         settings.certs_dir.clone(),
+        settings.rest_api_socket.clone(),
     )
     .await?;
 
-    tokio::select!(
+    let api = server.serve(
+        settings.clone(),
+        controller.clone(),
+        wallet.clone(),
+        webhook,
+        macaroon_auth,
+        quit_signal.clone(),
+    );
+    tokio::pin!(api);
+    let result = tokio::select!(
         _ = quit_signal.clone() => {
             info!("Received quit signal.");
-            Ok(())
+            None
         },
-        result = start_prometheus_exporter(settings.exporter_address.clone(), controller.clone(), quit_signal.clone()) => {
-            result.context("Prometheus exporter failed")
+        result = start_prometheus_exporter(settings.exporter_address.clone(), controller.clone(), wallet.clone(), quit_signal.clone()) => {
+            Some(result.context("Prometheus exporter failed"))
         },
-        result = server.serve(controller.clone(), wallet.clone(), macaroon_auth, quit_signal) => {
-            result.context("REST API failed")
+        result = &mut api => {
+            Some(result.context("REST API failed"))
+        },
+        result = lease.hold() => {
+            // Another process might be running the node already. The database is fenced, so
+            // stopping the controller doesn't persist anything.
+            return result.context("Lost database lease");
         }
-    )
+    );
+    // Stop taking API requests before the node stops so that no request sees a half stopped
+    // node, then flush the lightning state.
+    let result = match result {
+        Some(result) => result,
+        None => api.await.context("REST API failed"),
+    };
+    let shutdown = controller.shutdown().await;
+    if let Err(e) = lease.release().await {
+        warn!("Failed to release database lease: {e}");
+    }
+    result?;
+    shutdown
 }
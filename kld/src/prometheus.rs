@@ -14,6 +14,7 @@ use once_cell::sync::{Lazy, OnceCell};
 use prometheus::{self, register_gauge, Encoder, Gauge, TextEncoder};
 
 use crate::ldk::LightningInterface;
+use crate::wallet::WalletInterface;
 
 static START: OnceCell<Instant> = OnceCell::new();
 
@@ -39,12 +40,51 @@ static PEER_COUNT: Lazy<Gauge> =
 static WALLET_BALANCE: Lazy<Gauge> =
     Lazy::new(|| register_gauge!("wallet_balance", "The bitcoin wallet balance").unwrap());
 
+static REORG_COUNT: Lazy<Gauge> = Lazy::new(|| {
+    register_gauge!("reorg_count", "The number of chain reorgs since startup").unwrap()
+});
+
+static LAST_REORG_DEPTH: Lazy<Gauge> = Lazy::new(|| {
+    register_gauge!(
+        "last_reorg_depth",
+        "The number of blocks replaced by the last reorg"
+    )
+    .unwrap()
+});
+
+static CORRUPT_CHANNEL_MONITORS: Lazy<Gauge> = Lazy::new(|| {
+    register_gauge!(
+        "corrupt_channel_monitors",
+        "The number of channel monitors skipped at startup because they were corrupt"
+    )
+    .unwrap()
+});
+
 async fn response_examples(
     lightning_metrics: Arc<dyn LightningInterface + Send + Sync>,
+    wallet: Arc<dyn WalletInterface + Send + Sync>,
     req: Request<Body>,
 ) -> hyper::Result<Response<Body>> {
     match (req.method(), req.uri().path()) {
-        (&Method::GET, "/health") => Ok(Response::new(Body::from("OK"))),
+        (&Method::GET, "/health") => {
+            let mut status = "OK".to_string();
+            if lightning_metrics.maintenance_mode() {
+                status.push_str(" - maintenance mode");
+            }
+            let corrupt_monitors = lightning_metrics.corrupt_channel_monitors();
+            if corrupt_monitors > 0 {
+                status.push_str(&format!(
+                    " - {corrupt_monitors} corrupt channel monitors skipped"
+                ));
+            }
+            if let Some(progress) = wallet.rescan_progress() {
+                status.push_str(&format!(
+                    " - wallet rescan {}% complete",
+                    (progress * 100_f32).round()
+                ));
+            }
+            Ok(Response::new(Body::from(status)))
+        }
         (&Method::GET, "/pid") => Ok(Response::new(Body::from(process::id().to_string()))),
         (&Method::GET, "/metrics") => {
             UPTIME.set(START.get().unwrap().elapsed().as_millis() as f64);
@@ -52,6 +92,9 @@ async fn response_examples(
             CHANNEL_COUNT.set(lightning_metrics.graph_num_channels() as f64);
             PEER_COUNT.set(lightning_metrics.num_peers() as f64);
             WALLET_BALANCE.set(lightning_metrics.wallet_balance() as f64);
+            REORG_COUNT.set(lightning_metrics.num_reorgs() as f64);
+            LAST_REORG_DEPTH.set(lightning_metrics.last_reorg_depth() as f64);
+            CORRUPT_CHANNEL_MONITORS.set(lightning_metrics.corrupt_channel_monitors() as f64);
             let metric_families = prometheus::gather();
             let mut buffer = vec![];
             let encoder = TextEncoder::new();
@@ -75,14 +118,17 @@ fn not_found() -> Response<Body> {
 pub async fn start_prometheus_exporter(
     address: String,
     lightning_metrics: Arc<dyn LightningInterface + Send + Sync>,
+    wallet: Arc<dyn WalletInterface + Send + Sync>,
     quit_signal: Shared<impl Future<Output = ()>>,
 ) -> Result<()> {
     START.set(Instant::now()).unwrap();
     let addr = address.parse().context("Failed to parse exporter")?;
     let make_service = make_service_fn(move |_| {
         let lightning_metrics_clone = lightning_metrics.clone();
-        let service =
-            service_fn(move |req| response_examples(lightning_metrics_clone.clone(), req));
+        let wallet_clone = wallet.clone();
+        let service = service_fn(move |req| {
+            response_examples(lightning_metrics_clone.clone(), wallet_clone.clone(), req)
+        });
         async move { Ok::<_, hyper::Error>(service) }
     });
 
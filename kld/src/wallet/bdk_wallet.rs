@@ -1,14 +1,18 @@
 use std::{
     str::FromStr,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{bail, Result};
+use api::{CodedError, ErrorCode};
 use async_trait::async_trait;
 use bdk::{
     bitcoin::util::bip32::ExtendedPrivKey,
-    bitcoincore_rpc::{bitcoincore_rpc_json::ScanningDetails, RpcApi},
+    bitcoincore_rpc::{bitcoincore_rpc_json::ScanningDetails, jsonrpc, Error as RpcError, RpcApi},
     blockchain::{
         rpc::{Auth, RpcSyncParams},
         ConfigurableBlockchain, RpcBlockchain, RpcConfig,
@@ -19,16 +23,16 @@ use bdk::{
 };
 use bitcoin::{
     util::bip32::{ChildNumber, DerivationPath},
-    Address, OutPoint, Script, Transaction,
+    Address, OutPoint, Script, Transaction, Txid,
 };
 use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
 use lightning_block_sync::BlockSource;
 use log::{error, info};
 use settings::{Network, Settings};
 
-use crate::bitcoind::Synchronised;
+use crate::bitcoind::{BitcoindAuth, Synchronised};
 
-use super::WalletInterface;
+use super::{watch_only::WatchOnlyWallet, WalletInterface, WatchDescriptorDatabase};
 
 pub struct Wallet<
     D: Database + BatchDatabase + BatchOperations,
@@ -38,11 +42,15 @@ pub struct Wallet<
     wallet: Arc<Mutex<bdk::Wallet<D>>>,
     bitcoind_client: Arc<B>,
     settings: Arc<Settings>,
+    rescan_progress: Arc<Mutex<Option<f32>>>,
+    watch_only: Arc<Mutex<Vec<WatchOnlyWallet>>>,
+    // The watch-only wallets stay locked while they sync so their balance is cached after each sync.
+    watch_only_balance: Arc<Mutex<Balance>>,
 }
 
 #[async_trait]
 impl<
-        D: Database + BatchDatabase + BatchOperations + Send + 'static,
+        D: Database + BatchDatabase + BatchOperations + WatchDescriptorDatabase + Send + 'static,
         B: BlockSource + FeeEstimator + BroadcasterInterface + Synchronised,
     > WalletInterface for Wallet<D, B>
 {
@@ -53,6 +61,10 @@ impl<
         }
     }
 
+    fn watch_only_balance(&self) -> Result<Balance> {
+        Ok(self.watch_only_balance.lock().unwrap().clone())
+    }
+
     async fn transfer(
         &self,
         address: Address,
@@ -62,7 +74,10 @@ impl<
         utxos: Vec<OutPoint>,
     ) -> Result<(Transaction, TransactionDetails)> {
         if !self.bitcoind_client.is_synchronised().await? {
-            bail!("Bitcoind is syncronising the blockchain")
+            bail!(CodedError::new(
+                ErrorCode::BitcoindSyncing,
+                "Bitcoind is syncronising the blockchain"
+            ))
         }
         let height = match self.bitcoind_client.get_best_block().await {
             Ok((_, Some(height))) => height,
@@ -89,6 +104,11 @@ impl<
                     tx_builder.fee_rate(self.to_bdk_fee_rate(fee_rate));
                 }
                 let (mut psbt, tx_details) = tx_builder.finish()?;
+                check_reserve(
+                    self.settings.wallet_reserve_sats,
+                    &wallet.get_balance()?,
+                    &tx_details,
+                )?;
                 let _finalized = wallet.sign(&mut psbt, SignOptions::default())?;
                 let tx = psbt.extract_tx();
 
@@ -111,10 +131,101 @@ impl<
             .get_address(bdk::wallet::AddressIndex::LastUnused)?;
         Ok(address)
     }
+
+    fn estimate_funding_fee(&self, fee_rate: api::FeeRate) -> u64 {
+        self.to_bdk_fee_rate(fee_rate).fee_vb(FUNDING_TX_VBYTES)
+    }
+
+    async fn rescan(&self, birthday_height: Option<u32>) -> Result<()> {
+        {
+            let mut progress = self.rescan_progress.lock().unwrap();
+            if progress.is_some() {
+                bail!("Wallet rescan is already in progress")
+            }
+            *progress = Some(0.0);
+        }
+        let settings = self.settings.clone();
+        let wallet = self.wallet.clone();
+        let progress = self.rescan_progress.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let result = rescan_blocking(&settings, &wallet, &progress, birthday_height);
+            *progress.lock().unwrap() = None;
+            result
+        })
+        .await?;
+        match &result {
+            Ok(()) => info!("Wallet rescan complete"),
+            Err(e) => error!("Wallet rescan failed: {e}"),
+        }
+        result
+    }
+
+    fn rescan_progress(&self) -> Option<f32> {
+        *self.rescan_progress.lock().unwrap()
+    }
+
+    async fn import_descriptor(&self, descriptor: String) -> Result<()> {
+        let settings = self.settings.clone();
+        let wallet = self.wallet.clone();
+        let watch_only = self.watch_only.clone();
+        tokio::task::spawn_blocking(move || {
+            if watch_only
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|w| w.descriptor == descriptor)
+            {
+                return Ok(());
+            }
+            // Scan the whole chain for the new descriptor.
+            let watch_only_wallet = WatchOnlyWallet::new(&settings, &descriptor, 0)?;
+            wallet
+                .lock()
+                .expect("Cannot obtain mutex for wallet")
+                .database()
+                .persist_watch_descriptor(&descriptor, 0)?;
+            watch_only.lock().unwrap().push(watch_only_wallet);
+            info!("Watching descriptor {descriptor}");
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn abandon_transaction(&self, txid: Txid) -> Result<Vec<OutPoint>> {
+        let settings = self.settings.clone();
+        let wallet = self.wallet.clone();
+        tokio::task::spawn_blocking(move || {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let blockchain =
+                RpcBlockchain::from_config(&rpc_config(&settings, WALLET_NAME, now, false)?)?;
+            let tx_info = blockchain.get_transaction(&txid, None)?;
+            if tx_info.info.confirmations > 0 {
+                bail!("Transaction {txid} is already confirmed");
+            }
+            // Fails if the transaction is still in the mempool.
+            blockchain.abandon_transaction(&txid)?;
+            wallet
+                .lock()
+                .expect("Cannot obtain mutex for wallet")
+                .sync(&blockchain, SyncOptions::default())?;
+            let freed: Vec<OutPoint> = tx_info
+                .transaction()?
+                .input
+                .iter()
+                .map(|input| input.previous_output)
+                .collect();
+            info!(
+                "Abandoned transaction {txid}, freed {} outputs",
+                freed.len()
+            );
+            Ok(freed)
+        })
+        .await?
+    }
 }
 
 impl<
-        D: Database + BatchDatabase + BatchOperations + Send + 'static,
+        D: Database + BatchDatabase + BatchOperations + WatchDescriptorDatabase + Send + 'static,
         B: BlockSource + FeeEstimator + Synchronised,
     > Wallet<D, B>
 {
@@ -154,36 +265,34 @@ impl<
             wallet: bdk_wallet,
             bitcoind_client,
             settings,
+            rescan_progress: Arc::new(Mutex::new(None)),
+            watch_only: Arc::new(Mutex::new(vec![])),
+            watch_only_balance: Arc::new(Mutex::new(Balance::default())),
         })
     }
 
     pub fn keep_sync_with_chain(&self) -> Result<()> {
-        let url = format!(
-            "http://{}:{}",
-            self.settings.bitcoind_rpc_host, self.settings.bitcoind_rpc_port
-        );
-
         // Sometimes we get wallet sync failure - https://github.com/bitcoindevkit/bdk/issues/859
         // It prevents a historical sync. So only add funds while kld is running.
-        let rpc_sync_params = RpcSyncParams {
-            start_script_count: 100,
-            start_time: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
-            force_start_time: false,
-            poll_rate_sec: 10,
-        };
-
-        let wallet_config = RpcConfig {
-            url: url.clone(),
-            auth: Auth::Cookie {
-                file: self.settings.bitcoin_cookie_path.clone().into(),
-            },
-            network: self.settings.bitcoin_network.into(),
-            wallet_name: "kld-wallet".to_string(),
-            sync_params: Some(rpc_sync_params),
-        };
+        let start_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let wallet_config = rpc_config(&self.settings, WALLET_NAME, start_time, false)?;
+        let url = wallet_config.url.clone();
         let blockchain = RpcBlockchain::from_config(&wallet_config)?;
 
+        let watch_descriptors = self
+            .wallet
+            .lock()
+            .expect("Cannot obtain mutex for wallet")
+            .database()
+            .watch_descriptors()?;
+        for (descriptor, sync_time) in watch_descriptors {
+            let watch_only_wallet = WatchOnlyWallet::new(&self.settings, &descriptor, sync_time)?;
+            self.watch_only.lock().unwrap().push(watch_only_wallet);
+        }
+
         let wallet_clone = self.wallet.clone();
+        let watch_only_clone = self.watch_only.clone();
+        let watch_only_balance = self.watch_only_balance.clone();
         tokio::task::spawn_blocking(move || {
             loop {
                 match blockchain.get_wallet_info() {
@@ -207,6 +316,41 @@ impl<
                                 } else {
                                     info!("Wallet is synchronised to blockchain");
                                 }
+                                let watch_only_wallets = watch_only_clone.lock().unwrap();
+                                let mut total = Balance::default();
+                                for watch_only in watch_only_wallets.iter() {
+                                    match watch_only.sync() {
+                                        Ok(sync_time) => {
+                                            if let Err(e) = wallet_clone
+                                                .lock()
+                                                .expect("Cannot obtain mutex for wallet")
+                                                .database()
+                                                .persist_watch_descriptor(
+                                                    &watch_only.descriptor,
+                                                    sync_time,
+                                                )
+                                            {
+                                                error!(
+                                                    "Could not persist watch-only sync time: {e}"
+                                                );
+                                            }
+                                        }
+                                        Err(e) => {
+                                            error!(
+                                                "Watch-only wallet sync failed for {}: {e}",
+                                                watch_only.descriptor
+                                            );
+                                        }
+                                    }
+                                    match watch_only.balance() {
+                                        Ok(balance) => total = total + balance,
+                                        Err(e) => error!(
+                                            "Could not get watch-only balance for {}: {e}",
+                                            watch_only.descriptor
+                                        ),
+                                    }
+                                }
+                                *watch_only_balance.lock().unwrap() = total;
                             }
                         }
                     }
@@ -235,7 +379,12 @@ impl<
             .fee_rate(self.to_bdk_fee_rate(fee_rate))
             .enable_rbf();
 
-        let (mut psbt, _tx_details) = tx_builder.finish()?;
+        let (mut psbt, tx_details) = tx_builder.finish()?;
+        check_reserve(
+            self.settings.wallet_reserve_sats,
+            &wallet.get_balance()?,
+            &tx_details,
+        )?;
 
         let _finalized = wallet.sign(&mut psbt, SignOptions::default())?;
 
@@ -265,6 +414,104 @@ impl<
     }
 }
 
+const WALLET_NAME: &str = "kld-wallet";
+
+// One P2WPKH input, the P2WSH funding output and a P2WPKH change output.
+const FUNDING_TX_VBYTES: usize = 153;
+
+pub(super) fn rpc_config(
+    settings: &Settings,
+    wallet_name: &str,
+    start_time: u64,
+    force_start_time: bool,
+) -> Result<RpcConfig> {
+    let rpc_sync_params = RpcSyncParams {
+        start_script_count: 100,
+        start_time,
+        force_start_time,
+        poll_rate_sec: 10,
+    };
+
+    Ok(RpcConfig {
+        url: format!(
+            "http://{}:{}",
+            settings.bitcoind_rpc_host, settings.bitcoind_rpc_port
+        ),
+        auth: match BitcoindAuth::from_settings(settings)? {
+            BitcoindAuth::Cookie(file) => Auth::Cookie { file: file.into() },
+            BitcoindAuth::UserPass { user, password } => Auth::UserPass {
+                username: user,
+                password,
+            },
+        },
+        network: settings.bitcoin_network.into(),
+        wallet_name: wallet_name.to_string(),
+        sync_params: Some(rpc_sync_params),
+    })
+}
+
+fn rescan_blocking<D: BatchDatabase>(
+    settings: &Settings,
+    wallet: &Mutex<bdk::Wallet<D>>,
+    progress: &Arc<Mutex<Option<f32>>>,
+    birthday_height: Option<u32>,
+) -> Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let monitor = RpcBlockchain::from_config(&rpc_config(settings, WALLET_NAME, now, false)?)?;
+    let rescanner = RpcBlockchain::from_config(&rpc_config(settings, WALLET_NAME, now, false)?)?;
+    let start_height = birthday_height.unwrap_or(0);
+    info!("Rescanning wallet from block {start_height}");
+
+    let done = Arc::new(AtomicBool::new(false));
+    let done_clone = done.clone();
+    let progress_clone = progress.clone();
+    let monitor_thread = std::thread::spawn(move || {
+        while !done_clone.load(Ordering::Acquire) {
+            if let Ok(wallet_info) = monitor.get_wallet_info() {
+                if let Some(ScanningDetails::Scanning { progress, .. }) = wallet_info.scanning {
+                    *progress_clone.lock().unwrap() = Some(progress);
+                }
+            }
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    });
+    // The rescan in bitcoind can take hours, so the wallet is only locked for the sync which
+    // picks up the transactions it found afterwards. The call times out long before a large
+    // rescan is done but bitcoind carries on with it, so only errors from bitcoind itself fail.
+    let result = rescanner.rescan_blockchain(Some(start_height as usize), None);
+    if let Err(RpcError::JsonRpc(jsonrpc::Error::Rpc(e))) = result {
+        done.store(true, Ordering::Release);
+        let _ = monitor_thread.join();
+        bail!("Bitcoind failed to rescan the wallet: {}", e.message);
+    }
+    while let Some(ScanningDetails::Scanning { .. }) = rescanner.get_wallet_info()?.scanning {
+        std::thread::sleep(Duration::from_secs(1));
+    }
+    done.store(true, Ordering::Release);
+    let _ = monitor_thread.join();
+    let blockchain = RpcBlockchain::from_config(&rpc_config(settings, WALLET_NAME, now, false)?)?;
+    Ok(wallet
+        .lock()
+        .expect("Cannot obtain mutex for wallet")
+        .sync(&blockchain, SyncOptions::default())?)
+}
+
+/// Fail if the transaction would leave less than the reserve of confirmed funds in the wallet.
+fn check_reserve(reserve: u64, balance: &Balance, tx_details: &TransactionDetails) -> Result<()> {
+    if reserve == 0 {
+        return Ok(());
+    }
+    let spent = tx_details.sent.saturating_sub(tx_details.received);
+    let remaining = balance.confirmed.saturating_sub(spent);
+    if remaining < reserve {
+        bail!(CodedError::new(
+            ErrorCode::InsufficientFunds,
+            format!("Spending {spent} sats would leave {remaining} sats in the wallet, below the reserve of {reserve} sats")
+        ));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use std::{
@@ -273,15 +520,43 @@ mod test {
     };
 
     use anyhow::Result;
-    use bdk::{database::MemoryDatabase, wallet::get_funded_wallet, Balance};
+    use api::{CodedError, ErrorCode};
+    use bdk::{
+        database::{AnyDatabase, MemoryDatabase},
+        wallet::get_funded_wallet,
+        Balance,
+    };
     use bitcoin::Address;
     use settings::Settings;
     use test_utils::{TEST_ADDRESS, TEST_WPKH};
 
-    use crate::{bitcoind::MockBitcoindClient, wallet::WalletInterface};
+    use crate::{
+        bitcoind::MockBitcoindClient,
+        wallet::{WalletInterface, WatchDescriptorDatabase},
+    };
 
     use super::Wallet;
 
+    impl WatchDescriptorDatabase for MemoryDatabase {
+        fn watch_descriptors(&self) -> Result<Vec<(String, u64)>, bdk::Error> {
+            Ok(vec![])
+        }
+
+        fn persist_watch_descriptor(&self, _: &str, _: u64) -> Result<(), bdk::Error> {
+            Ok(())
+        }
+    }
+
+    impl WatchDescriptorDatabase for AnyDatabase {
+        fn watch_descriptors(&self) -> Result<Vec<(String, u64)>, bdk::Error> {
+            Ok(vec![])
+        }
+
+        fn persist_watch_descriptor(&self, _: &str, _: u64) -> Result<(), bdk::Error> {
+            Ok(())
+        }
+    }
+
     #[test]
     fn test_fee_rate() -> Result<()> {
         let wallet = Wallet::new(
@@ -321,6 +596,9 @@ mod test {
             settings: Arc::new(Settings::default()),
             bitcoind_client: bitcoind_client.clone(),
             wallet: Arc::new(Mutex::new(bdk_wallet)),
+            rescan_progress: Arc::new(Mutex::new(None)),
+            watch_only: Arc::new(Mutex::new(vec![])),
+            watch_only_balance: Arc::new(Mutex::new(Balance::default())),
         };
 
         let res = wallet
@@ -345,6 +623,9 @@ mod test {
             settings: Arc::new(Settings::default()),
             bitcoind_client: bitcoind_client.clone(),
             wallet: Arc::new(Mutex::new(bdk_wallet)),
+            rescan_progress: Arc::new(Mutex::new(None)),
+            watch_only: Arc::new(Mutex::new(vec![])),
+            watch_only_balance: Arc::new(Mutex::new(Balance::default())),
         };
 
         let (tx, tx_details) = wallet
@@ -366,4 +647,35 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_transfer_keeps_reserve() -> Result<()> {
+        let (bdk_wallet, _, _) = get_funded_wallet(TEST_WPKH);
+        let mut settings = Settings::default();
+        settings.wallet_reserve_sats = 10_000;
+        let wallet = Wallet {
+            settings: Arc::new(settings),
+            bitcoind_client: Arc::new(MockBitcoindClient::default()),
+            wallet: Arc::new(Mutex::new(bdk_wallet)),
+            rescan_progress: Arc::new(Mutex::new(None)),
+            watch_only: Arc::new(Mutex::new(vec![])),
+            watch_only_balance: Arc::new(Mutex::new(Balance::default())),
+        };
+        let address = Address::from_str(TEST_ADDRESS)?;
+
+        assert!(wallet
+            .transfer(address.clone(), u64::MAX, None, None, vec![])
+            .await
+            .is_err());
+        let error = wallet
+            .transfer(address.clone(), 45_000, None, None, vec![])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            Some(ErrorCode::InsufficientFunds),
+            error.downcast_ref::<CodedError>().map(|e| e.code)
+        );
+        wallet.transfer(address, 20_000, None, None, vec![]).await?;
+        Ok(())
+    }
 }
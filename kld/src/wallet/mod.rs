@@ -1,5 +1,7 @@
 mod bdk_wallet;
 mod wallet_interface;
+mod watch_only;
 
 pub use bdk_wallet::Wallet;
 pub use wallet_interface::WalletInterface;
+pub use watch_only::WatchDescriptorDatabase;
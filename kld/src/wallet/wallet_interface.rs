@@ -2,12 +2,15 @@ use anyhow::Result;
 use api::FeeRate;
 use async_trait::async_trait;
 use bdk::{wallet::AddressInfo, Balance, TransactionDetails};
-use bitcoin::{Address, OutPoint, Transaction};
+use bitcoin::{Address, OutPoint, Transaction, Txid};
 
 #[async_trait]
 pub trait WalletInterface {
     fn balance(&self) -> Result<Balance>;
 
+    /// Balance of the imported watch-only descriptors. The node never spends these funds.
+    fn watch_only_balance(&self) -> Result<Balance>;
+
     /// Set amount to u64::MAX to drain the wallet.
     async fn transfer(
         &self,
@@ -19,4 +22,21 @@ pub trait WalletInterface {
     ) -> Result<(Transaction, TransactionDetails)>;
 
     fn new_address(&self) -> Result<AddressInfo>;
+
+    /// Fee (sats) of a typical channel funding transaction with one input and change.
+    fn estimate_funding_fee(&self, fee_rate: FeeRate) -> u64;
+
+    /// Rescan the blockchain for wallet transactions, starting at the birthday height or
+    /// genesis. Returns once the rescan has completed.
+    async fn rescan(&self, birthday_height: Option<u32>) -> Result<()>;
+
+    /// Progress (0.0 - 1.0) of the rescan currently running, if any.
+    fn rescan_progress(&self) -> Option<f32>;
+
+    /// Watch an external descriptor. Only public keys are accepted.
+    async fn import_descriptor(&self, descriptor: String) -> Result<()>;
+
+    /// Forget an unconfirmed transaction which is no longer in the mempool so its inputs can be
+    /// spent again. Returns the freed outpoints.
+    async fn abandon_transaction(&self, txid: Txid) -> Result<Vec<OutPoint>>;
 }
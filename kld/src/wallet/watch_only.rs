@@ -0,0 +1,67 @@
+use std::{
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use bdk::{
+    bitcoin::secp256k1::Secp256k1,
+    blockchain::{ConfigurableBlockchain, RpcBlockchain},
+    database::MemoryDatabase,
+    miniscript::{Descriptor, DescriptorPublicKey},
+    wallet::wallet_name_from_descriptor,
+    Balance, SyncOptions,
+};
+use settings::Settings;
+
+use super::bdk_wallet::rpc_config;
+
+/// Storage for the external descriptors watched by the wallet.
+pub trait WatchDescriptorDatabase {
+    /// Watched descriptors with the unix timestamp (seconds) the next sync should scan from.
+    fn watch_descriptors(&self) -> Result<Vec<(String, u64)>, bdk::Error>;
+
+    fn persist_watch_descriptor(&self, descriptor: &str, sync_time: u64) -> Result<(), bdk::Error>;
+}
+
+/// A wallet for an external descriptor. It is kept apart from the node's own wallet so its UTXOs
+/// can never be selected when funding channels or withdrawing.
+pub(crate) struct WatchOnlyWallet {
+    pub descriptor: String,
+    wallet: bdk::Wallet<MemoryDatabase>,
+    blockchain: RpcBlockchain,
+}
+
+impl WatchOnlyWallet {
+    pub fn new(settings: &Settings, descriptor: &str, sync_time: u64) -> Result<WatchOnlyWallet> {
+        // Only public keys are accepted so there is nothing in here the node could sign with.
+        Descriptor::<DescriptorPublicKey>::from_str(descriptor)?;
+        let network = settings.bitcoin_network.into();
+        let wallet = bdk::Wallet::new(descriptor, None, network, MemoryDatabase::new())?;
+        let wallet_name =
+            wallet_name_from_descriptor(descriptor, None, network, &Secp256k1::new())?;
+        let blockchain = RpcBlockchain::from_config(&rpc_config(
+            settings,
+            &format!("kld-watch-{wallet_name}"),
+            sync_time,
+            false,
+        )?)?;
+        Ok(WatchOnlyWallet {
+            descriptor: descriptor.to_string(),
+            wallet,
+            blockchain,
+        })
+    }
+
+    /// Returns the time the sync started which is where the next sync after a restart can
+    /// continue from.
+    pub fn sync(&self) -> Result<u64> {
+        let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.wallet.sync(&self.blockchain, SyncOptions::default())?;
+        Ok(started)
+    }
+
+    pub fn balance(&self) -> Result<Balance> {
+        Ok(self.wallet.get_balance()?)
+    }
+}
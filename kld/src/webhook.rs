@@ -0,0 +1,255 @@
+//! HTTP notifications of node events for integrations that don't hold a websocket open. The
+//! same events are sent to connected websocket clients.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use bitcoin::hashes::{
+    hmac::{Hmac, HmacEngine},
+    sha256, Hash, HashEngine,
+};
+use hex::ToHex;
+use log::{debug, error, warn};
+use reqwest::Client;
+use serde_json::{json, Value};
+use settings::Settings;
+use tokio::sync::broadcast;
+
+/// Header containing the hex encoded HMAC-SHA256 of the request body.
+pub const SIGNATURE_HEADER: &str = "X-KLD-Signature";
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// Events buffered for each websocket client. Slower clients miss events.
+const WEBSOCKET_BUFFER: usize = 1024;
+
+#[derive(Clone)]
+pub struct PendingWebhook {
+    pub id: u64,
+    pub event: String,
+    /// Unix timestamp (seconds) of when the event was raised.
+    pub timestamp: u64,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    body: String,
+    signature: String,
+    created: Instant,
+    next_attempt: Instant,
+}
+
+pub struct WebhookNotifier {
+    url: String,
+    secret: String,
+    max_pending: usize,
+    max_age: Duration,
+    client: Client,
+    queue: Mutex<VecDeque<PendingWebhook>>,
+    next_id: AtomicU64,
+    websocket: broadcast::Sender<String>,
+}
+
+impl WebhookNotifier {
+    pub fn new(settings: &Settings) -> WebhookNotifier {
+        WebhookNotifier {
+            url: settings.webhook_url.clone(),
+            secret: settings.webhook_secret.clone(),
+            max_pending: settings.webhook_max_pending,
+            max_age: Duration::from_secs(settings.webhook_max_age_secs),
+            client: Client::new(),
+            queue: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(0),
+            websocket: broadcast::channel(WEBSOCKET_BUFFER).0,
+        }
+    }
+
+    /// Deliver queued events in the background until the process exits.
+    pub fn start(self: &Arc<Self>) {
+        if self.url.is_empty() {
+            return;
+        }
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            loop {
+                notifier.deliver_due().await;
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    /// Queue the event for delivery. This never blocks on the network so it is safe to call
+    /// while handling LDK events.
+    pub fn notify(&self, event: &str, data: Value) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let body = json!({
+            "event": event,
+            "timestamp": timestamp,
+            "data": data,
+        })
+        .to_string();
+        // Fails only if no websocket client is connected.
+        let _ = self.websocket.send(body.clone());
+        if self.url.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let pending = PendingWebhook {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            event: event.to_string(),
+            timestamp,
+            attempts: 0,
+            last_error: None,
+            signature: sign(&self.secret, &body),
+            body,
+            created: now,
+            next_attempt: now,
+        };
+        let mut queue = self.queue.lock().unwrap();
+        if queue.len() >= self.max_pending {
+            if let Some(dropped) = queue.pop_front() {
+                error!(
+                    "Webhook queue is full. Dropping {} event from {}",
+                    dropped.event, dropped.timestamp
+                );
+            }
+        }
+        queue.push_back(pending);
+    }
+
+    /// Receive the JSON body of every event raised from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.websocket.subscribe()
+    }
+
+    /// Events which have not been delivered yet.
+    pub fn pending(&self) -> Vec<PendingWebhook> {
+        self.queue.lock().unwrap().iter().cloned().collect()
+    }
+
+    async fn deliver_due(&self) {
+        let now = Instant::now();
+        let due: Vec<PendingWebhook> = {
+            let mut queue = self.queue.lock().unwrap();
+            queue.retain(|pending| {
+                let expired = now.duration_since(pending.created) > self.max_age;
+                if expired {
+                    error!(
+                        "Dropping webhook {} event from {} after {} failed attempts: {}",
+                        pending.event,
+                        pending.timestamp,
+                        pending.attempts,
+                        pending.last_error.as_deref().unwrap_or_default()
+                    );
+                }
+                !expired
+            });
+            queue
+                .iter()
+                .filter(|pending| pending.next_attempt <= now)
+                .cloned()
+                .collect()
+        };
+        for pending in due {
+            let result = post(&self.client, &self.url, &pending.body, &pending.signature).await;
+            let mut queue = self.queue.lock().unwrap();
+            match result {
+                Ok(()) => {
+                    debug!("Delivered webhook {} to {}", pending.event, self.url);
+                    queue.retain(|p| p.id != pending.id);
+                }
+                Err(e) => {
+                    if let Some(p) = queue.iter_mut().find(|p| p.id == pending.id) {
+                        p.attempts += 1;
+                        p.next_attempt = Instant::now() + backoff(p.attempts);
+                        warn!(
+                            "Webhook {} delivery attempt {} failed: {e}",
+                            p.event, p.attempts
+                        );
+                        p.last_error = Some(e.to_string());
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn backoff(attempts: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+async fn post(client: &Client, url: &str, body: &str, signature: &str) -> Result<()> {
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .body(body.to_string())
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        bail!("Receiver responded with {}", response.status());
+    }
+    Ok(())
+}
+
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut engine = HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(body.as_bytes());
+    Hmac::<sha256::Hash>::from_engine(engine)
+        .into_inner()
+        .encode_hex()
+}
+
+#[test]
+fn test_sign() {
+    // RFC 4231 test case 2
+    assert_eq!(
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+        sign("Jefe", "what do ya want for nothing?")
+    );
+}
+
+#[test]
+fn test_backoff() {
+    assert_eq!(Duration::from_secs(1), backoff(1));
+    assert_eq!(Duration::from_secs(8), backoff(4));
+    assert_eq!(MAX_BACKOFF, backoff(40));
+}
+
+#[test]
+fn test_pending_queue_is_bounded() {
+    let mut settings = Settings::default();
+    settings.webhook_url = "http://127.0.0.1:1/webhook".to_string();
+    settings.webhook_max_pending = 2;
+    let notifier = WebhookNotifier::new(&settings);
+    notifier.notify("channel_ready", json!({}));
+    notifier.notify("channel_closed", json!({}));
+    notifier.notify("payment_sent", json!({}));
+
+    let pending = notifier.pending();
+    assert_eq!(2, pending.len());
+    assert_eq!("channel_closed", pending[0].event);
+    assert_eq!("payment_sent", pending[1].event);
+}
+
+#[test]
+fn test_subscribe_without_webhook_url() {
+    let notifier = WebhookNotifier::new(&Settings::default());
+    let mut events = notifier.subscribe();
+    notifier.notify("channel_ready", json!({}));
+
+    let event: Value = serde_json::from_str(&events.try_recv().unwrap()).unwrap();
+    assert_eq!("channel_ready", event["event"]);
+    assert!(notifier.pending().is_empty());
+}
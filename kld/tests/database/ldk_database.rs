@@ -1,57 +1,656 @@
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::vec;
 
 use anyhow::Result;
 use bitcoin::blockdata::block::{Block, BlockHeader};
 use bitcoin::hashes::Hash;
-use bitcoin::{Network, TxMerkleNode};
+use bitcoin::{Network, Script, TxMerkleNode, TxOut, Txid};
+use futures::TryStreamExt;
+use kld::database::forward::{EarningsBucket, Forward};
+use kld::database::lease::NodeLease;
+use kld::database::payment::{Payment, PaymentDirection, PaymentStatus};
 use kld::database::peer::Peer;
-use kld::database::LdkDatabase;
+use kld::database::spendable_output::{SpendableOutput, SweepStatus};
+use kld::database::{connection, is_statement_timeout, migrate_database, LdkDatabase};
+use kld::ldk::controller::reconcile_pending_payments;
 
 use kld::logger::KldLogger;
 use lightning::chain::chaininterface::{BroadcasterInterface, FeeEstimator};
 use lightning::chain::chainmonitor::ChainMonitor;
-use lightning::chain::keysinterface::{InMemorySigner, KeysManager};
+use lightning::chain::keysinterface::{InMemorySigner, KeysManager, SpendableOutputDescriptor};
+use lightning::chain::transaction::OutPoint;
 use lightning::chain::Filter;
+use lightning::ln::channelmanager::RecentPaymentDetails;
 use lightning::ln::functional_test_utils::*;
 use lightning::ln::msgs::NetAddress;
+use lightning::ln::{PaymentHash, PaymentPreimage};
 use lightning::routing::gossip::{NetworkGraph, NodeId};
 use lightning::routing::router::DefaultRouter;
 use lightning::routing::scoring::{ProbabilisticScorer, ProbabilisticScoringParameters};
 use lightning::util::events::{ClosureReason, MessageSendEventsProvider};
 use lightning::util::persist::Persister;
+use lightning::util::ser::{Hostname, Writeable};
 use lightning::util::test_utils as ln_utils;
 use lightning::{check_added_monitors, check_closed_broadcast, check_closed_event};
 use test_utils::random_public_key;
 
 use super::{create_database, with_cockroach};
+use crate::test_settings;
 
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_peers() -> Result<()> {
     with_cockroach(|settings| async move {
         let database = LdkDatabase::new(settings).await?;
 
-        let peer = Peer {
-            public_key: random_public_key(),
-            net_address: NetAddress::IPv4 {
-                addr: [128, 23, 34, 2],
-                port: 1000,
-            },
+        let public_key = random_public_key();
+        let ipv4 = NetAddress::IPv4 {
+            addr: [128, 23, 34, 2],
+            port: 1000,
+        };
+        let ipv6 = NetAddress::IPv6 {
+            addr: [1; 16],
+            port: 1000,
+        };
+        let hostname = NetAddress::Hostname {
+            hostname: Hostname::try_from("node.example.com".to_string()).unwrap(),
+            port: 1000,
         };
-        let saved_peer = database.fetch_peer(&peer.public_key).await?;
+        let onion = NetAddress::OnionV3 {
+            ed25519_pubkey: [3; 32],
+            checksum: 1,
+            version: 3,
+            port: 1000,
+        };
+        let saved_peer = database.fetch_peer(&public_key).await?;
         assert_eq!(None, saved_peer);
 
-        database.persist_peer(&peer).await?;
+        database.persist_peer(&public_key, &ipv4).await?;
+        database.persist_peer(&public_key, &ipv6).await?;
 
-        let saved_peer = database.fetch_peer(&peer.public_key).await?;
+        let saved_peer = database.fetch_peer(&public_key).await?;
+        let peer = Peer {
+            public_key,
+            net_addresses: vec![ipv6.clone(), ipv4.clone()],
+        };
         assert_eq!(peer, saved_peer.unwrap());
 
+        // Connecting with an address again makes it the first to try.
+        database.persist_peer(&public_key, &ipv4).await?;
         let peers = database.fetch_peers().await?;
-        assert!(peers.contains_key(&peer.public_key));
+        assert_eq!(Some(&vec![ipv4, ipv6]), peers.get(&public_key));
+
+        // Every address type survives the database.
+        let other_public_key = random_public_key();
+        database.persist_peer(&other_public_key, &onion).await?;
+        database.persist_peer(&other_public_key, &hostname).await?;
+        let saved_peer = database.fetch_peer(&other_public_key).await?.unwrap();
+        assert_eq!(vec![hostname, onion], saved_peer.net_addresses);
+        database.delete_peer(&other_public_key).await?;
 
-        database.delete_peer(&peer.public_key).await?;
+        database.delete_peer(&public_key).await?;
         let peers = database.fetch_peers().await?;
-        assert!(!peers.contains_key(&peer.public_key));
+        assert!(!peers.contains_key(&public_key));
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_channel_stats() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let database = LdkDatabase::new(settings).await?;
+        let channel_id: [u8; 32] = rand::random();
+
+        database.record_channel_forward(&channel_id, true).await?;
+        database.record_channel_forward(&channel_id, true).await?;
+        database.record_channel_forward(&channel_id, false).await?;
+
+        let stats = database.fetch_channel_stats().await?;
+        let channel_stats = stats
+            .iter()
+            .find(|s| s.channel_id == channel_id)
+            .expect("missing channel stats");
+        assert_eq!(3, channel_stats.htlcs_attempted);
+        assert_eq!(2, channel_stats.htlcs_succeeded);
+        assert_eq!(1, channel_stats.htlcs_failed);
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_lease() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let database = LdkDatabase::new(settings).await?;
+        let duration = Duration::from_secs(30);
+
+        assert!(database.acquire_lease("first", duration).await?);
+        assert!(database.acquire_lease("first", duration).await?);
+        assert!(!database.acquire_lease("second", duration).await?);
+
+        database.release_lease("first").await?;
+        assert!(
+            database
+                .acquire_lease("second", Duration::from_millis(100))
+                .await?
+        );
+        // An expired lease is only taken over a safety margin after it expired.
+        let duration = Duration::from_millis(1200);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!database.acquire_lease("first", duration).await?);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(database.acquire_lease("first", duration).await?);
+        database.release_lease("first").await?;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_hold_lease() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let mut settings = create_database(settings, "test_hold_lease").await;
+        settings.database_lease_secs = 3;
+        let database = Arc::new(LdkDatabase::new(&settings).await?);
+        let lease = NodeLease::acquire(database.clone(), &settings).await?;
+
+        // The leader stops as soon as another process has the lease.
+        connection(&settings)
+            .await?
+            .execute("DELETE FROM node_lease", &[])
+            .await?;
+        assert!(
+            database
+                .acquire_lease("other", Duration::from_secs(30))
+                .await?
+        );
+        let error = tokio::time::timeout(Duration::from_secs(5), lease.hold())
+            .await?
+            .expect_err("lease was taken");
+        assert!(error.to_string().contains("taken by another process"));
+
+        // Stopping the node afterwards doesn't write the channel manager.
+        let chanmon_cfgs = create_chanmon_cfgs(1);
+        let node_cfgs = create_node_cfgs(1, &chanmon_cfgs);
+        let node_chanmgrs = create_node_chanmgrs(1, &node_cfgs, &[None]);
+        let nodes = create_network(1, &node_cfgs, &node_chanmgrs);
+        assert!(
+            <LdkDatabase as Persister<'_, _, _, _, _, _, _, _, _, TestScorer>>::persist_manager(
+                &database,
+                nodes[0].node
+            )
+            .is_err()
+        );
+        let client = connection(&settings).await?;
+        assert!(client
+            .query("SELECT manager FROM channel_manager", &[])
+            .await?
+            .is_empty());
+        client.execute("DELETE FROM node_lease", &[]).await?;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_standby_takes_over() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let mut settings = create_database(settings, "test_standby").await;
+        settings.database_lease_secs = 1;
+        let database = Arc::new(LdkDatabase::new(&settings).await?);
+        assert!(
+            database
+                .acquire_lease("leader", Duration::from_secs(1))
+                .await?
+        );
+
+        // Another process refuses to start while the leader keeps renewing the lease.
+        let renew_database = database.clone();
+        let renew = tokio::spawn(async move {
+            loop {
+                renew_database
+                    .acquire_lease("leader", Duration::from_secs(1))
+                    .await
+                    .unwrap();
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        });
+        assert!(NodeLease::acquire(database.clone(), &settings)
+            .await
+            .is_err());
+        renew.abort();
+
+        // It starts once the lease of a crashed leader has expired.
+        let lease = tokio::time::timeout(
+            Duration::from_secs(10),
+            NodeLease::acquire(database.clone(), &settings),
+        )
+        .await??;
+        lease.release().await?;
+
+        // The leader stops renewing so the standby takes over once the lease has expired.
+        assert!(
+            database
+                .acquire_lease("leader", Duration::from_secs(1))
+                .await?
+        );
+        settings.standby = true;
+        let lease = tokio::time::timeout(
+            Duration::from_secs(10),
+            NodeLease::acquire(database.clone(), &settings),
+        )
+        .await??;
+        assert!(
+            !database
+                .acquire_lease("leader", Duration::from_secs(1))
+                .await?
+        );
+        lease.release().await?;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_payments() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let database = LdkDatabase::new(settings).await?;
+        let hash = PaymentHash(rand::random());
+
+        let mut payment = Payment::new_outbound(hash, Some(5000));
+        database.persist_payment(&payment).await?;
+        let persisted = database
+            .fetch_payment(&hash, PaymentDirection::Outbound)
+            .await?
+            .expect("payment not found");
+        assert_eq!(payment, persisted);
+        assert!(database
+            .fetch_payment(&hash, PaymentDirection::Inbound)
+            .await?
+            .is_none());
+
+        payment.succeeded(PaymentPreimage(rand::random()), Some(12));
+        database.persist_payment(&payment).await?;
+        let persisted = database
+            .fetch_payment(&hash, PaymentDirection::Outbound)
+            .await?
+            .expect("payment not found");
+        assert_eq!(PaymentStatus::Succeeded, persisted.status);
+        assert_eq!(payment.preimage, persisted.preimage);
+        assert_eq!(Some(5000), persisted.amount_msat);
+        assert_eq!(Some(12), persisted.fee_msat);
+        assert!(persisted.settled_at.is_some());
+
+        let mut failed = Payment::new_outbound(PaymentHash(rand::random()), Some(1000));
+        failed.failed("No route".to_string());
+        database.persist_payment(&failed).await?;
+        database
+            .persist_payment(&Payment::new_outbound(PaymentHash(rand::random()), None))
+            .await?;
+
+        let succeeded = database
+            .fetch_payments(
+                PaymentDirection::Outbound,
+                Some(PaymentStatus::Succeeded),
+                None,
+                None,
+            )
+            .await?;
+        assert!(succeeded.iter().any(|p| p.hash == hash));
+        assert!(succeeded
+            .iter()
+            .all(|p| p.status == PaymentStatus::Succeeded));
+
+        let settled = database
+            .fetch_payments(
+                PaymentDirection::Outbound,
+                None,
+                payment.settled_at,
+                Some(SystemTime::now() + Duration::from_secs(60)),
+            )
+            .await?;
+        assert!(settled.iter().all(|p| p.settled_at.is_some()));
+        assert!(settled
+            .windows(2)
+            .all(|w| w[0].settled_at <= w[1].settled_at));
+        assert!(settled.iter().any(|p| p.hash == failed.hash));
+
+        let future = database
+            .fetch_payments(
+                PaymentDirection::Outbound,
+                None,
+                Some(SystemTime::now() + Duration::from_secs(60)),
+                None,
+            )
+            .await?;
+        assert!(future.is_empty());
+
+        // Only a failed payment can be attempted again.
+        assert!(!database.insert_pending_payment(&payment).await?);
+        assert!(database.insert_pending_payment(&failed).await?);
+        assert!(!database.insert_pending_payment(&failed).await?);
+        let retried = database
+            .fetch_payment(&failed.hash, PaymentDirection::Outbound)
+            .await?
+            .expect("payment not found");
+        assert_eq!(PaymentStatus::Pending, retried.status);
+        assert!(retried.failure_reason.is_none());
+        let new = Payment::new_outbound(PaymentHash(rand::random()), Some(2000));
+        assert!(database.insert_pending_payment(&new).await?);
+
+        let pending = database.fetch_pending_payments().await?;
+        assert!(pending.iter().all(
+            |p| p.status == PaymentStatus::Pending && p.direction == PaymentDirection::Outbound
+        ));
+        assert!(!pending.iter().any(|p| p.hash == hash));
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_reconcile_pending_payments() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let settings = create_database(settings, "test_reconcile").await;
+        let database = LdkDatabase::new(&settings).await?;
+        let in_flight = Payment::new_outbound(PaymentHash(rand::random()), Some(1000));
+        let never_sent = Payment::new_outbound(PaymentHash(rand::random()), Some(2000));
+        assert!(database.insert_pending_payment(&in_flight).await?);
+        assert!(database.insert_pending_payment(&never_sent).await?);
+
+        reconcile_pending_payments(
+            &database,
+            &[RecentPaymentDetails::Abandoned {
+                payment_hash: in_flight.hash,
+            }],
+        )
+        .await?;
+        let pending = database.fetch_pending_payments().await?;
+        assert_eq!(1, pending.len());
+        assert_eq!(in_flight.hash, pending[0].hash);
+        let failed = database
+            .fetch_payment(&never_sent.hash, PaymentDirection::Outbound)
+            .await?
+            .expect("payment not found");
+        assert_eq!(PaymentStatus::Failed, failed.status);
+
+        // The invoice can be paid again.
+        assert!(database.insert_pending_payment(&never_sent).await?);
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_read_replica() -> Result<()> {
+    with_cockroach(|settings| async move {
+        // The single test node stands in for the replica.
+        let mut settings = settings.clone();
+        settings.database_read_host = settings.database_host.clone();
+        let database = LdkDatabase::new(&settings).await?;
+        let payment = Payment::new_outbound(PaymentHash(rand::random()), Some(5000));
+        database.persist_payment(&payment).await?;
+
+        let payments = database
+            .fetch_payments(PaymentDirection::Outbound, None, None, None)
+            .await?;
+        assert!(payments.iter().any(|p| p.hash == payment.hash));
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_forwards() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let database = LdkDatabase::new(settings).await?;
+        let start = SystemTime::now();
+        let forward = Forward {
+            in_channel_id: Some(rand::random()),
+            out_channel_id: None,
+            in_scid: Some(1),
+            out_scid: None,
+            amount_in_msat: None,
+            amount_out_msat: None,
+            fee_msat: Some(21),
+            timestamp: start + Duration::from_secs(10),
+        };
+        database.persist_forward(&forward).await?;
+        database
+            .persist_forward(&Forward {
+                timestamp: start + Duration::from_secs(20),
+                ..forward.clone()
+            })
+            .await?;
+
+        let forwards: Vec<Forward> = database
+            .fetch_forwards(Some(start), Some(start + Duration::from_secs(15)))
+            .await?
+            .try_collect()
+            .await?;
+        assert_eq!(1, forwards.len());
+        assert_eq!(forward.in_channel_id, forwards[0].in_channel_id);
+        assert_eq!(Some(21), forwards[0].fee_msat);
+
+        let forwards: Vec<Forward> = database
+            .fetch_forwards(Some(start), None)
+            .await?
+            .try_collect()
+            .await?;
+        assert_eq!(2, forwards.len());
+        assert!(forwards[0].timestamp < forwards[1].timestamp);
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_channel_earnings() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let database = LdkDatabase::new(settings).await?;
+        let scid: u64 = rand::random::<u32>().into();
+        let day = UNIX_EPOCH + Duration::from_secs(19_000 * 86400);
+        let forward = Forward {
+            in_channel_id: None,
+            out_channel_id: None,
+            in_scid: Some(scid),
+            out_scid: Some(scid + 1),
+            amount_in_msat: None,
+            amount_out_msat: None,
+            fee_msat: Some(5),
+            timestamp: day + Duration::from_secs(60),
+        };
+        database.persist_forward(&forward).await?;
+        database
+            .persist_forward(&Forward {
+                in_scid: Some(scid + 1),
+                out_scid: Some(scid),
+                fee_msat: Some(7),
+                timestamp: day + Duration::from_secs(7200),
+                ..forward.clone()
+            })
+            .await?;
+        database
+            .persist_forward(&Forward {
+                timestamp: day + Duration::from_secs(86400),
+                ..forward.clone()
+            })
+            .await?;
+
+        let earnings = database
+            .fetch_channel_earnings(scid, EarningsBucket::Day)
+            .await?;
+        assert_eq!(2, earnings.len());
+        assert_eq!(day, earnings[0].bucket_start);
+        assert_eq!(5, earnings[0].inbound_fee_msat);
+        assert_eq!(7, earnings[0].outbound_fee_msat);
+        assert_eq!(1, earnings[0].inbound_forwards);
+        assert_eq!(1, earnings[0].outbound_forwards);
+        assert_eq!(day + Duration::from_secs(86400), earnings[1].bucket_start);
+
+        let earnings = database
+            .fetch_channel_earnings(scid, EarningsBucket::Hour)
+            .await?;
+        assert_eq!(3, earnings.len());
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_closed_channels() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let database = LdkDatabase::new(settings).await?;
+        let channel_id: [u8; 32] = rand::random();
+        let counterparty = random_public_key();
+        let funding_txo = bitcoin::OutPoint::new(Txid::from_inner(rand::random()), 1);
+        let closing_txid = Txid::from_inner(rand::random());
+        let sweep_txid = Txid::from_inner(rand::random());
+
+        // The closing transaction can confirm before LDK reports the close.
+        database
+            .persist_closing_txid(&channel_id, &closing_txid)
+            .await?;
+        database
+            .persist_channel_closed(
+                &channel_id,
+                Some(counterparty),
+                Some(funding_txo),
+                "cooperative close",
+            )
+            .await?;
+        let mut output = spendable_output(closing_txid);
+        output.sweeping(sweep_txid);
+        database.persist_sweep(&[output], &sweep_txid).await?;
+
+        let channels = database.fetch_closed_channels().await?;
+        let channel = channels
+            .iter()
+            .find(|c| c.channel_id == channel_id)
+            .expect("missing closed channel");
+        assert_eq!(Some(counterparty), channel.counterparty_node_id);
+        assert_eq!(Some(funding_txo), channel.funding_txo);
+        assert_eq!(Some("cooperative close".to_string()), channel.reason);
+        assert_eq!(Some(closing_txid), channel.closing_txid);
+        assert_eq!(Some(sweep_txid), channel.sweep_txid);
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_statement_timeout() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let mut settings = settings.clone();
+        settings.database_statement_timeout_ms = 100;
+        let error = connection(&settings)
+            .await?
+            .execute("SELECT pg_sleep(1)", &[])
+            .await
+            .expect_err("statement should have timed out");
+        assert!(is_statement_timeout(&error));
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_connect_gives_up() {
+    let mut settings = test_settings("connect_gives_up");
+    settings.database_port = "1".to_string();
+    settings.database_connect_attempts = 2;
+    let error = migrate_database(&settings)
+        .await
+        .expect_err("nothing listens on the port");
+    assert!(error
+        .to_string()
+        .contains("giving up connecting to database after 2 attempts"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_sweep_is_atomic() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let settings = create_database(settings, "test_sweep").await;
+        let database = LdkDatabase::new(&settings).await?;
+        let output = spendable_output(Txid::from_inner(rand::random()));
+        database.persist_spendable_output(&output).await?;
+
+        // Make the last write of the sweep fail after the outputs have been written.
+        connection(&settings)
+            .await?
+            .execute(
+                "ALTER TABLE closed_channels RENAME TO closed_channels_gone",
+                &[],
+            )
+            .await?;
+        let mut swept = output.clone();
+        swept.sweeping(Txid::from_inner(rand::random()));
+        let sweep_txid = swept.sweep_txid.unwrap();
+        assert!(database.persist_sweep(&[swept], &sweep_txid).await.is_err());
+
+        let outputs = database.fetch_unswept_outputs().await?;
+        let fetched = outputs
+            .iter()
+            .find(|o| o.outpoint() == output.outpoint())
+            .expect("missing spendable output");
+        assert_eq!(SweepStatus::Pending, fetched.status);
+        assert_eq!(None, fetched.sweep_txid);
+        Ok(())
+    })
+    .await
+}
+
+fn spendable_output(txid: Txid) -> SpendableOutput {
+    SpendableOutput::new(SpendableOutputDescriptor::StaticOutput {
+        outpoint: OutPoint { txid, index: 0 },
+        output: TxOut {
+            value: 50000,
+            script_pubkey: Script::new(),
+        },
+    })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_spendable_outputs() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let database = LdkDatabase::new(settings).await?;
+        let mut output = SpendableOutput::new(SpendableOutputDescriptor::StaticOutput {
+            outpoint: OutPoint {
+                txid: Txid::from_inner(rand::random()),
+                index: 2,
+            },
+            output: TxOut {
+                value: 50000,
+                script_pubkey: Script::new(),
+            },
+        });
+        database.persist_spendable_output(&output).await?;
+
+        let outputs = database.fetch_unswept_outputs().await?;
+        let fetched = outputs
+            .iter()
+            .find(|o| o.outpoint() == output.outpoint())
+            .expect("missing spendable output");
+        assert_eq!(output.descriptor, fetched.descriptor);
+        assert_eq!(SweepStatus::Pending, fetched.status);
+        assert_eq!(50000, fetched.value_sats());
+
+        let sweep_txid = Txid::from_inner(rand::random());
+        output.sweeping(sweep_txid);
+        database.persist_spendable_output(&output).await?;
+        let outputs = database.fetch_unswept_outputs().await?;
+        let fetched = outputs
+            .iter()
+            .find(|o| o.outpoint() == output.outpoint())
+            .expect("missing spendable output");
+        assert_eq!(SweepStatus::Sweeping, fetched.status);
+        assert_eq!(Some(sweep_txid), fetched.sweep_txid);
+
+        output.status = SweepStatus::Swept;
+        database.persist_spendable_output(&output).await?;
+        let outputs = database.fetch_unswept_outputs().await?;
+        assert!(!outputs.iter().any(|o| o.outpoint() == output.outpoint()));
         Ok(())
     })
     .await
@@ -169,6 +768,80 @@ pub async fn test_channel_monitors() -> Result<()> {
 
         // Make sure everything is persisted as expected after close.
         check_persisted_data!(11);
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_corrupt_channel_monitors() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let settings = create_database(settings, "test_corrupt_monitors").await;
+        let database = LdkDatabase::new(&settings).await?;
+
+        let chanmon_cfgs = create_chanmon_cfgs(2);
+        let mut node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+        let chain_mon = ln_utils::TestChainMonitor::new(
+            Some(&chanmon_cfgs[0].chain_source),
+            &chanmon_cfgs[0].tx_broadcaster,
+            &chanmon_cfgs[0].logger,
+            &chanmon_cfgs[0].fee_estimator,
+            &database,
+            node_cfgs[0].keys_manager,
+        );
+        node_cfgs[0].chain_monitor = chain_mon;
+        let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+        let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+        let _ = create_announced_chan_between_nodes(&nodes, 0, 1);
+        let persisted = database
+            .fetch_channel_monitors(nodes[0].keys_manager, nodes[0].keys_manager)
+            .await?;
+        assert_eq!(persisted.len(), 1);
+        assert_eq!(database.corrupt_channel_monitors(), 0);
+
+        // A monitor which doesn't deserialize and a copy of the good one under another outpoint.
+        let mut out_point = vec![];
+        persisted[0].1.get_funding_txo().0.write(&mut out_point)?;
+        let client = connection(&settings).await?;
+        client
+            .execute(
+                "INSERT INTO channel_monitors (out_point, monitor, update_id) VALUES ($1, $2, 0)",
+                &[&vec![1u8; 34], &b"garbage".to_vec()],
+            )
+            .await?;
+        client
+            .execute(
+                "INSERT INTO channel_monitors (out_point, monitor, update_id) \
+                SELECT $1, monitor, update_id FROM channel_monitors WHERE out_point = $2",
+                &[&vec![2u8; 34], &out_point],
+            )
+            .await?;
+
+        // Both are skipped and counted without losing the good one.
+        let monitors = database
+            .fetch_channel_monitors(nodes[0].keys_manager, nodes[0].keys_manager)
+            .await?;
+        assert_eq!(monitors.len(), 1);
+        assert_eq!(
+            monitors[0].1.get_funding_txo(),
+            persisted[0].1.get_funding_txo()
+        );
+        assert_eq!(database.corrupt_channel_monitors(), 2);
+
+        // Reading them again for a check doesn't overwrite the count from startup.
+        client
+            .execute(
+                "DELETE FROM channel_monitors WHERE out_point = $1",
+                &[&vec![1u8; 34]],
+            )
+            .await?;
+        let (monitors, corrupt) = database
+            .read_channel_monitors(nodes[0].keys_manager, nodes[0].keys_manager)
+            .await?;
+        assert_eq!(monitors.len(), 1);
+        assert_eq!(corrupt, 1);
+        assert_eq!(database.corrupt_channel_monitors(), 2);
         Ok(())
     })
     .await
@@ -7,6 +7,8 @@ use bitcoin::consensus::encode::deserialize;
 use bitcoin::hashes::hex::*;
 use bitcoin::*;
 use kld::database::WalletDatabase;
+use kld::wallet::WatchDescriptorDatabase;
+use test_utils::TEST_WATCH_DESCRIPTOR;
 
 use super::with_cockroach;
 
@@ -196,3 +198,21 @@ pub async fn test_sync_time() -> Result<()> {
     })
     .await
 }
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_watch_descriptors() -> Result<()> {
+    with_cockroach(|settings| async move {
+        let wallet_database = WalletDatabase::new(settings).await?;
+        assert!(wallet_database.watch_descriptors()?.is_empty());
+
+        wallet_database.persist_watch_descriptor(TEST_WATCH_DESCRIPTOR, 0)?;
+        wallet_database.persist_watch_descriptor(TEST_WATCH_DESCRIPTOR, 1000)?;
+
+        assert_eq!(
+            wallet_database.watch_descriptors()?,
+            vec![(TEST_WATCH_DESCRIPTOR.to_string(), 1000)]
+        );
+        Ok(())
+    })
+    .await
+}
@@ -1,3 +1,4 @@
+use std::str::FromStr;
 use std::thread::spawn;
 use std::time::Duration;
 use std::{fs, sync::Arc};
@@ -5,28 +6,43 @@ use std::{fs, sync::Arc};
 use anyhow::{Context, Result};
 use axum::http::HeaderValue;
 use futures::FutureExt;
-use hyper::header::CONTENT_TYPE;
+use hex::ToHex;
+use hyper::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, ETAG, IF_NONE_MATCH};
 use hyper::Method;
 use kld::api::bind_api_server;
 use kld::api::MacaroonAuth;
 use kld::logger::KldLogger;
+use kld::webhook::WebhookNotifier;
+use lightning::ln::PaymentPreimage;
+use lightning::util::config::ChannelConfig;
+use lightning_invoice::Invoice;
 use once_cell::sync::Lazy;
 use reqwest::RequestBuilder;
 use reqwest::StatusCode;
 use serde::Serialize;
 use settings::Settings;
 use test_utils::ports::get_available_port;
-use test_utils::{https_client, TEST_ADDRESS, TEST_ALIAS, TEST_PUBLIC_KEY, TEST_SHORT_CHANNEL_ID};
+use test_utils::{
+    https_client, TEST_ADDRESS, TEST_ALIAS, TEST_PUBLIC_KEY, TEST_SHORT_CHANNEL_ID,
+    TEST_WATCH_DESCRIPTOR,
+};
 
 use api::{
-    routes, Address, Channel, ChannelFee, FeeRate, FundChannel, FundChannelResponse, GetInfo,
-    NetworkChannel, NetworkNode, NewAddress, NewAddressResponse, Peer, SetChannelFeeResponse,
-    WalletBalance, WalletTransfer, WalletTransferResponse,
+    routes, AbandonChannel, Address, AnnounceResponse, BatchRequest, BatchResponse, Channel,
+    ChannelEarnings, ChannelFee, ChannelFeeReport, ChannelStats, ClosedChannel, DeriveKey,
+    DeriveKeyResponse, ErrorCode, ExportState, FailHtlc, FeeRate, Forward, ForwardHtlc,
+    FundChannel, FundChannelResponse, FundingPsbt, GenerateInvoice, GenerateInvoiceResponse,
+    GetInfo, Htlc, ImportDescriptor, InterceptScid, InterceptedHtlc, Maintenance, MonitorCheck,
+    NetworkChannel, NetworkNode, NetworkStats, NewAddress, NewAddressResponse, OpenChannelPreview,
+    OpenChannelPsbt, OpenChannelPsbtResponse, PayLnurl, Payment, Peer, PendingChannelOpen,
+    PendingSweep, PendingWebhook, PruneNetworkGraphResponse, ResetScorerResponse, SetAlias,
+    SetChannelFeeResponse, SignMessage, SignMessageResponse, VerifyMessage, VerifyMessageResponse,
+    WalletBalance, WalletRescan, WalletRescanResponse, WalletTransfer, WalletTransferResponse,
 };
 use tokio::runtime::Runtime;
 use tokio::sync::RwLock;
 
-use crate::mocks::mock_lightning::MockLightning;
+use crate::mocks::mock_lightning::{MockLightning, TEST_PAYMENT_HASH};
 use crate::mocks::mock_wallet::MockWallet;
 use crate::{quit_signal, test_settings};
 
@@ -61,6 +77,13 @@ pub async fn test_unauthorized() -> Result<()> {
             .await?
             .status()
     );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::LIST_CLOSED_CHANNELS)
+            .send()
+            .await?
+            .status()
+    );
     assert_eq!(
         StatusCode::UNAUTHORIZED,
         unauthorized_request(&context, Method::POST, routes::OPEN_CHANNEL)
@@ -80,6 +103,32 @@ pub async fn test_unauthorized() -> Result<()> {
         .await?
         .status()
     );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::OPEN_CHANNEL_PSBT)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request_with_body(
+            &context,
+            Method::POST,
+            routes::OPEN_CHANNEL_PSBT,
+            open_channel_psbt_request
+        )?
+        .send()
+        .await?
+        .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::FUNDING_PSBT)
+            .send()
+            .await?
+            .status()
+    );
     assert_eq!(
         StatusCode::UNAUTHORIZED,
         unauthorized_request(&context, Method::POST, routes::SET_CHANNEL_FEE,)
@@ -117,6 +166,100 @@ pub async fn test_unauthorized() -> Result<()> {
         .await?
         .status()
     );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::GET_PENDING_CHANNEL_OPEN)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::ABANDON_CHANNEL)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request_with_body(
+            &context,
+            Method::POST,
+            routes::ABANDON_CHANNEL,
+            abandon_channel_request
+        )?
+        .send()
+        .await?
+        .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request(&context, Method::GET, routes::GET_INTERCEPT_SCID)?
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request_with_body(
+            &context,
+            Method::POST,
+            routes::FORWARD_HTLC,
+            forward_htlc_request
+        )?
+        .send()
+        .await?
+        .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request_with_body(&context, Method::POST, routes::FAIL_HTLC, fail_htlc_request)?
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::LIST_HTLCS)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::LIST_INTERCEPTED_HTLCS)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::GET_CHANNEL_STATS)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::OPEN_CHANNEL_PREVIEW)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::GET_CHANNEL_EARNINGS)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::FEE_REPORT)
+            .send()
+            .await?
+            .status()
+    );
     assert_eq!(
         StatusCode::UNAUTHORIZED,
         unauthorized_request(&context, Method::POST, routes::WITHDRAW)
@@ -186,6 +329,17 @@ pub async fn test_unauthorized() -> Result<()> {
         .await?
         .status()
     );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request(
+            &context,
+            Method::DELETE,
+            &routes::FORGET_PEER.replace(":id", TEST_PUBLIC_KEY),
+        )?
+        .send()
+        .await?
+        .status()
+    );
     assert_eq!(
         StatusCode::UNAUTHORIZED,
         unauthorized_request(&context, Method::GET, routes::LIST_NETWORK_NODE)
@@ -214,58 +368,443 @@ pub async fn test_unauthorized() -> Result<()> {
             .await?
             .status()
     );
-    Ok(())
-}
-
-#[tokio::test(flavor = "multi_thread")]
-async fn test_not_found() -> Result<()> {
-    let context = create_api_server().await?;
     assert_eq!(
-        StatusCode::NOT_FOUND,
-        admin_request(&context, Method::GET, "/x")?
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::GET_NETWORK_STATS)
             .send()
             .await?
             .status()
     );
-    Ok(())
-}
-
-#[tokio::test(flavor = "multi_thread")]
-async fn test_root_readonly() -> Result<()> {
-    let context = create_api_server().await?;
-    assert!(readonly_request(&context, Method::GET, routes::ROOT)?
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::LIST_NETWORK_CHANNELS_BETWEEN)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::LIST_PENDING_WEBHOOKS)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::PRUNE_NETWORK_GRAPH)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request(&context, Method::POST, routes::PRUNE_NETWORK_GRAPH)?
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::CHECK_MONITORS)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request(&context, Method::GET, routes::CHECK_MONITORS)?
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::RESET_SCORER)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request(&context, Method::POST, routes::RESET_SCORER)?
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::PENDING_SWEEPS)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request(&context, Method::GET, routes::EXPORT_STATE)?
+            .query(&[("confirm", "true")])
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request(&context, Method::POST, routes::SIGN_MESSAGE)?
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        admin_request_with_body(
+            &context,
+            Method::POST,
+            routes::DERIVE_KEY,
+            derive_key_request
+        )?
         .send()
         .await?
         .status()
-        .is_success());
-    Ok(())
-}
-
-#[tokio::test(flavor = "multi_thread")]
-async fn test_root_admin() -> Result<()> {
-    let context = create_api_server().await?;
-    assert!(admin_request(&context, Method::GET, routes::ROOT)?
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::VERIFY_MESSAGE)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::MAINTENANCE)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request(&context, Method::POST, routes::ANNOUNCE)?
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request_with_body(&context, Method::POST, routes::SET_ALIAS, || {
+            SetAlias {
+                alias: "test".to_string(),
+                color: None,
+            }
+        })?
         .send()
         .await?
         .status()
-        .is_success());
-    Ok(())
-}
-
-#[tokio::test(flavor = "multi_thread")]
-async fn test_get_info_readonly() -> Result<()> {
-    let context = create_api_server().await?;
-    let info: GetInfo = readonly_request(&context, Method::GET, routes::GET_INFO)?
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request_with_body(&context, Method::POST, routes::MAINTENANCE, || {
+            Maintenance { enabled: true }
+        })?
         .send()
         .await?
-        .json()
-        .await?;
-    assert_eq!(LIGHTNING.num_peers, info.num_peers);
-    Ok(())
-}
-
-#[tokio::test(flavor = "multi_thread")]
-async fn test_get_balance_readonly() -> Result<()> {
+        .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::GENERATE_INVOICE)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request_with_body(
+            &context,
+            Method::POST,
+            routes::GENERATE_INVOICE,
+            generate_invoice_request
+        )?
+        .send()
+        .await?
+        .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::PAY_LNURL)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request_with_body(&context, Method::POST, routes::PAY_LNURL, || {
+            PayLnurl {
+                target: "satoshi@example.com".to_string(),
+                amount: 1000,
+                max_fee_msat: None,
+                max_fee_percent: None,
+            }
+        })?
+        .send()
+        .await?
+        .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::EXPORT_FORWARDS)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::LIST_FORWARDS)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::LIST_INVOICES)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::LIST_PAYMENTS)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::GET, routes::GET_PAYMENT)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::IMPORT_DESCRIPTOR)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request_with_body(
+            &context,
+            Method::POST,
+            routes::IMPORT_DESCRIPTOR,
+            import_descriptor_request
+        )?
+        .send()
+        .await?
+        .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::WALLET_RESCAN)
+            .send()
+            .await?
+            .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        readonly_request_with_body(
+            &context,
+            Method::POST,
+            routes::WALLET_RESCAN,
+            WalletRescan::default
+        )?
+        .send()
+        .await?
+        .status()
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        unauthorized_request(&context, Method::POST, routes::BATCH)
+            .send()
+            .await?
+            .status()
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_not_found() -> Result<()> {
+    let context = create_api_server().await?;
+    assert_eq!(
+        StatusCode::NOT_FOUND,
+        admin_request(&context, Method::GET, "/x")?
+            .send()
+            .await?
+            .status()
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_batch_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let responses: Vec<BatchResponse> =
+        admin_request_with_body(&context, Method::POST, routes::BATCH, || {
+            vec![
+                BatchRequest {
+                    method: "GET".to_string(),
+                    path: routes::GET_INFO.to_string(),
+                    body: None,
+                },
+                BatchRequest {
+                    method: "POST".to_string(),
+                    path: routes::SIGN_MESSAGE.to_string(),
+                    body: Some(serde_json::json!({ "message": "test" })),
+                },
+                BatchRequest {
+                    method: "GET".to_string(),
+                    path: "/x".to_string(),
+                    body: None,
+                },
+            ]
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(3, responses.len());
+    assert_eq!(200, responses[0].status);
+    let info: GetInfo = serde_json::from_value(responses[0].body.clone())?;
+    assert_eq!(TEST_PUBLIC_KEY, info.id);
+    assert_eq!(200, responses[1].status);
+    let signed: SignMessageResponse = serde_json::from_value(responses[1].body.clone())?;
+    assert!(!signed.signature.is_empty());
+    assert_eq!(404, responses[2].status);
+    assert_eq!("NOT_FOUND", responses[2].body["code"]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_batch_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let responses: Vec<BatchResponse> =
+        readonly_request_with_body(&context, Method::POST, routes::BATCH, || {
+            vec![
+                BatchRequest {
+                    method: "GET".to_string(),
+                    path: routes::LIST_PEERS.to_string(),
+                    body: None,
+                },
+                BatchRequest {
+                    method: "GET".to_string(),
+                    path: routes::LIST_CHANNELS.to_string(),
+                    body: None,
+                },
+            ]
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert!(responses.iter().all(|response| response.status == 200));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_batch_unauthorized_sub_request() -> Result<()> {
+    let context = create_api_server().await?;
+    let responses: Vec<BatchResponse> =
+        readonly_request_with_body(&context, Method::POST, routes::BATCH, || {
+            vec![
+                BatchRequest {
+                    method: "GET".to_string(),
+                    path: routes::GET_INFO.to_string(),
+                    body: None,
+                },
+                BatchRequest {
+                    method: "POST".to_string(),
+                    path: routes::RESET_SCORER.to_string(),
+                    body: None,
+                },
+            ]
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(200, responses[0].status);
+    assert_eq!(401, responses[1].status);
+
+    assert_eq!(
+        StatusCode::BAD_REQUEST,
+        admin_request_with_body(&context, Method::POST, routes::BATCH, || {
+            vec![
+                BatchRequest {
+                    method: "POST".to_string(),
+                    path: routes::RESET_SCORER.to_string(),
+                    body: None,
+                },
+                BatchRequest {
+                    method: "NOT A METHOD".to_string(),
+                    path: routes::GET_INFO.to_string(),
+                    body: None,
+                },
+            ]
+        })?
+        .send()
+        .await?
+        .status()
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_root_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    assert!(readonly_request(&context, Method::GET, routes::ROOT)?
+        .send()
+        .await?
+        .status()
+        .is_success());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_root_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    assert!(admin_request(&context, Method::GET, routes::ROOT)?
+        .send()
+        .await?
+        .status()
+        .is_success());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_info_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let info: GetInfo = readonly_request(&context, Method::GET, routes::GET_INFO)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(LIGHTNING.num_peers, info.num_peers);
+    assert_eq!("signet", info.network);
+    assert!(info.testnet);
+    assert_eq!("signet", info.chains[0].network);
+    assert_eq!("010203", info.color);
+    assert_eq!(
+        vec![
+            format!("{TEST_PUBLIC_KEY}@127.0.0.1:2324"),
+            format!("{TEST_PUBLIC_KEY}@194.454.23.2:2020")
+        ],
+        info.uris
+    );
+    assert!(!info.maintenance_mode);
+    assert_eq!(5, info.watch_only_balance);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_balance_readonly() -> Result<()> {
     let context = create_api_server().await?;
     let balance: WalletBalance = readonly_request(&context, Method::GET, routes::GET_BALANCE)?
         .send()
@@ -275,6 +814,9 @@ async fn test_get_balance_readonly() -> Result<()> {
     assert_eq!(9, balance.total_balance);
     assert_eq!(4, balance.conf_balance);
     assert_eq!(5, balance.unconf_balance);
+    assert_eq!(0, balance.reserved_balance);
+    assert_eq!(1, balance.immature_balance);
+    assert_eq!(5, balance.watch_only_balance);
     Ok(())
 }
 
@@ -305,255 +847,1347 @@ async fn test_list_channels_readonly() -> Result<()> {
     assert_eq!("5000", channel.their_channel_reserve_satoshis);
     assert_eq!("10000", channel.our_channel_reserve_satoshis);
     assert_eq!("100000", channel.spendable_msatoshi);
+    assert_eq!(Some(10), channel.confirmations);
+    assert_eq!(Some(3), channel.confirmations_required);
     assert_eq!(1, channel.direction);
     assert_eq!(TEST_ALIAS, channel.alias);
+    assert_eq!(5, channel.htlcs_attempted);
+    assert_eq!(3, channel.htlcs_succeeded);
+    assert_eq!(2, channel.htlcs_failed);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_closed_channels_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let channels: Vec<ClosedChannel> =
+        readonly_request(&context, Method::GET, routes::LIST_CLOSED_CHANNELS)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    let channel = channels.get(0).context("Missing closed channel")?;
+    assert_eq!(
+        "0202020202020202020202020202020202020202020202020202020202020202",
+        channel.channel_id
+    );
+    assert_eq!(
+        Some(TEST_PUBLIC_KEY.to_string()),
+        channel.counterparty_node_id
+    );
+    assert_eq!(Some("cooperative close".to_string()), channel.reason);
+    assert_eq!(
+        Some("0000000000000000000000000000000000000000000000000000000000000000".to_string()),
+        channel.closing_txid
+    );
+    assert_eq!(None, channel.sweep_txid);
+    assert_eq!(1_700_000_000, channel.closed_at);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_channel_stats_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let stats: ChannelStats = readonly_request(
+        &context,
+        Method::GET,
+        &routes::GET_CHANNEL_STATS.replace(":id", &TEST_SHORT_CHANNEL_ID.to_string()),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(TEST_SHORT_CHANNEL_ID.to_string(), stats.short_channel_id);
+    assert_eq!(5, stats.htlcs_attempted);
+    assert_eq!(3, stats.htlcs_succeeded);
+    assert_eq!(2, stats.htlcs_failed);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_channel_earnings_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let route = routes::GET_CHANNEL_EARNINGS.replace(":id", &TEST_SHORT_CHANNEL_ID.to_string());
+    let earnings: Vec<ChannelEarnings> = readonly_request(&context, Method::GET, &route)?
+        .query(&[("bucket", "week")])
+        .send()
+        .await?
+        .json()
+        .await?;
+    let bucket = earnings.get(0).context("missing earnings")?;
+    assert_eq!(86400, bucket.bucket_start);
+    assert_eq!(10, bucket.inbound_fee_msat);
+    assert_eq!(20, bucket.outbound_fee_msat);
+    assert_eq!(1, bucket.inbound_forwards);
+    assert_eq!(2, bucket.outbound_forwards);
+
+    let response = readonly_request(&context, Method::GET, &route)?
+        .query(&[("bucket", "month")])
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fee_report_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let report: Vec<ChannelFeeReport> =
+        readonly_request(&context, Method::GET, routes::FEE_REPORT)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(1, report.len());
+    assert_eq!(
+        TEST_SHORT_CHANNEL_ID.to_string(),
+        report[0].short_channel_id
+    );
+    let local_policy = report[0].local_policy.as_ref().context("missing policy")?;
+    assert_eq!(1000, local_policy.base_fee_msat);
+    assert_eq!(200, local_policy.ppm_fee);
+    assert_eq!(Some(1000), local_policy.htlc_minimum_msat);
+    assert!(report[0].remote_policy.is_none());
+    assert_eq!(
+        Some(ChannelConfig::default().max_dust_htlc_exposure_msat),
+        report[0].max_dust_htlc_exposure_msat
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_open_channel_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: FundChannelResponse = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::OPEN_CHANNEL,
+        fund_channel_request,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(
+        "fba98a9a61ef62c081b31769f66a81f1640b4f94d48b550a550034cb4990eded",
+        response.txid
+    );
+    assert_eq!(
+        "0101010101010101010101010101010101010101010101010101010101010101",
+        response.channel_id
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_open_channel_preview_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let preview: OpenChannelPreview = readonly_request_with_body(
+        &context,
+        Method::POST,
+        routes::OPEN_CHANNEL_PREVIEW,
+        fund_channel_request,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(2100000, preview.channel_size_sat);
+    assert_eq!(2099990000, preview.local_balance_msat);
+    assert_eq!(10000, preview.remote_balance_msat);
+    assert_eq!(21000, preview.local_reserve_sat);
+    assert_eq!(2078990000, preview.spendable_msat);
+    assert_eq!(0, preview.receivable_msat);
+    assert_eq!(4, preview.confirmed_balance_sat);
+    assert!(!preview.sufficient_funds);
+
+    let response =
+        readonly_request_with_body(&context, Method::POST, routes::OPEN_CHANNEL_PREVIEW, || {
+            FundChannel {
+                push_msat: Some("2100000001".to_string()),
+                ..fund_channel_request()
+            }
+        })?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let response =
+        readonly_request_with_body(&context, Method::POST, routes::OPEN_CHANNEL_PREVIEW, || {
+            FundChannel {
+                satoshis: u64::MAX.to_string(),
+                ..fund_channel_request()
+            }
+        })?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_open_channel_psbt_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: OpenChannelPsbtResponse = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::OPEN_CHANNEL_PSBT,
+        open_channel_psbt_request,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(
+        "0101010101010101010101010101010101010101010101010101010101010101",
+        response.channel_id
+    );
+    assert_eq!(TEST_ADDRESS, response.address);
+    assert_eq!(1000000, response.satoshis);
+
+    let funded: FundChannelResponse =
+        admin_request_with_body(&context, Method::POST, routes::FUNDING_PSBT, || {
+            FundingPsbt {
+                channel_id: response.channel_id.clone(),
+                psbt: response.psbt.clone(),
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(response.channel_id, funded.channel_id);
+    assert_eq!(1, funded.tx.output.len());
+    assert_eq!(1000000, funded.tx.output[0].value);
+
+    let response = admin_request_with_body(&context, Method::POST, routes::FUNDING_PSBT, || {
+        FundingPsbt {
+            channel_id: "0101010101010101010101010101010101010101010101010101010101010101"
+                .to_string(),
+            psbt: "invalid".to_string(),
+        }
+    })?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_channel_fee_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: SetChannelFeeResponse = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::SET_CHANNEL_FEE,
+        set_channel_fee_request,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+
+    let fee = response.0.get(0).context("Bad response")?;
+    assert_eq!(TEST_SHORT_CHANNEL_ID.to_string(), fee.short_channel_id);
+    assert_eq!(TEST_PUBLIC_KEY, fee.peer_id);
+    assert_eq!(set_channel_fee_request().base, Some(fee.base));
+    assert_eq!(set_channel_fee_request().ppm, Some(fee.ppm));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_all_channel_fees_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = ChannelFee {
+        id: "all".to_string(),
+        base: Some(32500),
+        ppm: Some(1200),
+    };
+    let response: SetChannelFeeResponse =
+        admin_request_with_body(&context, Method::POST, routes::SET_CHANNEL_FEE, || {
+            request.clone()
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    let fee = response.0.get(0).context("Bad response")?;
+    assert_eq!(TEST_SHORT_CHANNEL_ID.to_string(), fee.short_channel_id);
+    assert_eq!(TEST_PUBLIC_KEY, fee.peer_id);
+    assert_eq!(request.base, Some(fee.base));
+    assert_eq!(request.ppm, Some(fee.ppm));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_peer_channel_fees_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = ChannelFee {
+        id: TEST_PUBLIC_KEY.to_string(),
+        base: Some(1000),
+        ppm: Some(250),
+    };
+    let response: SetChannelFeeResponse =
+        admin_request_with_body(&context, Method::POST, routes::SET_CHANNEL_FEE, || {
+            request.clone()
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    assert_eq!(LIGHTNING.channels.len(), response.0.len());
+    let fee = response.0.get(0).context("Bad response")?;
+    assert_eq!(TEST_SHORT_CHANNEL_ID.to_string(), fee.short_channel_id);
+    assert_eq!(TEST_PUBLIC_KEY, fee.peer_id);
+    assert_eq!(request.base, Some(fee.base));
+    assert_eq!(request.ppm, Some(fee.ppm));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_channel_fee_above_maximum() -> Result<()> {
+    let context = create_api_server().await?;
+    let request = ChannelFee {
+        id: TEST_SHORT_CHANNEL_ID.to_string(),
+        base: None,
+        ppm: Some(context.settings.max_channel_fee_ppm + 1),
+    };
+    let response =
+        admin_request_with_body(&context, Method::POST, routes::SET_CHANNEL_FEE, || {
+            request.clone()
+        })?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let response: SetChannelFeeResponse = admin_request_with_body(
+        &context,
+        Method::POST,
+        &format!("{}?force=true", routes::SET_CHANNEL_FEE),
+        || request.clone(),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    let fee = response.0.get(0).context("Bad response")?;
+    assert_eq!(request.ppm, Some(fee.ppm));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_channel_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let result = admin_request(
+        &context,
+        Method::DELETE,
+        &routes::CLOSE_CHANNEL.replace(":id", &TEST_SHORT_CHANNEL_ID.to_string()),
+    )?
+    .send()
+    .await?;
+    assert!(result.status().is_success());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_open_channel_invalid_minimum_depth() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request_with_body(&context, Method::POST, routes::OPEN_CHANNEL, || {
+        let mut request = fund_channel_request();
+        request.minimum_depth = Some(1000);
+        request
+    })?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_open_channel_async_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let route = format!("{}?async=true", routes::OPEN_CHANNEL);
+    let response = admin_request_with_body(&context, Method::POST, &route, fund_channel_request)?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::ACCEPTED, response.status());
+    let pending: PendingChannelOpen = response.json().await?;
+    assert_eq!("pending", pending.status);
+
+    let route = routes::GET_PENDING_CHANNEL_OPEN.replace(":id", &pending.id);
+    let mut status = pending.status;
+    for _ in 0..10 {
+        let pending: PendingChannelOpen = readonly_request(&context, Method::GET, &route)?
+            .send()
+            .await?
+            .json()
+            .await?;
+        status = pending.status;
+        if status != "pending" {
+            assert_eq!(
+                "fba98a9a61ef62c081b31769f66a81f1640b4f94d48b550a550034cb4990eded",
+                pending.result.unwrap().txid
+            );
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!("funded", status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_pending_channel_open_unknown() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = readonly_request(
+        &context,
+        Method::GET,
+        &routes::GET_PENDING_CHANNEL_OPEN.replace(":id", "unknown"),
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_abandon_ready_channel_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::ABANDON_CHANNEL,
+        abandon_channel_request,
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_abandon_unknown_channel_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response =
+        admin_request_with_body(&context, Method::POST, routes::ABANDON_CHANNEL, || {
+            AbandonChannel {
+                id: "02".repeat(32),
+            }
+        })?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_intercept_scid_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request(&context, Method::GET, routes::GET_INTERCEPT_SCID)?
+        .send()
+        .await?;
+    let scid: InterceptScid = response.json().await?;
+    assert_eq!(TEST_SHORT_CHANNEL_ID + 1, scid.short_channel_id);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_forward_htlc_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::FORWARD_HTLC,
+        forward_htlc_request,
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::OK, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_forward_htlc_unknown_channel_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request_with_body(&context, Method::POST, routes::FORWARD_HTLC, || {
+        ForwardHtlc {
+            channel_id: "02".repeat(32),
+            ..forward_htlc_request()
+        }
+    })?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fail_htlc_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response =
+        admin_request_with_body(&context, Method::POST, routes::FAIL_HTLC, fail_htlc_request)?
+            .send()
+            .await?;
+    assert_eq!(StatusCode::OK, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_htlcs_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let htlcs: Vec<Htlc> = readonly_request(&context, Method::GET, routes::LIST_HTLCS)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(1, htlcs.len());
+    assert_eq!("01".repeat(32), htlcs[0].channel_id);
+    assert_eq!("outbound", htlcs[0].direction);
+    assert_eq!(100, htlcs[0].amount_sat);
+    assert_eq!(50040, htlcs[0].cltv_expiry);
+    assert_eq!(40, htlcs[0].blocks_until_expiry);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_intercepted_htlcs_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let htlcs: Vec<InterceptedHtlc> =
+        readonly_request(&context, Method::GET, routes::LIST_INTERCEPTED_HTLCS)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(1, htlcs.len());
+    assert_eq!("03".repeat(32), htlcs[0].intercept_id);
+    assert_eq!(
+        TEST_PAYMENT_HASH.0.encode_hex::<String>(),
+        htlcs[0].payment_hash
+    );
+    assert_eq!(TEST_SHORT_CHANNEL_ID + 1, htlcs[0].requested_next_hop_scid);
+    assert_eq!(101000, htlcs[0].inbound_amount_msat);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_withdraw_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: WalletTransferResponse =
+        admin_request_with_body(&context, Method::POST, routes::WITHDRAW, withdraw_request)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!("0200000003c26f3eb7932f7acddc5ddd26602b77e7516079b03090a16e2c2f5485d1fd600f0100000000ffffffffc26f3eb7932f7acddc5ddd26602b77e7516079b03090a16e2c2f5485d1fd600f0000000000ffffffff571fb3e02278217852dd5d299947e2b7354a639adc32ec1fa7b82cfb5dec530e0500000000ffffffff03e80300000000000002aaeee80300000000000001aa200300000000000001ff00000000", response.tx);
+    assert_eq!(
+        "fba98a9a61ef62c081b31769f66a81f1640b4f94d48b550a550034cb4990eded",
+        response.txid
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_wallet_rescan_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: WalletRescanResponse =
+        admin_request_with_body(&context, Method::POST, routes::WALLET_RESCAN, || {
+            WalletRescan {
+                birthday_height: Some(780000),
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!("complete", response.status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_wallet_rescan_async_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let route = format!("{}?async=true", routes::WALLET_RESCAN);
+    let response = admin_request_with_body(&context, Method::POST, &route, WalletRescan::default)?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::ACCEPTED, response.status());
+    let response: WalletRescanResponse = response.json().await?;
+    assert_eq!("started", response.status);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_import_descriptor_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::IMPORT_DESCRIPTOR,
+        import_descriptor_request,
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::OK, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_import_descriptor_with_private_key() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request_with_body(&context, Method::POST, routes::IMPORT_DESCRIPTOR, || {
+        ImportDescriptor {
+            descriptor: "wpkh(tprv8ZgxMBicQKsPd9TeAdPADNnSyH9SSUUbTVeFszDE23Ki6TBB5nCefAdHkK8Fm3qMQR6sHwA56zqRmKmxnHk37JkiFzvncDqoKmPWubu7hDF/0/*)".to_string(),
+        }
+    })?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_new_address_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: NewAddressResponse =
+        admin_request_with_body(&context, Method::GET, routes::NEW_ADDR, NewAddress::default)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(TEST_ADDRESS.to_string(), response.address);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_peers_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: Vec<Peer> = readonly_request(&context, Method::GET, routes::LIST_PEERS)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    let netaddr = Some(Address {
+        address_type: "ipv4".to_string(),
+        address: "127.0.0.1".to_string(),
+        port: 5555,
+    });
+    assert!(response.contains(&Peer {
+        id: TEST_PUBLIC_KEY.to_string(),
+        connected: true,
+        netaddr,
+        alias: TEST_ALIAS.to_string(),
+        num_channels: 1,
+        capacity_sat: 1000000,
+    }));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connect_peer_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: String =
+        admin_request_with_body(&context, Method::POST, routes::CONNECT_PEER, || {
+            TEST_PUBLIC_KEY
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(TEST_PUBLIC_KEY, response);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connect_peer_with_address_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: String =
+        admin_request_with_body(&context, Method::POST, routes::CONNECT_PEER, || {
+            format!("{TEST_PUBLIC_KEY}@127.0.0.1:9735")
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(TEST_PUBLIC_KEY, response);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_disconnect_peer_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request(
+        &context,
+        Method::DELETE,
+        &routes::DISCONNECT_PEER.replace(":id", TEST_PUBLIC_KEY),
+    )?
+    .send()
+    .await?;
+    assert!(response.status().is_success());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_forget_peer_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = admin_request(
+        &context,
+        Method::DELETE,
+        &routes::FORGET_PEER.replace(":id", TEST_PUBLIC_KEY),
+    )?
+    .send()
+    .await?;
+    assert!(response.status().is_success());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_disconnect_peer_admin_malformed_key() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: api::Error = admin_request(
+        &context,
+        Method::DELETE,
+        &routes::DISCONNECT_PEER.replace(":id", "abcd"),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST.to_string());
+    assert_eq!(ErrorCode::InvalidRequest, response.code);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_network_node_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let nodes: Vec<NetworkNode> = readonly_request(
+        &context,
+        Method::GET,
+        &routes::LIST_NETWORK_NODE.replace(":id", TEST_PUBLIC_KEY),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    let node = nodes.get(0).context("no node in response")?;
+    assert_eq!(TEST_PUBLIC_KEY, node.node_id);
+    assert_eq!(TEST_ALIAS, node.alias);
+    assert_eq!("010203", node.color);
+    assert_eq!(21000000, node.last_timestamp);
+    assert!(node.addresses.contains(&Address {
+        address_type: "ipv4".to_string(),
+        address: "127.0.0.1".to_string(),
+        port: 5555
+    }));
+    assert!(!node.features.is_empty());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_network_node_malformed_key() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: api::Error = readonly_request(
+        &context,
+        Method::GET,
+        &routes::LIST_NETWORK_NODE.replace(":id", "abcd"),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST.to_string());
+    assert_eq!(ErrorCode::InvalidRequest, response.code);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_network_nodes_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let nodes: Vec<NetworkNode> =
+        readonly_request(&context, Method::GET, routes::LIST_NETWORK_NODES)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(TEST_PUBLIC_KEY, nodes.get(0).context("bad result")?.node_id);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_network_graph_not_modified() -> Result<()> {
+    let context = create_api_server().await?;
+    for route in [routes::LIST_NETWORK_NODES, routes::LIST_NETWORK_CHANNELS] {
+        let response = readonly_request(&context, Method::GET, route)?
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, response.status());
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .context("missing etag")?
+            .clone();
+        let response = readonly_request(&context, Method::GET, route)?
+            .header(IF_NONE_MATCH, etag.clone())
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_MODIFIED, response.status());
+        assert_eq!(Some(&etag), response.headers().get(ETAG));
+        let response = readonly_request(&context, Method::GET, route)?
+            .header(IF_NONE_MATCH, "\"outdated\"")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::OK, response.status());
+    }
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_network_graph_compressed() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = readonly_request(&context, Method::GET, routes::LIST_NETWORK_NODES)?
+        .header(ACCEPT_ENCODING, "gzip")
+        .send()
+        .await?;
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(
+        Some("gzip"),
+        response
+            .headers()
+            .get(CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+    );
+    let response = readonly_request(&context, Method::GET, routes::LIST_NETWORK_NODES)?
+        .send()
+        .await?;
+    assert!(response.headers().get(CONTENT_ENCODING).is_none());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_network_channel_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = readonly_request(
+        &context,
+        Method::GET,
+        &routes::LIST_NETWORK_CHANNEL.replace(":id", "123456789"),
+    )?
+    .send()
+    .await?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_network_channels_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let _channels: Vec<NetworkChannel> =
+        readonly_request(&context, Method::GET, routes::LIST_NETWORK_CHANNELS)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_network_channels_between_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let channels: Vec<NetworkChannel> = readonly_request(
+        &context,
+        Method::GET,
+        &routes::LIST_NETWORK_CHANNELS_BETWEEN
+            .replace(":node1", TEST_PUBLIC_KEY)
+            .replace(
+                ":node2",
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            ),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert!(channels.is_empty());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_network_channels_between_malformed_id() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = readonly_request(
+        &context,
+        Method::GET,
+        &routes::LIST_NETWORK_CHANNELS_BETWEEN
+            .replace(":node1", TEST_PUBLIC_KEY)
+            .replace(":node2", "abc"),
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_network_stats_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let stats: NetworkStats = readonly_request(&context, Method::GET, routes::GET_NETWORK_STATS)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(6, stats.num_nodes);
+    assert_eq!(0, stats.num_channels);
+    assert_eq!(0, stats.total_capacity_sat);
+    assert_eq!(0, stats.median_channel_size_sat);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_generate_invoice_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: GenerateInvoiceResponse = admin_request_with_body(
+        &context,
+        Method::POST,
+        routes::GENERATE_INVOICE,
+        generate_invoice_request,
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    let invoice = Invoice::from_str(&response.bolt11)?;
+    assert_eq!(Some(100000), invoice.amount_milli_satoshis());
+    assert_eq!(invoice.payment_hash().to_string(), response.payment_hash);
+    assert_eq!(1, invoice.fallbacks().len());
+    assert_eq!(
+        invoice.duration_since_epoch().as_secs() + 600,
+        response.expires_at
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pay_lnurl_invalid_target() -> Result<()> {
+    let context = create_api_server().await?;
+    let response =
+        admin_request_with_body(&context, Method::POST, routes::PAY_LNURL, || PayLnurl {
+            target: "lnurl1invalid".to_string(),
+            amount: 1000,
+            max_fee_msat: None,
+            max_fee_percent: None,
+        })?
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_payment_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let payment: Payment = readonly_request(
+        &context,
+        Method::GET,
+        &routes::GET_PAYMENT.replace(":payment_hash", &TEST_PAYMENT_HASH.0.encode_hex::<String>()),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!("succeeded", payment.status);
+    assert_eq!(
+        Some(PaymentPreimage([3; 32]).0.encode_hex::<String>()),
+        payment.payment_preimage
+    );
+    assert_eq!(Some(10), payment.fee_msat);
+    assert!(payment.failure_reason.is_none());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_payment_not_found() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = readonly_request(
+        &context,
+        Method::GET,
+        &routes::GET_PAYMENT.replace(":payment_hash", &"00".repeat(32)),
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    let error: api::Error = response.json().await?;
+    assert_eq!(ErrorCode::NotFound, error.code);
+
+    let response = readonly_request(
+        &context,
+        Method::GET,
+        &routes::GET_PAYMENT.replace(":payment_hash", "abc"),
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_payments_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let payments: Vec<Payment> = readonly_request(
+        &context,
+        Method::GET,
+        &format!("{}?status=succeeded&since=0", routes::LIST_PAYMENTS),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert_eq!(1, payments.len());
+    assert!(payments[0].settled_at.is_some());
+
+    let payments: Vec<Payment> = readonly_request(
+        &context,
+        Method::GET,
+        &format!("{}?status=pending", routes::LIST_INVOICES),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert!(payments.is_empty());
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_open_channel_admin() -> Result<()> {
+async fn test_list_payments_invalid_status() -> Result<()> {
     let context = create_api_server().await?;
-    let response: FundChannelResponse = admin_request_with_body(
+    let response = readonly_request(
         &context,
-        Method::POST,
-        routes::OPEN_CHANNEL,
-        fund_channel_request,
+        Method::GET,
+        &format!("{}?status=paid", routes::LIST_PAYMENTS),
+    )?
+    .send()
+    .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_export_forwards_readonly() -> Result<()> {
+    let context = create_api_server().await?;
+    let response = readonly_request(
+        &context,
+        Method::GET,
+        &format!("{}?since=0&until=1700000000", routes::EXPORT_FORWARDS),
     )?
     .send()
-    .await?
-    .json()
     .await?;
+    assert_eq!(StatusCode::OK, response.status());
     assert_eq!(
-        "fba98a9a61ef62c081b31769f66a81f1640b4f94d48b550a550034cb4990eded",
-        response.txid
+        Some(&HeaderValue::from_static("text/csv")),
+        response.headers().get(CONTENT_TYPE)
     );
     assert_eq!(
-        "0101010101010101010101010101010101010101010101010101010101010101",
-        response.channel_id
+        format!(
+            "timestamp,in_scid,out_scid,amount_in_msat,amount_out_msat,fee_msat\n1681300000,{TEST_SHORT_CHANNEL_ID},,100010,100000,10\n"
+        ),
+        response.text().await?
     );
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_set_channel_fee_admin() -> Result<()> {
+async fn test_list_forwards_readonly() -> Result<()> {
     let context = create_api_server().await?;
-    let response: SetChannelFeeResponse = admin_request_with_body(
+    let forwards: Vec<Forward> = readonly_request(
         &context,
-        Method::POST,
-        routes::SET_CHANNEL_FEE,
-        set_channel_fee_request,
+        Method::GET,
+        &format!("{}?since=0&limit=10", routes::LIST_FORWARDS),
     )?
     .send()
     .await?
     .json()
     .await?;
+    let forward = forwards.get(0).context("no forward in response")?;
+    assert_eq!(Some(TEST_SHORT_CHANNEL_ID), forward.in_short_channel_id);
+    assert_eq!(Some(10), forward.fee_msat);
+    assert_eq!(1681300000, forward.timestamp);
 
-    let fee = response.0.get(0).context("Bad response")?;
-    assert_eq!(TEST_SHORT_CHANNEL_ID.to_string(), fee.short_channel_id);
-    assert_eq!(TEST_PUBLIC_KEY, fee.peer_id);
-    assert_eq!(set_channel_fee_request().base, Some(fee.base));
-    assert_eq!(set_channel_fee_request().ppm, Some(fee.ppm));
+    let forwards: Vec<Forward> = readonly_request(
+        &context,
+        Method::GET,
+        &format!("{}?limit=0", routes::LIST_FORWARDS),
+    )?
+    .send()
+    .await?
+    .json()
+    .await?;
+    assert!(forwards.is_empty());
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_set_all_channel_fees_admin() -> Result<()> {
+async fn test_bind_multiple_addresses() -> Result<()> {
     let context = create_api_server().await?;
-    let request = ChannelFee {
-        id: "all".to_string(),
-        base: Some(32500),
-        ppm: Some(1200),
-    };
-    let response: SetChannelFeeResponse =
-        admin_request_with_body(&context, Method::POST, routes::SET_CHANNEL_FEE, || {
-            request.clone()
-        })?
-        .send()
-        .await?
-        .json()
-        .await?;
+    let port1 = get_available_port().context("no port available")?;
+    let port2 = get_available_port().context("no port available")?;
+    bind_api_server(
+        format!("127.0.0.1:{port1}, [::1]:{port2}"),
+        context.settings.certs_dir.clone(),
+        String::new(),
+    )
+    .await?;
+    assert!(bind_api_server(
+        format!("127.0.0.1:{port1},localhost"),
+        context.settings.certs_dir.clone(),
+        String::new(),
+    )
+    .await
+    .is_err());
+    Ok(())
+}
 
-    let fee = response.0.get(0).context("Bad response")?;
-    assert_eq!(TEST_SHORT_CHANNEL_ID.to_string(), fee.short_channel_id);
-    assert_eq!(TEST_PUBLIC_KEY, fee.peer_id);
-    assert_eq!(request.base, Some(fee.base));
-    assert_eq!(request.ppm, Some(fee.ppm));
+#[tokio::test(flavor = "multi_thread")]
+async fn test_prune_network_graph_admin() -> Result<()> {
+    let context = create_api_server().await?;
+    let response: PruneNetworkGraphResponse =
+        admin_request(&context, Method::POST, routes::PRUNE_NETWORK_GRAPH)?
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(2, response.removed_channels);
+    assert_eq!(1, response.removed_nodes);
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_close_channel_admin() -> Result<()> {
+async fn test_check_monitors_admin() -> Result<()> {
     let context = create_api_server().await?;
-    let result = admin_request(
-        &context,
-        Method::DELETE,
-        &routes::CLOSE_CHANNEL.replace(":id", &TEST_SHORT_CHANNEL_ID.to_string()),
-    )?
-    .send()
-    .await?;
-    assert!(result.status().is_success());
+    let check: MonitorCheck = admin_request(&context, Method::GET, routes::CHECK_MONITORS)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(1, check.checked_monitors);
+    assert_eq!(0, check.corrupt_monitors);
+    assert!(check.discrepancies.is_empty());
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_withdraw_admin() -> Result<()> {
+async fn test_reset_scorer_admin() -> Result<()> {
     let context = create_api_server().await?;
-    let response: WalletTransferResponse =
-        admin_request_with_body(&context, Method::POST, routes::WITHDRAW, withdraw_request)?
+    let response: ResetScorerResponse =
+        admin_request(&context, Method::POST, routes::RESET_SCORER)?
             .send()
             .await?
             .json()
             .await?;
-    assert_eq!("0200000003c26f3eb7932f7acddc5ddd26602b77e7516079b03090a16e2c2f5485d1fd600f0100000000ffffffffc26f3eb7932f7acddc5ddd26602b77e7516079b03090a16e2c2f5485d1fd600f0000000000ffffffff571fb3e02278217852dd5d299947e2b7354a639adc32ec1fa7b82cfb5dec530e0500000000ffffffff03e80300000000000002aaeee80300000000000001aa200300000000000001ff00000000", response.tx);
-    assert_eq!(
-        "fba98a9a61ef62c081b31769f66a81f1640b4f94d48b550a550034cb4990eded",
-        response.txid
-    );
+    assert_eq!("reset", response.status);
+    assert!(response.timestamp > 0);
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_new_address_admin() -> Result<()> {
+async fn test_list_pending_sweeps_readonly() -> Result<()> {
     let context = create_api_server().await?;
-    let response: NewAddressResponse =
-        admin_request_with_body(&context, Method::GET, routes::NEW_ADDR, NewAddress::default)?
+    let sweeps: Vec<PendingSweep> =
+        readonly_request(&context, Method::GET, routes::PENDING_SWEEPS)?
             .send()
             .await?
             .json()
             .await?;
-    assert_eq!(TEST_ADDRESS.to_string(), response.address);
+    let sweep = sweeps.get(0).context("Missing pending sweep")?;
+    assert_eq!(
+        "0000000000000000000000000000000000000000000000000000000000000000:1",
+        sweep.outpoint
+    );
+    assert_eq!(20000, sweep.value_sats);
+    assert_eq!("sweeping", sweep.status);
+    assert_eq!(
+        Some("0000000000000000000000000000000000000000000000000000000000000000".to_string()),
+        sweep.sweep_txid
+    );
+    assert_eq!(1_700_000_000, sweep.created_at);
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_list_peers_readonly() -> Result<()> {
+async fn test_export_state_admin() -> Result<()> {
     let context = create_api_server().await?;
-    let response: Vec<Peer> = readonly_request(&context, Method::GET, routes::LIST_PEERS)?
+    let state: ExportState = admin_request(&context, Method::GET, routes::EXPORT_STATE)?
+        .query(&[("confirm", "true")])
         .send()
         .await?
         .json()
         .await?;
-    let netaddr = Some(Address {
-        address_type: "ipv4".to_string(),
-        address: "127.0.0.1".to_string(),
-        port: 5555,
-    });
-    assert!(response.contains(&Peer {
-        id: TEST_PUBLIC_KEY.to_string(),
-        connected: true,
-        netaddr,
-        alias: TEST_ALIAS.to_string()
-    }));
+    assert_eq!(1, state.version);
+    assert_eq!(TEST_PUBLIC_KEY, state.node_id);
+    assert_eq!(vec!["010203".to_string()], state.channel_monitors);
+    assert_eq!(None, state.network_graph);
+    let peer = state.peers.get(0).context("Missing peer")?;
+    assert_eq!(TEST_PUBLIC_KEY, peer.id);
+    assert_eq!("127.0.0.1:5555", peer.address);
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_connect_peer_admin() -> Result<()> {
+async fn test_export_state_requires_confirmation() -> Result<()> {
     let context = create_api_server().await?;
-    let response: String =
-        admin_request_with_body(&context, Method::POST, routes::CONNECT_PEER, || {
-            TEST_PUBLIC_KEY
-        })?
+    let response = admin_request(&context, Method::GET, routes::EXPORT_STATE)?
         .send()
-        .await?
-        .json()
         .await?;
-    assert_eq!(TEST_PUBLIC_KEY, response);
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_disconnect_peer_admin() -> Result<()> {
+async fn test_derive_key() -> Result<()> {
     let context = create_api_server().await?;
-    let response = admin_request(
-        &context,
-        Method::DELETE,
-        &routes::DISCONNECT_PEER.replace(":id", TEST_PUBLIC_KEY),
-    )?
-    .send()
-    .await?;
-    assert!(response.status().is_success());
+    let body = serde_json::to_string(&derive_key_request())?;
+    let derived: DeriveKeyResponse =
+        unauthorized_request(&context, Method::POST, routes::DERIVE_KEY)
+            .header("macaroon", context.derivekey_macaroon.clone())
+            .body(body.clone())
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!("m/138'/0", derived.path);
+    assert_eq!(66, derived.public_key.len());
+
+    let derived_again: DeriveKeyResponse =
+        unauthorized_request(&context, Method::POST, routes::DERIVE_KEY)
+            .header("macaroon", context.derivekey_macaroon.clone())
+            .body(body)
+            .send()
+            .await?
+            .json()
+            .await?;
+    assert_eq!(derived.public_key, derived_again.public_key);
+
+    let response = unauthorized_request(&context, Method::POST, routes::DERIVE_KEY)
+        .header("macaroon", context.derivekey_macaroon.clone())
+        .body(serde_json::to_string(&DeriveKey {
+            path: "not a path".to_string(),
+        })?)
+        .send()
+        .await?;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_disconnect_peer_admin_malformed_key() -> Result<()> {
+async fn test_sign_and_verify_message() -> Result<()> {
     let context = create_api_server().await?;
-    let response: api::Error = admin_request(
-        &context,
-        Method::DELETE,
-        &routes::DISCONNECT_PEER.replace(":id", "abcd"),
-    )?
-    .send()
-    .await?
-    .json()
-    .await?;
-    assert_eq!(response.status, StatusCode::BAD_REQUEST.to_string());
+    let signed: SignMessageResponse =
+        admin_request_with_body(&context, Method::POST, routes::SIGN_MESSAGE, || {
+            SignMessage {
+                message: "test".to_string(),
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    let verified: VerifyMessageResponse =
+        readonly_request_with_body(&context, Method::POST, routes::VERIFY_MESSAGE, || {
+            VerifyMessage {
+                message: "test".to_string(),
+                signature: signed.signature.clone(),
+                pubkey: None,
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert!(verified.verified);
+    assert!(verified.known_node);
+
+    let response: VerifyMessageResponse =
+        readonly_request_with_body(&context, Method::POST, routes::VERIFY_MESSAGE, || {
+            VerifyMessage {
+                message: "other".to_string(),
+                signature: signed.signature.clone(),
+                pubkey: Some(verified.pubkey.clone()),
+            }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert!(!response.verified);
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_list_network_node_readonly() -> Result<()> {
+async fn test_maintenance_admin() -> Result<()> {
     let context = create_api_server().await?;
-    let nodes: Vec<NetworkNode> = readonly_request(
-        &context,
-        Method::GET,
-        &routes::LIST_NETWORK_NODE.replace(":id", TEST_PUBLIC_KEY),
-    )?
-    .send()
-    .await?
-    .json()
-    .await?;
-    let node = nodes.get(0).context("no node in response")?;
-    assert_eq!(TEST_PUBLIC_KEY, node.node_id);
-    assert_eq!(TEST_ALIAS, node.alias);
-    assert_eq!("010203", node.color);
-    assert_eq!(21000000, node.last_timestamp);
-    assert!(node.addresses.contains(&Address {
-        address_type: "ipv4".to_string(),
-        address: "127.0.0.1".to_string(),
-        port: 5555
-    }));
-    assert!(!node.features.is_empty());
+    // The mock is shared by all tests so don't leave maintenance mode enabled.
+    let response: Maintenance =
+        admin_request_with_body(&context, Method::POST, routes::MAINTENANCE, || {
+            Maintenance { enabled: false }
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert!(!response.enabled);
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_list_network_nodes_readonly() -> Result<()> {
+async fn test_announce_admin() -> Result<()> {
     let context = create_api_server().await?;
-    let nodes: Vec<NetworkNode> =
-        readonly_request(&context, Method::GET, routes::LIST_NETWORK_NODES)?
-            .send()
-            .await?
-            .json()
-            .await?;
-    assert_eq!(TEST_PUBLIC_KEY, nodes.get(0).context("bad result")?.node_id);
+    let response: AnnounceResponse = admin_request(&context, Method::POST, routes::ANNOUNCE)?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert!(response.announced);
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_list_network_channel_readonly() -> Result<()> {
+async fn test_set_alias_admin() -> Result<()> {
     let context = create_api_server().await?;
-    let response = readonly_request(
-        &context,
-        Method::GET,
-        &routes::LIST_NETWORK_CHANNEL.replace(":id", "123456789"),
-    )?
-    .send()
-    .await?;
-    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response: SetAlias =
+        admin_request_with_body(&context, Method::POST, routes::SET_ALIAS, || SetAlias {
+            alias: "test".to_string(),
+            color: Some("010203".to_string()),
+        })?
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!("test", response.alias);
+    assert_eq!(Some("010203".to_string()), response.color);
+
+    for (alias, color) in [("a".repeat(33), None), ("test".to_string(), Some("xyz"))] {
+        let response =
+            admin_request_with_body(&context, Method::POST, routes::SET_ALIAS, || SetAlias {
+                alias,
+                color: color.map(str::to_string),
+            })?
+            .send()
+            .await?;
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_list_network_channels_readonly() -> Result<()> {
+async fn test_list_pending_webhooks_readonly() -> Result<()> {
     let context = create_api_server().await?;
-    let _channels: Vec<NetworkChannel> =
-        readonly_request(&context, Method::GET, routes::LIST_NETWORK_CHANNELS)?
+    let pending: Vec<PendingWebhook> =
+        readonly_request(&context, Method::GET, routes::LIST_PENDING_WEBHOOKS)?
             .send()
             .await?
             .json()
             .await?;
+    assert!(pending.is_empty());
     Ok(())
 }
 
@@ -567,6 +2201,47 @@ fn withdraw_request() -> WalletTransfer {
     }
 }
 
+fn generate_invoice_request() -> GenerateInvoice {
+    GenerateInvoice {
+        amount: Some(100000),
+        description: "test invoice".to_string(),
+        expiry: Some(600),
+        fallback_address: true,
+    }
+}
+
+fn forward_htlc_request() -> ForwardHtlc {
+    ForwardHtlc {
+        intercept_id: "03".repeat(32),
+        channel_id: "01".repeat(32),
+        amount_msat: 1000,
+    }
+}
+
+fn fail_htlc_request() -> FailHtlc {
+    FailHtlc {
+        intercept_id: "03".repeat(32),
+    }
+}
+
+fn derive_key_request() -> DeriveKey {
+    DeriveKey {
+        path: "m/138'/0".to_string(),
+    }
+}
+
+fn abandon_channel_request() -> AbandonChannel {
+    AbandonChannel {
+        id: "01".repeat(32),
+    }
+}
+
+fn import_descriptor_request() -> ImportDescriptor {
+    ImportDescriptor {
+        descriptor: TEST_WATCH_DESCRIPTOR.to_string(),
+    }
+}
+
 fn fund_channel_request() -> FundChannel {
     FundChannel {
         id: TEST_PUBLIC_KEY.to_string() + "@1.2.3.4:1234",
@@ -577,11 +2252,21 @@ fn fund_channel_request() -> FundChannel {
         close_to: None,
         request_amt: None,
         compact_lease: None,
+        minimum_depth: None,
         min_conf: Some(5),
         utxos: vec![],
     }
 }
 
+fn open_channel_psbt_request() -> OpenChannelPsbt {
+    OpenChannelPsbt {
+        id: TEST_PUBLIC_KEY.to_string(),
+        satoshis: "1000000".to_string(),
+        announce: None,
+        push_msat: None,
+    }
+}
+
 fn set_channel_fee_request() -> ChannelFee {
     ChannelFee {
         id: TEST_SHORT_CHANNEL_ID.to_string(),
@@ -598,6 +2283,7 @@ pub struct TestContext {
     pub settings: Settings,
     admin_macaroon: Vec<u8>,
     readonly_macaroon: Vec<u8>,
+    derivekey_macaroon: Vec<u8>,
 }
 
 pub async fn create_api_server() -> Result<Arc<TestContext>> {
@@ -611,23 +2297,30 @@ pub async fn create_api_server() -> Result<Arc<TestContext>> {
     let rest_api_address = format!("127.0.0.1:{rest_api_port}");
     let mut settings = test_settings("api");
     settings.rest_api_address = rest_api_address.clone();
+    settings.rest_api_socket = format!("{}/kld.sock", settings.data_dir);
     let certs_dir = settings.certs_dir.clone();
+    let rest_api_socket = settings.rest_api_socket.clone();
     let macaroon_auth = Arc::new(
         MacaroonAuth::init(&[0u8; 32], &settings.data_dir)
             .context("cannot initialize macaroon auth")?,
     );
     let admin_macaroon = admin_macaroon(&settings)?;
     let readonly_macaroon = readonly_macaroon(&settings)?;
+    let derivekey_macaroon = derivekey_macaroon(&settings)?;
+    let api_settings = Arc::new(settings.clone());
+    let webhook = Arc::new(WebhookNotifier::new(&settings));
 
     // Run the API with its own runtime in its own thread.
     spawn(move || {
         API_RUNTIME
             .block_on(async {
-                bind_api_server(rest_api_address, certs_dir)
+                bind_api_server(rest_api_address, certs_dir, rest_api_socket)
                     .await?
                     .serve(
+                        api_settings,
                         LIGHTNING.clone(),
                         Arc::new(MockWallet::default()),
+                        webhook,
                         macaroon_auth,
                         quit_signal().shared(),
                     )
@@ -640,6 +2333,7 @@ pub async fn create_api_server() -> Result<Arc<TestContext>> {
         settings,
         admin_macaroon,
         readonly_macaroon,
+        derivekey_macaroon,
     };
 
     while !readonly_request(&new_context, Method::GET, routes::ROOT)?
@@ -666,6 +2360,11 @@ fn readonly_macaroon(settings: &Settings) -> Result<Vec<u8>> {
     fs::read(&path).with_context(|| format!("Failed to read {path}"))
 }
 
+fn derivekey_macaroon(settings: &Settings) -> Result<Vec<u8>> {
+    let path = format!("{}/macaroons/derivekey.macaroon", settings.data_dir);
+    fs::read(&path).with_context(|| format!("Failed to read {path}"))
+}
+
 static LIGHTNING: Lazy<Arc<MockLightning>> = Lazy::new(|| Arc::new(MockLightning::default()));
 
 fn unauthorized_request(context: &TestContext, method: Method, route: &str) -> RequestBuilder {
@@ -1,15 +1,24 @@
-use std::process::{Command, Output};
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    process::{Command, Output},
+};
 
 use anyhow::{bail, Result};
 use api::{
-    Channel, FundChannelResponse, GetInfo, NetworkChannel, NetworkNode, NewAddressResponse, Peer,
-    SetChannelFeeResponse, WalletBalance, WalletTransferResponse,
+    AnnounceResponse, Channel, ChannelEarnings, ChannelFeeReport, ChannelStats, ClosedChannel,
+    ExportState, Forward, FundChannelResponse, GenerateInvoiceResponse, GetInfo, Htlc,
+    InterceptScid, InterceptedHtlc, Maintenance, MonitorCheck, NetworkChannel, NetworkNode,
+    NetworkStats, NewAddressResponse, OpenChannelPreview, OpenChannelPsbtResponse, Payment, Peer,
+    PendingChannelOpen, PendingSweep, PendingWebhook, ResetScorerResponse, SetChannelFeeResponse,
+    SignMessageResponse, VerifyMessageResponse, WalletBalance, WalletRescanResponse,
+    WalletTransferResponse,
 };
 use bitcoin::secp256k1::PublicKey;
 
 use serde::de;
 
-use test_utils::{TEST_ADDRESS, TEST_PUBLIC_KEY, TEST_SHORT_CHANNEL_ID};
+use test_utils::{TEST_ADDRESS, TEST_PUBLIC_KEY, TEST_SHORT_CHANNEL_ID, TEST_WATCH_DESCRIPTOR};
 
 use super::api::create_api_server;
 
@@ -20,6 +29,62 @@ async fn test_cli_get_info() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_unix_socket() -> Result<()> {
+    let context = create_api_server().await?;
+    let mode = fs::metadata(&context.settings.rest_api_socket)?
+        .permissions()
+        .mode();
+    assert_eq!(0o600, mode & 0o777);
+    let output = Command::new(env!("CARGO_BIN_EXE_kld-cli"))
+        .args(["--socket", &context.settings.rest_api_socket, "get-info"])
+        .output()?;
+    assert!(output.status.success());
+    let _: GetInfo = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_config_file() -> Result<()> {
+    let context = create_api_server().await?;
+    let settings = &context.settings;
+    let config_path = format!("{}/cli.toml", settings.data_dir);
+    fs::write(
+        &config_path,
+        format!(
+            "target = \"{}\"\ncert-path = \"{}/kld.crt\"\nmacaroon-path = \"{}/macaroons/admin.macaroon\"\n",
+            settings.rest_api_address, settings.certs_dir, settings.data_dir
+        ),
+    )?;
+    let output = Command::new(env!("CARGO_BIN_EXE_kld-cli"))
+        .args(["--config", &config_path, "get-info"])
+        .output()?;
+    assert!(output.status.success());
+    let _: GetInfo = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_env_credentials() -> Result<()> {
+    let context = create_api_server().await?;
+    let settings = &context.settings;
+    let output = Command::new(env!("CARGO_BIN_EXE_kld-cli"))
+        .env("KLD_TARGET", &settings.rest_api_address)
+        .env(
+            "KLD_CERT",
+            fs::read_to_string(format!("{}/kld.crt", settings.certs_dir))?,
+        )
+        .env(
+            "KLD_MACAROON",
+            fs::read_to_string(format!("{}/macaroons/admin.macaroon", settings.data_dir))?,
+        )
+        .arg("get-info")
+        .output()?;
+    assert!(output.status.success());
+    let _: GetInfo = deserialize(&output.stdout)?;
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_get_balance() -> Result<()> {
     let output = run_cli("get-balance", &[]).await?;
@@ -27,6 +92,14 @@ async fn test_cli_get_balance() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_get_balance_table() -> Result<()> {
+    let output = run_cli("--output", &["table", "get-balance"]).await?;
+    let table = String::from_utf8(output.stdout)?;
+    assert!(table.starts_with("BALANCE"));
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_new_address() -> Result<()> {
     let output = run_cli("new-address", &[]).await?;
@@ -52,6 +125,25 @@ async fn test_cli_withdraw() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_rescan_wallet() -> Result<()> {
+    let output = run_cli("rescan-wallet", &["--birthday-height", "780000"]).await?;
+    let response: WalletRescanResponse = deserialize(&output.stdout)?;
+    assert_eq!("complete", response.status);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_import_descriptor() -> Result<()> {
+    let output = run_cli(
+        "import-descriptor",
+        &["--descriptor", TEST_WATCH_DESCRIPTOR],
+    )
+    .await?;
+    assert!(output.stdout.is_empty());
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_list_channels() -> Result<()> {
     let output = run_cli("list-channels", &[]).await?;
@@ -59,6 +151,81 @@ async fn test_cli_list_channels() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_list_closed_channels() -> Result<()> {
+    let output = run_cli("list-closed-channels", &[]).await?;
+    let channels: Vec<ClosedChannel> = deserialize(&output.stdout)?;
+    assert_eq!(1, channels.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_generate_invoice() -> Result<()> {
+    let output = run_cli(
+        "generate-invoice",
+        &[
+            "--amount",
+            "100000",
+            "--description",
+            "test",
+            "--fallback-address",
+        ],
+    )
+    .await?;
+    let _: GenerateInvoiceResponse = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_pay_lnurl_invalid_target() -> Result<()> {
+    let output = run_cli(
+        "pay-lnurl",
+        &["--target", "lnurl1invalid", "--amount", "1000"],
+    )
+    .await?;
+    let _: api::Error = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_get_payment() -> Result<()> {
+    let output = run_cli("get-payment", &["--payment-hash", &"02".repeat(32)]).await?;
+    let payment: Payment = deserialize(&output.stdout)?;
+    assert_eq!("succeeded", payment.status);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_list_payments() -> Result<()> {
+    let output = run_cli("list-payments", &["--status", "succeeded"]).await?;
+    let payments: Vec<Payment> = deserialize(&output.stdout)?;
+    assert_eq!(1, payments.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_list_invoices() -> Result<()> {
+    let output = run_cli("list-invoices", &["--since", "0"]).await?;
+    let _: Vec<Payment> = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_list_forwards() -> Result<()> {
+    let output = run_cli("list-forwards", &["--since", "0", "--limit", "10"]).await?;
+    let forwards: Vec<Forward> = deserialize(&output.stdout)?;
+    assert_eq!(1, forwards.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_export_forwards() -> Result<()> {
+    let output = run_cli("export-forwards", &["--since", "0"]).await?;
+    let csv = String::from_utf8(output.stdout)?;
+    assert!(csv.starts_with("timestamp,in_scid,out_scid"));
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_list_peers() -> Result<()> {
     let output = run_cli("list-peers", &[]).await?;
@@ -88,6 +255,14 @@ async fn test_cli_disconnect_peer() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_forget_peer() -> Result<()> {
+    let output = run_cli("forget-peer", &["--public-key", TEST_PUBLIC_KEY]).await?;
+
+    assert!(&output.stdout.is_empty());
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_open_channel() -> Result<()> {
     let output = run_cli(
@@ -108,6 +283,54 @@ async fn test_cli_open_channel() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_open_channel_preview() -> Result<()> {
+    let output = run_cli(
+        "open-channel-preview",
+        &["--public-key", TEST_PUBLIC_KEY, "--sats", "1000000"],
+    )
+    .await?;
+    let preview: OpenChannelPreview = deserialize(&output.stdout)?;
+    assert_eq!(10000, preview.local_reserve_sat);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_open_channel_async() -> Result<()> {
+    let output = run_cli(
+        "open-channel",
+        &[
+            "--public-key",
+            TEST_PUBLIC_KEY,
+            "--sats",
+            "1000",
+            "--run-async",
+        ],
+    )
+    .await?;
+    let pending: PendingChannelOpen = deserialize(&output.stdout)?;
+    assert_eq!("pending", pending.status);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_open_channel_psbt() -> Result<()> {
+    let output = run_cli(
+        "open-channel-psbt",
+        &["--public-key", TEST_PUBLIC_KEY, "--sats", "1000"],
+    )
+    .await?;
+    let opened: OpenChannelPsbtResponse = deserialize(&output.stdout)?;
+    let output = run_cli(
+        "funding-psbt",
+        &["--channel-id", &opened.channel_id, "--psbt", &opened.psbt],
+    )
+    .await?;
+    let funded: FundChannelResponse = deserialize(&output.stdout)?;
+    assert_eq!(opened.channel_id, funded.channel_id);
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_set_channel_fee() -> Result<()> {
     let output = run_cli(
@@ -137,6 +360,17 @@ async fn test_cli_set_channel_fee_all() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_set_channel_fee_force() -> Result<()> {
+    let output = run_cli(
+        "set-channel-fee",
+        &["--id", "all", "--ppm-fee", "10000000", "--force"],
+    )
+    .await?;
+    let _: SetChannelFeeResponse = deserialize(&output.stdout)?;
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_close_channel() -> Result<()> {
     let output = run_cli(
@@ -148,6 +382,42 @@ async fn test_cli_close_channel() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_channel_stats() -> Result<()> {
+    let output = run_cli(
+        "channel-stats",
+        &["--id", &TEST_SHORT_CHANNEL_ID.to_string()],
+    )
+    .await?;
+    let _: ChannelStats = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_channel_earnings() -> Result<()> {
+    let output = run_cli(
+        "channel-earnings",
+        &[
+            "--id",
+            &TEST_SHORT_CHANNEL_ID.to_string(),
+            "--bucket",
+            "hour",
+        ],
+    )
+    .await?;
+    let earnings: Vec<ChannelEarnings> = deserialize(&output.stdout)?;
+    assert!(!earnings.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_fee_report() -> Result<()> {
+    let output = run_cli("fee-report", &[]).await?;
+    let report: Vec<ChannelFeeReport> = deserialize(&output.stdout)?;
+    assert!(!report.is_empty());
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cli_get_network_node() -> Result<()> {
     let output = run_cli("network-nodes", &["--id", TEST_PUBLIC_KEY]).await?;
@@ -180,6 +450,148 @@ async fn test_cli_list_network_channels() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_cli_network_stats() -> Result<()> {
+    let output = run_cli("network-stats", &[]).await?;
+    let _: NetworkStats = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_prune_network_graph() -> Result<()> {
+    let output = run_cli("prune-network-graph", &[]).await?;
+    let response: PruneNetworkGraphResponse = deserialize(&output.stdout)?;
+    assert_eq!(2, response.removed_channels);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_check_monitors() -> Result<()> {
+    let output = run_cli("check-monitors", &[]).await?;
+    let check: MonitorCheck = deserialize(&output.stdout)?;
+    assert_eq!(1, check.checked_monitors);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_reset_scorer() -> Result<()> {
+    let output = run_cli("reset-scorer", &[]).await?;
+    let _: ResetScorerResponse = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_pending_sweeps() -> Result<()> {
+    let output = run_cli("pending-sweeps", &[]).await?;
+    let sweeps: Vec<PendingSweep> = deserialize(&output.stdout)?;
+    assert_eq!(1, sweeps.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_export_state() -> Result<()> {
+    let output = run_cli("export-state", &["--confirm"]).await?;
+    let state: ExportState = deserialize(&output.stdout)?;
+    assert_eq!(1, state.channel_monitors.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_sign_and_verify_message() -> Result<()> {
+    let output = run_cli("sign-message", &["--message", "test"]).await?;
+    let signed: SignMessageResponse = deserialize(&output.stdout)?;
+    let output = run_cli(
+        "verify-message",
+        &["--message", "test", "--signature", &signed.signature],
+    )
+    .await?;
+    let verified: VerifyMessageResponse = deserialize(&output.stdout)?;
+    assert!(verified.known_node);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_maintenance() -> Result<()> {
+    let output = run_cli("maintenance", &[]).await?;
+    let response: Maintenance = deserialize(&output.stdout)?;
+    assert!(!response.enabled);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_announce() -> Result<()> {
+    let output = run_cli("announce", &[]).await?;
+    let response: AnnounceResponse = deserialize(&output.stdout)?;
+    assert!(response.announced);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_set_alias() -> Result<()> {
+    let output = run_cli("set-alias", &["--alias", "test", "--color", "010203"]).await?;
+    let response: SetAlias = deserialize(&output.stdout)?;
+    assert_eq!("test", response.alias);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_list_pending_webhooks() -> Result<()> {
+    let output = run_cli("list-pending-webhooks", &[]).await?;
+    let _: Vec<PendingWebhook> = deserialize(&output.stdout)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_list_htlcs() -> Result<()> {
+    let output = run_cli("list-htlcs", &[]).await?;
+    let htlcs: Vec<Htlc> = deserialize(&output.stdout)?;
+    assert_eq!(1, htlcs.len());
+    assert_eq!(40, htlcs[0].blocks_until_expiry);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_intercept_scid() -> Result<()> {
+    let output = run_cli("intercept-scid", &[]).await?;
+    let scid: InterceptScid = deserialize(&output.stdout)?;
+    assert_eq!(TEST_SHORT_CHANNEL_ID + 1, scid.short_channel_id);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_forward_htlc() -> Result<()> {
+    let output = run_cli(
+        "forward-htlc",
+        &[
+            "--intercept-id",
+            &"03".repeat(32),
+            "--channel-id",
+            &"01".repeat(32),
+            "--amount-msat",
+            "1000",
+        ],
+    )
+    .await?;
+    assert!(&output.stdout.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_fail_htlc() -> Result<()> {
+    let output = run_cli("fail-htlc", &["--intercept-id", &"03".repeat(32)]).await?;
+    assert!(&output.stdout.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_list_intercepted_htlcs() -> Result<()> {
+    let output = run_cli("list-intercepted-htlcs", &[]).await?;
+    let htlcs: Vec<InterceptedHtlc> = deserialize(&output.stdout)?;
+    assert_eq!(1, htlcs.len());
+    assert_eq!("03".repeat(32), htlcs[0].intercept_id);
+    Ok(())
+}
+
 fn deserialize<'a, T>(bytes: &'a [u8]) -> Result<T>
 where
     T: de::Deserialize<'a>,
@@ -3,7 +3,10 @@ use futures::FutureExt;
 use std::sync::Arc;
 use test_utils::{poll, ports::get_available_port};
 
-use crate::{mocks::mock_lightning::MockLightning, quit_signal};
+use crate::{
+    mocks::{mock_lightning::MockLightning, mock_wallet::MockWallet},
+    quit_signal,
+};
 use kld::prometheus::start_prometheus_exporter;
 
 #[tokio::test(flavor = "multi_thread")]
@@ -15,6 +18,7 @@ pub async fn test_prometheus() -> Result<()> {
     tokio::spawn(start_prometheus_exporter(
         address.clone(),
         metrics.clone(),
+        Arc::new(MockWallet::default()),
         quit_signal().shared(),
     ));
     poll!(3, call_exporter(&address, "health").await.is_ok());
@@ -37,6 +41,8 @@ pub async fn test_prometheus() -> Result<()> {
         get_metric(&result, "wallet_balance")?,
         metrics.wallet_balance as f64
     );
+    assert_eq!(get_metric(&result, "reorg_count")?, 0.0);
+    assert_eq!(get_metric(&result, "last_reorg_depth")?, 0.0);
 
     let not_found = call_exporter(&address, "wrong").await?;
     assert_eq!(not_found, "Not Found");
@@ -1,23 +1,56 @@
-use std::str::FromStr;
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use api::FeeRate;
 use async_trait::async_trait;
-use bitcoin::{consensus::deserialize, hashes::Hash, secp256k1::PublicKey, Network, Txid};
+use bitcoin::{
+    consensus::deserialize,
+    hashes::{sha256, Hash},
+    secp256k1::{PublicKey, Secp256k1, SecretKey},
+    util::{
+        address::Payload,
+        bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey},
+        psbt::PartiallySignedTransaction,
+    },
+    Address, Network, PackedLockTime, Script, Transaction, TxOut, Txid,
+};
+use futures::stream::{self, BoxStream, StreamExt};
 use hex::FromHex;
-use kld::ldk::{net_utils::PeerAddress, LightningInterface, OpenChannelResult, Peer, PeerStatus};
+use kld::database::{
+    channel_stats::ChannelStats,
+    closed_channel::ClosedChannel,
+    forward::{ChannelEarnings, EarningsBucket, Forward},
+    payment::{Payment, PaymentDirection, PaymentStatus},
+    spendable_output::{SpendableOutput, SweepStatus},
+};
+use kld::ldk::{
+    net_utils::PeerAddress, InterceptedHtlc, LightningInterface, MonitorCheck, NodeState,
+    OpenChannelPsbtResult, OpenChannelResult, PaymentResult, Peer, PeerStatus, PendingHtlc,
+};
 use lightning::{
-    chain::transaction::OutPoint,
+    chain::{keysinterface::SpendableOutputDescriptor, transaction::OutPoint},
     ln::{
         channelmanager::{ChannelCounterparty, ChannelDetails},
         features::{Features, InitFeatures},
         msgs::NetAddress,
+        PaymentHash, PaymentPreimage, PaymentSecret,
     },
     routing::gossip::{ChannelInfo, NodeAlias, NodeAnnouncementInfo, NodeId, NodeInfo},
-    util::{config::UserConfig, indexed_map::IndexedMap},
+    util::{
+        config::{ChannelConfig, UserConfig},
+        indexed_map::IndexedMap,
+        message_signing,
+    },
 };
 
-use test_utils::{TEST_ALIAS, TEST_PUBLIC_KEY, TEST_SHORT_CHANNEL_ID, TEST_TX};
+use lightning_invoice::{Currency, Fallback, Invoice, InvoiceBuilder};
+use test_utils::{TEST_ADDRESS, TEST_ALIAS, TEST_PUBLIC_KEY, TEST_SHORT_CHANNEL_ID, TEST_TX};
+
+pub const TEST_PAYMENT_HASH: PaymentHash = PaymentHash([2; 32]);
 
 pub struct MockLightning {
     pub num_peers: usize,
@@ -27,6 +60,7 @@ pub struct MockLightning {
     pub channels: Vec<ChannelDetails>,
     pub public_key: PublicKey,
     pub ipv4_address: NetAddress,
+    maintenance_mode: AtomicBool,
 }
 
 impl Default for MockLightning {
@@ -66,7 +100,11 @@ impl Default for MockLightning {
             is_public: true,
             inbound_htlc_minimum_msat: Some(300),
             inbound_htlc_maximum_msat: Some(300000),
-            config: None,
+            config: Some(ChannelConfig {
+                forwarding_fee_base_msat: 1000,
+                forwarding_fee_proportional_millionths: 200,
+                ..ChannelConfig::default()
+            }),
         };
         let ipv4_address = NetAddress::IPv4 {
             addr: [127, 0, 0, 1],
@@ -80,6 +118,7 @@ impl Default for MockLightning {
             channels: vec![channel],
             public_key,
             ipv4_address,
+            maintenance_mode: AtomicBool::new(false),
         }
     }
 }
@@ -89,6 +128,18 @@ impl LightningInterface for MockLightning {
     fn alias(&self) -> String {
         "test".to_string()
     }
+
+    fn color(&self) -> [u8; 3] {
+        [1, 2, 3]
+    }
+
+    fn set_alias(&self, _alias: String, _color: Option<[u8; 3]>) -> Result<()> {
+        Ok(())
+    }
+
+    fn announce_node(&self) -> bool {
+        true
+    }
     fn identity_pubkey(&self) -> PublicKey {
         self.public_key
     }
@@ -108,7 +159,8 @@ impl LightningInterface for MockLightning {
     }
 
     fn network(&self) -> bitcoin::Network {
-        Network::Bitcoin
+        // Signet can't be told apart from testnet by the testnet flag of get-info.
+        Network::Signet
     }
     fn num_active_channels(&self) -> usize {
         0
@@ -133,6 +185,36 @@ impl LightningInterface for MockLightning {
         self.channels.clone()
     }
 
+    async fn list_closed_channels(&self) -> Result<Vec<ClosedChannel>> {
+        Ok(vec![ClosedChannel {
+            channel_id: [2u8; 32],
+            counterparty_node_id: Some(self.public_key),
+            funding_txo: None,
+            reason: Some("cooperative close".to_string()),
+            closing_txid: Some(Txid::all_zeros()),
+            sweep_txid: None,
+            closed_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        }])
+    }
+
+    async fn pending_sweeps(&self) -> Result<Vec<SpendableOutput>> {
+        Ok(vec![SpendableOutput {
+            descriptor: SpendableOutputDescriptor::StaticOutput {
+                outpoint: OutPoint {
+                    txid: Txid::all_zeros(),
+                    index: 1,
+                },
+                output: TxOut {
+                    value: 20000,
+                    script_pubkey: Script::new(),
+                },
+            },
+            status: SweepStatus::Sweeping,
+            sweep_txid: Some(Txid::all_zeros()),
+            created_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        }])
+    }
+
     fn set_channel_fee(
         &self,
         _counterparty_node_id: &PublicKey,
@@ -146,6 +228,29 @@ impl LightningInterface for MockLightning {
         ))
     }
 
+    async fn channel_stats(&self) -> Result<Vec<ChannelStats>> {
+        Ok(vec![ChannelStats {
+            channel_id: [1u8; 32],
+            htlcs_attempted: 5,
+            htlcs_succeeded: 3,
+            htlcs_failed: 2,
+        }])
+    }
+
+    async fn channel_earnings(
+        &self,
+        _short_channel_id: u64,
+        _bucket: EarningsBucket,
+    ) -> Result<Vec<ChannelEarnings>> {
+        Ok(vec![ChannelEarnings {
+            bucket_start: UNIX_EPOCH + Duration::from_secs(86400),
+            inbound_fee_msat: 10,
+            outbound_fee_msat: 20,
+            inbound_forwards: 1,
+            outbound_forwards: 2,
+        }])
+    }
+
     fn alias_of(&self, _node_id: &PublicKey) -> Option<String> {
         Some(TEST_ALIAS.to_string())
     }
@@ -175,6 +280,44 @@ impl LightningInterface for MockLightning {
         })
     }
 
+    async fn open_channel_psbt(
+        &self,
+        _their_network_key: PublicKey,
+        channel_value_satoshis: u64,
+        _push_msat: Option<u64>,
+        _override_config: Option<UserConfig>,
+    ) -> Result<OpenChannelPsbtResult> {
+        let address = Address::from_str(TEST_ADDRESS)?;
+        let psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut {
+                value: channel_value_satoshis,
+                script_pubkey: address.script_pubkey(),
+            }],
+        })?;
+        Ok(OpenChannelPsbtResult {
+            channel_id: [1u8; 32],
+            address,
+            psbt,
+        })
+    }
+
+    async fn fund_channel_psbt(
+        &self,
+        channel_id: &[u8; 32],
+        psbt: PartiallySignedTransaction,
+    ) -> Result<OpenChannelResult> {
+        let transaction = psbt.extract_tx();
+        let txid = transaction.txid();
+        Ok(OpenChannelResult {
+            transaction,
+            txid,
+            channel_id: *channel_id,
+        })
+    }
+
     async fn list_peers(&self) -> Result<Vec<Peer>> {
         Ok(vec![Peer {
             public_key: self.public_key,
@@ -192,6 +335,10 @@ impl LightningInterface for MockLightning {
         Ok(())
     }
 
+    async fn forget_peer(&self, _public_key: PublicKey) -> Result<()> {
+        Ok(())
+    }
+
     async fn disconnect_peer(&self, _public_key: PublicKey) -> Result<()> {
         Ok(())
     }
@@ -204,6 +351,52 @@ impl LightningInterface for MockLightning {
         Ok(())
     }
 
+    async fn abandon_channel(
+        &self,
+        _channel_id: &[u8; 32],
+        _counterparty_node_id: &PublicKey,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn intercept_scid(&self) -> u64 {
+        TEST_SHORT_CHANNEL_ID + 1
+    }
+
+    fn forward_intercepted_htlc(
+        &self,
+        _intercept_id: [u8; 32],
+        _channel_id: &[u8; 32],
+        _counterparty_node_id: PublicKey,
+        _amount_msat: u64,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn fail_intercepted_htlc(&self, _intercept_id: [u8; 32]) -> Result<()> {
+        Ok(())
+    }
+
+    fn list_intercepted_htlcs(&self) -> Vec<InterceptedHtlc> {
+        vec![InterceptedHtlc {
+            intercept_id: [3; 32],
+            requested_next_hop_scid: TEST_SHORT_CHANNEL_ID + 1,
+            payment_hash: TEST_PAYMENT_HASH,
+            inbound_amount_msat: 101000,
+            expected_outbound_amount_msat: 100000,
+            intercepted_at: SystemTime::now(),
+        }]
+    }
+
+    fn list_pending_htlcs(&self) -> Vec<PendingHtlc> {
+        vec![PendingHtlc {
+            channel_id: [1; 32],
+            direction: PaymentDirection::Outbound,
+            amount_sat: 100,
+            cltv_expiry: 50040,
+        }]
+    }
+
     fn get_node(&self, _node_id: &NodeId) -> Option<NodeInfo> {
         let mut alias = [0u8; 32];
         alias[..TEST_ALIAS.len()].copy_from_slice(TEST_ALIAS.as_bytes());
@@ -239,4 +432,150 @@ impl LightningInterface for MockLightning {
     fn user_config(&self) -> UserConfig {
         UserConfig::default()
     }
+
+    fn maintenance_mode(&self) -> bool {
+        self.maintenance_mode.load(Ordering::Acquire)
+    }
+
+    fn set_maintenance_mode(&self, enabled: bool) {
+        self.maintenance_mode.store(enabled, Ordering::Release)
+    }
+
+    fn ready(&self) -> bool {
+        true
+    }
+
+    fn num_reorgs(&self) -> u64 {
+        0
+    }
+
+    fn last_reorg_depth(&self) -> u32 {
+        0
+    }
+
+    fn corrupt_channel_monitors(&self) -> usize {
+        0
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<String> {
+        Ok(message_signing::sign(
+            message,
+            &SecretKey::from_slice(&[42; 32])?,
+        )?)
+    }
+
+    fn derive_public_key(&self, path: &DerivationPath) -> Result<PublicKey> {
+        let secp = Secp256k1::new();
+        let key =
+            ExtendedPrivKey::new_master(Network::Regtest, &[42; 32])?.derive_priv(&secp, path)?;
+        Ok(ExtendedPubKey::from_priv(&secp, &key).public_key)
+    }
+
+    async fn reset_scorer(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn prune_network_graph(&self) -> Result<(usize, usize)> {
+        Ok((2, 1))
+    }
+
+    async fn check_channel_monitors(&self) -> Result<MonitorCheck> {
+        Ok(MonitorCheck {
+            checked: 1,
+            corrupt: 0,
+            discrepancies: vec![],
+        })
+    }
+
+    async fn export_state(&self) -> Result<NodeState> {
+        Ok(NodeState {
+            channel_monitors: vec![vec![1, 2, 3]],
+            network_graph: None,
+            peers: vec![(self.public_key, self.ipv4_address.clone())],
+        })
+    }
+
+    async fn generate_invoice(
+        &self,
+        amount_msat: Option<u64>,
+        description: String,
+        expiry_secs: Option<u32>,
+        fallback_address: bool,
+    ) -> Result<Invoice> {
+        let private_key = SecretKey::from_slice(&[42; 32])?;
+        let mut builder = InvoiceBuilder::new(Currency::Regtest)
+            .description(description)
+            .payment_hash(sha256::Hash::from_slice(&[1; 32])?)
+            .payment_secret(PaymentSecret([2; 32]))
+            .current_timestamp()
+            .min_final_cltv_expiry_delta(144)
+            .expiry_time(Duration::from_secs(expiry_secs.unwrap_or(3600).into()));
+        if let Some(amount_msat) = amount_msat {
+            builder = builder.amount_milli_satoshis(amount_msat);
+        }
+        if fallback_address {
+            let script_hash = match Address::from_str(TEST_ADDRESS)?.payload {
+                Payload::ScriptHash(hash) => hash,
+                _ => unreachable!("test address is p2sh"),
+            };
+            builder = builder.fallback(Fallback::ScriptHash(script_hash.into_inner()));
+        }
+        builder
+            .build_signed(|hash| Secp256k1::new().sign_ecdsa_recoverable(hash, &private_key))
+            .map_err(|e| anyhow!("{e}"))
+    }
+
+    async fn pay_invoice(&self, invoice: Invoice, _max_fee_msat: u64) -> Result<PaymentResult> {
+        Ok(PaymentResult {
+            payment_hash: PaymentHash(invoice.payment_hash().into_inner()),
+            preimage: PaymentPreimage([3; 32]),
+            amount_msat: invoice.amount_milli_satoshis(),
+            fee_paid_msat: Some(10),
+        })
+    }
+
+    async fn fetch_outbound_payment(&self, payment_hash: PaymentHash) -> Result<Option<Payment>> {
+        if payment_hash != TEST_PAYMENT_HASH {
+            return Ok(None);
+        }
+        let mut payment = Payment::new_outbound(payment_hash, Some(100000));
+        payment.succeeded(PaymentPreimage([3; 32]), Some(10));
+        Ok(Some(payment))
+    }
+
+    async fn list_payments(
+        &self,
+        direction: PaymentDirection,
+        status: Option<PaymentStatus>,
+        _since: Option<SystemTime>,
+        _until: Option<SystemTime>,
+    ) -> Result<Vec<Payment>> {
+        let mut payment = match direction {
+            PaymentDirection::Inbound => Payment::new_inbound(TEST_PAYMENT_HASH, Some(100000)),
+            PaymentDirection::Outbound => Payment::new_outbound(TEST_PAYMENT_HASH, Some(100000)),
+        };
+        payment.succeeded(PaymentPreimage([3; 32]), Some(10));
+        if status.map_or(false, |s| s != payment.status) {
+            return Ok(vec![]);
+        }
+        Ok(vec![payment])
+    }
+
+    async fn forwards(
+        &self,
+        _since: Option<SystemTime>,
+        _until: Option<SystemTime>,
+    ) -> Result<BoxStream<'static, Result<Forward>>> {
+        let forward = Forward {
+            in_channel_id: Some([1; 32]),
+            out_channel_id: Some([2; 32]),
+            in_scid: Some(TEST_SHORT_CHANNEL_ID),
+            out_scid: None,
+            amount_in_msat: Some(100010),
+            amount_out_msat: Some(100000),
+            fee_msat: Some(10),
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(1681300000),
+        };
+        Ok(stream::iter(vec![Ok(forward)]).boxed())
+    }
 }
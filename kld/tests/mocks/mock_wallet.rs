@@ -3,7 +3,7 @@ use std::str::FromStr;
 use anyhow::Result;
 use async_trait::async_trait;
 use bdk::{wallet::AddressInfo, Balance, KeychainKind, TransactionDetails};
-use bitcoin::{consensus::deserialize, hashes::hex::FromHex, Address, OutPoint, Transaction};
+use bitcoin::{consensus::deserialize, hashes::hex::FromHex, Address, OutPoint, Transaction, Txid};
 use kld::wallet::WalletInterface;
 
 use test_utils::{TEST_ADDRESS, TEST_TX};
@@ -18,6 +18,13 @@ impl WalletInterface for MockWallet {
         Ok(self.balance.clone())
     }
 
+    fn watch_only_balance(&self) -> Result<Balance> {
+        Ok(Balance {
+            confirmed: 5,
+            ..Default::default()
+        })
+    }
+
     async fn transfer(
         &self,
         _address: Address,
@@ -46,6 +53,26 @@ impl WalletInterface for MockWallet {
             keychain: KeychainKind::External,
         })
     }
+
+    fn estimate_funding_fee(&self, _fee_rate: api::FeeRate) -> u64 {
+        1
+    }
+
+    async fn rescan(&self, _birthday_height: Option<u32>) -> Result<()> {
+        Ok(())
+    }
+
+    fn rescan_progress(&self) -> Option<f32> {
+        None
+    }
+
+    async fn import_descriptor(&self, _descriptor: String) -> Result<()> {
+        Ok(())
+    }
+
+    async fn abandon_transaction(&self, txid: Txid) -> Result<Vec<OutPoint>> {
+        Ok(vec![OutPoint { txid, vout: 0 }])
+    }
 }
 
 impl Default for MockWallet {
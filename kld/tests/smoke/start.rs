@@ -3,7 +3,11 @@ use std::time::Duration;
 use crate::smoke::{start_all, START_N_BLOCKS};
 use anyhow::Result;
 use api::{routes, GetInfo};
-use tokio::time::{sleep_until, Instant};
+use tokio::{
+    io::AsyncReadExt,
+    net::TcpStream,
+    time::{sleep_until, timeout, Instant},
+};
 
 // This test is run separately (in its own process) from the other threads.
 // As it starts all the services it might clash with other tests.
@@ -24,6 +28,12 @@ pub async fn test_start() -> Result<()> {
     assert_eq!(START_N_BLOCKS, info.block_height);
     assert!(info.synced_to_chain);
 
+    // A peer which never sends its part of the handshake is disconnected.
+    let mut silent_peer = TcpStream::connect(("127.0.0.1", kld.peer_port)).await?;
+    let mut buf = vec![0; 1024];
+    let read = timeout(Duration::from_secs(30), silent_peer.read(&mut buf)).await??;
+    assert_eq!(0, read);
+
     Ok(())
 }
 
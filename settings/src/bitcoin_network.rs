@@ -30,6 +30,18 @@ impl fmt::Display for Network {
     }
 }
 
+impl Network {
+    /// The chain name bitcoind reports in getblockchaininfo.
+    pub fn bitcoind_chain(&self) -> &'static str {
+        match self {
+            Network::Main => "main",
+            Network::Testnet => "test",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        }
+    }
+}
+
 impl From<Network> for bitcoin::Network {
     fn from(network: Network) -> Self {
         match network {
@@ -46,7 +58,7 @@ impl FromStr for Network {
 
     fn from_str(input: &str) -> Result<Network, Self::Err> {
         match input {
-            "main" => Ok(Network::Main),
+            "main" | "bitcoin" => Ok(Network::Main),
             "testnet" => Ok(Network::Testnet),
             "signet" => Ok(Network::Signet),
             "regtest" => Ok(Network::Regtest),
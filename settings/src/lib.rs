@@ -1,8 +1,11 @@
 mod bitcoin_network;
 
 pub use crate::bitcoin_network::Network;
-use clap::{builder::OsStr, Parser};
+use clap::{builder::OsStr, ArgAction, Parser};
+use std::{fs::File, net::SocketAddr};
 
+/// Every setting can be given as a command line flag or as a KLD_* environment variable. Flags
+/// take precedence over the environment, which takes precedence over the defaults.
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Settings {
@@ -10,14 +13,42 @@ pub struct Settings {
     pub bitcoind_rpc_host: String,
     #[arg(long, default_value = "8333", env = "KLD_BITCOIN_RPC_PORT")]
     pub bitcoind_rpc_port: u16,
+    /// Network of the node and bitcoind: main (or bitcoin), testnet, signet or regtest.
     #[arg(long, default_value = "testnet", env = "KLD_BITCOIN_NETWORK")]
     pub bitcoin_network: Network,
+    /// Cookie file for authenticating with bitcoind. Preferred over the RPC user and password.
+    /// bitcoind writes a new cookie when it restarts, which is picked up automatically.
     #[arg(
         long,
         default_value = "/var/lib/bitcoind-testnet/.cookie",
         env = "KLD_BITCOIN_COOKIE_PATH"
     )]
     pub bitcoin_cookie_path: String,
+    /// RPC user for bitcoind. Only used if the cookie file doesn't exist or no cookie path is set.
+    #[arg(long, default_value = "", env = "KLD_BITCOIN_RPC_USER")]
+    pub bitcoind_rpc_user: String,
+    #[arg(long, default_value = "", env = "KLD_BITCOIN_RPC_PASSWORD")]
+    pub bitcoind_rpc_password: String,
+
+    /// ZMQ endpoint of bitcoind's hashblock notifications (-zmqpubhashblock). New blocks are
+    /// processed as soon as they are announced instead of on the next poll.
+    #[arg(long, default_value = "", env = "KLD_BITCOIND_ZMQ_BLOCK")]
+    pub bitcoind_zmq_block: String,
+    /// Milliseconds between polls of bitcoind for a new chain tip.
+    #[arg(long, default_value = "1000", env = "KLD_CHAIN_POLL_INTERVAL_MS")]
+    pub chain_poll_interval_ms: u64,
+
+    /// Confirmed on-chain funds (sats) that withdrawals and channel opens never spend, so there
+    /// is always something left to fee bump a force close.
+    #[arg(long, default_value = "0", env = "KLD_WALLET_RESERVE_SATS")]
+    pub wallet_reserve_sats: u64,
+    /// Address that the funds of closed channels are swept to, e.g. cold storage. Uses a new
+    /// wallet address if empty.
+    #[arg(long, default_value = "", env = "KLD_SWEEP_ADDRESS")]
+    pub sweep_address: String,
+    /// Fee rate of sweep transactions: urgent, normal, slow, <sats>perkw or <sats>perkb.
+    #[arg(long, default_value = "normal", env = "KLD_SWEEP_FEE_RATE")]
+    pub sweep_fee_rate: String,
 
     #[arg(long, default_value = "/var/lib/kld", env = "KLD_DATA_DIR")]
     pub data_dir: String,
@@ -29,10 +60,21 @@ pub struct Settings {
         env = "KLD_MNEMONIC_PATH"
     )]
     pub mnemonic_path: String,
+    /// "local" to sign with the keys derived from the mnemonic or "remote" to sign with the
+    /// external signer at remote_signer_url.
+    #[arg(long, default_value = "local", env = "KLD_SIGNER_MODE")]
+    pub signer_mode: String,
+    /// URL of the external signer used in the remote signer mode.
+    #[arg(long, default_value = "", env = "KLD_REMOTE_SIGNER_URL")]
+    pub remote_signer_url: String,
     #[arg(long, default_value = "one", env = "KLD_NODE_ID")]
     pub node_id: String,
     #[arg(long, default_value = "info", env = "KLD_LOG_LEVEL")]
     pub log_level: String,
+    /// "logfmt" for one line per record or "tracing" to also print the spans of operations like
+    /// opening channels and paying invoices with each line.
+    #[arg(long, default_value = "logfmt", env = "KLD_LOG_FORMAT")]
+    pub log_format: String,
     #[arg(long, default_value = "test", env = "KLD_ENV")]
     pub env: String,
     /// The port to listen to new peer connections on.
@@ -44,11 +86,134 @@ pub struct Settings {
     /// Public addresses to broadcast to the lightning network.
     #[arg(long, value_parser = addresses_parser, default_value = "127.0.0.1:9234", env = "KLD_PUBLIC_ADDRESSES")]
     pub public_addresses: Addresses,
+    /// Peers to connect to at startup as pubkey@host:port, separated by commas. Gives a new node
+    /// without channels peers to learn the network graph from.
+    #[arg(long, value_parser = addresses_parser, default_value = "", env = "KLD_BOOTSTRAP_PEERS")]
+    pub bootstrap_peers: Addresses,
+    /// Query the lightning DNS seeds of the network for peers at startup if the node has no
+    /// channels yet. Only mainnet and testnet have DNS seeds.
+    #[arg(long, default_value = "false", action = ArgAction::Set, env = "KLD_USE_DNS_SEEDS")]
+    pub use_dns_seeds: bool,
+
+    /// Seconds between attempts to reconnect to channel peers. Lower values restore connections
+    /// faster after a network drop but cause more connection attempts to unreachable peers.
+    #[arg(long, default_value = "1", env = "KLD_PEER_RECONNECT_INTERVAL_SECS")]
+    pub peer_reconnect_interval_secs: u64,
+    /// Inbound peer connections the operating system queues while the node isn't accepting new
+    /// ones.
+    #[arg(long, default_value = "1024", env = "KLD_PEER_LISTEN_BACKLOG")]
+    pub peer_listen_backlog: u32,
+    /// Inbound peer connections which may be in the handshake at the same time. Further
+    /// connections wait in the listen backlog.
+    #[arg(long, default_value = "32", env = "KLD_MAX_INBOUND_HANDSHAKES")]
+    pub max_inbound_handshakes: usize,
+    /// Seconds between additional pings to connected peers, disabled if 0. Peers are always
+    /// pinged every 10 seconds so only smaller values have an effect. Pinging more often detects
+    /// dead connections sooner at the cost of bandwidth and CPU, and disconnects peers which are
+    /// slow to answer, which can hurt on high latency links.
+    #[arg(long, default_value = "0", env = "KLD_PEER_PING_INTERVAL_SECS")]
+    pub peer_ping_interval_secs: u64,
+    /// Seconds between removing stale channels and the nodes left without channels from the
+    /// network graph, disabled if 0.
+    #[arg(
+        long,
+        default_value = "3600",
+        env = "KLD_NETWORK_GRAPH_PRUNE_INTERVAL_SECS"
+    )]
+    pub network_graph_prune_interval_secs: u64,
+    /// Confirmations of the funding transaction required before a channel can be used (1-144).
+    /// The peer funding a channel has to wait at least this long. For channels we fund, LDK
+    /// follows the depth requested by the peer.
+    #[arg(long, default_value = "6", env = "KLD_CHANNEL_MINIMUM_DEPTH")]
+    pub channel_minimum_depth: u32,
+    /// Announce new channels to the network unless the open request says otherwise.
+    #[arg(
+        long,
+        default_value = "true",
+        action = ArgAction::Set,
+        env = "KLD_ANNOUNCE_CHANNELS_BY_DEFAULT"
+    )]
+    pub announce_channels_by_default: bool,
+    /// Hold HTLCs to fake short channel ids from /v1/htlc/interceptscid until they are forwarded
+    /// or failed through the API. Used by LSPs to open channels just in time.
+    #[arg(long, default_value = "false", action = ArgAction::Set, env = "KLD_INTERCEPT_HTLCS")]
+    pub intercept_htlcs: bool,
+    /// Reject inbound channels from peers which already have this many channels with us.
+    #[arg(long, default_value = "10", env = "KLD_MAX_CHANNELS_PER_PEER")]
+    pub max_channels_per_peer: usize,
+    /// Largest total value (msat) of dust HTLCs a channel accepts. Dust HTLCs are lost to fees
+    /// on a force close, so this limits what a peer can make us lose by griefing with them.
+    /// Applied to existing channels at startup.
+    #[arg(
+        long,
+        default_value = "5000000",
+        env = "KLD_MAX_DUST_HTLC_EXPOSURE_MSAT"
+    )]
+    pub max_dust_htlc_exposure_msat: u64,
+    /// Fail intercepted HTLCs whose inbound amount (msat) is above this, whatever the channel
+    /// limits are. It doesn't limit ordinary forwards, LDK 0.0.114 forwards those without asking.
+    /// No limit if 0.
+    #[arg(long, default_value = "0", env = "KLD_MAX_INTERCEPTED_HTLC_MSAT")]
+    pub max_intercepted_htlc_msat: u64,
+
+    /// Largest base fee (msat) that can be set on a channel without forcing it.
+    #[arg(long, default_value = "100000", env = "KLD_MAX_CHANNEL_FEE_BASE_MSAT")]
+    pub max_channel_fee_base_msat: u32,
+    /// Largest proportional fee (ppm) that can be set on a channel without forcing it.
+    #[arg(long, default_value = "100000", env = "KLD_MAX_CHANNEL_FEE_PPM")]
+    pub max_channel_fee_ppm: u32,
+    /// Periodically raise the proportional fee of channels that run low on outbound liquidity
+    /// and lower it again once they are balanced.
+    #[arg(long, default_value = "false", action = ArgAction::Set, env = "KLD_AUTO_FEES")]
+    pub auto_fees: bool,
+    /// Seconds between automatic fee adjustments.
+    #[arg(long, default_value = "3600", env = "KLD_AUTO_FEES_INTERVAL_SECS")]
+    pub auto_fees_interval_secs: u64,
+    /// Raise the fee of channels with less than this share (%) of the capacity on our side.
+    #[arg(long, default_value = "20", env = "KLD_AUTO_FEES_LOW_OUTBOUND_PERCENT")]
+    pub auto_fees_low_outbound_percent: u8,
+    /// Lower the fee of channels with at least this share (%) of the capacity on our side.
+    #[arg(
+        long,
+        default_value = "40",
+        env = "KLD_AUTO_FEES_BALANCED_OUTBOUND_PERCENT"
+    )]
+    pub auto_fees_balanced_outbound_percent: u8,
+    /// Amount (ppm) by which the fee is changed in each adjustment.
+    #[arg(long, default_value = "50", env = "KLD_AUTO_FEES_PPM_STEP")]
+    pub auto_fees_ppm_step: u32,
+    /// Lowest proportional fee (ppm) that automatic adjustment lowers to.
+    #[arg(long, default_value = "100", env = "KLD_AUTO_FEES_MIN_PPM")]
+    pub auto_fees_min_ppm: u32,
+    /// Highest proportional fee (ppm) that automatic adjustment raises to.
+    #[arg(long, default_value = "2000", env = "KLD_AUTO_FEES_MAX_PPM")]
+    pub auto_fees_max_ppm: u32,
+
+    /// URL to POST a JSON notification to on channel and payment events. Disabled if empty.
+    #[arg(long, default_value = "", env = "KLD_WEBHOOK_URL")]
+    pub webhook_url: String,
+    /// Secret used to sign webhook payloads with HMAC-SHA256.
+    #[arg(long, default_value = "", env = "KLD_WEBHOOK_SECRET")]
+    pub webhook_secret: String,
+    /// Maximum number of undelivered webhook events to keep. The oldest are dropped first.
+    #[arg(long, default_value = "1000", env = "KLD_WEBHOOK_MAX_PENDING")]
+    pub webhook_max_pending: usize,
+    /// Seconds to keep retrying a webhook event before dropping it.
+    #[arg(long, default_value = "86400", env = "KLD_WEBHOOK_MAX_AGE_SECS")]
+    pub webhook_max_age_secs: u64,
 
     #[arg(long, default_value = "127.0.0.1:2233", env = "KLD_EXPORTER_ADDRESS")]
     pub exporter_address: String,
+    /// Comma separated list of addresses to serve the REST API on.
     #[arg(long, default_value = "127.0.0.1:2244", env = "KLD_REST_API_ADDRESS")]
     pub rest_api_address: String,
+    /// Path of a Unix socket to also serve the REST API on, without TLS. Requests on the socket
+    /// are authorised as admin so access is controlled by its file permissions. Disabled if empty.
+    #[arg(long, default_value = "", env = "KLD_REST_API_SOCKET")]
+    pub rest_api_socket: String,
+    /// Seconds the REST API waits for open requests to finish when shutting down.
+    #[arg(long, default_value = "30", env = "KLD_SHUTDOWN_TIMEOUT_SECS")]
+    pub shutdown_timeout_secs: u64,
 
     #[arg(long, default_value = "127.0.0.1", env = "KLD_DATABASE_HOST")]
     pub database_host: String,
@@ -58,18 +223,175 @@ pub struct Settings {
     pub database_user: String,
     #[arg(long, default_value = "defaultdb", env = "KLD_DATABASE_NAME")]
     pub database_name: String,
+    /// Host of a database node to send large reads for listings to, so they don't compete with
+    /// writes on the primary. Its results can be slightly stale. Uses the primary if empty.
+    #[arg(long, default_value = "", env = "KLD_DATABASE_READ_HOST")]
+    pub database_read_host: String,
     #[arg(long, default_value = "", env = "KLD_DATABASE_CA_CERT_PATH")]
     pub database_ca_cert_path: String,
     #[arg(long, default_value = "", env = "KLD_DATABASE_CLIENT_CERT_PATH")]
     pub database_client_cert_path: String,
     #[arg(long, default_value = "", env = "KLD_DATABASE_CLIENT_KEY_PATH")]
     pub database_client_key_path: String,
+    /// Milliseconds after which the database aborts a statement, so a hung query fails instead of
+    /// blocking the node. Disabled if 0.
+    #[arg(
+        long,
+        default_value = "30000",
+        env = "KLD_DATABASE_STATEMENT_TIMEOUT_MS"
+    )]
+    pub database_statement_timeout_ms: u64,
+    /// Seconds to wait for a connection to the database before the attempt is given up.
+    #[arg(long, default_value = "10", env = "KLD_DATABASE_CONNECT_TIMEOUT_SECS")]
+    pub database_connect_timeout_secs: u64,
+    /// Number of times to try connecting to the database at startup, with a growing delay in
+    /// between, so that the node can be started before the database is ready.
+    #[arg(long, default_value = "10", env = "KLD_DATABASE_CONNECT_ATTEMPTS")]
+    pub database_connect_attempts: u32,
+    /// Seconds a kld process holds its lease on the database without renewing it. A second
+    /// process using the same database waits this long and refuses to start if the lease is
+    /// still being renewed.
+    #[arg(long, default_value = "30", env = "KLD_DATABASE_LEASE_SECS")]
+    pub database_lease_secs: u64,
+    /// Wait as a standby while another kld process holds the database lease, instead of
+    /// refusing to start, and take over when the leader stops renewing it. The standby needs the
+    /// same mnemonic as the leader.
+    #[arg(long, default_value = "false", action = ArgAction::Set, env = "KLD_STANDBY")]
+    pub standby: bool,
 }
 
 impl Settings {
     pub fn load() -> Settings {
         Settings::parse()
     }
+
+    /// Check the settings before anything is started so that every problem is reported at once.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = vec![];
+        if self.node_name.len() > 32 {
+            problems.push(format!(
+                "node_name {} is longer than 32 bytes",
+                self.node_name
+            ));
+        }
+        if !["error", "warn", "info", "debug", "trace"]
+            .contains(&self.log_level.to_lowercase().as_str())
+        {
+            problems.push(format!("log_level {} is not a log level", self.log_level));
+        }
+        if self.chain_poll_interval_ms == 0 {
+            problems.push("chain_poll_interval_ms must be at least 1".to_string());
+        }
+        if self.auto_fees_interval_secs == 0 {
+            problems.push("auto_fees_interval_secs must be at least 1".to_string());
+        }
+        if self.max_inbound_handshakes == 0 {
+            problems.push("max_inbound_handshakes must be at least 1".to_string());
+        }
+        if self.database_connect_timeout_secs == 0 {
+            problems.push("database_connect_timeout_secs must be at least 1".to_string());
+        }
+        if self.database_connect_attempts == 0 {
+            problems.push("database_connect_attempts must be at least 1".to_string());
+        }
+        if self.database_lease_secs < 3 {
+            problems.push("database_lease_secs must be at least 3".to_string());
+        }
+        if !["logfmt", "tracing"].contains(&self.log_format.as_str()) {
+            problems.push(format!(
+                "log_format {} is not logfmt or tracing",
+                self.log_format
+            ));
+        }
+        if !["local", "remote"].contains(&self.signer_mode.as_str()) {
+            problems.push(format!(
+                "signer_mode {} is not local or remote",
+                self.signer_mode
+            ));
+        }
+        if self.signer_mode == "remote" && self.remote_signer_url.is_empty() {
+            problems.push("remote_signer_url is required for signer_mode remote".to_string());
+        }
+        for address in &self.public_addresses {
+            if !has_port(address) {
+                problems.push(format!("public address {address} is not host:port"));
+            }
+        }
+        for peer in &self.bootstrap_peers {
+            let valid = peer
+                .split_once('@')
+                .map(|(public_key, address)| public_key.len() == 66 && has_port(address))
+                .unwrap_or_default();
+            if !valid {
+                problems.push(format!("bootstrap peer {peer} is not pubkey@host:port"));
+            }
+        }
+        for address in self.rest_api_address.split(',').map(str::trim) {
+            if address.parse::<SocketAddr>().is_err() {
+                problems.push(format!("rest_api_address {address} is not ip:port"));
+            }
+        }
+        if self.exporter_address.parse::<SocketAddr>().is_err() {
+            problems.push(format!(
+                "exporter_address {} is not ip:port",
+                self.exporter_address
+            ));
+        }
+        for file in ["kld.crt", "kld.key"] {
+            check_readable(
+                &mut problems,
+                "certs_dir",
+                &format!("{}/{file}", self.certs_dir),
+            );
+        }
+        for (name, path) in [
+            ("database_ca_cert_path", &self.database_ca_cert_path),
+            ("database_client_cert_path", &self.database_client_cert_path),
+            ("database_client_key_path", &self.database_client_key_path),
+        ] {
+            if !path.is_empty() {
+                check_readable(&mut problems, name, path);
+            }
+        }
+        if self.auto_fees_balanced_outbound_percent > 100
+            || self.auto_fees_low_outbound_percent >= self.auto_fees_balanced_outbound_percent
+        {
+            problems.push(
+                "auto_fees_low_outbound_percent must be below auto_fees_balanced_outbound_percent, which is at most 100"
+                    .to_string(),
+            );
+        }
+        if self.auto_fees_min_ppm > self.auto_fees_max_ppm {
+            problems.push("auto_fees_min_ppm is above auto_fees_max_ppm".to_string());
+        }
+        if !self.webhook_url.is_empty()
+            && !self.webhook_url.starts_with("http://")
+            && !self.webhook_url.starts_with("https://")
+        {
+            problems.push(format!(
+                "webhook_url {} is not a http(s) URL",
+                self.webhook_url
+            ));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+fn has_port(address: &str) -> bool {
+    address
+        .rsplit_once(':')
+        .map(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+        .unwrap_or(false)
+}
+
+fn check_readable(problems: &mut Vec<String>, name: &str, path: &str) {
+    if let Err(e) = File::open(path) {
+        problems.push(format!("{name}: can't read {path}: {e}"));
+    }
 }
 
 impl Default for Settings {
@@ -92,7 +414,9 @@ fn addresses_parser(env: &str) -> Result<Addresses, std::io::Error> {
 mod test {
     use std::env::set_var;
 
-    use crate::Settings;
+    use clap::Parser;
+
+    use crate::{Network, Settings};
 
     #[test]
     pub fn test_parse_settings() {
@@ -105,5 +429,118 @@ mod test {
         let settings = Settings::load();
 
         assert_eq!(settings.public_addresses.len(), 2);
+        assert!(settings.announce_channels_by_default);
+
+        set_var("KLD_ANNOUNCE_CHANNELS_BY_DEFAULT", "false");
+        let settings = Settings::load();
+
+        assert!(!settings.announce_channels_by_default);
+        assert!(!settings.auto_fees);
+        assert_eq!(20, settings.auto_fees_low_outbound_percent);
+    }
+
+    #[test]
+    pub fn test_settings_precedence() {
+        let settings = Settings::parse_from(["kld"]);
+        assert_eq!(9234, settings.peer_port);
+        assert_eq!("127.0.0.1", settings.database_host);
+
+        set_var("KLD_PEER_PORT", "9999");
+        set_var("KLD_DATABASE_HOST", "cockroach");
+        let settings = Settings::parse_from(["kld"]);
+        assert_eq!(9999, settings.peer_port);
+        assert_eq!("cockroach", settings.database_host);
+
+        let settings = Settings::parse_from(["kld", "--peer-port", "1234"]);
+        assert_eq!(1234, settings.peer_port);
+        assert_eq!("cockroach", settings.database_host);
+    }
+
+    #[test]
+    pub fn test_validate_settings() {
+        let settings = Settings::parse_from([
+            "kld",
+            "--certs-dir",
+            "/nonexistent",
+            "--node-name",
+            "a".repeat(33).as_str(),
+            "--public-addresses",
+            "127.0.0.1:9234,example.com",
+            "--rest-api-address",
+            "127.0.0.1:2244,[::1]:2244",
+            "--bootstrap-peers",
+            "127.0.0.1:9735",
+        ]);
+        let problems = settings.validate().unwrap_err();
+        assert_eq!(5, problems.len(), "{problems:?}");
+        assert!(problems[0].starts_with("node_name"));
+        assert!(problems[1].contains("example.com"));
+        assert!(problems[2].starts_with("bootstrap peer"));
+        assert!(problems[3].contains("/nonexistent/kld.crt"));
+        assert!(problems[4].contains("/nonexistent/kld.key"));
+    }
+
+    #[test]
+    pub fn test_validate_signer_mode() {
+        let settings = Settings::parse_from(["kld", "--signer-mode", "remote"]);
+        let problems = settings.validate().unwrap_err();
+        assert!(problems.iter().any(|p| p.starts_with("remote_signer_url")));
+
+        let settings = Settings::parse_from(["kld", "--signer-mode", "hsm"]);
+        let problems = settings.validate().unwrap_err();
+        assert!(problems.iter().any(|p| p.starts_with("signer_mode")));
+    }
+
+    #[test]
+    pub fn test_validate_intervals() {
+        let settings = Settings::parse_from([
+            "kld",
+            "--chain-poll-interval-ms",
+            "0",
+            "--auto-fees-interval-secs",
+            "0",
+        ]);
+        let problems = settings.validate().unwrap_err();
+        assert!(problems
+            .iter()
+            .any(|p| p.starts_with("chain_poll_interval_ms")));
+        assert!(problems
+            .iter()
+            .any(|p| p.starts_with("auto_fees_interval_secs")));
+    }
+
+    #[test]
+    pub fn test_parse_networks() {
+        for (name, network, bitcoin_network, bitcoind_chain) in [
+            ("main", Network::Main, bitcoin::Network::Bitcoin, "main"),
+            ("bitcoin", Network::Main, bitcoin::Network::Bitcoin, "main"),
+            (
+                "testnet",
+                Network::Testnet,
+                bitcoin::Network::Testnet,
+                "test",
+            ),
+            (
+                "signet",
+                Network::Signet,
+                bitcoin::Network::Signet,
+                "signet",
+            ),
+            (
+                "regtest",
+                Network::Regtest,
+                bitcoin::Network::Regtest,
+                "regtest",
+            ),
+        ] {
+            let settings = Settings::parse_from(["kld", "--bitcoin-network", name]);
+            assert_eq!(network, settings.bitcoin_network);
+            assert_eq!(
+                bitcoin_network,
+                bitcoin::Network::from(settings.bitcoin_network)
+            );
+            assert_eq!(bitcoind_chain, settings.bitcoin_network.bitcoind_chain());
+        }
+        assert!(Settings::try_parse_from(["kld", "--bitcoin-network", "mainnet"]).is_err());
     }
 }
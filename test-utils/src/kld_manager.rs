@@ -14,6 +14,7 @@ pub struct KldManager {
     bin_path: String,
     pub exporter_address: String,
     pub rest_api_address: String,
+    pub peer_port: u16,
     rest_client: reqwest::Client,
 }
 
@@ -64,6 +65,7 @@ impl KldManager {
             "127.0.0.1:{}",
             get_available_port().expect("Cannot find free port")
         );
+        let peer_port = get_available_port().expect("Cannot find free port");
         let manager = Manager::new(output_dir, "kld", instance);
 
         let certs_dir = format!("{}/certs", env!("CARGO_MANIFEST_DIR"));
@@ -76,10 +78,13 @@ impl KldManager {
         );
         set_var("KLD_EXPORTER_ADDRESS", &exporter_address);
         set_var("KLD_REST_API_ADDRESS", &rest_api_address);
+        set_var("KLD_PEER_PORT", peer_port.to_string());
         set_var("KLD_BITCOIN_NETWORK", &bitcoin.network);
         set_var("KLD_BITCOIN_COOKIE_PATH", bitcoin.cookie_path());
         set_var("KLD_BITCOIN_RPC_HOST", "127.0.0.1");
         set_var("KLD_BITCOIN_RPC_PORT", bitcoin.rpc_port.to_string());
+        // Regtest blocks are mined on demand so pick them up quickly.
+        set_var("KLD_CHAIN_POLL_INTERVAL_MS", "100");
         set_var("KLD_DATABASE_PORT", cockroach.sql_port.to_string());
         set_var(
             "KLD_DATABASE_CA_CERT_PATH",
@@ -102,6 +107,7 @@ impl KldManager {
             bin_path: bin_path.to_string(),
             exporter_address,
             rest_api_address,
+            peer_port,
             rest_client: client,
         }
     }
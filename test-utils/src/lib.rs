@@ -32,6 +32,8 @@ pub const TEST_ALIAS: &str = "test node";
 
 pub const TEST_WPKH: &str = "wpkh(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW)";
 
+pub const TEST_WATCH_DESCRIPTOR: &str = "wpkh(tpubD6NzVbkrYhZ4XHndKkuB8FifXm8r5FQHwrN6oZuWCz13qb93rtgKvD4PQsqC4HP4yhV3tA2fqr2RbY5mNXfM7RxXUoeABoDtsFUq2zJq6YK/0/*)";
+
 pub fn test_settings(tmp_dir: &str, name: &str) -> Settings {
     let mut settings = Settings::default();
     settings.certs_dir = format!("{}/certs", env!("CARGO_MANIFEST_DIR"));